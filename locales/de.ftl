@@ -0,0 +1,116 @@
+## Shared
+greeting = Hallo { $name },
+email-footer = Sie erhalten diese E-Mail, weil Sie sich für unseren Newsletter angemeldet haben.
+
+## Confirmation email
+confirmation-subject = Willkommen!
+confirmation-intro = Willkommen bei meinem Newsletter!
+confirmation-link-text = Klicken Sie hier, um Ihr Abonnement zu bestätigen.
+confirmation-text-cta = Öffnen Sie { $link }, um Ihr Abonnement zu bestätigen.
+confirmation-footer = Falls Sie sich nicht angemeldet haben, können Sie diese E-Mail ignorieren.
+
+## Welcome email
+welcome-subject = Sie sind angemeldet!
+welcome-body = Ihr Abonnement ist bestätigt. Danke, dass Sie dabei sind!
+
+## Unsubscribe email
+unsubscribe-subject = Sie wurden abgemeldet
+unsubscribe-body = Sie wurden abgemeldet und erhalten keine weiteren Ausgaben von uns.
+unsubscribe-footer = Dies ist die letzte E-Mail, die Sie von uns erhalten.
+
+## Confirmation pages
+confirmed-page-title = Abonnement bestätigt
+confirmed-page-body = Danke! Ihr Abonnement ist bestätigt.
+already-confirmed-page-title = Bereits bestätigt
+already-confirmed-page-body = Ihr Abonnement wurde bereits bestätigt. Sie müssen nichts weiter tun.
+expired-page-title = Link abgelaufen
+expired-page-body = Dieser Bestätigungslink ist abgelaufen. Geben Sie Ihre E-Mail-Adresse ein, und wir senden Ihnen einen neuen.
+expired-page-email = E-Mail-Adresse
+expired-page-button = Neuen Link senden
+invalid-token-page-title = Ungültiger Link
+invalid-token-page-body = Dieser Bestätigungslink ist ungültig. Bitte prüfen Sie, ob Sie ihn vollständig kopiert haben.
+
+## Privacy request emails
+privacy-request-footer = Jemand hat dies mit Ihrer E-Mail-Adresse angefordert. Falls Sie es nicht waren, können Sie diese E-Mail ignorieren.
+data-access-subject = Ihre Daten
+data-access-intro = Sie haben eine Kopie der Daten angefordert, die wir über Sie gespeichert haben.
+data-access-link-text = Klicken Sie hier, um sie herunterzuladen.
+data-access-text-cta = Öffnen Sie { $link }, um sie herunterzuladen.
+erasure-subject = Ihre Daten löschen
+erasure-intro = Sie haben uns gebeten, die Daten zu löschen, die wir über Sie gespeichert haben.
+erasure-link-text = Klicken Sie hier, um das zu bestätigen.
+erasure-text-cta = Öffnen Sie { $link }, um das zu bestätigen.
+
+## Privacy pages
+confirm-erasure-page-title = Ihre Daten löschen?
+confirm-erasure-page-body = Damit werden Ihr Name und Ihre E-Mail-Adresse endgültig entfernt und Ihr Abonnement beendet. Das kann nicht rückgängig gemacht werden.
+confirm-erasure-page-button = Meine Daten löschen
+erased-page-title = Ihre Daten wurden gelöscht
+erased-page-body = Wir haben Ihre personenbezogenen Daten gelöscht. Sie werden nichts mehr von uns hören.
+invalid-privacy-link-page-title = Ungültiger Link
+invalid-privacy-link-page-body = Dieser Link ist ungültig oder abgelaufen. Bitte stellen Sie eine neue Anfrage.
+
+## Tracking
+tracking-opt-out-link-text = Nicht mehr erfassen, wenn ich diesen Newsletter öffne oder anklicke.
+tracking-opt-out-text-cta = Damit wir nicht mehr erfassen, wann Sie diesen Newsletter öffnen oder anklicken, öffnen Sie { $link }
+confirm-tracking-opt-out-page-title = Erfassung beenden?
+confirm-tracking-opt-out-page-body = Wir erfassen dann nicht mehr, wann Sie unseren Newsletter öffnen oder seine Links anklicken. Ihr Abonnement bleibt bestehen.
+confirm-tracking-opt-out-page-button = Erfassung beenden
+tracking-opted-out-page-title = Erfassung beendet
+tracking-opted-out-page-body = Wir erfassen nicht mehr, wann Sie unseren Newsletter öffnen oder seine Links anklicken.
+invalid-tracking-link-page-title = Ungültiger Link
+invalid-tracking-link-page-body = Dieser Link ist ungültig. Bitte prüfen Sie, ob Sie ihn vollständig kopiert haben.
+
+## Feed digests
+feed-digest-subject = Neu im Blog
+feed-digest-intro = Das haben wir seit der letzten Ausgabe veröffentlicht:
+feed-digest-read-more = Weiterlesen
+
+## Archive
+archive-page-empty = Hier wurde noch nichts veröffentlicht.
+archive-page-newer = Neuere Ausgaben
+archive-page-older = Ältere Ausgaben
+archive-page-feed = Feed abonnieren
+archived-issue-page-sent-on = Versendet am { $date }
+
+## Preference center
+preferences-subject = Ihr Abonnement verwalten
+preferences-intro = Sie möchten ändern, wie Sie unseren Newsletter erhalten.
+preferences-link-text = Klicken Sie hier, um Ihr Abonnement zu verwalten.
+preferences-text-cta = Öffnen Sie { $link }, um Ihr Abonnement zu verwalten.
+preferences-page-title = Ihr Abonnement
+preferences-page-saved = Ihre Änderungen sind gespeichert.
+preferences-page-name = Name
+preferences-page-lists = Listen
+preferences-page-delivery = Zustellung
+preferences-page-not-paused = Jede Ausgabe senden
+preferences-page-paused-until = Pausiert bis { $date }
+preferences-page-pause-weeks = { $weeks } Wochen pausieren
+preferences-page-save = Speichern
+preferences-page-unsubscribe-body = Sie möchten gar nichts mehr von uns hören?
+preferences-page-unsubscribe = Alles abbestellen
+preferences-error-name = Bitte geben Sie einen gültigen Namen ein.
+preferences-error-lists = Bitte wählen Sie mindestens eine Liste, oder bestellen Sie alles ab.
+preferences-error-pause = Bitte wählen Sie, wie lange pausiert werden soll.
+preferences-signed-out-page-title = Ihr Abonnement verwalten
+preferences-signed-out-page-body = Geben Sie Ihre E-Mail-Adresse ein, und wir senden Ihnen einen Link, mit dem Sie Ihr Abonnement verwalten können.
+preferences-signed-out-page-email = E-Mail-Adresse
+preferences-signed-out-page-button = Link senden
+unsubscribed-page-title = Sie sind abgemeldet
+unsubscribed-page-body = Sie erhalten keine weiteren Ausgaben von uns.
+
+## Email address changes
+preferences-page-email = E-Mail-Adresse
+preferences-page-change-email = E-Mail-Adresse ändern
+preferences-email-change-sent = Falls die neue Adresse verwendet werden kann, haben wir einen Link dorthin gesendet. Ihr Abonnement zieht um, sobald Sie ihm folgen.
+preferences-error-email = Bitte geben Sie eine gültige neue E-Mail-Adresse ein.
+email-change-subject = Bestätigen Sie Ihre neue E-Mail-Adresse
+email-change-intro = Sie möchten unseren Newsletter ab jetzt an diese Adresse erhalten.
+email-change-link-text = Klicken Sie hier, um Ihre neue E-Mail-Adresse zu bestätigen.
+email-change-text-cta = Öffnen Sie { $link }, um Ihre neue E-Mail-Adresse zu bestätigen.
+email-changed-page-title = Ihre E-Mail-Adresse wurde geändert
+email-changed-page-body = Ab jetzt senden wir unseren Newsletter an Ihre neue Adresse.
+email-change-failed-page-title = Ihre E-Mail-Adresse wurde nicht geändert
+email-change-failed-taken = Mit dieser Adresse ist bereits jemand angemeldet.
+email-change-failed-suppressed = Diese Adresse kann nicht mehr angemeldet werden.
+email-change-failed-stale = Ihre E-Mail-Adresse hat sich seit Ihrer Anfrage geändert. Bitte fragen Sie im Einstellungsbereich erneut an.
@@ -0,0 +1,116 @@
+## Shared
+greeting = Hi { $name },
+email-footer = You are receiving this email because you signed up for our newsletter.
+
+## Confirmation email
+confirmation-subject = Welcome!
+confirmation-intro = Welcome to my newsletter!
+confirmation-link-text = Click here to confirm your subscription.
+confirmation-text-cta = Click { $link } to confirm your subscription.
+confirmation-footer = If you did not sign up, you can safely ignore this email.
+
+## Welcome email
+welcome-subject = You're subscribed!
+welcome-body = Your subscription is confirmed. Thanks for joining!
+
+## Unsubscribe email
+unsubscribe-subject = You have been unsubscribed
+unsubscribe-body = You have been unsubscribed and will not receive any more issues from us.
+unsubscribe-footer = This is the last email you will receive from us.
+
+## Confirmation pages
+confirmed-page-title = Subscription confirmed
+confirmed-page-body = Thanks! Your subscription is confirmed.
+already-confirmed-page-title = Already confirmed
+already-confirmed-page-body = Your subscription was already confirmed. There is nothing else to do.
+expired-page-title = Link expired
+expired-page-body = This confirmation link has expired. Enter your email address and we will send you a new one.
+expired-page-email = Email address
+expired-page-button = Send a new link
+invalid-token-page-title = Invalid link
+invalid-token-page-body = This confirmation link is not valid. Please check that you copied it completely.
+
+## Privacy request emails
+privacy-request-footer = Someone asked for this with your email address. If it was not you, you can safely ignore this email.
+data-access-subject = Your data
+data-access-intro = You asked for a copy of the data we hold about you.
+data-access-link-text = Click here to download it.
+data-access-text-cta = Open { $link } to download it.
+erasure-subject = Delete your data
+erasure-intro = You asked us to delete the data we hold about you.
+erasure-link-text = Click here to confirm.
+erasure-text-cta = Open { $link } to confirm.
+
+## Privacy pages
+confirm-erasure-page-title = Delete your data?
+confirm-erasure-page-body = This removes your name and email address for good and ends your subscription. It cannot be undone.
+confirm-erasure-page-button = Delete my data
+erased-page-title = Your data was deleted
+erased-page-body = We have deleted your personal data. You will not hear from us again.
+invalid-privacy-link-page-title = Invalid link
+invalid-privacy-link-page-body = This link is invalid or has expired. Please make a new request.
+
+## Tracking
+tracking-opt-out-link-text = Stop tracking when I open or click this newsletter.
+tracking-opt-out-text-cta = To stop us tracking when you open or click this newsletter, open { $link }
+confirm-tracking-opt-out-page-title = Stop tracking?
+confirm-tracking-opt-out-page-body = We will no longer record when you open our newsletter or click its links. You stay subscribed.
+confirm-tracking-opt-out-page-button = Stop tracking
+tracking-opted-out-page-title = Tracking stopped
+tracking-opted-out-page-body = We no longer record when you open our newsletter or click its links.
+invalid-tracking-link-page-title = Invalid link
+invalid-tracking-link-page-body = This link is not valid. Please check that you copied it completely.
+
+## Feed digests
+feed-digest-subject = New on the blog
+feed-digest-intro = Here is what we published since the last issue:
+feed-digest-read-more = Read more
+
+## Archive
+archive-page-empty = Nothing has been published here yet.
+archive-page-newer = Newer issues
+archive-page-older = Older issues
+archive-page-feed = Subscribe to the feed
+archived-issue-page-sent-on = Sent on { $date }
+
+## Preference center
+preferences-subject = Manage your subscription
+preferences-intro = You asked to change how you receive our newsletter.
+preferences-link-text = Click here to manage your subscription.
+preferences-text-cta = Open { $link } to manage your subscription.
+preferences-page-title = Your subscription
+preferences-page-saved = Your changes are saved.
+preferences-page-name = Name
+preferences-page-lists = Lists
+preferences-page-delivery = Delivery
+preferences-page-not-paused = Send me every issue
+preferences-page-paused-until = Paused until { $date }
+preferences-page-pause-weeks = Pause for { $weeks } weeks
+preferences-page-save = Save
+preferences-page-unsubscribe-body = Rather not hear from us at all?
+preferences-page-unsubscribe = Unsubscribe from everything
+preferences-error-name = Please enter a valid name.
+preferences-error-lists = Please choose at least one list, or unsubscribe from everything.
+preferences-error-pause = Please choose how long to pause for.
+preferences-signed-out-page-title = Manage your subscription
+preferences-signed-out-page-body = Enter your email address and we will send you a link to manage your subscription.
+preferences-signed-out-page-email = Email address
+preferences-signed-out-page-button = Send me a link
+unsubscribed-page-title = You are unsubscribed
+unsubscribed-page-body = You will not receive any more issues from us.
+
+## Email address changes
+preferences-page-email = Email address
+preferences-page-change-email = Change email address
+preferences-email-change-sent = If the new address can be used, we sent a link there. Your subscription moves once you follow it.
+preferences-error-email = Please enter a valid new email address.
+email-change-subject = Confirm your new email address
+email-change-intro = You asked to receive our newsletter at this address from now on.
+email-change-link-text = Click here to confirm your new email address.
+email-change-text-cta = Open { $link } to confirm your new email address.
+email-changed-page-title = Your email address is changed
+email-changed-page-body = From now on we will send our newsletter to your new address.
+email-change-failed-page-title = Your email address was not changed
+email-change-failed-taken = Someone is already subscribed with this address.
+email-change-failed-suppressed = This address can no longer be subscribed.
+email-change-failed-stale = Your email address has changed since you asked. Please ask again from the preference center.
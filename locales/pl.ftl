@@ -0,0 +1,116 @@
+## Shared
+greeting = Cześć { $name },
+email-footer = Otrzymujesz tę wiadomość, ponieważ zapisałeś się do naszego newslettera.
+
+## Confirmation email
+confirmation-subject = Witaj!
+confirmation-intro = Witaj w moim newsletterze!
+confirmation-link-text = Kliknij tutaj, aby potwierdzić subskrypcję.
+confirmation-text-cta = Otwórz { $link }, aby potwierdzić subskrypcję.
+confirmation-footer = Jeśli to nie Ty się zapisałeś, zignoruj tę wiadomość.
+
+## Welcome email
+welcome-subject = Subskrypcja aktywna!
+welcome-body = Twoja subskrypcja została potwierdzona. Dziękujemy!
+
+## Unsubscribe email
+unsubscribe-subject = Wypisano Cię z newslettera
+unsubscribe-body = Wypisano Cię i nie będziesz już otrzymywać od nas kolejnych wydań.
+unsubscribe-footer = To ostatnia wiadomość, jaką od nas otrzymasz.
+
+## Confirmation pages
+confirmed-page-title = Subskrypcja potwierdzona
+confirmed-page-body = Dziękujemy! Twoja subskrypcja została potwierdzona.
+already-confirmed-page-title = Już potwierdzono
+already-confirmed-page-body = Twoja subskrypcja została już wcześniej potwierdzona. Nie musisz nic więcej robić.
+expired-page-title = Link wygasł
+expired-page-body = Ten link potwierdzający wygasł. Podaj swój adres e-mail, a wyślemy Ci nowy.
+expired-page-email = Adres e-mail
+expired-page-button = Wyślij nowy link
+invalid-token-page-title = Nieprawidłowy link
+invalid-token-page-body = Ten link potwierdzający jest nieprawidłowy. Sprawdź, czy skopiowałeś go w całości.
+
+## Privacy request emails
+privacy-request-footer = Ktoś poprosił o to, podając Twój adres e-mail. Jeśli to nie Ty, zignoruj tę wiadomość.
+data-access-subject = Twoje dane
+data-access-intro = Poprosiłeś o kopię danych, które o Tobie przechowujemy.
+data-access-link-text = Kliknij tutaj, aby je pobrać.
+data-access-text-cta = Otwórz { $link }, aby je pobrać.
+erasure-subject = Usunięcie Twoich danych
+erasure-intro = Poprosiłeś nas o usunięcie danych, które o Tobie przechowujemy.
+erasure-link-text = Kliknij tutaj, aby potwierdzić.
+erasure-text-cta = Otwórz { $link }, aby potwierdzić.
+
+## Privacy pages
+confirm-erasure-page-title = Usunąć Twoje dane?
+confirm-erasure-page-body = Twoje imię i adres e-mail zostaną trwale usunięte, a subskrypcja zakończona. Tego nie można cofnąć.
+confirm-erasure-page-button = Usuń moje dane
+erased-page-title = Twoje dane zostały usunięte
+erased-page-body = Usunęliśmy Twoje dane osobowe. Nie będziemy się już z Tobą kontaktować.
+invalid-privacy-link-page-title = Nieprawidłowy link
+invalid-privacy-link-page-body = Ten link jest nieprawidłowy lub wygasł. Złóż nowe żądanie.
+
+## Tracking
+tracking-opt-out-link-text = Przestań śledzić, kiedy otwieram ten newsletter lub klikam jego linki.
+tracking-opt-out-text-cta = Aby przestać śledzić, kiedy otwierasz ten newsletter lub klikasz jego linki, otwórz { $link }
+confirm-tracking-opt-out-page-title = Wyłączyć śledzenie?
+confirm-tracking-opt-out-page-body = Nie będziemy już zapisywać, kiedy otwierasz nasz newsletter lub klikasz jego linki. Twoja subskrypcja pozostaje aktywna.
+confirm-tracking-opt-out-page-button = Wyłącz śledzenie
+tracking-opted-out-page-title = Śledzenie wyłączone
+tracking-opted-out-page-body = Nie zapisujemy już, kiedy otwierasz nasz newsletter lub klikasz jego linki.
+invalid-tracking-link-page-title = Nieprawidłowy link
+invalid-tracking-link-page-body = Ten link jest nieprawidłowy. Sprawdź, czy skopiowałeś go w całości.
+
+## Feed digests
+feed-digest-subject = Nowości na blogu
+feed-digest-intro = Oto co opublikowaliśmy od ostatniego wydania:
+feed-digest-read-more = Czytaj dalej
+
+## Archive
+archive-page-empty = Nic jeszcze tu nie opublikowano.
+archive-page-newer = Nowsze wydania
+archive-page-older = Starsze wydania
+archive-page-feed = Subskrybuj kanał
+archived-issue-page-sent-on = Wysłano { $date }
+
+## Preference center
+preferences-subject = Zarządzaj subskrypcją
+preferences-intro = Poprosiłeś o zmianę sposobu otrzymywania naszego newslettera.
+preferences-link-text = Kliknij tutaj, aby zarządzać subskrypcją.
+preferences-text-cta = Otwórz { $link }, aby zarządzać subskrypcją.
+preferences-page-title = Twoja subskrypcja
+preferences-page-saved = Zmiany zostały zapisane.
+preferences-page-name = Imię
+preferences-page-lists = Listy
+preferences-page-delivery = Wysyłka
+preferences-page-not-paused = Wysyłaj mi każde wydanie
+preferences-page-paused-until = Wstrzymano do { $date }
+preferences-page-pause-weeks = Wstrzymaj na { $weeks } tyg.
+preferences-page-save = Zapisz
+preferences-page-unsubscribe-body = Nie chcesz już od nas nic otrzymywać?
+preferences-page-unsubscribe = Wypisz mnie ze wszystkiego
+preferences-error-name = Podaj prawidłowe imię.
+preferences-error-lists = Wybierz co najmniej jedną listę albo wypisz się ze wszystkiego.
+preferences-error-pause = Wybierz, na jak długo wstrzymać wysyłkę.
+preferences-signed-out-page-title = Zarządzaj subskrypcją
+preferences-signed-out-page-body = Podaj swój adres e-mail, a wyślemy Ci link do zarządzania subskrypcją.
+preferences-signed-out-page-email = Adres e-mail
+preferences-signed-out-page-button = Wyślij link
+unsubscribed-page-title = Zostałeś wypisany
+unsubscribed-page-body = Nie otrzymasz od nas więcej wydań.
+
+## Email address changes
+preferences-page-email = Adres e-mail
+preferences-page-change-email = Zmień adres e-mail
+preferences-email-change-sent = Jeśli nowego adresu można użyć, wysłaliśmy na niego link. Subskrypcja zostanie przeniesiona, gdy go otworzysz.
+preferences-error-email = Podaj prawidłowy nowy adres e-mail.
+email-change-subject = Potwierdź nowy adres e-mail
+email-change-intro = Poprosiłeś o wysyłanie naszego newslettera od teraz na ten adres.
+email-change-link-text = Kliknij tutaj, aby potwierdzić nowy adres e-mail.
+email-change-text-cta = Otwórz { $link }, aby potwierdzić nowy adres e-mail.
+email-changed-page-title = Twój adres e-mail został zmieniony
+email-changed-page-body = Od teraz będziemy wysyłać newsletter na Twój nowy adres.
+email-change-failed-page-title = Twój adres e-mail nie został zmieniony
+email-change-failed-taken = Ktoś jest już zapisany z tym adresem.
+email-change-failed-suppressed = Tego adresu nie można już zapisać.
+email-change-failed-stale = Twój adres e-mail zmienił się od czasu prośby. Poproś ponownie w centrum preferencji.
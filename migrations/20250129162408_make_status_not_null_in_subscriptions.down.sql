@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE subscriptions ALTER COLUMN status DROP NOT NULL;
@@ -0,0 +1,7 @@
+-- Add migration script here
+DROP INDEX subscriptions_subscribed_at_id_idx;
+ALTER TABLE subscription_tokens
+    DROP CONSTRAINT subscription_tokens_subscriber_id_fkey,
+    ADD CONSTRAINT subscription_tokens_subscriber_id_fkey
+        FOREIGN KEY (subscriber_id) REFERENCES subscriptions (id);
+ALTER TABLE subscriptions DROP COLUMN confirmed_at;
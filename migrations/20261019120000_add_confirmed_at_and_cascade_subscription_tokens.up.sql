@@ -0,0 +1,9 @@
+-- Add migration script here
+ALTER TABLE subscriptions ADD COLUMN confirmed_at timestamptz NULL;
+-- Deleting a subscriber takes their confirmation tokens with them.
+ALTER TABLE subscription_tokens
+    DROP CONSTRAINT subscription_tokens_subscriber_id_fkey,
+    ADD CONSTRAINT subscription_tokens_subscriber_id_fkey
+        FOREIGN KEY (subscriber_id) REFERENCES subscriptions (id) ON DELETE CASCADE;
+-- Supports paging through subscribers, newest first.
+CREATE INDEX subscriptions_subscribed_at_id_idx ON subscriptions (subscribed_at DESC, id DESC);
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- Where consent came from for subscribers confirmed without a double opt-in,
+-- e.g. an import from another provider.
+ALTER TABLE subscriptions ADD COLUMN consent_source TEXT NULL;
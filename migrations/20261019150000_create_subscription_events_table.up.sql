@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Append-only record of how each subscription came about and changed.
+CREATE TABLE subscription_events(
+    id BIGSERIAL PRIMARY KEY,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    occurred_at timestamptz NOT NULL,
+    ip TEXT NULL,
+    user_agent TEXT NULL,
+    request_id TEXT NULL,
+    source TEXT NULL
+);
+CREATE INDEX subscription_events_subscriber_id_idx ON subscription_events (subscriber_id, id);
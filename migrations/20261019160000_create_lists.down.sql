@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE subscription_events DROP COLUMN list_id;
+DELETE FROM subscription_tokens
+    WHERE list_id <> (SELECT id FROM lists WHERE slug = 'default');
+ALTER TABLE subscription_tokens DROP COLUMN list_id;
+DROP TABLE list_memberships;
+DROP TABLE lists;
@@ -0,0 +1,44 @@
+-- Add migration script here
+-- Publications people subscribe to; everyone so far goes on the default one.
+CREATE TABLE lists(
+    id uuid PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    sender_email TEXT NULL,
+    sender_name TEXT NULL,
+    confirmation_subject TEXT NULL,
+    confirmation_intro TEXT NULL,
+    created_at timestamptz NOT NULL
+);
+INSERT INTO lists (id, slug, name, created_at)
+    VALUES (gen_random_uuid(), 'default', 'Newsletter', now());
+
+-- Who is on which list, with a status of its own; `subscriptions.status`
+-- remains the status of the person.
+CREATE TABLE list_memberships(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    list_id uuid NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    subscribed_at timestamptz NOT NULL,
+    confirmed_at timestamptz NULL,
+    PRIMARY KEY (subscriber_id, list_id)
+);
+INSERT INTO list_memberships (subscriber_id, list_id, status, subscribed_at, confirmed_at)
+    SELECT s.id, l.id, s.status, s.subscribed_at, s.confirmed_at
+    FROM subscriptions s CROSS JOIN lists l
+    WHERE l.slug = 'default';
+
+-- A token confirms one membership.
+ALTER TABLE subscription_tokens ADD COLUMN list_id uuid NULL;
+UPDATE subscription_tokens SET list_id = (SELECT id FROM lists WHERE slug = 'default');
+ALTER TABLE subscription_tokens
+    ALTER COLUMN list_id SET NOT NULL,
+    ADD CONSTRAINT subscription_tokens_membership_fkey
+        FOREIGN KEY (subscriber_id, list_id)
+        REFERENCES list_memberships (subscriber_id, list_id) ON DELETE CASCADE;
+
+ALTER TABLE subscription_events
+    ADD COLUMN list_id uuid NULL REFERENCES lists (id) ON DELETE SET NULL;
+UPDATE subscription_events
+    SET list_id = (SELECT id FROM lists WHERE slug = 'default')
+    WHERE kind IN ('subscribed', 'confirmed', 'imported');
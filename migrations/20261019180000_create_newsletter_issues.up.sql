@@ -0,0 +1,19 @@
+-- Add migration script here
+-- Issues of a list's newsletter: `draft`, then `scheduled` for `send_at`,
+-- `sending` once an instance has claimed it, and finally `sent`.
+CREATE TABLE newsletter_issues(
+    id uuid PRIMARY KEY,
+    list_id uuid NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    segment TEXT NULL,
+    status TEXT NOT NULL,
+    send_at timestamptz NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    sent_at timestamptz NULL,
+    recipient_count BIGINT NULL
+);
+CREATE INDEX newsletter_issues_due_idx ON newsletter_issues (send_at)
+    WHERE status = 'scheduled';
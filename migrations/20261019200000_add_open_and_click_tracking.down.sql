@@ -0,0 +1,4 @@
+-- Add migration script here
+DROP TABLE tracking_events;
+ALTER TABLE subscriptions DROP COLUMN tracking_opt_out;
+ALTER TABLE newsletter_issues DROP COLUMN tracking;
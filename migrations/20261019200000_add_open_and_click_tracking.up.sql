@@ -0,0 +1,15 @@
+-- Add migration script here
+-- Open and click tracking: issues opt in, subscribers can opt out, and
+-- every open of the pixel or click through a rewritten link is an event.
+ALTER TABLE newsletter_issues ADD COLUMN tracking BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE subscriptions ADD COLUMN tracking_opt_out BOOLEAN NOT NULL DEFAULT false;
+CREATE TABLE tracking_events(
+    id BIGSERIAL PRIMARY KEY,
+    issue_id uuid NOT NULL REFERENCES newsletter_issues (id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    -- `open` or `click`; clicks have the url they went to.
+    kind TEXT NOT NULL,
+    url TEXT NULL,
+    occurred_at timestamptz NOT NULL
+);
+CREATE INDEX tracking_events_issue_id_idx ON tracking_events (issue_id, kind);
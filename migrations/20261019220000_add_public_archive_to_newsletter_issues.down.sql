@@ -0,0 +1,5 @@
+-- Add migration script here
+DROP INDEX newsletter_issues_archive_idx;
+DROP INDEX newsletter_issues_slug_idx;
+ALTER TABLE newsletter_issues DROP COLUMN slug;
+ALTER TABLE newsletter_issues DROP COLUMN public;
@@ -0,0 +1,10 @@
+-- Add migration script here
+-- Issues editors opt in show in the public archive once sent, at
+-- `/archive/<slug>`. Existing issues get a slug from their id.
+ALTER TABLE newsletter_issues ADD COLUMN public BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE newsletter_issues ADD COLUMN slug TEXT NULL;
+UPDATE newsletter_issues SET slug = 'issue-' || left(replace(id::text, '-', ''), 8);
+ALTER TABLE newsletter_issues ALTER COLUMN slug SET NOT NULL;
+CREATE UNIQUE INDEX newsletter_issues_slug_idx ON newsletter_issues (slug);
+CREATE INDEX newsletter_issues_archive_idx ON newsletter_issues (sent_at)
+    WHERE public AND status = 'sent';
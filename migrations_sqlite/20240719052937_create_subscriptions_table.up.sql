@@ -0,0 +1,9 @@
+-- Add migration script here
+-- Create Subscriptions Table
+CREATE TABLE subscriptions(
+    id BLOB NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    subscribed_at TEXT NOT NULL,
+    PRIMARY KEY (id)
+);
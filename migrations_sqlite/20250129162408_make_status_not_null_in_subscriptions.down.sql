@@ -0,0 +1,14 @@
+-- Add migration script here
+-- SQLite cannot alter a column's constraints, so the table is rebuilt.
+CREATE TABLE subscriptions_new(
+    id BLOB NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    subscribed_at TEXT NOT NULL,
+    status TEXT NULL,
+    PRIMARY KEY (id)
+);
+INSERT INTO subscriptions_new (id, email, name, subscribed_at, status)
+    SELECT id, email, name, subscribed_at, status FROM subscriptions;
+DROP TABLE subscriptions;
+ALTER TABLE subscriptions_new RENAME TO subscriptions;
@@ -0,0 +1,17 @@
+-- Add migration script here
+-- SQLite cannot alter a column's constraints, so the table is rebuilt.
+UPDATE subscriptions
+    SET status = 'confirmed'
+    WHERE status IS NULL;
+CREATE TABLE subscriptions_new(
+    id BLOB NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    subscribed_at TEXT NOT NULL,
+    status TEXT NOT NULL,
+    PRIMARY KEY (id)
+);
+INSERT INTO subscriptions_new (id, email, name, subscribed_at, status)
+    SELECT id, email, name, subscribed_at, status FROM subscriptions;
+DROP TABLE subscriptions;
+ALTER TABLE subscriptions_new RENAME TO subscriptions;
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE subscription_tokens(
+    token TEXT NOT NULL,
+    subscriber_id BLOB NOT NULL
+        REFERENCES subscriptions (id),
+    PRIMARY KEY (token)
+);
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE subscription_tokens DROP COLUMN created_at;
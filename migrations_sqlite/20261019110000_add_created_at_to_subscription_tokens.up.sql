@@ -0,0 +1,13 @@
+-- Add migration script here
+-- SQLite only adds columns with constant defaults, so the table is rebuilt.
+CREATE TABLE subscription_tokens_new(
+    token TEXT NOT NULL,
+    subscriber_id BLOB NOT NULL
+        REFERENCES subscriptions (id),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (token)
+);
+INSERT INTO subscription_tokens_new (token, subscriber_id)
+    SELECT token, subscriber_id FROM subscription_tokens;
+DROP TABLE subscription_tokens;
+ALTER TABLE subscription_tokens_new RENAME TO subscription_tokens;
@@ -0,0 +1,14 @@
+-- Add migration script here
+DROP INDEX subscriptions_subscribed_at_id_idx;
+CREATE TABLE subscription_tokens_new(
+    token TEXT NOT NULL,
+    subscriber_id BLOB NOT NULL
+        REFERENCES subscriptions (id),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (token)
+);
+INSERT INTO subscription_tokens_new (token, subscriber_id, created_at)
+    SELECT token, subscriber_id, created_at FROM subscription_tokens;
+DROP TABLE subscription_tokens;
+ALTER TABLE subscription_tokens_new RENAME TO subscription_tokens;
+ALTER TABLE subscriptions DROP COLUMN confirmed_at;
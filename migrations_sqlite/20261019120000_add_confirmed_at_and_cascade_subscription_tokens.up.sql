@@ -0,0 +1,17 @@
+-- Add migration script here
+ALTER TABLE subscriptions ADD COLUMN confirmed_at TEXT NULL;
+-- Deleting a subscriber takes their confirmation tokens with them; SQLite
+-- cannot change a foreign key, so the table is rebuilt.
+CREATE TABLE subscription_tokens_new(
+    token TEXT NOT NULL,
+    subscriber_id BLOB NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (token)
+);
+INSERT INTO subscription_tokens_new (token, subscriber_id, created_at)
+    SELECT token, subscriber_id, created_at FROM subscription_tokens;
+DROP TABLE subscription_tokens;
+ALTER TABLE subscription_tokens_new RENAME TO subscription_tokens;
+-- Supports paging through subscribers, newest first.
+CREATE INDEX subscriptions_subscribed_at_id_idx ON subscriptions (subscribed_at DESC, id DESC);
@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Hashes of erased addresses, which must not be imported again.
+CREATE TABLE suppressions(
+    email_hash TEXT NOT NULL PRIMARY KEY,
+    created_at TEXT NOT NULL
+);
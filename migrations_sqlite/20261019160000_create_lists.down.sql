@@ -0,0 +1,35 @@
+-- Add migration script here
+-- SQLite cannot drop columns used by foreign keys, so both tables are rebuilt.
+CREATE TABLE subscription_events_new(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    subscriber_id BLOB NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    occurred_at TEXT NOT NULL,
+    ip TEXT NULL,
+    user_agent TEXT NULL,
+    request_id TEXT NULL,
+    source TEXT NULL
+);
+INSERT INTO subscription_events_new
+        (id, subscriber_id, kind, occurred_at, ip, user_agent, request_id, source)
+    SELECT id, subscriber_id, kind, occurred_at, ip, user_agent, request_id, source
+    FROM subscription_events;
+DROP TABLE subscription_events;
+ALTER TABLE subscription_events_new RENAME TO subscription_events;
+CREATE INDEX subscription_events_subscriber_id_idx ON subscription_events (subscriber_id, id);
+
+CREATE TABLE subscription_tokens_new(
+    token TEXT NOT NULL,
+    subscriber_id BLOB NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (token)
+);
+INSERT INTO subscription_tokens_new (token, subscriber_id, created_at)
+    SELECT token, subscriber_id, created_at FROM subscription_tokens
+    WHERE list_id = (SELECT id FROM lists WHERE slug = 'default');
+DROP TABLE subscription_tokens;
+ALTER TABLE subscription_tokens_new RENAME TO subscription_tokens;
+
+DROP TABLE list_memberships;
+DROP TABLE lists;
@@ -0,0 +1,54 @@
+-- Add migration script here
+-- Publications people subscribe to; everyone so far goes on the default one.
+CREATE TABLE lists(
+    id BLOB NOT NULL PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    sender_email TEXT NULL,
+    sender_name TEXT NULL,
+    confirmation_subject TEXT NULL,
+    confirmation_intro TEXT NULL,
+    created_at TEXT NOT NULL
+);
+INSERT INTO lists (id, slug, name, created_at)
+    VALUES (randomblob(16), 'default', 'Newsletter', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+
+-- Who is on which list, with a status of its own; `subscriptions.status`
+-- remains the status of the person.
+CREATE TABLE list_memberships(
+    subscriber_id BLOB NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    list_id BLOB NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    subscribed_at TEXT NOT NULL,
+    confirmed_at TEXT NULL,
+    PRIMARY KEY (subscriber_id, list_id)
+);
+INSERT INTO list_memberships (subscriber_id, list_id, status, subscribed_at, confirmed_at)
+    SELECT s.id, l.id, s.status, s.subscribed_at, s.confirmed_at
+    FROM subscriptions s CROSS JOIN lists l
+    WHERE l.slug = 'default';
+
+-- A token confirms one membership; SQLite cannot add a foreign key, so the
+-- table is rebuilt.
+CREATE TABLE subscription_tokens_new(
+    token TEXT NOT NULL,
+    subscriber_id BLOB NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    list_id BLOB NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (token),
+    FOREIGN KEY (subscriber_id, list_id)
+        REFERENCES list_memberships (subscriber_id, list_id) ON DELETE CASCADE
+);
+INSERT INTO subscription_tokens_new (token, subscriber_id, list_id, created_at)
+    SELECT t.token, t.subscriber_id, l.id, t.created_at
+    FROM subscription_tokens t CROSS JOIN lists l
+    WHERE l.slug = 'default';
+DROP TABLE subscription_tokens;
+ALTER TABLE subscription_tokens_new RENAME TO subscription_tokens;
+
+ALTER TABLE subscription_events
+    ADD COLUMN list_id BLOB NULL REFERENCES lists (id) ON DELETE SET NULL;
+UPDATE subscription_events
+    SET list_id = (SELECT id FROM lists WHERE slug = 'default')
+    WHERE kind IN ('subscribed', 'confirmed', 'imported');
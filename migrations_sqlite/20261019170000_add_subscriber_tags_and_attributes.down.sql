@@ -0,0 +1,3 @@
+-- Add migration script here
+DROP TABLE subscriber_tags;
+ALTER TABLE subscriptions DROP COLUMN attributes;
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- Free-form key/value details as a JSON object, limited to the keys in
+-- `segmentation.attribute_keys`, and tags; both can be used in segments.
+ALTER TABLE subscriptions ADD COLUMN attributes TEXT NOT NULL DEFAULT '{}';
+
+CREATE TABLE subscriber_tags(
+    subscriber_id BLOB NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, tag)
+);
+CREATE INDEX subscriber_tags_tag_idx ON subscriber_tags (tag);
@@ -0,0 +1,16 @@
+-- Add migration script here
+-- One row per recipient of an issue: `queued` when the issue starts going
+-- out, then `sent` with the provider's message id, or `failed` with the
+-- last error; `bounced` once the provider reports it did not arrive.
+CREATE TABLE deliveries(
+    issue_id BLOB NOT NULL REFERENCES newsletter_issues (id) ON DELETE CASCADE,
+    subscriber_id BLOB NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    message_id TEXT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (issue_id, subscriber_id)
+);
+CREATE INDEX deliveries_message_id_idx ON deliveries (message_id);
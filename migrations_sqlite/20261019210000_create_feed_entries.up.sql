@@ -0,0 +1,18 @@
+-- Add migration script here
+-- Entries of the feeds digest issues are drafted from, one row per entry
+-- however often it is fetched. `digested_at` is set once an entry made it
+-- into a digest.
+CREATE TABLE feed_entries(
+    id BLOB NOT NULL PRIMARY KEY,
+    feed_url TEXT NOT NULL,
+    guid TEXT NOT NULL,
+    title TEXT NOT NULL,
+    link TEXT NULL,
+    summary TEXT NULL,
+    published_at TEXT NULL,
+    fetched_at TEXT NOT NULL,
+    digested_at TEXT NULL,
+    UNIQUE (feed_url, guid)
+);
+CREATE INDEX feed_entries_undigested_idx ON feed_entries (fetched_at)
+    WHERE digested_at IS NULL;
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- Subscribers can pause delivery from the preference center; issues skip
+-- them until then.
+ALTER TABLE subscriptions ADD COLUMN paused_until TEXT NULL;
@@ -1,11 +1,94 @@
-use crate::email_client;
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+
+use crate::{
+    audit::TrustedProxies,
+    configuration::{
+        AdminSettings, ArchiveSettings, ConfirmationSettings, SchedulerSettings, SegmentationSettings,
+        TrackingSettings,
+    },
+    confirmation::ResendThrottle,
+    email_client, email_validation::EmailValidator,
+    links::LinkBuilder, preferences::{EmailChangeSigner, SessionSigner}, privacy::LinkSigner, repository::Repository,
+    templates::Templates, tracking::TrackingSigner,
+};
 
-use sqlx::PgPool;
 use email_client::EmailClient;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub connection_pool: PgPool,
+    pub subscribers: Arc<dyn Repository>,
+    /// The shared client for outbound requests; see
+    /// [`startup::http_client`](crate::startup::http_client).
+    pub http_client: reqwest::Client,
     pub email_client: EmailClient,
-    pub host: String,
-}
\ No newline at end of file
+    pub email_validator: EmailValidator,
+    pub templates: Templates,
+    pub links: LinkBuilder,
+    pub confirmation: ConfirmationSettings,
+    pub resends: ResendThrottle,
+    pub admin: AdminSettings,
+    pub privacy_links: LinkSigner,
+    /// Turns away access and erasure requests made too often.
+    pub privacy_requests: ResendThrottle,
+    pub preference_sessions: SessionSigner,
+    pub email_changes: EmailChangeSigner,
+    /// Turns away email changes asked for too often, by the subscriber or
+    /// for the address.
+    pub email_change_requests: ResendThrottle,
+    pub segmentation: SegmentationSettings,
+    pub tracking: TrackingSettings,
+    /// Present whenever a tracking key is configured, so links keep
+    /// working while tracking is off.
+    pub tracking_links: Option<TrackingSigner>,
+    pub archive: ArchiveSettings,
+    pub trusted_proxies: TrustedProxies,
+    pub scheduler: SchedulerSettings,
+}
+
+impl FromRef<AppState> for TrustedProxies {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.trusted_proxies
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// Default settings around `subscribers`, sending emails to `email_base_url`.
+    pub(crate) fn for_tests(subscribers: Arc<dyn Repository>, email_base_url: String) -> Self {
+        use crate::{
+            configuration::EmailValidationSettings, domain::SubscriberEmail, i18n::Localizer,
+        };
+
+        let http_client = reqwest::Client::new();
+        Self {
+            subscribers,
+            http_client: http_client.clone(),
+            email_client: EmailClient::new(
+                http_client,
+                email_base_url,
+                SubscriberEmail::parse("newsletter@example.com".into()).unwrap(),
+                secrecy::SecretString::from("token"),
+                std::time::Duration::from_millis(200),
+            ),
+            email_validator: EmailValidator::new(&EmailValidationSettings::default(), None).unwrap(),
+            templates: Templates::new(None, Arc::new(Localizer::new().unwrap())).unwrap(),
+            links: LinkBuilder::new("http://127.0.0.1".parse().unwrap()).unwrap(),
+            confirmation: ConfirmationSettings::default(),
+            resends: ResendThrottle::new(chrono::Duration::minutes(15), 8),
+            admin: AdminSettings::default(),
+            privacy_links: LinkSigner::new("privacy-key".to_owned().into(), chrono::Duration::hours(24)),
+            privacy_requests: ResendThrottle::new(chrono::Duration::minutes(15), 8),
+            preference_sessions: SessionSigner::new("privacy-key".to_owned().into(), chrono::Duration::minutes(60)),
+            email_changes: EmailChangeSigner::new("privacy-key".to_owned().into(), chrono::Duration::hours(24)),
+            email_change_requests: ResendThrottle::new(chrono::Duration::minutes(15), 8),
+            segmentation: SegmentationSettings::default(),
+            tracking: TrackingSettings::default(),
+            tracking_links: None,
+            archive: ArchiveSettings::default(),
+            trusted_proxies: TrustedProxies::default(),
+            scheduler: SchedulerSettings::default(),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! src/archive.rs
+//!
+//! The public archive of sent issues, and its Atom feed.
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{links::LinkBuilder, repository::NewsletterIssue};
+
+/// Escapes `text` for XML content and attribute values.
+pub fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// When an archived issue was published; sent issues all have a `sent_at`.
+pub fn published_at(issue: &NewsletterIssue) -> DateTime<Utc> {
+    issue.sent_at.unwrap_or(issue.updated_at)
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// An Atom feed titled `title` of `issues`, newest first, with their full
+/// HTML content.
+pub fn atom_feed(title: &str, links: &LinkBuilder, issues: &[NewsletterIssue]) -> String {
+    let archive_link = links.archive_page_link(1);
+    // An empty feed has not changed since the epoch, which keeps it
+    // cacheable like any other.
+    let updated = issues
+        .iter()
+        .map(published_at)
+        .max()
+        .unwrap_or(DateTime::UNIX_EPOCH);
+    let mut feed = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>{archive}</id>
+  <title>{title}</title>
+  <updated>{updated}</updated>
+  <link rel="alternate" type="text/html" href="{archive}"/>
+  <link rel="self" type="application/atom+xml" href="{feed}"/>
+"#,
+        archive = xml_escape(archive_link.as_str()),
+        title = xml_escape(title),
+        updated = timestamp(updated),
+        feed = xml_escape(links.archive_feed_link().as_str()),
+    );
+    for issue in issues {
+        let published = timestamp(published_at(issue));
+        feed.push_str(&format!(
+            r#"  <entry>
+    <id>urn:uuid:{id}</id>
+    <title>{title}</title>
+    <published>{published}</published>
+    <updated>{published}</updated>
+    <link rel="alternate" type="text/html" href="{link}"/>
+    <content type="html">{content}</content>
+  </entry>
+"#,
+            id = issue.id,
+            title = xml_escape(&issue.title),
+            link = xml_escape(links.archive_link(&issue.slug).as_str()),
+            content = xml_escape(&issue.html_content),
+        ));
+    }
+    feed.push_str("</feed>\n");
+    feed
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    use crate::{archive::atom_feed, links::LinkBuilder, repository::NewsletterIssue};
+
+    fn issue(title: &str, slug: &str, sent_at: i64) -> NewsletterIssue {
+        let sent_at = Utc.timestamp_opt(sent_at, 0).unwrap();
+        NewsletterIssue {
+            id: Uuid::new_v4(),
+            list_id: Uuid::new_v4(),
+            list: "default".into(),
+            title: title.into(),
+            html_content: r#"<p>Fish &amp; chips, <a href="https://example.com/?a=1&amp;b=2">here</a></p>"#.into(),
+            text_content: "Fish & chips".into(),
+            segment: None,
+            tracking: false,
+            public: true,
+            slug: slug.into(),
+            status: "sent".into(),
+            send_at: None,
+            created_at: sent_at,
+            updated_at: sent_at,
+            sent_at: Some(sent_at),
+            recipient_count: Some(1),
+        }
+    }
+
+    #[test]
+    fn feeds_parse_back_into_their_issues() {
+        let links = LinkBuilder::new("https://example.com/news".parse().unwrap()).unwrap();
+        let issues = [
+            issue("Issue #2: <Rust> & \"you\"", "issue-2-rust-you-00000002", 1_760_000_000),
+            issue("Issue #1", "issue-1-00000001", 1_750_000_000),
+        ];
+
+        let feed = feed_rs::parser::parse(atom_feed("Tom's archive", &links, &issues).as_bytes()).unwrap();
+
+        assert_eq!(feed.title.unwrap().content, "Tom's archive");
+        assert_eq!(feed.updated, issues[0].sent_at);
+        assert_eq!(feed.entries.len(), 2);
+        let entry = &feed.entries[0];
+        assert_eq!(entry.id, format!("urn:uuid:{}", issues[0].id));
+        assert_eq!(entry.title.as_ref().unwrap().content, "Issue #2: <Rust> & \"you\"");
+        assert_eq!(entry.links[0].href, "https://example.com/news/archive/issue-2-rust-you-00000002");
+        assert_eq!(entry.published, issues[0].sent_at);
+        assert_eq!(entry.content.as_ref().unwrap().body.as_deref(), Some(issues[0].html_content.as_str()));
+    }
+}
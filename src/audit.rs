@@ -0,0 +1,206 @@
+//! src/audit.rs
+//!
+//! The consent audit trail: an append-only list of events per subscriber,
+//! recording when and how they subscribed, confirmed and so on, and from
+//! which request.
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::{ConnectInfo, FromRef, FromRequestParts};
+use axum::http::{header::USER_AGENT, request::Parts, HeaderMap};
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// Through the subscription form, to one list.
+    Subscribed,
+    Confirmed,
+    Imported,
+    Unsubscribed,
+    /// Soft-deleted by an admin. Hard deletes take the trail with them.
+    Deleted,
+    /// At the subscriber's request; see [`crate::privacy`].
+    Erased,
+    /// Moved to a new address they confirmed.
+    EmailChanged,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Subscribed => "subscribed",
+            EventKind::Confirmed => "confirmed",
+            EventKind::Imported => "imported",
+            EventKind::Unsubscribed => "unsubscribed",
+            EventKind::Deleted => "deleted",
+            EventKind::Erased => "erased",
+            EventKind::EmailChanged => "email_changed",
+        }
+    }
+}
+
+/// Where an event came from. Extracted from the request by handlers; the
+/// CLI fills in `source` only.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventContext {
+    /// The client address as our trusted proxies saw it, otherwise the
+    /// peer address.
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// From the `x-request-id` header our middleware sets.
+    pub request_id: Option<String>,
+    /// The form or process behind the event, e.g. `import`.
+    pub source: Option<String>,
+}
+
+impl EventContext {
+    pub fn with_source(self, source: impl Into<String>) -> Self {
+        Self {
+            source: Some(source.into()),
+            ..self
+        }
+    }
+
+    /// Without the fields that identify a person.
+    pub fn anonymized(&self) -> Self {
+        Self {
+            ip: None,
+            user_agent: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// How many proxies in front of us append to `X-Forwarded-For`. Clients
+/// can send the header too, so only the entries those proxies added are
+/// believed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrustedProxies(pub usize);
+
+impl TrustedProxies {
+    /// Each proxy appends the address it got the request from, so the
+    /// first of the entries our proxies added is the client's. `None` if
+    /// the request did not come through them all.
+    fn client(&self, headers: &HeaderMap) -> Option<IpAddr> {
+        if self.0 == 0 {
+            return None;
+        }
+        let hops: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        hops.get(hops.len().checked_sub(self.0)?)?.parse().ok()
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for EventContext
+where
+    TrustedProxies: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let header = |headers: &HeaderMap, name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let forwarded_for = TrustedProxies::from_ref(state)
+            .client(&parts.headers)
+            .map(|ip| ip.to_string());
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string());
+        Ok(EventContext {
+            ip: forwarded_for.or(peer),
+            user_agent: header(&parts.headers, USER_AGENT.as_str()),
+            request_id: header(&parts.headers, "x-request-id"),
+            source: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct SubscriptionEvent {
+    pub kind: String,
+    /// The list's slug, for events about a single list.
+    pub list: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub source: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use axum::extract::{ConnectInfo, FromRequestParts};
+    use axum::http::Request;
+
+    use crate::audit::{EventContext, TrustedProxies};
+
+    async fn context_behind(proxies: usize, request: Request<()>) -> EventContext {
+        let (mut parts, _) = request.into_parts();
+        EventContext::from_request_parts(&mut parts, &TrustedProxies(proxies))
+            .await
+            .unwrap()
+    }
+
+    async fn context(request: Request<()>) -> EventContext {
+        context_behind(0, request).await
+    }
+
+    #[tokio::test]
+    async fn context_is_taken_from_the_request() {
+        let mut request = Request::builder()
+            .header("user-agent", "curl/8")
+            .header("x-request-id", "abc")
+            .body(())
+            .unwrap();
+        let peer: SocketAddr = "192.0.2.1:4000".parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(peer));
+
+        assert_eq!(
+            context(request).await,
+            EventContext {
+                ip: Some("192.0.2.1".into()),
+                user_agent: Some("curl/8".into()),
+                request_id: Some("abc".into()),
+                source: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn the_address_our_proxy_forwarded_wins_over_the_proxy() {
+        let request = |forwarded_for: &str| {
+            let mut request = Request::builder()
+                .header("x-forwarded-for", forwarded_for)
+                .body(())
+                .unwrap();
+            let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+            request.extensions_mut().insert(ConnectInfo(peer));
+            request
+        };
+
+        // The client claimed to be 203.0.113.9; our proxy saw 198.51.100.7.
+        let spoofed = "203.0.113.9, 198.51.100.7";
+        assert_eq!(context_behind(1, request(spoofed)).await.ip.as_deref(), Some("198.51.100.7"));
+        assert_eq!(
+            context_behind(2, request("203.0.113.9, 198.51.100.7, 10.0.0.2")).await.ip.as_deref(),
+            Some("198.51.100.7")
+        );
+        // Without trusted proxies, or with fewer hops than proxies, the
+        // header is ignored.
+        assert_eq!(context(request(spoofed)).await.ip.as_deref(), Some("10.0.0.1"));
+        assert_eq!(context_behind(3, request(spoofed)).await.ip.as_deref(), Some("10.0.0.1"));
+        assert_eq!(context_behind(1, request("not an address")).await.ip.as_deref(), Some("10.0.0.1"));
+    }
+}
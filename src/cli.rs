@@ -0,0 +1,249 @@
+//! src/cli.rs
+use std::path::{Path, PathBuf};
+
+use eyre::{eyre, Result};
+
+use crate::export::{ExportColumn, ExportFormat};
+use crate::import::ImportFormat;
+
+const USAGE: &str = "Usage: zero-to-prod [migrate up | migrate status | migrate down-to <version> \
+    | import <file> [--format csv|jsonl] [--list <slug>] [--consent-source <source>] \
+    | export <file> [--format csv|jsonl] [--status <status>] [--columns <column,...>]]";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Serve HTTP requests; what runs without arguments.
+    Serve,
+    Migrate(MigrateCommand),
+    Import(ImportCommand),
+    Export(ExportCommand),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MigrateCommand {
+    Up,
+    Status,
+    DownTo(i64),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImportCommand {
+    pub path: PathBuf,
+    /// Taken from the file extension unless given with `--format`.
+    pub format: ImportFormat,
+    /// Slug of the list to import into; the default list if absent.
+    pub list: Option<String>,
+    /// With `--consent-source`, everyone is imported as confirmed.
+    pub consent_source: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExportCommand {
+    pub path: PathBuf,
+    /// Taken from the file extension unless given with `--format`.
+    pub format: ExportFormat,
+    pub status: Option<String>,
+    pub columns: Vec<ExportColumn>,
+}
+
+impl Command {
+    /// Parses the arguments following the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let args: Vec<String> = args.into_iter().collect();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            [] | ["serve"] => Ok(Command::Serve),
+            ["migrate", "up"] => Ok(Command::Migrate(MigrateCommand::Up)),
+            ["migrate", "status"] => Ok(Command::Migrate(MigrateCommand::Status)),
+            ["migrate", "down-to", version] => {
+                let version = version
+                    .parse()
+                    .map_err(|_| eyre!("Invalid migration version {}\n{}", version, USAGE))?;
+                Ok(Command::Migrate(MigrateCommand::DownTo(version)))
+            }
+            ["import", path, options @ ..] => parse_import(path, options).map(Command::Import),
+            ["export", path, options @ ..] => parse_export(path, options).map(Command::Export),
+            _ => Err(eyre!(USAGE)),
+        }
+    }
+}
+
+fn parse_import(path: &str, mut options: &[&str]) -> Result<ImportCommand> {
+    let mut format = None;
+    let mut list = None;
+    let mut consent_source = None;
+    loop {
+        match options {
+            [] => break,
+            ["--format", value, rest @ ..] => {
+                format = Some(value.parse().map_err(|e| eyre!("{}\n{}", e, USAGE))?);
+                options = rest;
+            }
+            ["--list", value, rest @ ..] => {
+                list = Some(value.to_string());
+                options = rest;
+            }
+            ["--consent-source", value, rest @ ..] => {
+                consent_source = Some(value.to_string());
+                options = rest;
+            }
+            _ => return Err(eyre!(USAGE)),
+        }
+    }
+    let path = PathBuf::from(path);
+    let format = format_of(&path, format)?;
+    Ok(ImportCommand {
+        path,
+        format,
+        list,
+        consent_source,
+    })
+}
+
+fn parse_export(path: &str, mut options: &[&str]) -> Result<ExportCommand> {
+    let mut format = None;
+    let mut status = None;
+    let mut columns = ExportColumn::ALL.to_vec();
+    loop {
+        match options {
+            [] => break,
+            ["--format", value, rest @ ..] => {
+                format = Some(value.parse().map_err(|e| eyre!("{}\n{}", e, USAGE))?);
+                options = rest;
+            }
+            ["--status", value, rest @ ..] => {
+                status = Some(value.to_string());
+                options = rest;
+            }
+            ["--columns", value, rest @ ..] => {
+                columns = ExportColumn::parse_list(value).map_err(|e| eyre!("{}\n{}", e, USAGE))?;
+                options = rest;
+            }
+            _ => return Err(eyre!(USAGE)),
+        }
+    }
+    let path = PathBuf::from(path);
+    let format = format_of(&path, format)?;
+    Ok(ExportCommand {
+        path,
+        format,
+        status,
+        columns,
+    })
+}
+
+/// `format` if given, otherwise whatever the file extension names.
+fn format_of<F: std::str::FromStr>(path: &Path, format: Option<F>) -> Result<F> {
+    match format {
+        Some(format) => Ok(format),
+        None => path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+            .ok_or_else(|| {
+                eyre!("Cannot tell the format of {}; pass --format\n{}", path.display(), USAGE)
+            }),
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod tests {
+    use claim::assert_err;
+
+    use crate::cli::{Command, ExportCommand, ImportCommand, MigrateCommand};
+    use crate::export::{ExportColumn, ExportFormat};
+    use crate::import::ImportFormat;
+
+    fn parse(args: &[&str]) -> eyre::Result<Command> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_serves() {
+        assert_eq!(parse(&[]).unwrap(), Command::Serve);
+        assert_eq!(parse(&["serve"]).unwrap(), Command::Serve);
+    }
+
+    #[test]
+    fn migrate_subcommands_are_parsed() {
+        assert_eq!(parse(&["migrate", "up"]).unwrap(), Command::Migrate(MigrateCommand::Up));
+        assert_eq!(
+            parse(&["migrate", "status"]).unwrap(),
+            Command::Migrate(MigrateCommand::Status)
+        );
+        assert_eq!(
+            parse(&["migrate", "down-to", "20250129162809"]).unwrap(),
+            Command::Migrate(MigrateCommand::DownTo(20250129162809))
+        );
+    }
+
+    #[test]
+    fn import_takes_its_format_from_the_extension_unless_given() {
+        assert_eq!(
+            parse(&["import", "list.csv"]).unwrap(),
+            Command::Import(ImportCommand {
+                path: "list.csv".into(),
+                format: ImportFormat::Csv,
+                list: None,
+                consent_source: None,
+            })
+        );
+        assert_eq!(
+            parse(&[
+                "import",
+                "export.txt",
+                "--consent-source",
+                "old provider",
+                "--format",
+                "jsonl",
+                "--list",
+                "weekly",
+            ])
+            .unwrap(),
+            Command::Import(ImportCommand {
+                path: "export.txt".into(),
+                format: ImportFormat::Jsonl,
+                list: Some("weekly".into()),
+                consent_source: Some("old provider".into()),
+            })
+        );
+        assert_err!(parse(&["import", "export.txt"]));
+        assert_err!(parse(&["import", "list.csv", "--format", "xml"]));
+        assert_err!(parse(&["import", "list.csv", "--consent-source"]));
+    }
+
+    #[test]
+    fn export_takes_a_status_and_columns() {
+        assert_eq!(
+            parse(&["export", "out.jsonl", "--status", "confirmed", "--columns", "email,name"])
+                .unwrap(),
+            Command::Export(ExportCommand {
+                path: "out.jsonl".into(),
+                format: ExportFormat::Jsonl,
+                status: Some("confirmed".into()),
+                columns: vec![ExportColumn::Email, ExportColumn::Name],
+            })
+        );
+        assert_eq!(
+            parse(&["export", "out", "--format", "csv"]).unwrap(),
+            Command::Export(ExportCommand {
+                path: "out".into(),
+                format: ExportFormat::Csv,
+                status: None,
+                columns: ExportColumn::ALL.to_vec(),
+            })
+        );
+        assert_err!(parse(&["export", "out"]));
+        assert_err!(parse(&["export", "out.csv", "--columns", "password"]));
+    }
+
+    #[test]
+    fn unknown_or_incomplete_commands_are_rejected() {
+        assert_err!(parse(&["migrate"]));
+        assert_err!(parse(&["migrate", "down-to"]));
+        assert_err!(parse(&["migrate", "down-to", "latest"]));
+        assert_err!(parse(&["frobnicate"]));
+        assert_err!(parse(&["import"]));
+    }
+}
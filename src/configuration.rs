@@ -11,6 +11,29 @@ pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    #[serde(default)]
+    pub http_client: HttpClientSettings,
+    #[serde(default)]
+    pub email_validation: EmailValidationSettings,
+    #[serde(default)]
+    pub templates: TemplateSettings,
+    #[serde(default)]
+    pub confirmation: ConfirmationSettings,
+    #[serde(default)]
+    pub admin: AdminSettings,
+    pub privacy: PrivacySettings,
+    #[serde(default)]
+    pub segmentation: SegmentationSettings,
+    #[serde(default)]
+    pub scheduler: SchedulerSettings,
+    #[serde(default)]
+    pub tracking: TrackingSettings,
+    #[serde(default)]
+    pub feeds: FeedSettings,
+    #[serde(default)]
+    pub archive: ArchiveSettings,
+    #[serde(default)]
+    pub preferences: PreferenceSettings,
 }
 
 #[derive(serde::Deserialize)]
@@ -18,10 +41,30 @@ pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub host: String,
+    /// Public URL the application is reachable at, used to build links.
+    pub base_url: url::Url,
+    /// How many proxies in front of the application append to
+    /// `X-Forwarded-For`; client addresses are taken from what they added.
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub trusted_proxies: usize,
 }
 
-#[derive(serde::Deserialize)]
+/// Where subscribers are stored. `Sqlite` needs the `sqlite` feature.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    #[default]
+    Postgres,
+    Sqlite,
+}
+
+#[derive(serde::Deserialize, Clone)]
 pub struct DatabaseSettings {
+    #[serde(default)]
+    pub backend: DatabaseBackend,
+    /// Database file used by the SQLite backend.
+    #[serde(default = "default_sqlite_path")]
+    pub sqlite_path: String,
     pub username: String,
     pub password: SecretString,
     #[serde(deserialize_with = "deserialize_number_from_string")]
@@ -29,6 +72,10 @@ pub struct DatabaseSettings {
     pub host: String,
     pub database_name: String,
     pub require_ssl: bool,
+    /// Apply pending migrations before serving; otherwise the application
+    /// refuses to start until `zero-to-prod migrate up` has been run.
+    #[serde(default)]
+    pub run_migrations_on_startup: bool,
 }
 
 #[derive(serde::Deserialize)]
@@ -37,6 +84,589 @@ pub struct EmailClientSettings {
     pub sender_email: String,
     pub authorization_token: SecretString,
     pub timeout_milliseconds: u64,
+    #[serde(default)]
+    pub batch: BatchSettings,
+}
+
+/// How newsletter issues go out through the provider's batch API.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct BatchSettings {
+    /// Messages per request; the provider takes 500 at most.
+    #[serde(
+        default = "default_batch_size",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub size: usize,
+    /// Requests in flight at once.
+    #[serde(
+        default = "default_batch_concurrency",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub concurrency: usize,
+    /// Requests started per second at most, across all batches and their
+    /// retries; no limit if 0.
+    #[serde(
+        default = "default_batch_requests_per_second",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub requests_per_second: u32,
+    /// How many more times messages that failed are sent again.
+    #[serde(
+        default = "default_batch_max_retries",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_retries: u32,
+    /// How long to wait before each retry.
+    #[serde(
+        default = "default_batch_retry_delay_milliseconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub retry_delay_milliseconds: u64,
+}
+
+/// The HTTP client shared by the email client and every other outbound
+/// integration.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct HttpClientSettings {
+    #[serde(
+        default = "default_connect_timeout_milliseconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub connect_timeout_milliseconds: u64,
+    /// How long to wait for each read from an open connection.
+    #[serde(
+        default = "default_read_timeout_milliseconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub read_timeout_milliseconds: u64,
+    /// How long an unused connection stays in the pool.
+    #[serde(
+        default = "default_pool_idle_timeout_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub pool_idle_timeout_seconds: u64,
+    /// Unused connections kept per host at most; no limit if unset.
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    #[serde(default)]
+    pub http_version: HttpVersion,
+    /// PEM file with certificate authorities to trust on top of the
+    /// built-in ones.
+    #[serde(default)]
+    pub ca_bundle_path: Option<String>,
+    /// Send every request through this proxy, instead of the one the
+    /// `HTTP_PROXY`/`HTTPS_PROXY` variables name, if any.
+    #[serde(default)]
+    pub proxy: Option<ProxySettings>,
+}
+
+/// Which HTTP version outbound requests use.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpVersion {
+    /// HTTP/2 where the server offers it during the TLS handshake, and
+    /// HTTP/1.1 otherwise.
+    #[default]
+    Auto,
+    Http1,
+    /// HTTP/2 only, even without TLS; the server has to support it.
+    Http2,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct ProxySettings {
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<SecretString>,
+    /// Comma-separated hosts, domains and IP ranges reached directly, in
+    /// the `NO_PROXY` format.
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+}
+
+#[derive(serde::Deserialize, Default)]
+pub struct EmailValidationSettings {
+    /// File with one disposable email domain per line.
+    #[serde(default)]
+    pub disposable_domains_path: Option<String>,
+    /// If non-empty, only these domains (and their subdomains) may subscribe.
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    #[serde(default)]
+    pub denied_domains: Vec<String>,
+    /// Reject domains that have no MX records.
+    #[serde(default)]
+    pub check_mx: bool,
+}
+
+#[derive(serde::Deserialize, Default)]
+pub struct TemplateSettings {
+    /// Directory whose files override the templates embedded in the binary.
+    #[serde(default)]
+    pub directory: Option<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct ConfirmationSettings {
+    /// How long a confirmation link stays valid.
+    #[serde(
+        default = "default_token_ttl_hours",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub token_ttl_hours: i64,
+    /// How long an address waits between confirmation emails it asks to
+    /// have sent again.
+    #[serde(
+        default = "default_resend_interval_minutes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub resend_interval_minutes: i64,
+    /// How many resends run at once; requests past that are turned away.
+    #[serde(
+        default = "default_max_concurrent_resends",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_concurrent_resends: usize,
+    #[serde(default)]
+    pub redirects: ConfirmationRedirects,
+}
+
+/// Where to send browsers after each confirmation outcome, instead of
+/// rendering our own page.
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct ConfirmationRedirects {
+    #[serde(default)]
+    pub confirmed: Option<url::Url>,
+    #[serde(default)]
+    pub already_confirmed: Option<url::Url>,
+    #[serde(default)]
+    pub expired: Option<url::Url>,
+    #[serde(default)]
+    pub invalid_token: Option<url::Url>,
+}
+
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct AdminSettings {
+    /// Bearer token for the `/admin` endpoints, which refuse every request
+    /// while it is unset.
+    #[serde(default)]
+    pub api_token: Option<SecretString>,
+}
+
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct SegmentationSettings {
+    /// Attributes subscribers may have; any other key is rejected, both
+    /// when storing attributes and in segments.
+    #[serde(default)]
+    pub attribute_keys: Vec<String>,
+    /// Tags the signup form may set; admins can set any tag.
+    #[serde(default)]
+    pub signup_tags: Vec<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SchedulerSettings {
+    /// Send scheduled newsletter issues from this instance once they are
+    /// due; any number of instances can.
+    #[serde(default = "default_scheduler_enabled")]
+    pub enabled: bool,
+    /// How often to look for due issues.
+    #[serde(
+        default = "default_poll_interval_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub poll_interval_seconds: u64,
+    /// How long an issue may go without progress while sending before a
+    /// poll takes it over, as if the instance sending it had stopped.
+    #[serde(
+        default = "default_stalled_after_minutes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub stalled_after_minutes: i64,
+}
+
+/// Blog feeds whose new entries are collected into digest issues.
+#[derive(serde::Deserialize, Clone)]
+pub struct FeedSettings {
+    /// Poll the feeds from this instance; any number of instances can,
+    /// each new entry still makes it into one digest only.
+    #[serde(default)]
+    pub enabled: bool,
+    /// RSS or Atom feeds.
+    #[serde(default)]
+    pub urls: Vec<url::Url>,
+    #[serde(
+        default = "default_feed_poll_interval_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub poll_interval_seconds: u64,
+    /// Slug of the list digest issues are for.
+    #[serde(default = "default_feed_list")]
+    pub list: String,
+    #[serde(default)]
+    pub digest: DigestMode,
+}
+
+/// What becomes of a digest issue once drafted.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestMode {
+    /// It stays a draft, for an editor to review and schedule.
+    #[default]
+    Draft,
+    /// It is scheduled right away, and goes out once the scheduler next
+    /// looks for due issues.
+    Send,
+}
+
+/// The public web archive of the issues published in it.
+#[derive(serde::Deserialize, Clone)]
+pub struct ArchiveSettings {
+    /// Shown atop the archive and as the title of its feed.
+    #[serde(default = "default_archive_title")]
+    pub title: String,
+    /// Issues per page of the archive, and in its feed.
+    #[serde(
+        default = "default_archive_page_size",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub page_size: i64,
+    /// How long browsers and CDNs may cache archive responses.
+    #[serde(
+        default = "default_archive_max_age_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_age_seconds: u64,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PreferenceSettings {
+    /// How long a preference center session lasts once a subscriber
+    /// followed their link; signed with the privacy key.
+    #[serde(
+        default = "default_session_ttl_minutes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub session_ttl_minutes: i64,
+    /// How long the link confirming a new address stays valid.
+    #[serde(
+        default = "default_email_change_ttl_hours",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub email_change_ttl_hours: i64,
+    /// How often a subscriber can have a link sent to a new address, and
+    /// how often any address can be sent one.
+    #[serde(
+        default = "default_email_change_interval_minutes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub email_change_interval_minutes: i64,
+    /// How many of those links are sent at once; requests past that are
+    /// turned away.
+    #[serde(
+        default = "default_max_concurrent_email_changes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_concurrent_email_changes: usize,
+}
+
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct TrackingSettings {
+    /// Track opens and clicks of the issues that ask for it. Links in
+    /// issues sent while it was on keep working once it is off, but are no
+    /// longer recorded.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Key for signing tracking links; required while tracking is enabled.
+    #[serde(default)]
+    pub signing_key: Option<SecretString>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PrivacySettings {
+    /// Key for signing the links in access and erasure emails, preference
+    /// sessions and email changes; required.
+    #[serde(default)]
+    pub signing_key: Option<SecretString>,
+    /// How long those links stay valid.
+    #[serde(
+        default = "default_privacy_link_ttl_hours",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub link_ttl_hours: i64,
+    /// How often each address can have an access or erasure link sent.
+    #[serde(
+        default = "default_privacy_request_interval_minutes",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub request_interval_minutes: i64,
+    /// How many of those requests run at once; ones past that are turned
+    /// away.
+    #[serde(
+        default = "default_max_concurrent_privacy_requests",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_concurrent_requests: usize,
+}
+
+impl PrivacySettings {
+    pub fn link_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.link_ttl_hours)
+    }
+
+    pub fn request_interval(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.request_interval_minutes)
+    }
+}
+
+fn default_sqlite_path() -> String {
+    "newsletter.sqlite3".into()
+}
+
+fn default_token_ttl_hours() -> i64 {
+    72
+}
+
+fn default_resend_interval_minutes() -> i64 {
+    15
+}
+
+fn default_max_concurrent_resends() -> usize {
+    8
+}
+
+fn default_privacy_link_ttl_hours() -> i64 {
+    24
+}
+
+fn default_privacy_request_interval_minutes() -> i64 {
+    15
+}
+
+fn default_max_concurrent_privacy_requests() -> usize {
+    8
+}
+
+fn default_email_change_ttl_hours() -> i64 {
+    24
+}
+
+fn default_email_change_interval_minutes() -> i64 {
+    15
+}
+
+fn default_max_concurrent_email_changes() -> usize {
+    8
+}
+
+fn default_connect_timeout_milliseconds() -> u64 {
+    5000
+}
+
+fn default_read_timeout_milliseconds() -> u64 {
+    30000
+}
+
+fn default_pool_idle_timeout_seconds() -> u64 {
+    90
+}
+
+fn default_batch_size() -> usize {
+    500
+}
+
+fn default_batch_concurrency() -> usize {
+    4
+}
+
+fn default_batch_requests_per_second() -> u32 {
+    10
+}
+
+fn default_batch_max_retries() -> u32 {
+    2
+}
+
+fn default_batch_retry_delay_milliseconds() -> u64 {
+    1000
+}
+
+fn default_feed_poll_interval_seconds() -> u64 {
+    3600
+}
+
+fn default_feed_list() -> String {
+    crate::repository::DEFAULT_LIST.to_owned()
+}
+
+fn default_archive_title() -> String {
+    "Newsletter archive".into()
+}
+
+fn default_archive_page_size() -> i64 {
+    20
+}
+
+fn default_archive_max_age_seconds() -> u64 {
+    300
+}
+
+fn default_session_ttl_minutes() -> i64 {
+    60
+}
+
+fn default_scheduler_enabled() -> bool {
+    true
+}
+
+fn default_poll_interval_seconds() -> u64 {
+    30
+}
+
+fn default_stalled_after_minutes() -> i64 {
+    15
+}
+
+impl Default for HttpClientSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout_milliseconds: default_connect_timeout_milliseconds(),
+            read_timeout_milliseconds: default_read_timeout_milliseconds(),
+            pool_idle_timeout_seconds: default_pool_idle_timeout_seconds(),
+            pool_max_idle_per_host: None,
+            http_version: HttpVersion::default(),
+            ca_bundle_path: None,
+            proxy: None,
+        }
+    }
+}
+
+impl HttpClientSettings {
+    pub fn connect_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.connect_timeout_milliseconds)
+    }
+
+    pub fn read_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.read_timeout_milliseconds)
+    }
+
+    pub fn pool_idle_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.pool_idle_timeout_seconds)
+    }
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        Self {
+            size: default_batch_size(),
+            concurrency: default_batch_concurrency(),
+            requests_per_second: default_batch_requests_per_second(),
+            max_retries: default_batch_max_retries(),
+            retry_delay_milliseconds: default_batch_retry_delay_milliseconds(),
+        }
+    }
+}
+
+impl BatchSettings {
+    pub fn retry_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.retry_delay_milliseconds)
+    }
+}
+
+impl Default for FeedSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            urls: Vec::new(),
+            poll_interval_seconds: default_feed_poll_interval_seconds(),
+            list: default_feed_list(),
+            digest: DigestMode::default(),
+        }
+    }
+}
+
+impl FeedSettings {
+    pub fn poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.poll_interval_seconds)
+    }
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        Self {
+            title: default_archive_title(),
+            page_size: default_archive_page_size(),
+            max_age_seconds: default_archive_max_age_seconds(),
+        }
+    }
+}
+
+impl Default for PreferenceSettings {
+    fn default() -> Self {
+        Self {
+            session_ttl_minutes: default_session_ttl_minutes(),
+            email_change_ttl_hours: default_email_change_ttl_hours(),
+            email_change_interval_minutes: default_email_change_interval_minutes(),
+            max_concurrent_email_changes: default_max_concurrent_email_changes(),
+        }
+    }
+}
+
+impl PreferenceSettings {
+    pub fn session_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.session_ttl_minutes)
+    }
+
+    pub fn email_change_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.email_change_ttl_hours)
+    }
+
+    pub fn email_change_interval(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.email_change_interval_minutes)
+    }
+}
+
+impl Default for SchedulerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_scheduler_enabled(),
+            poll_interval_seconds: default_poll_interval_seconds(),
+            stalled_after_minutes: default_stalled_after_minutes(),
+        }
+    }
+}
+
+impl SchedulerSettings {
+    pub fn poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.poll_interval_seconds)
+    }
+
+    pub fn stalled_after(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.stalled_after_minutes)
+    }
+}
+
+impl Default for ConfirmationSettings {
+    fn default() -> Self {
+        Self {
+            token_ttl_hours: default_token_ttl_hours(),
+            resend_interval_minutes: default_resend_interval_minutes(),
+            max_concurrent_resends: default_max_concurrent_resends(),
+            redirects: ConfirmationRedirects::default(),
+        }
+    }
+}
+
+impl ConfirmationSettings {
+    pub fn token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.token_ttl_hours)
+    }
+
+    pub fn resend_interval(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.resend_interval_minutes)
+    }
 }
 
 impl ApplicationSettings {
@@ -74,10 +704,21 @@ impl DatabaseSettings {
         PgConnectOptions::new()
             .host(&self.host)
             .username(&self.username)
-            .password(&self.password.expose_secret())
+            .password(self.password.expose_secret())
             .port(self.port)
             .ssl_mode(ssl_mode)
     }
+
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(&self) -> sqlx::sqlite::SqliteConnectOptions {
+        sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(&self.sqlite_path)
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+            .busy_timeout(std::time::Duration::from_secs(5))
+            .log_statements(tracing::log::LevelFilter::Trace)
+    }
 }
 
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
@@ -0,0 +1,113 @@
+//! src/confirmation.rs
+//!
+//! Limits on emails sent on request: confirmation emails sent again,
+//! access and erasure links, and links confirming a new address. Anyone
+//! can ask, for any address, so requests
+//! are turned away before any work is queued: an address asking again
+//! within the interval, and any address while too many requests are
+//! running. Whether the address is subscribed plays no part, so being
+//! turned away tells nothing about it.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How many addresses are remembered as having asked within the interval;
+/// past that, every request is turned away until some are forgotten.
+const MAX_RECENT_ADDRESSES: usize = 10_000;
+
+#[derive(Clone)]
+pub struct ResendThrottle {
+    interval: Duration,
+    max_running: usize,
+    running: Arc<Semaphore>,
+    /// Lowercased address -> when it last had a request admitted.
+    recent: Arc<Mutex<HashMap<String, DateTime<Utc>>>>,
+}
+
+impl ResendThrottle {
+    pub fn new(interval: Duration, max_running: usize) -> Self {
+        Self {
+            interval,
+            max_running,
+            running: Arc::new(Semaphore::new(max_running)),
+            recent: Arc::default(),
+        }
+    }
+
+    /// Admits a resend for `email`, unless it is turned away. The resend
+    /// holds on to the permit for as long as it runs.
+    pub fn admit(&self, email: &str, now: DateTime<Utc>) -> Option<OwnedSemaphorePermit> {
+        self.admit_all(&[email], now)
+    }
+
+    /// Like `admit`, for a request each of `keys` must be allowed to make,
+    /// such as a subscriber asking for a link to some address.
+    pub fn admit_all(&self, keys: &[&str], now: DateTime<Utc>) -> Option<OwnedSemaphorePermit> {
+        let keys: Vec<_> = keys.iter().map(|key| key.to_lowercase()).collect();
+        let mut recent = self.recent.lock().unwrap();
+        if keys.iter().any(|key| recent.get(key).is_some_and(|at| now - *at < self.interval)) {
+            return None;
+        }
+        if recent.len() + keys.len() > MAX_RECENT_ADDRESSES {
+            recent.retain(|_, at| now - *at < self.interval);
+            if recent.len() + keys.len() > MAX_RECENT_ADDRESSES {
+                return None;
+            }
+        }
+        let permit = self.running.clone().try_acquire_owned().ok()?;
+        for key in keys {
+            recent.insert(key, now);
+        }
+        Some(permit)
+    }
+
+    /// Waits until every admitted resend has finished.
+    pub async fn until_idle(&self) {
+        let _ = self.running.acquire_many(self.max_running as u32).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use claim::{assert_none, assert_some};
+
+    use crate::confirmation::ResendThrottle;
+
+    #[test]
+    fn an_address_is_admitted_once_per_interval() {
+        let throttle = ResendThrottle::new(Duration::minutes(15), 8);
+        let now = Utc::now();
+
+        assert!(throttle.admit("ursula@example.com", now).is_some());
+        assert_none!(throttle.admit("Ursula@Example.com", now + Duration::minutes(14)));
+        assert!(throttle.admit("octavia@example.com", now).is_some());
+        assert!(throttle.admit("ursula@example.com", now + Duration::minutes(15)).is_some());
+    }
+
+    #[test]
+    fn requests_are_turned_away_if_any_of_their_keys_asked_recently() {
+        let throttle = ResendThrottle::new(Duration::minutes(15), 8);
+        let now = Utc::now();
+
+        assert!(throttle.admit_all(&["subscriber-1", "ursula@example.com"], now).is_some());
+        assert_none!(throttle.admit_all(&["subscriber-1", "octavia@example.com"], now));
+        assert_none!(throttle.admit_all(&["subscriber-2", "ursula@example.com"], now));
+        // Turned away requests do not count against their other keys.
+        assert!(throttle.admit_all(&["subscriber-2", "octavia@example.com"], now).is_some());
+    }
+
+    #[tokio::test]
+    async fn requests_are_turned_away_while_too_many_resends_run() {
+        let throttle = ResendThrottle::new(Duration::zero(), 1);
+        let now = Utc::now();
+
+        let running = assert_some!(throttle.admit("ursula@example.com", now));
+        assert_none!(throttle.admit("octavia@example.com", now));
+        drop(running);
+        throttle.until_idle().await;
+        assert!(throttle.admit("octavia@example.com", now).is_some());
+    }
+}
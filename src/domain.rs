@@ -1,6 +1,8 @@
+mod email_rejection;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
+pub use email_rejection::EmailRejection;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
-pub use subscriber_name::SubscriberName;
\ No newline at end of file
+pub use subscriber_name::SubscriberName;
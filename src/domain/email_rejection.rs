@@ -0,0 +1,50 @@
+use std::fmt;
+
+/// Why a subscriber email address was turned away.
+///
+/// Every variant maps to a stable, machine-readable `code` that the API
+/// returns to clients, so they can tell a typo apart from a blocked domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailRejection {
+    InvalidSyntax,
+    AddressTooLong,
+    LocalPartTooLong,
+    DomainTooLong,
+    DisposableDomain,
+    DeniedDomain,
+    DomainNotAllowed,
+    NoMailExchanger,
+}
+
+impl EmailRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            EmailRejection::InvalidSyntax => "invalid_syntax",
+            EmailRejection::AddressTooLong => "address_too_long",
+            EmailRejection::LocalPartTooLong => "local_part_too_long",
+            EmailRejection::DomainTooLong => "domain_too_long",
+            EmailRejection::DisposableDomain => "disposable_domain",
+            EmailRejection::DeniedDomain => "denied_domain",
+            EmailRejection::DomainNotAllowed => "domain_not_allowed",
+            EmailRejection::NoMailExchanger => "no_mail_exchanger",
+        }
+    }
+}
+
+impl fmt::Display for EmailRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            EmailRejection::InvalidSyntax => "The email address is not syntactically valid.",
+            EmailRejection::AddressTooLong => "The email address is longer than 254 characters.",
+            EmailRejection::LocalPartTooLong => "The part before the @ is longer than 64 characters.",
+            EmailRejection::DomainTooLong => "The email domain is longer than 255 characters.",
+            EmailRejection::DisposableDomain => "Disposable email addresses are not accepted.",
+            EmailRejection::DeniedDomain => "Email addresses from this domain are not accepted.",
+            EmailRejection::DomainNotAllowed => "Only email addresses from approved domains are accepted.",
+            EmailRejection::NoMailExchanger => "The email domain cannot receive email.",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for EmailRejection {}
@@ -5,4 +5,6 @@ use super::SubscriberEmail;
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    /// One of the locales we have a message catalog for.
+    pub locale: String,
 }
\ No newline at end of file
@@ -1,16 +1,41 @@
 use validator::validate_email;
-use eyre::{eyre, Result};
+
+use super::EmailRejection;
+
+/// RFC 5321 caps a forward-path at 256 octets including the angle brackets,
+/// which leaves 254 for the address itself.
+const MAX_ADDRESS_LENGTH: usize = 254;
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+const MAX_DOMAIN_LENGTH: usize = 255;
 
 #[derive(Debug, Clone)]
 pub struct SubscriberEmail(String);
 impl SubscriberEmail {
-    pub fn parse(s: String) -> Result<SubscriberEmail> {
+    pub fn parse(s: String) -> Result<SubscriberEmail, EmailRejection> {
+        let (local_part, domain) = s.rsplit_once('@').ok_or(EmailRejection::InvalidSyntax)?;
+        // The parts first: a domain too long makes the address too long as
+        // well, and the part to blame is the more useful answer.
+        if local_part.len() > MAX_LOCAL_PART_LENGTH {
+            return Err(EmailRejection::LocalPartTooLong);
+        }
+        if domain.len() > MAX_DOMAIN_LENGTH {
+            return Err(EmailRejection::DomainTooLong);
+        }
+        if s.len() > MAX_ADDRESS_LENGTH {
+            return Err(EmailRejection::AddressTooLong);
+        }
         if validate_email(&s) {
             Ok(Self(s))
         } else {
-            Err(eyre!("{} is not a valid subscriber email.", s))
+            Err(EmailRejection::InvalidSyntax)
         }
     }
+
+    /// The part of the address after the last `@`, lowercased.
+    pub fn domain(&self) -> String {
+        let (_, domain) = self.0.rsplit_once('@').unwrap_or_default();
+        domain.to_lowercase()
+    }
 }
 
 impl AsRef<str> for SubscriberEmail {
@@ -23,6 +48,7 @@ impl AsRef<str> for SubscriberEmail {
 #[allow(unused_must_use)]
 mod tests {
     use super::SubscriberEmail;
+    use crate::domain::EmailRejection;
     use claim::assert_err;
     #[test]
     fn empty_string_is_rejected() {
@@ -39,6 +65,42 @@ mod tests {
         let email = "@domain.com".to_string();
         assert_err!(SubscriberEmail::parse(email));
     }
+    #[test]
+    fn a_64_character_local_part_is_valid() {
+        let email = format!("{}@domain.com", "a".repeat(64));
+        claim::assert_ok!(SubscriberEmail::parse(email));
+    }
+    #[test]
+    fn a_local_part_longer_than_64_characters_is_rejected() {
+        let email = format!("{}@domain.com", "a".repeat(65));
+        assert_eq!(
+            SubscriberEmail::parse(email).unwrap_err(),
+            EmailRejection::LocalPartTooLong
+        );
+    }
+    #[test]
+    fn an_address_longer_than_254_characters_is_rejected() {
+        let label = "a".repeat(63);
+        let email = format!("{}@{label}.{label}.{label}.com", "a".repeat(64));
+        assert_eq!(
+            SubscriberEmail::parse(email).unwrap_err(),
+            EmailRejection::AddressTooLong
+        );
+    }
+    #[test]
+    fn a_domain_longer_than_255_characters_is_rejected() {
+        let label = "a".repeat(63);
+        let email = format!("ursula@{label}.{label}.{label}.{label}.com");
+        assert_eq!(
+            SubscriberEmail::parse(email).unwrap_err(),
+            EmailRejection::DomainTooLong
+        );
+    }
+    #[test]
+    fn domain_is_lowercased() {
+        let email = SubscriberEmail::parse("ursula@Domain.COM".to_string()).unwrap();
+        assert_eq!(email.domain(), "domain.com");
+    }
 
     // We are importing the `SafeEmail` faker!
     // We also need the `Fake` trait to get access to the
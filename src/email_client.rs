@@ -1,9 +1,20 @@
 //! src/email_client.rs
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use eyre::eyre;
+use futures_util::stream::{self, StreamExt};
 use reqwest::Client;
 use secrecy::{ExposeSecret, SecretString};
+use tokio::{sync::Mutex, time::Instant};
 
+use crate::configuration::BatchSettings;
 use crate::domain::SubscriberEmail;
 
+/// Messages the provider takes in one batch request at most.
+pub const MAX_BATCH_SIZE: usize = 500;
+
 #[derive(serde::Serialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 struct SendEmailRequest<'a> {
@@ -15,51 +26,175 @@ struct SendEmailRequest<'a> {
     message_stream: &'a str,
 }
 
+#[derive(serde::Deserialize)]
+struct SendEmailResponse {
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
+/// The provider's answer for one message of a batch.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BatchResult {
+    error_code: i64,
+    #[serde(default)]
+    message: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
+/// One message of a batch.
+#[derive(Debug, Clone)]
+pub struct BatchEmail {
+    /// May include a display name: `"Name" <address>`.
+    pub sender: String,
+    pub recipient: SubscriberEmail,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+/// Why a message of a batch was not sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    /// The provider turned the message down.
+    Rejected { code: i64, message: String },
+    /// The request carrying it failed altogether.
+    Request(String),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Rejected { code, message } => write!(f, "Rejected with error {}: {}", code, message),
+            BatchError::Request(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+/// The provider's message id, if it gave one, or why the message was not
+/// sent.
+pub type BatchOutcome = Result<Option<String>, BatchError>;
+
+/// Spaces out the starts of requests, across every batch sent through a
+/// client and its clones.
+#[derive(Clone)]
+struct RateLimiter {
+    period: Option<Duration>,
+    next: Arc<Mutex<Option<Instant>>>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: u32) -> Self {
+        Self {
+            period: (requests_per_second > 0).then(|| Duration::from_secs(1) / requests_per_second),
+            next: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Waits for the next free slot, taking it.
+    async fn wait(&self) {
+        let Some(period) = self.period else {
+            return;
+        };
+        let start = {
+            let mut next = self.next.lock().await;
+            let now = Instant::now();
+            let start = next.map_or(now, |next| next.max(now));
+            *next = Some(start + period);
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+}
+
 #[derive(Clone)]
 pub struct EmailClient {
     http_client: Client,
     base_url: String,
+    timeout: Duration,
     sender: SubscriberEmail,
     authorization_token: SecretString,
+    batching: BatchSettings,
+    rate_limiter: RateLimiter,
 }
 
 impl EmailClient {
+    /// Sends through `http_client`, giving up on each request after
+    /// `timeout`.
     pub fn new(
+        http_client: Client,
         base_url: String,
         sender: SubscriberEmail,
         authorization_token: SecretString,
-        timeout: std::time::Duration,
+        timeout: Duration,
     ) -> Self {
-        let http_client = Client::builder().timeout(timeout).build().unwrap();
+        let batching = BatchSettings::default();
         Self {
             http_client,
             base_url,
+            timeout,
             sender,
             authorization_token,
+            rate_limiter: RateLimiter::new(batching.requests_per_second),
+            batching,
         }
     }
 
+    /// Sends batches as `batching` says; the size is capped at what the
+    /// provider takes, and at least one request is in flight.
+    pub fn with_batching(mut self, mut batching: BatchSettings) -> Self {
+        batching.size = batching.size.clamp(1, MAX_BATCH_SIZE);
+        batching.concurrency = batching.concurrency.max(1);
+        self.rate_limiter = RateLimiter::new(batching.requests_per_second);
+        self.batching = batching;
+        self
+    }
+
+    /// The configured sender, used unless a list has its own.
+    pub fn sender(&self) -> &SubscriberEmail {
+        &self.sender
+    }
+
+    /// Returns the id Postmark gave the message, if its response had one.
     pub async fn send_email(
         &self,
         recipient: SubscriberEmail,
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), reqwest::Error> {
+    ) -> Result<Option<String>, reqwest::Error> {
+        self.send_email_from(self.sender.as_ref(), recipient, subject, html_content, text_content)
+            .await
+    }
+
+    /// Like [`send_email`](Self::send_email), from `sender`, which may
+    /// include a display name: `"Name" <address>`.
+    pub async fn send_email_from(
+        &self,
+        sender: &str,
+        recipient: SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<Option<String>, reqwest::Error> {
         let url = format!("{}/email", self.base_url);
         let request_body = SendEmailRequest {
-            from: self.sender.as_ref(),
+            from: sender,
             to: recipient.as_ref(),
-            subject: subject,
+            subject,
             html_body: html_content,
             text_body: text_content,
             message_stream: "newsletter_confirmations"
         };
 
         dbg!(&request_body);
-        let _builder = dbg!(dbg!(self
+        let response = dbg!(dbg!(self
             .http_client
             .post(&url)
+            .timeout(self.timeout)
             .header(
                 "X-Postmark-Server-Token",
                 self.authorization_token.expose_secret(),
@@ -69,19 +204,138 @@ impl EmailClient {
             .await)?
             .error_for_status()?;
 
-        Ok(())
+        // A response without an id still means the message was accepted.
+        let body = response.bytes().await?;
+        Ok(serde_json::from_slice::<SendEmailResponse>(&body)
+            .ok()
+            .and_then(|response| response.message_id))
+    }
+
+    /// Sends `emails` through the batch API, several batches at once, and
+    /// sends those that failed again, alone, up to the configured number
+    /// of retries. The outcomes are in the order of `emails`.
+    pub async fn send_batch(&self, emails: &[BatchEmail]) -> Vec<BatchOutcome> {
+        let mut outcomes: Vec<BatchOutcome> = vec![Ok(None); emails.len()];
+        let mut pending: Vec<usize> = (0..emails.len()).collect();
+        for attempt in 0..=self.batching.max_retries {
+            if pending.is_empty() {
+                break;
+            }
+            if attempt > 0 {
+                tracing::warn!(failed = pending.len(), attempt, "Retrying failed messages of a batch");
+                tokio::time::sleep(self.batching.retry_delay()).await;
+            }
+            // Collected first: a lazy iterator here keeps the compiler from
+            // seeing that the future is `Send`.
+            let batches: Vec<_> = pending
+                .chunks(self.batching.size)
+                .map(|indices| self.post_batch(emails, indices))
+                .collect();
+            let results: Vec<_> = stream::iter(batches)
+                .buffer_unordered(self.batching.concurrency)
+                .collect()
+                .await;
+            let mut failed = Vec::new();
+            for (indices, results) in results {
+                for (&i, outcome) in indices.iter().zip(results) {
+                    if outcome.is_err() {
+                        failed.push(i);
+                    }
+                    outcomes[i] = outcome;
+                }
+            }
+            pending = failed;
+        }
+        outcomes
+    }
+
+    /// Sends the messages of `emails` at `indices` in one request, with one
+    /// outcome per message; all of them fail if the request does, or if
+    /// its results cannot be matched up with them.
+    async fn post_batch<'a>(
+        &self,
+        emails: &[BatchEmail],
+        indices: &'a [usize],
+    ) -> (&'a [usize], Vec<BatchOutcome>) {
+        let batch: Vec<_> = indices.iter().map(|&i| &emails[i]).collect();
+        self.rate_limiter.wait().await;
+        let outcomes = match self.request_batch(&batch).await {
+            Ok(outcomes) => outcomes,
+            Err(e) => {
+                tracing::error!("Failed to send a batch of {} emails: {:?}", batch.len(), e);
+                let error = BatchError::Request(e.to_string());
+                batch.iter().map(|_| Err(error.clone())).collect()
+            }
+        };
+        (indices, outcomes)
+    }
+
+    async fn request_batch(&self, emails: &[&BatchEmail]) -> eyre::Result<Vec<BatchOutcome>> {
+        let url = format!("{}/email/batch", self.base_url);
+        let request_body: Vec<_> = emails
+            .iter()
+            .map(|email| SendEmailRequest {
+                from: &email.sender,
+                to: email.recipient.as_ref(),
+                subject: &email.subject,
+                html_body: &email.html_body,
+                text_body: &email.text_body,
+                message_stream: "newsletter_confirmations",
+            })
+            .collect();
+        let response = self
+            .http_client
+            .post(&url)
+            .timeout(self.timeout)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        // The provider answers with a result per message, in order. Like
+        // a single send, a response without them means all were accepted;
+        // one with more or fewer leaves no telling which message failed.
+        let body = response.bytes().await?;
+        let results = serde_json::from_slice::<Vec<BatchResult>>(&body).ok();
+        if let Some(results) = results.as_ref().filter(|results| results.len() != emails.len()) {
+            return Err(eyre!(
+                "The provider answered {} messages with {} results",
+                emails.len(),
+                results.len()
+            ));
+        }
+        Ok(match results {
+            Some(results) => results
+                .into_iter()
+                .map(|result| match result.error_code {
+                    0 => Ok(result.message_id),
+                    code => Err(BatchError::Rejected {
+                        code,
+                        message: result.message,
+                    }),
+                })
+                .collect(),
+            None => emails.iter().map(|_| Ok(None)).collect(),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::configuration::BatchSettings;
     use crate::domain::SubscriberEmail;
-    use crate::email_client::EmailClient;
+    use crate::email_client::{BatchEmail, BatchError, EmailClient};
     use claim::{assert_err, assert_ok};
     use fake::faker::internet::en::SafeEmail;
     use fake::faker::lorem::en::{Paragraph, Sentence};
     use fake::Fake;
+    use reqwest::Client;
     use secrecy::SecretString;
+    use serde_json::{json, Value};
     use wiremock::matchers::{any, header, header_exists, method, path};
     use wiremock::{Mock, MockServer, Request, ResponseTemplate};
 
@@ -121,6 +375,7 @@ mod tests {
     /// Get a test instance of `EmailClient`.
     fn email_client(base_url: String) -> EmailClient {
         EmailClient::new(
+            Client::new(),
             base_url,
             email(),
             SecretString::new("fake_string".into()),
@@ -171,6 +426,36 @@ mod tests {
         assert_ok!(outcome);
     }
 
+    #[tokio::test]
+    async fn send_email_returns_the_message_id_postmark_assigned() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "To": "ursula@example.com",
+                "SubmittedAt": "2026-10-19T09:00:00.0000000Z",
+                "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+                "ErrorCode": 0,
+                "Message": "OK",
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_eq!(
+            assert_ok!(outcome).as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+    }
+
     #[tokio::test]
     async fn send_email_fails_if_the_server_returns_500() {
         // Arrange
@@ -212,4 +497,239 @@ mod tests {
         // Assert
         assert_err!(outcome);
     }
+
+    /// A test client sending batches of `size`, without waiting between
+    /// requests or retries.
+    fn batch_client(base_url: String, size: usize, max_retries: u32) -> EmailClient {
+        email_client(base_url).with_batching(BatchSettings {
+            size,
+            concurrency: 2,
+            requests_per_second: 0,
+            max_retries,
+            retry_delay_milliseconds: 0,
+        })
+    }
+
+    fn batch(recipients: &[&str]) -> Vec<BatchEmail> {
+        recipients
+            .iter()
+            .map(|recipient| BatchEmail {
+                sender: "newsletter@example.com".into(),
+                recipient: SubscriberEmail::parse((*recipient).into()).unwrap(),
+                subject: subject(),
+                html_body: content(),
+                text_body: content(),
+            })
+            .collect()
+    }
+
+    fn batch_result(to: &str, error_code: i64, message_id: Option<&str>) -> Value {
+        json!({
+            "To": to,
+            "ErrorCode": error_code,
+            "Message": if error_code == 0 { "OK" } else { "Recipient is inactive" },
+            "MessageID": message_id,
+        })
+    }
+
+    /// The recipients of each batch the server received, in order.
+    async fn received_batches(mock_server: &MockServer) -> Vec<Vec<String>> {
+        mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| {
+                let body: Vec<Value> = serde_json::from_slice(&request.body).unwrap();
+                body.iter().map(|email| email["To"].as_str().unwrap().to_owned()).collect()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn send_batch_maps_results_back_and_retries_only_the_failed_messages() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = batch_client(mock_server.uri(), 500, 1);
+        Mock::given(path("/email/batch"))
+            .and(method("POST"))
+            .and(header_exists("X-Postmark-Server-Token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                batch_result("ursula@example.com", 0, Some("message-1")),
+                batch_result("octavia@example.com", 406, None),
+                batch_result("lauren@example.com", 0, Some("message-3")),
+            ])))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                batch_result("octavia@example.com", 0, Some("message-2")),
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(&batch(&["ursula@example.com", "octavia@example.com", "lauren@example.com"]))
+            .await;
+
+        // Assert
+        assert_eq!(
+            outcomes,
+            [
+                Ok(Some("message-1".to_owned())),
+                Ok(Some("message-2".to_owned())),
+                Ok(Some("message-3".to_owned()))
+            ]
+        );
+        assert_eq!(
+            received_batches(&mock_server).await,
+            [
+                vec!["ursula@example.com", "octavia@example.com", "lauren@example.com"],
+                vec!["octavia@example.com"]
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_batch_reports_messages_still_failing_after_the_retries() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = batch_client(mock_server.uri(), 500, 2);
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                batch_result("ursula@example.com", 0, Some("message-1")),
+                batch_result("octavia@example.com", 406, None),
+            ])))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                batch_result("octavia@example.com", 406, None),
+            ])))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(&batch(&["ursula@example.com", "octavia@example.com"]))
+            .await;
+
+        // Assert
+        assert_eq!(outcomes[0], Ok(Some("message-1".to_owned())));
+        assert_eq!(
+            outcomes[1],
+            Err(BatchError::Rejected {
+                code: 406,
+                message: "Recipient is inactive".into()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn send_batch_fails_every_message_of_a_failed_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = batch_client(mock_server.uri(), 2, 0);
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(&batch(&["a@example.com", "b@example.com", "c@example.com"]))
+            .await;
+
+        // Assert
+        assert_eq!(outcomes.len(), 3);
+        for outcome in outcomes {
+            assert!(matches!(outcome, Err(BatchError::Request(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn send_batch_fails_and_retries_a_batch_with_a_result_missing() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = batch_client(mock_server.uri(), 500, 1);
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                batch_result("ursula@example.com", 0, Some("message-1")),
+            ])))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(&batch(&["ursula@example.com", "octavia@example.com"]))
+            .await;
+
+        // Assert
+        assert_eq!(outcomes.len(), 2);
+        for outcome in outcomes {
+            assert!(matches!(outcome, Err(BatchError::Request(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn send_batch_splits_messages_into_batches_of_the_configured_size() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = batch_client(mock_server.uri(), 2, 0);
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+        let recipients = ["a@example.com", "b@example.com", "c@example.com", "d@example.com", "e@example.com"];
+
+        // Act
+        let outcomes = email_client.send_batch(&batch(&recipients)).await;
+
+        // Assert
+        assert_eq!(outcomes, vec![Ok(None); 5]);
+        let mut batches = received_batches(&mock_server).await;
+        batches.sort();
+        assert_eq!(
+            batches,
+            [vec!["a@example.com", "b@example.com"], vec!["c@example.com", "d@example.com"], vec!["e@example.com"]]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_batch_spaces_requests_out_to_the_rate_limit() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri()).with_batching(BatchSettings {
+            size: 1,
+            concurrency: 3,
+            requests_per_second: 10,
+            max_retries: 0,
+            retry_delay_milliseconds: 0,
+        });
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+        let started = std::time::Instant::now();
+
+        // Act
+        email_client
+            .send_batch(&batch(&["a@example.com", "b@example.com", "c@example.com"]))
+            .await;
+
+        // Assert
+        assert!(started.elapsed() >= std::time::Duration::from_millis(200));
+    }
 }
@@ -0,0 +1,309 @@
+//! src/email_validation.rs
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use hickory_resolver::{error::ResolveErrorKind, TokioAsyncResolver};
+
+use crate::configuration::EmailValidationSettings;
+use crate::domain::{EmailRejection, SubscriberEmail};
+
+/// The exchange of a "null MX" (RFC 7505), which says the domain takes no
+/// mail at all.
+pub const NULL_MX: &str = ".";
+
+/// Looks up the mail exchangers of a domain.
+///
+/// Production uses [`HickoryDnsResolver`]; tests use [`InMemoryDnsResolver`]
+/// because CI has no network access.
+#[async_trait]
+pub trait DnsResolver: Send + Sync {
+    /// Returns the MX hosts of `domain`, [`NULL_MX`] among them if it has a
+    /// null MX, or an empty list if it has none.
+    async fn mx_records(&self, domain: &str) -> Result<Vec<String>, eyre::Report>;
+
+    /// Whether `domain` has an A or AAAA record.
+    async fn has_address(&self, domain: &str) -> Result<bool, eyre::Report>;
+}
+
+pub struct HickoryDnsResolver(TokioAsyncResolver);
+
+impl HickoryDnsResolver {
+    pub fn from_system_conf() -> eyre::Result<Self> {
+        Ok(Self(TokioAsyncResolver::tokio_from_system_conf()?))
+    }
+}
+
+#[async_trait]
+impl DnsResolver for HickoryDnsResolver {
+    async fn mx_records(&self, domain: &str) -> Result<Vec<String>, eyre::Report> {
+        match self.0.mx_lookup(domain).await {
+            Ok(lookup) => Ok(lookup
+                .iter()
+                .map(|mx| match mx.exchange().is_root() {
+                    true => NULL_MX.to_owned(),
+                    false => mx.exchange().to_utf8(),
+                })
+                .collect()),
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { .. } => Ok(vec![]),
+                _ => Err(e.into()),
+            },
+        }
+    }
+
+    async fn has_address(&self, domain: &str) -> Result<bool, eyre::Report> {
+        match self.0.lookup_ip(domain).await {
+            Ok(lookup) => Ok(lookup.iter().next().is_some()),
+            Err(e) => match e.kind() {
+                ResolveErrorKind::NoRecordsFound { .. } => Ok(false),
+                _ => Err(e.into()),
+            },
+        }
+    }
+}
+
+/// A resolver answering from a fixed table, for tests.
+#[derive(Default)]
+pub struct InMemoryDnsResolver {
+    records: HashMap<String, Vec<String>>,
+    addresses: HashSet<String>,
+}
+
+impl InMemoryDnsResolver {
+    pub fn with_mx(mut self, domain: &str, exchange: &str) -> Self {
+        self.records
+            .entry(domain.to_lowercase())
+            .or_default()
+            .push(exchange.to_owned());
+        self
+    }
+
+    /// Gives `domain` an A record.
+    pub fn with_address(mut self, domain: &str) -> Self {
+        self.addresses.insert(domain.to_lowercase());
+        self
+    }
+}
+
+#[async_trait]
+impl DnsResolver for InMemoryDnsResolver {
+    async fn mx_records(&self, domain: &str) -> Result<Vec<String>, eyre::Report> {
+        Ok(self.records.get(domain).cloned().unwrap_or_default())
+    }
+
+    async fn has_address(&self, domain: &str) -> Result<bool, eyre::Report> {
+        Ok(self.addresses.contains(domain))
+    }
+}
+
+/// Checks a syntactically valid address against our domain policy.
+///
+/// Runs in order: allow list, deny list, disposable-domain blocklist and,
+/// if a resolver is configured, an MX lookup. A domain without MX records
+/// that has an address record is its own mail exchanger (RFC 5321 §5.1).
+#[derive(Clone)]
+pub struct EmailValidator {
+    allowed_domains: Arc<HashSet<String>>,
+    denied_domains: Arc<HashSet<String>>,
+    disposable_domains: Arc<HashSet<String>>,
+    resolver: Option<Arc<dyn DnsResolver>>,
+}
+
+impl EmailValidator {
+    pub fn new(
+        settings: &EmailValidationSettings,
+        resolver: Option<Arc<dyn DnsResolver>>,
+    ) -> eyre::Result<Self> {
+        let disposable_domains = match &settings.disposable_domains_path {
+            Some(path) => parse_domain_list(&std::fs::read_to_string(path).map_err(|e| {
+                eyre::eyre!("Failed to read disposable domain list {}: {}", path, e)
+            })?),
+            None => HashSet::new(),
+        };
+        Ok(Self {
+            allowed_domains: Arc::new(normalise(&settings.allowed_domains)),
+            denied_domains: Arc::new(normalise(&settings.denied_domains)),
+            disposable_domains: Arc::new(disposable_domains),
+            resolver,
+        })
+    }
+
+    /// Builds the validator described by `settings`, using the system
+    /// resolver when MX checks are enabled.
+    pub fn from_settings(settings: &EmailValidationSettings) -> eyre::Result<Self> {
+        let resolver: Option<Arc<dyn DnsResolver>> = if settings.check_mx {
+            Some(Arc::new(HickoryDnsResolver::from_system_conf()?))
+        } else {
+            None
+        };
+        Self::new(settings, resolver)
+    }
+
+    #[tracing::instrument(name = "Validating subscriber email", skip(self, email))]
+    pub async fn validate(&self, email: &SubscriberEmail) -> Result<(), EmailRejection> {
+        let domain = email.domain();
+        if !self.allowed_domains.is_empty() && !matches_any(&domain, &self.allowed_domains) {
+            return Err(EmailRejection::DomainNotAllowed);
+        }
+        if matches_any(&domain, &self.denied_domains) {
+            return Err(EmailRejection::DeniedDomain);
+        }
+        if matches_any(&domain, &self.disposable_domains) {
+            return Err(EmailRejection::DisposableDomain);
+        }
+        if let Some(resolver) = &self.resolver {
+            let takes_mail = match resolver.mx_records(&domain).await {
+                Ok(records) if records.iter().any(|mx| mx == NULL_MX) => Ok(false),
+                Ok(records) if records.is_empty() => resolver.has_address(&domain).await,
+                Ok(_) => Ok(true),
+                Err(e) => Err(e),
+            };
+            match takes_mail {
+                Ok(true) => {}
+                Ok(false) => return Err(EmailRejection::NoMailExchanger),
+                // A flaky resolver should not lock people out of signing up.
+                Err(e) => tracing::warn!("MX lookup for {} failed: {:?}", domain, e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `domain` or any of its parent domains is in `list`.
+fn matches_any(domain: &str, list: &HashSet<String>) -> bool {
+    let mut candidate = domain;
+    loop {
+        if list.contains(candidate) {
+            return true;
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) => candidate = parent,
+            None => return false,
+        }
+    }
+}
+
+fn normalise(domains: &[String]) -> HashSet<String> {
+    domains.iter().map(|d| d.trim().to_lowercase()).collect()
+}
+
+/// One domain per line; blank lines and `#` comments are ignored.
+fn parse_domain_list(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::configuration::EmailValidationSettings;
+    use crate::domain::{EmailRejection, SubscriberEmail};
+    use crate::email_validation::{parse_domain_list, EmailValidator, InMemoryDnsResolver, NULL_MX};
+
+    fn settings() -> EmailValidationSettings {
+        EmailValidationSettings {
+            disposable_domains_path: None,
+            allowed_domains: vec![],
+            denied_domains: vec![],
+            check_mx: false,
+        }
+    }
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn any_domain_is_accepted_by_default() {
+        let validator = EmailValidator::new(&settings(), None).unwrap();
+        assert_eq!(validator.validate(&email("ursula@domain.com")).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn denied_domains_and_their_subdomains_are_rejected() {
+        let mut settings = settings();
+        settings.denied_domains = vec!["Spam.example".into()];
+        let validator = EmailValidator::new(&settings, None).unwrap();
+
+        for address in ["ursula@spam.example", "ursula@mail.spam.example"] {
+            assert_eq!(
+                validator.validate(&email(address)).await,
+                Err(EmailRejection::DeniedDomain)
+            );
+        }
+        assert_eq!(validator.validate(&email("ursula@notspam.example")).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn domains_outside_a_non_empty_allow_list_are_rejected() {
+        let mut settings = settings();
+        settings.allowed_domains = vec!["corp.example".into()];
+        let validator = EmailValidator::new(&settings, None).unwrap();
+
+        assert_eq!(validator.validate(&email("ursula@corp.example")).await, Ok(()));
+        assert_eq!(
+            validator.validate(&email("ursula@domain.com")).await,
+            Err(EmailRejection::DomainNotAllowed)
+        );
+    }
+
+    #[tokio::test]
+    async fn disposable_domains_from_the_list_file_are_rejected() {
+        let mut settings = settings();
+        settings.disposable_domains_path = Some("configuration/disposable_domains.txt".into());
+        let validator = EmailValidator::new(&settings, None).unwrap();
+
+        assert_eq!(
+            validator.validate(&email("ursula@mailinator.com")).await,
+            Err(EmailRejection::DisposableDomain)
+        );
+    }
+
+    #[test]
+    fn a_missing_disposable_domain_list_is_an_error() {
+        let mut settings = settings();
+        settings.disposable_domains_path = Some("does/not/exist.txt".into());
+        assert!(EmailValidator::new(&settings, None).is_err());
+    }
+
+    #[test]
+    fn domain_list_skips_comments_and_blank_lines() {
+        let domains = parse_domain_list("# header\n\nMailinator.com\n  yopmail.com \n");
+        assert_eq!(domains.len(), 2);
+        assert!(domains.contains("mailinator.com"));
+        assert!(domains.contains("yopmail.com"));
+    }
+
+    #[tokio::test]
+    async fn domains_without_mx_records_are_rejected_when_mx_checks_are_on() {
+        let resolver = InMemoryDnsResolver::default().with_mx("domain.com", "mx.domain.com");
+        let validator = EmailValidator::new(&settings(), Some(Arc::new(resolver))).unwrap();
+
+        assert_eq!(validator.validate(&email("ursula@domain.com")).await, Ok(()));
+        assert_eq!(
+            validator.validate(&email("ursula@no-mail.example")).await,
+            Err(EmailRejection::NoMailExchanger)
+        );
+    }
+
+    #[tokio::test]
+    async fn domains_with_only_an_address_record_are_their_own_mail_exchanger() {
+        let resolver = InMemoryDnsResolver::default()
+            .with_address("a-only.example")
+            .with_address("null-mx.example")
+            .with_mx("null-mx.example", NULL_MX);
+        let validator = EmailValidator::new(&settings(), Some(Arc::new(resolver))).unwrap();
+
+        assert_eq!(validator.validate(&email("ursula@a-only.example")).await, Ok(()));
+        assert_eq!(
+            validator.validate(&email("ursula@null-mx.example")).await,
+            Err(EmailRejection::NoMailExchanger)
+        );
+    }
+}
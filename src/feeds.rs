@@ -0,0 +1,283 @@
+//! src/feeds.rs
+//!
+//! Polling blog feeds, and drafting digest issues from their new entries.
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use eyre::eyre;
+use tokio::time::MissedTickBehavior;
+use url::Url;
+
+use crate::{
+    app_state::AppState,
+    configuration::{DigestMode, FeedSettings},
+    i18n::DEFAULT_LOCALE,
+    repository::{FeedEntry, IssueContent, NewFeedEntry, NewsletterIssue},
+    templates::{EmailTemplate, RenderedEmail, Templates},
+};
+
+/// Summaries longer than this many characters are cut short.
+const SUMMARY_LENGTH: usize = 280;
+
+/// Reads the entries of the RSS or Atom feed at `url`.
+#[tracing::instrument(name = "Fetch feed", skip(http_client))]
+pub async fn fetch_feed(http_client: &reqwest::Client, url: &Url) -> eyre::Result<Vec<NewFeedEntry>> {
+    let body = http_client
+        .get(url.clone())
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let feed = feed_rs::parser::parse(body.as_ref())
+        .map_err(|e| eyre!("Failed to parse feed {}: {}", url, e))?;
+    Ok(feed
+        .entries
+        .into_iter()
+        .map(|entry| new_entry(entry, url))
+        .collect())
+}
+
+fn new_entry(entry: feed_rs::model::Entry, feed_url: &Url) -> NewFeedEntry {
+    let link = entry
+        .links
+        .iter()
+        .find(|link| link.rel.as_deref().is_none_or(|rel| rel == "alternate"))
+        .and_then(|link| feed_url.join(&link.href).ok())
+        .filter(|link| matches!(link.scheme(), "http" | "https"))
+        .map(String::from);
+    let summary = entry
+        .summary
+        .map(|summary| text(&summary))
+        .or_else(|| {
+            let content = entry.content?;
+            let body = content.body?;
+            Some(if content.content_type.subty() == "plain" {
+                body
+            } else {
+                strip_markup(&body)
+            })
+        })
+        .map(|summary| shorten(summary.split_whitespace().collect::<Vec<_>>().join(" ")))
+        .filter(|summary| !summary.is_empty());
+    let title = entry
+        .title
+        .map(|title| text(&title).split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|title| !title.is_empty())
+        .or_else(|| link.clone())
+        .unwrap_or_else(|| entry.id.clone());
+    NewFeedEntry {
+        guid: entry.id,
+        title,
+        link,
+        summary,
+        published_at: entry.published.or(entry.updated),
+    }
+}
+
+/// The text of a feed's text construct, without any markup.
+fn text(text: &feed_rs::model::Text) -> String {
+    if text.content_type.subty() == "plain" {
+        text.content.clone()
+    } else {
+        strip_markup(&text.content)
+    }
+}
+
+/// Drops the tags from `html` and decodes its character references.
+fn strip_markup(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(['<', '&']) {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with('<') {
+            // Tags separate words as often as not.
+            text.push(' ');
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let character = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                reference => reference
+                    .strip_prefix("#x")
+                    .or_else(|| reference.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| reference.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32),
+            };
+            character.map(|character| (character, end))
+        });
+        match decoded {
+            Some((character, end)) => {
+                text.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// `summary` cut short at a word boundary if it is over
+/// [`SUMMARY_LENGTH`].
+fn shorten(summary: String) -> String {
+    match summary.char_indices().nth(SUMMARY_LENGTH) {
+        None => summary,
+        Some((limit, _)) => {
+            let end = summary[..limit].rfind(' ').unwrap_or(limit);
+            format!("{}…", summary[..end].trim_end_matches(|c: char| c.is_ascii_punctuation()))
+        }
+    }
+}
+
+/// Renders a digest of `entries`: the subject is the issue's title, and
+/// the bodies are its content.
+pub fn render_digest(templates: &Templates, entries: &[FeedEntry]) -> Result<RenderedEmail, minijinja::Error> {
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            minijinja::context! {
+                title => entry.title,
+                summary => entry.summary,
+                link => entry.link,
+                // Links are parsed URLs, so ampersands are all that is
+                // left to escape in an attribute.
+                href => entry.link.as_ref().map(|link| {
+                    minijinja::Value::from_safe_string(link.replace('&', "&amp;"))
+                }),
+                published_at => entry.published_at,
+            }
+        })
+        .collect();
+    templates.render_email(
+        EmailTemplate::FeedDigest,
+        minijinja::context! {
+            locale => DEFAULT_LOCALE,
+            entries => entries,
+        },
+    )
+}
+
+/// Fetches every feed and stores the entries not seen before, then drafts
+/// a digest issue from those not digested yet, scheduling it right away
+/// if `settings` say so. Returns the issue, if there were new entries.
+///
+/// A feed that cannot be fetched is skipped until the next poll.
+pub async fn ingest_feeds(
+    app_state: &AppState,
+    settings: &FeedSettings,
+    now: DateTime<Utc>,
+) -> eyre::Result<Option<NewsletterIssue>> {
+    for url in &settings.urls {
+        match fetch_feed(&app_state.http_client, url).await {
+            Ok(entries) => {
+                let stored = app_state
+                    .subscribers
+                    .store_feed_entries(url.as_str(), &entries, now)
+                    .await?;
+                tracing::info!(feed = %url, entries = entries.len(), new = stored, "Feed fetched");
+            }
+            Err(e) => tracing::error!("Failed to fetch feed {}: {:?}", url, e),
+        }
+    }
+    let mut entries = app_state.subscribers.claim_feed_entries(now).await?;
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    entries.sort_by_key(|entry| (entry.published_at.unwrap_or(entry.fetched_at), entry.fetched_at));
+    match draft_digest(app_state, settings, &entries, now).await {
+        Ok(issue) => {
+            tracing::info!(issue_id = %issue.id, entries = entries.len(), "Digest issue drafted");
+            Ok(Some(issue))
+        }
+        Err(e) => {
+            let entry_ids: Vec<_> = entries.iter().map(|entry| entry.id).collect();
+            app_state.subscribers.release_feed_entries(&entry_ids).await?;
+            Err(e)
+        }
+    }
+}
+
+async fn draft_digest(
+    app_state: &AppState,
+    settings: &FeedSettings,
+    entries: &[FeedEntry],
+    now: DateTime<Utc>,
+) -> eyre::Result<NewsletterIssue> {
+    let list = app_state
+        .subscribers
+        .find_list(&settings.list)
+        .await?
+        .ok_or_else(|| eyre!("There is no list `{}`", settings.list))?;
+    let digest = render_digest(&app_state.templates, entries)?;
+    let issue = app_state
+        .subscribers
+        .create_issue(&IssueContent {
+            list_id: list.id,
+            title: digest.subject,
+            html_content: digest.html_body,
+            text_content: digest.text_body,
+            segment: None,
+            tracking: false,
+            public: false,
+        })
+        .await?;
+    match settings.digest {
+        DigestMode::Draft => Ok(issue),
+        DigestMode::Send => app_state
+            .subscribers
+            .schedule_issue(issue.id, Some(now))
+            .await?
+            .ok_or_else(|| eyre!("Digest issue {} was gone before it could be scheduled", issue.id)),
+    }
+}
+
+/// Ingests the feeds every `poll_interval`, forever.
+pub async fn run_feed_poller(app_state: AppState, settings: FeedSettings) {
+    let mut ticks = tokio::time::interval(settings.poll_interval().max(Duration::from_secs(1)));
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        if let Err(e) = ingest_feeds(&app_state, &settings, Utc::now()).await {
+            tracing::error!("Failed to ingest feeds: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feeds::{shorten, strip_markup, SUMMARY_LENGTH};
+
+    #[test]
+    fn markup_is_stripped_and_references_decoded() {
+        assert_eq!(
+            strip_markup("<p>Fish &amp; chips &lt;3 &#x2014; &#8220;fresh&#8221;&nbsp;&bogus;</p>"),
+            " Fish & chips <3 \u{2014} \u{201c}fresh\u{201d} &bogus; "
+        );
+        // An unterminated tag runs to the end.
+        assert_eq!(strip_markup("1 &amp; 2 <b"), "1 & 2  ");
+    }
+
+    #[test]
+    fn long_summaries_are_cut_short_at_a_word_boundary() {
+        let short = "A short summary.".to_owned();
+        assert_eq!(shorten(short.clone()), short);
+
+        let long = "word, ".repeat(SUMMARY_LENGTH);
+        let shortened = shorten(long.trim_end().to_owned());
+        assert!(shortened.chars().count() <= SUMMARY_LENGTH + 1);
+        assert!(shortened.ends_with("word…"));
+    }
+}
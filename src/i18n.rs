@@ -0,0 +1,152 @@
+//! src/i18n.rs
+use std::collections::HashMap;
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use fluent_langneg::{negotiate_languages, NegotiationStrategy};
+use unic_langid::LanguageIdentifier;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Message catalogs compiled into the binary, one per supported locale.
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+    ("pl", include_str!("../locales/pl.ftl")),
+];
+
+/// Picks the best supported locale for an `Accept-Language`-style list
+/// (a single tag such as `de` is a valid list too).
+pub fn negotiate(requested: Option<&str>) -> &'static str {
+    let requested = requested
+        .map(fluent_langneg::parse_accepted_languages)
+        .unwrap_or_default();
+    let available: Vec<LanguageIdentifier> = CATALOGS
+        .iter()
+        .map(|(locale, _)| locale.parse().expect("Invalid catalog locale"))
+        .collect();
+    let default: LanguageIdentifier = DEFAULT_LOCALE.parse().expect("Invalid default locale");
+    let chosen = negotiate_languages(
+        &requested,
+        &available,
+        Some(&default),
+        NegotiationStrategy::Lookup,
+    )[0];
+    available
+        .iter()
+        .position(|locale| locale == chosen)
+        .map(|index| CATALOGS[index].0)
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Looks up translated messages, falling back to [`DEFAULT_LOCALE`] when
+/// a locale is unknown or lacks a message.
+pub struct Localizer {
+    bundles: HashMap<&'static str, FluentBundle<FluentResource>>,
+}
+
+impl Localizer {
+    pub fn new() -> eyre::Result<Self> {
+        let mut bundles = HashMap::new();
+        for (locale, source) in CATALOGS {
+            let resource = FluentResource::try_new(source.to_string()).map_err(|(_, errors)| {
+                eyre::eyre!("Failed to parse the {} catalog: {:?}", locale, errors)
+            })?;
+            let mut bundle = FluentBundle::new_concurrent(vec![locale.parse()?]);
+            // Unicode isolation marks around placeables would end up in
+            // email subjects and confuse link detection in mail clients.
+            bundle.set_use_isolating(false);
+            bundle
+                .add_resource(resource)
+                .map_err(|errors| eyre::eyre!("Invalid {} catalog: {:?}", locale, errors))?;
+            bundles.insert(*locale, bundle);
+        }
+        Ok(Self { bundles })
+    }
+
+    pub fn translate(&self, locale: &str, key: &str, args: &[(&str, String)]) -> String {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, FluentValue::from(value.as_str()));
+        }
+        for candidate in [locale, DEFAULT_LOCALE] {
+            let Some(bundle) = self.bundles.get(candidate) else {
+                continue;
+            };
+            let Some(pattern) = bundle.get_message(key).and_then(|m| m.value()) else {
+                continue;
+            };
+            let mut errors = vec![];
+            let message = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+            if !errors.is_empty() {
+                tracing::warn!("Errors formatting {} in {}: {:?}", key, candidate, errors);
+            }
+            return message.into_owned();
+        }
+        tracing::error!("No translation for {}", key);
+        key.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use fluent_syntax::{ast, parser};
+
+    use crate::i18n::{negotiate, Localizer, CATALOGS, DEFAULT_LOCALE};
+
+    fn message_ids(source: &str) -> HashSet<String> {
+        parser::parse(source)
+            .expect("Failed to parse catalog")
+            .body
+            .into_iter()
+            .filter_map(|entry| match entry {
+                ast::Entry::Message(message) => Some(message.id.name.to_owned()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_catalog_has_the_same_keys_as_the_default_one() {
+        let (_, default_source) = CATALOGS
+            .iter()
+            .find(|(locale, _)| *locale == DEFAULT_LOCALE)
+            .unwrap();
+        let expected = message_ids(default_source);
+        for (locale, source) in CATALOGS {
+            let actual = message_ids(source);
+            let missing: Vec<_> = expected.difference(&actual).collect();
+            let extra: Vec<_> = actual.difference(&expected).collect();
+            assert!(missing.is_empty(), "{} is missing {:?}", locale, missing);
+            assert!(extra.is_empty(), "{} has unknown keys {:?}", locale, extra);
+        }
+    }
+
+    #[test]
+    fn negotiation_honours_quality_values_and_falls_back_to_the_default() {
+        assert_eq!(negotiate(Some("de")), "de");
+        assert_eq!(negotiate(Some("pl-PL")), "pl");
+        assert_eq!(negotiate(Some("fr-CH, fr;q=0.9, de;q=0.8, en;q=0.7")), "de");
+        assert_eq!(negotiate(Some("fr")), DEFAULT_LOCALE);
+        assert_eq!(negotiate(Some("")), DEFAULT_LOCALE);
+        assert_eq!(negotiate(None), DEFAULT_LOCALE);
+    }
+
+    #[test]
+    fn arguments_are_interpolated_without_isolation_marks() {
+        let localizer = Localizer::new().unwrap();
+        let greeting = localizer.translate("de", "greeting", &[("name", "Ursula".into())]);
+        assert_eq!(greeting, "Hallo Ursula,");
+    }
+
+    #[test]
+    fn unknown_locales_and_keys_fall_back() {
+        let localizer = Localizer::new().unwrap();
+        assert_eq!(
+            localizer.translate("xx", "confirmation-subject", &[]),
+            "Welcome!"
+        );
+        assert_eq!(localizer.translate("de", "no-such-key", &[]), "no-such-key");
+    }
+}
@@ -0,0 +1,560 @@
+//! src/import.rs
+//!
+//! Bulk import of subscribers from CSV or JSON Lines, one subscriber per
+//! record, streamed and stored in batches. CSV records may span lines, in
+//! quoted fields.
+use std::collections::HashSet;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    audit::EventContext,
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    i18n,
+    privacy::suppression_hash,
+    repository::{ImportedStatus, ImportedSubscriber, MailingList, Repository},
+    routes::{generate_subscription_token, send_confirmation_email},
+};
+
+/// Rows stored per round trip to the database.
+const BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// A header line naming the `email`, `name` and optional `locale` columns.
+    #[default]
+    Csv,
+    /// One `{"email": ..., "name": ..., "locale": ...}` object per line.
+    Jsonl,
+}
+
+impl std::str::FromStr for ImportFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ImportFormat::Csv),
+            "jsonl" | "ndjson" => Ok(ImportFormat::Jsonl),
+            _ => Err(eyre::eyre!("Unknown import format {}", s)),
+        }
+    }
+}
+
+pub struct ImportOptions {
+    pub format: ImportFormat,
+    /// The list everyone is added to.
+    pub list_id: Uuid,
+    /// Imports everyone as confirmed, recording where they gave consent.
+    /// Otherwise they are imported as pending and sent a confirmation email.
+    pub consent_source: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// The input is unusable as a whole, e.g. a CSV header without `email`.
+    InvalidInput(String),
+    Unexpected(eyre::Report),
+}
+
+impl From<eyre::Report> for ImportError {
+    fn from(e: eyre::Report) -> Self {
+        ImportError::Unexpected(e)
+    }
+}
+
+/// Why a record was not imported.
+#[derive(Debug, serde::Serialize, PartialEq, Eq)]
+pub struct RowError {
+    /// The line the record starts on, 1-based and counting the CSV header.
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// `malformed_row`, `invalid_email`, `invalid_name`, `duplicate` (earlier
+    /// in the same input), `already_subscribed` (to any list) or
+    /// `suppressed` (erased at the subscriber's request).
+    pub error: &'static str,
+    /// Details for `invalid_email`, as in the subscription form's response.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// A subscriber imported as pending, who still has to be sent their link.
+pub struct PendingConfirmation {
+    pub new_subscriber: NewSubscriber,
+    pub token: String,
+}
+
+pub struct ImportOutcome {
+    pub report: ImportReport,
+    pub pending_confirmations: Vec<PendingConfirmation>,
+}
+
+#[derive(serde::Deserialize)]
+struct Row {
+    email: String,
+    name: String,
+    #[serde(default)]
+    locale: Option<String>,
+}
+
+/// Positions of the columns we use in a CSV record.
+struct CsvColumns {
+    email: usize,
+    name: usize,
+    locale: Option<usize>,
+}
+
+struct Importer<'a> {
+    options: &'a ImportOptions,
+    repository: &'a dyn Repository,
+    context: EventContext,
+    csv_columns: Option<CsvColumns>,
+    seen: HashSet<String>,
+    batch: Vec<(usize, ImportedSubscriber)>,
+    outcome: ImportOutcome,
+}
+
+/// Each stored subscriber gets an `imported` event from `context`, with
+/// the consent source (or `import`) as its source.
+#[tracing::instrument(name = "Importing subscribers", skip_all, fields(format = ?options.format))]
+pub async fn import_subscribers<R: AsyncBufRead + Unpin>(
+    mut reader: R,
+    options: &ImportOptions,
+    repository: &dyn Repository,
+    context: EventContext,
+) -> Result<ImportOutcome, ImportError> {
+    let source = options.consent_source.as_deref().unwrap_or("import");
+    let mut importer = Importer {
+        options,
+        repository,
+        context: context.with_source(source),
+        csv_columns: None,
+        seen: HashSet::new(),
+        batch: Vec::with_capacity(BATCH_SIZE),
+        outcome: ImportOutcome {
+            report: ImportReport::default(),
+            pending_confirmations: vec![],
+        },
+    };
+    match options.format {
+        ImportFormat::Jsonl => {
+            let mut buffer = Vec::new();
+            let mut line_number = 0;
+            loop {
+                buffer.clear();
+                let read = reader
+                    .read_until(b'\n', &mut buffer)
+                    .await
+                    .map_err(|e| ImportError::Unexpected(e.into()))?;
+                if read == 0 {
+                    break;
+                }
+                line_number += 1;
+                importer.json_line(line_number, &buffer).await?;
+            }
+        }
+        ImportFormat::Csv => {
+            let mut records = CsvRecords::new(reader);
+            while let Some((line_number, record)) = records
+                .next()
+                .await
+                .map_err(|e| ImportError::Unexpected(e.into()))?
+            {
+                importer.csv_record(line_number, record).await?;
+            }
+            if importer.csv_columns.is_none() {
+                return Err(ImportError::InvalidInput("The CSV input has no header".into()));
+            }
+        }
+    }
+    importer.flush().await?;
+    // Rows rejected by the database are only known once their batch is stored.
+    importer.outcome.report.errors.sort_by_key(|error| error.line);
+    tracing::info!(
+        imported = importer.outcome.report.imported,
+        rejected = importer.outcome.report.errors.len(),
+        "Import finished"
+    );
+    Ok(importer.outcome)
+}
+
+impl Importer<'_> {
+    async fn json_line(&mut self, line_number: usize, bytes: &[u8]) -> Result<(), ImportError> {
+        let Ok(line) = std::str::from_utf8(bytes) else {
+            self.reject(line_number, None, "malformed_row", None);
+            return Ok(());
+        };
+        let line = line.trim_start_matches('\u{feff}').trim_end_matches(['\r', '\n']);
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        self.row(line_number, serde_json::from_str::<Row>(line).ok()).await
+    }
+
+    /// `record` is `None` if it is not valid UTF-8.
+    async fn csv_record(
+        &mut self,
+        line_number: usize,
+        record: Option<csv::StringRecord>,
+    ) -> Result<(), ImportError> {
+        if record.as_ref().is_some_and(|record| record.iter().all(|field| field.trim().is_empty())) {
+            return Ok(());
+        }
+        let row = match (&self.csv_columns, record) {
+            (None, record) => {
+                self.csv_columns = Some(csv_columns(record)?);
+                return Ok(());
+            }
+            (Some(columns), Some(record)) => csv_row(&record, columns),
+            (Some(_), None) => None,
+        };
+        self.row(line_number, row).await
+    }
+
+    /// `row` is `None` if its record could not be read.
+    async fn row(&mut self, line_number: usize, row: Option<Row>) -> Result<(), ImportError> {
+        let Some(row) = row else {
+            self.reject(line_number, None, "malformed_row", None);
+            return Ok(());
+        };
+
+        let email = row.email.trim().to_owned();
+        let new_subscriber = match (
+            SubscriberEmail::parse(email.clone()),
+            SubscriberName::parse(row.name),
+        ) {
+            (Err(rejection), _) => {
+                self.reject(line_number, Some(email), "invalid_email", Some(rejection.code()));
+                return Ok(());
+            }
+            (_, Err(_)) => {
+                self.reject(line_number, Some(email), "invalid_name", None);
+                return Ok(());
+            }
+            (Ok(email), Ok(name)) => NewSubscriber {
+                email,
+                name,
+                locale: i18n::negotiate(row.locale.as_deref()).to_owned(),
+            },
+        };
+        if !self.seen.insert(email.clone()) {
+            self.reject(line_number, Some(email), "duplicate", None);
+            return Ok(());
+        }
+        let status = match &self.options.consent_source {
+            Some(consent_source) => ImportedStatus::Confirmed {
+                consent_source: consent_source.clone(),
+            },
+            None => ImportedStatus::Pending {
+                token: generate_subscription_token(),
+            },
+        };
+        self.batch.push((line_number, ImportedSubscriber { new_subscriber, status }));
+        if self.batch.len() >= BATCH_SIZE {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), ImportError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::take(&mut self.batch);
+        let hashes: Vec<_> = batch
+            .iter()
+            .map(|(_, subscriber)| suppression_hash(subscriber.new_subscriber.email.as_ref()))
+            .collect();
+        let suppressed = self.repository.suppressed(&hashes).await?;
+        let mut lines = Vec::with_capacity(batch.len());
+        let mut subscribers = Vec::with_capacity(batch.len());
+        for ((line_number, subscriber), hash) in batch.into_iter().zip(hashes) {
+            if suppressed.contains(&hash) {
+                let email = subscriber.new_subscriber.email.as_ref().to_owned();
+                self.reject(line_number, Some(email), "suppressed", None);
+                continue;
+            }
+            lines.push(line_number);
+            subscribers.push(subscriber);
+        }
+        let inserted = self.repository
+            .import(&subscribers, self.options.list_id, &self.context).await?;
+        for (line_number, subscriber) in lines.into_iter().zip(subscribers) {
+            let email = subscriber.new_subscriber.email.as_ref();
+            if !inserted.contains(email) {
+                let email = email.to_owned();
+                self.reject(line_number, Some(email), "already_subscribed", None);
+                continue;
+            }
+            self.outcome.report.imported += 1;
+            if let ImportedStatus::Pending { token } = subscriber.status {
+                self.outcome.pending_confirmations.push(PendingConfirmation {
+                    new_subscriber: subscriber.new_subscriber,
+                    token,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn reject(
+        &mut self,
+        line: usize,
+        email: Option<String>,
+        error: &'static str,
+        reason: Option<&'static str>,
+    ) {
+        self.outcome.report.errors.push(RowError {
+            line,
+            email,
+            error,
+            reason,
+        });
+    }
+}
+
+/// CSV records read as the input arrives, each with the line it starts on.
+struct CsvRecords<R> {
+    reader: R,
+    parser: csv_core::Reader,
+    /// Line breaks between records, which are skipped before `parser` sees
+    /// them and so are not in its count.
+    skipped_lines: u64,
+    fields: Vec<u8>,
+    ends: Vec<usize>,
+}
+
+impl<R: AsyncBufRead + Unpin> CsvRecords<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            parser: csv_core::Reader::new(),
+            skipped_lines: 0,
+            fields: vec![0; 1024],
+            ends: vec![0; 16],
+        }
+    }
+
+    /// The next record, `None` in place of one that is not valid UTF-8.
+    async fn next(&mut self) -> std::io::Result<Option<(usize, Option<csv::StringRecord>)>> {
+        use csv_core::ReadRecordResult;
+
+        loop {
+            let input = self.reader.fill_buf().await?;
+            let blank = input.iter().take_while(|byte| matches!(byte, b'\r' | b'\n')).count();
+            self.skipped_lines += input[..blank].iter().filter(|byte| **byte == b'\n').count() as u64;
+            let at_record = blank < input.len() || input.is_empty();
+            self.reader.consume(blank);
+            if at_record {
+                break;
+            }
+        }
+        let line_number = (self.parser.line() + self.skipped_lines) as usize;
+        let (mut fields_len, mut ends_len) = (0, 0);
+        loop {
+            let input = self.reader.fill_buf().await?;
+            let (result, read, written, ended) = self.parser.read_record(
+                input,
+                &mut self.fields[fields_len..],
+                &mut self.ends[ends_len..],
+            );
+            self.reader.consume(read);
+            fields_len += written;
+            ends_len += ended;
+            match result {
+                ReadRecordResult::InputEmpty => {}
+                ReadRecordResult::OutputFull => self.fields.resize(self.fields.len() * 2, 0),
+                ReadRecordResult::OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                ReadRecordResult::Record => break,
+                ReadRecordResult::End => return Ok(None),
+            }
+        }
+        let mut record = csv::ByteRecord::with_capacity(fields_len, ends_len);
+        let mut start = 0;
+        for &end in &self.ends[..ends_len] {
+            record.push_field(&self.fields[start..end]);
+            start = end;
+        }
+        Ok(Some((line_number, csv::StringRecord::from_byte_record(record).ok())))
+    }
+}
+
+fn csv_columns(header: Option<csv::StringRecord>) -> Result<CsvColumns, ImportError> {
+    let record = header
+        .ok_or_else(|| ImportError::InvalidInput("The CSV header is malformed".into()))?;
+    let position = |name: &str| record
+        .iter()
+        .position(|column| column.trim_start_matches('\u{feff}').trim().eq_ignore_ascii_case(name));
+    match (position("email"), position("name")) {
+        (Some(email), Some(name)) => Ok(CsvColumns {
+            email,
+            name,
+            locale: position("locale"),
+        }),
+        _ => Err(ImportError::InvalidInput(
+            "The CSV header must name an `email` and a `name` column".into(),
+        )),
+    }
+}
+
+fn csv_row(record: &csv::StringRecord, columns: &CsvColumns) -> Option<Row> {
+    Some(Row {
+        email: record.get(columns.email)?.to_owned(),
+        name: record.get(columns.name)?.to_owned(),
+        locale: columns
+            .locale
+            .and_then(|locale| record.get(locale))
+            .filter(|locale| !locale.is_empty())
+            .map(str::to_owned),
+    })
+}
+
+/// Sends the confirmation emails for pending imports to `list` one after
+/// the other, logging failures; returns how many were sent.
+pub async fn send_confirmations(
+    app_state: &AppState,
+    list: &MailingList,
+    pending: Vec<PendingConfirmation>,
+) -> usize {
+    let mut sent = 0;
+    for confirmation in pending {
+        let result = send_confirmation_email(
+            &app_state.links,
+            &app_state.email_client,
+            &app_state.templates,
+            &confirmation.new_subscriber,
+            list,
+            &confirmation.token,
+        )
+        .await;
+        match result {
+            Ok(()) => sent += 1,
+            Err(e) => tracing::error!(
+                "Failed to send confirmation email to {}: {:?}",
+                confirmation.new_subscriber.email.as_ref(),
+                e
+            ),
+        }
+    }
+    sent
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::EventContext;
+    use crate::import::{import_subscribers, ImportError, ImportFormat, ImportOptions};
+    use crate::repository::{InMemorySubscriberRepository, ListRepository, SubscriberRepository, DEFAULT_LIST};
+
+    async fn options(
+        repository: &InMemorySubscriberRepository,
+        format: ImportFormat,
+        consent_source: Option<String>,
+    ) -> ImportOptions {
+        let list = repository.find_list(DEFAULT_LIST).await.unwrap().unwrap();
+        ImportOptions {
+            format,
+            list_id: list.id,
+            consent_source,
+        }
+    }
+
+    async fn import(
+        repository: &InMemorySubscriberRepository,
+        options: &ImportOptions,
+        input: &str,
+    ) -> Result<crate::import::ImportOutcome, ImportError> {
+        import_subscribers(input.as_bytes(), options, repository, EventContext::default()).await
+    }
+
+    #[tokio::test]
+    async fn csv_rows_are_validated_deduplicated_and_reported_by_line() {
+        let repository = InMemorySubscriberRepository::default();
+        let input = "\u{feff}Name,Email,Locale\r\n\
+            Ursula,ursula@example.com,de\r\n\
+            \"Le Guin, Ursula\",leguin@example.com,\r\n\
+            Nobody,not-an-email,\r\n\
+            Ursula again,ursula@example.com,\r\n\
+            \r\n\
+            ,blank@example.com,\r\n\
+            Short\r\n";
+
+        let options = options(&repository, ImportFormat::Csv, None).await;
+        let outcome = import(&repository, &options, input).await.unwrap();
+
+        assert_eq!(outcome.report.imported, 2);
+        let errors: Vec<_> = outcome
+            .report
+            .errors
+            .iter()
+            .map(|e| (e.line, e.error))
+            .collect();
+        assert_eq!(
+            errors,
+            [(4, "invalid_email"), (5, "duplicate"), (7, "invalid_name"), (8, "malformed_row")]
+        );
+        assert_eq!(outcome.pending_confirmations.len(), 2);
+        let ursula = repository.find_by_email("ursula@example.com").await.unwrap().unwrap();
+        assert_eq!(ursula.status, "pending");
+        assert_eq!(ursula.locale, "de");
+        let leguin = repository.find_by_email("leguin@example.com").await.unwrap().unwrap();
+        assert_eq!(leguin.name, "Le Guin, Ursula");
+    }
+
+    #[tokio::test]
+    async fn existing_subscribers_are_skipped_and_consented_rows_are_confirmed() {
+        let repository = InMemorySubscriberRepository::default();
+        let options = options(&repository, ImportFormat::Jsonl, Some("previous provider".into())).await;
+        let input = r#"{"email": "ursula@example.com", "name": "Ursula"}"#;
+        import(&repository, &options, input).await.unwrap();
+
+        let input = "{\"email\": \"ursula@example.com\", \"name\": \"Ursula\"}\n\
+            {\"email\": \"leguin@example.com\", \"name\": \"Le Guin\", \"locale\": \"pl\"}\n\
+            [1, 2, 3]\n";
+        let outcome = import(&repository, &options, input).await.unwrap();
+
+        assert_eq!(outcome.report.imported, 1);
+        let errors: Vec<_> = outcome.report.errors.iter().map(|e| (e.line, e.error)).collect();
+        assert_eq!(errors, [(1, "already_subscribed"), (3, "malformed_row")]);
+        assert!(outcome.pending_confirmations.is_empty());
+        let leguin = repository.find_by_email("leguin@example.com").await.unwrap().unwrap();
+        assert_eq!(leguin.status, "confirmed");
+        assert_eq!(leguin.locale, "pl");
+        assert_eq!(leguin.consent_source.as_deref(), Some("previous provider"));
+    }
+
+    #[tokio::test]
+    async fn quoted_csv_fields_may_span_lines() {
+        let repository = InMemorySubscriberRepository::default();
+        let input = "email,name\n\
+            ursula@example.com,\"Ursula\nLe Guin\"\n\
+            nobody,Nobody\n";
+
+        let options = options(&repository, ImportFormat::Csv, None).await;
+        let outcome = import(&repository, &options, input).await.unwrap();
+
+        assert_eq!(outcome.report.imported, 1);
+        let errors: Vec<_> = outcome.report.errors.iter().map(|e| (e.line, e.error)).collect();
+        assert_eq!(errors, [(4, "invalid_email")]);
+        let ursula = repository.find_by_email("ursula@example.com").await.unwrap().unwrap();
+        assert_eq!(ursula.name, "Ursula\nLe Guin");
+    }
+
+    #[tokio::test]
+    async fn csv_without_the_required_columns_is_rejected_as_a_whole() {
+        let repository = InMemorySubscriberRepository::default();
+        let options = options(&repository, ImportFormat::Csv, None).await;
+        for input in ["", "email,surname\nursula@example.com,Le Guin\n"] {
+            let result = import(&repository, &options, input).await;
+            assert!(matches!(result, Err(ImportError::InvalidInput(_))), "{:?}", input);
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! src/issues.rs
+//!
+//! Rendering newsletter issues, and sending scheduled ones once they are
+//! due.
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use eyre::eyre;
+use tokio::time::MissedTickBehavior;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::SubscriberEmail,
+    email_client::BatchEmail,
+    i18n::DEFAULT_LOCALE,
+    links::LinkBuilder,
+    repository::{DeliveryCounts, DeliveryOutcome, MailingList, NewsletterIssue, Subscriber},
+    segment::Segment,
+    templates::{EmailTemplate, RenderedEmail, Templates},
+    tracking::{rewrite_links, TrackingSigner, TrackingToken},
+};
+
+/// Recipients whose messages are rendered and handed to the email client
+/// at once, which bounds how many are held in memory.
+const SEND_CHUNK: usize = 2000;
+
+fn issue_context(issue: &NewsletterIssue, locale: &str) -> minijinja::Value {
+    minijinja::context! {
+        locale => locale,
+        title => issue.title,
+        html_content => issue.html_content,
+        text_content => issue.text_content,
+    }
+}
+
+pub fn render_issue(
+    templates: &Templates,
+    issue: &NewsletterIssue,
+    locale: &str,
+) -> Result<RenderedEmail, minijinja::Error> {
+    templates.render_email(EmailTemplate::NewsletterIssue, issue_context(issue, locale))
+}
+
+/// Renders the issue for `recipient` alone, with its web links going
+/// through the click redirector, the open pixel and a link to opt out of
+/// tracking. The text body's links are left alone.
+pub fn render_tracked_issue(
+    templates: &Templates,
+    links: &LinkBuilder,
+    signer: &TrackingSigner,
+    issue: &NewsletterIssue,
+    recipient: &Subscriber,
+) -> Result<RenderedEmail, minijinja::Error> {
+    let (issue_id, subscriber_id) = (issue.id, recipient.id);
+    let html_content = rewrite_links(&issue.html_content, |target| {
+        let url = Url::parse(target)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))?;
+        let token = signer.sign(&TrackingToken::Click {
+            issue_id,
+            subscriber_id,
+            url: url.into(),
+        });
+        Some(links.tracking_link("c", &token).into())
+    });
+    let open = signer.sign(&TrackingToken::Open {
+        issue_id,
+        subscriber_id,
+    });
+    let opt_out = signer.sign(&TrackingToken::OptOut { subscriber_id });
+    templates.render_email(
+        EmailTemplate::NewsletterIssue,
+        minijinja::context! {
+            html_content => html_content,
+            // We build the links ourselves, so they do not need escaping.
+            open_pixel => minijinja::Value::from_safe_string(links.tracking_link("o", &open).into()),
+            tracking_opt_out_link => minijinja::Value::from_safe_string(
+                links.tracking_link("opt-out", &opt_out).into()
+            ),
+            ..issue_context(issue, &recipient.locale)
+        },
+    )
+}
+
+/// Sends `issue` to `recipient` alone, as it would go out, with the subject
+/// marked as a test.
+pub async fn send_test(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+    list: &MailingList,
+    recipient: SubscriberEmail,
+) -> eyre::Result<()> {
+    let email = render_issue(&app_state.templates, issue, DEFAULT_LOCALE)?;
+    app_state
+        .email_client
+        .send_email_from(
+            &list.sender(app_state.email_client.sender().as_ref()),
+            recipient,
+            &format!("[Test] {}", email.subject),
+            &email.html_body,
+            &email.text_body,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Claims and sends every issue due by `now`, one after the other; returns
+/// how many were sent.
+///
+/// An issue whose list or segment no longer works goes back to being a
+/// draft. One interrupted while sending stays `sending` until it has made
+/// no progress for the scheduler's `stalled_after`; it is then taken over
+/// and sent to those its deliveries still have queued.
+pub async fn send_due_issues(app_state: &AppState, now: DateTime<Utc>) -> eyre::Result<usize> {
+    let stalled_before = now - app_state.scheduler.stalled_after();
+    let mut sent = 0;
+    while let Some(issue) = app_state
+        .subscribers
+        .claim_stalled_issue(now, stalled_before)
+        .await?
+    {
+        tracing::warn!("Resuming newsletter issue {}, stalled while sending", issue.id);
+        if send_issue(app_state, &issue, resume(app_state, &issue).await).await? {
+            sent += 1;
+        }
+    }
+    while let Some(issue) = app_state.subscribers.claim_due_issue(now).await? {
+        if send_issue(app_state, &issue, prepare(app_state, &issue).await).await? {
+            sent += 1;
+        }
+    }
+    Ok(sent)
+}
+
+/// Sends a claimed issue to the `prepared` recipients and marks it sent;
+/// `false` if it could not be prepared and went back to being a draft.
+async fn send_issue(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+    prepared: eyre::Result<(MailingList, Vec<Subscriber>)>,
+) -> eyre::Result<bool> {
+    let (list, recipients) = match prepared {
+        Ok(found) => found,
+        Err(e) => {
+            tracing::error!(
+                "Cannot send newsletter issue {}, turning it back into a draft: {:?}",
+                issue.id,
+                e
+            );
+            app_state.subscribers.release_issue(issue.id).await?;
+            return Ok(false);
+        }
+    };
+    let delivered = deliver(app_state, issue, &list, &recipients).await;
+    // A resumed issue also counts what went out before it stalled.
+    let sent = app_state.subscribers.delivery_counts(issue.id).await?.sent;
+    app_state.subscribers.finish_issue(issue.id, sent).await?;
+    tracing::info!(
+        issue_id = %issue.id,
+        recipients = recipients.len(),
+        delivered,
+        "Newsletter issue sent"
+    );
+    Ok(true)
+}
+
+/// Finds the issue's list and recipients, and queues a delivery to each.
+async fn prepare(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+) -> eyre::Result<(MailingList, Vec<Subscriber>)> {
+    let list = issue_list(app_state, issue).await?;
+    let segment = issue
+        .segment
+        .as_deref()
+        .map(|segment| Segment::parse(segment, &app_state.segmentation.attribute_keys))
+        .transpose()?;
+    let recipients = app_state
+        .subscribers
+        .recipients(list.id, segment.as_ref())
+        .await?;
+    let subscriber_ids: Vec<_> = recipients.iter().map(|recipient| recipient.id).collect();
+    app_state
+        .subscribers
+        .queue_deliveries(issue.id, &subscriber_ids)
+        .await?;
+    Ok((list, recipients))
+}
+
+/// Finds the list of an issue that stalled while sending, and those it is
+/// still queued for; one that stalled before queueing anyone is prepared
+/// afresh.
+async fn resume(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+) -> eyre::Result<(MailingList, Vec<Subscriber>)> {
+    if app_state.subscribers.delivery_counts(issue.id).await? == DeliveryCounts::default() {
+        return prepare(app_state, issue).await;
+    }
+    let list = issue_list(app_state, issue).await?;
+    let recipients = app_state.subscribers.queued_recipients(issue.id).await?;
+    Ok((list, recipients))
+}
+
+async fn issue_list(app_state: &AppState, issue: &NewsletterIssue) -> eyre::Result<MailingList> {
+    app_state
+        .subscribers
+        .find_list(&issue.list)
+        .await?
+        .ok_or_else(|| eyre!("There is no list `{}`", issue.list))
+}
+
+/// How a tracked issue goes out.
+struct Tracking<'a> {
+    signer: &'a TrackingSigner,
+    /// Recipients who opted out of tracking, who get the issue untracked.
+    opted_out: HashSet<Uuid>,
+}
+
+/// `None` unless both the issue and this instance track opens and clicks.
+async fn tracking<'a>(
+    app_state: &'a AppState,
+    issue: &NewsletterIssue,
+) -> eyre::Result<Option<Tracking<'a>>> {
+    let Some(signer) = app_state
+        .tracking_links
+        .as_ref()
+        .filter(|_| app_state.tracking.enabled && issue.tracking)
+    else {
+        return Ok(None);
+    };
+    let opted_out = app_state.subscribers.untracked_recipients(issue.id).await?;
+    Ok(Some(Tracking { signer, opted_out }))
+}
+
+/// Sends the issue to each recipient in their locale, in batches,
+/// recording how each delivery went; returns how many were sent.
+async fn deliver(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+    list: &MailingList,
+    recipients: &[Subscriber],
+) -> i64 {
+    let sender = list.sender(app_state.email_client.sender().as_ref());
+    // Without knowing who opted out, nobody can be tracked.
+    let tracking = tracking(app_state, issue).await.unwrap_or_else(|e| {
+        tracing::error!(
+            "Failed to look up tracking opt-outs, sending newsletter issue {} untracked: {:?}",
+            issue.id,
+            e
+        );
+        None
+    });
+    let mut rendered: HashMap<&str, RenderedEmail> = HashMap::new();
+    let mut delivered = 0;
+    for chunk in recipients.chunks(SEND_CHUNK) {
+        let mut emails = Vec::with_capacity(chunk.len());
+        let mut addressed = Vec::with_capacity(chunk.len());
+        for recipient in chunk {
+            match compose(app_state, issue, &sender, recipient, tracking.as_ref(), &mut rendered) {
+                Ok(email) => {
+                    emails.push(email);
+                    addressed.push(recipient);
+                }
+                Err(e) => {
+                    let outcome = failed(issue, recipient, &e);
+                    record_delivery(app_state, issue, recipient, &outcome).await;
+                }
+            }
+        }
+        let outcomes = app_state.email_client.send_batch(&emails).await;
+        for (recipient, outcome) in addressed.into_iter().zip(outcomes) {
+            let outcome = match outcome {
+                Ok(message_id) => {
+                    delivered += 1;
+                    DeliveryOutcome::Sent { message_id }
+                }
+                Err(e) => failed(issue, recipient, &e),
+            };
+            record_delivery(app_state, issue, recipient, &outcome).await;
+        }
+    }
+    delivered
+}
+
+fn failed(issue: &NewsletterIssue, recipient: &Subscriber, error: &dyn std::fmt::Display) -> DeliveryOutcome {
+    tracing::error!(
+        "Failed to send newsletter issue {} to {}: {}",
+        issue.id,
+        recipient.email,
+        error
+    );
+    DeliveryOutcome::Failed { error: error.to_string() }
+}
+
+async fn record_delivery(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+    recipient: &Subscriber,
+    outcome: &DeliveryOutcome,
+) {
+    if let Err(e) = app_state
+        .subscribers
+        .record_delivery(issue.id, recipient.id, outcome)
+        .await
+    {
+        tracing::error!("Failed to record a delivery of newsletter issue {}: {:?}", issue.id, e);
+    }
+}
+
+/// The issue's message to one recipient, rendered for them if it is
+/// tracked, or else for their locale unless it already is in `rendered`.
+fn compose<'a>(
+    app_state: &AppState,
+    issue: &NewsletterIssue,
+    sender: &str,
+    recipient: &'a Subscriber,
+    tracking: Option<&Tracking<'_>>,
+    rendered: &mut HashMap<&'a str, RenderedEmail>,
+) -> eyre::Result<BatchEmail> {
+    let tracked;
+    let email = match tracking.filter(|tracking| !tracking.opted_out.contains(&recipient.id)) {
+        Some(tracking) => {
+            tracked = render_tracked_issue(
+                &app_state.templates,
+                &app_state.links,
+                tracking.signer,
+                issue,
+                recipient,
+            )?;
+            &tracked
+        }
+        None => {
+            if !rendered.contains_key(recipient.locale.as_str()) {
+                let email = render_issue(&app_state.templates, issue, &recipient.locale)?;
+                rendered.insert(&recipient.locale, email);
+            }
+            &rendered[recipient.locale.as_str()]
+        }
+    };
+    Ok(BatchEmail {
+        sender: sender.to_owned(),
+        recipient: SubscriberEmail::parse(recipient.email.clone()).map_err(|e| eyre!(e.to_string()))?,
+        subject: email.subject.clone(),
+        html_body: email.html_body.clone(),
+        text_body: email.text_body.clone(),
+    })
+}
+
+/// Sends due issues every `poll_interval`, forever. Safe to run in every
+/// instance: each issue is claimed by one of them only.
+pub async fn run_scheduler(app_state: AppState, poll_interval: Duration) {
+    let mut ticks = tokio::time::interval(poll_interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        if let Err(e) = send_due_issues(&app_state, Utc::now()).await {
+            tracing::error!("Failed to send due newsletter issues: {:?}", e);
+        }
+    }
+}
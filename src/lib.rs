@@ -1,10 +1,28 @@
 //! src/lib.rs
 pub mod app_state;
+pub mod archive;
+pub mod audit;
+pub mod cli;
 pub mod configuration;
+pub mod confirmation;
 pub mod domain;
 pub mod email_client;
+pub mod email_validation;
+pub mod export;
+pub mod feeds;
+pub mod i18n;
+pub mod import;
+pub mod issues;
+pub mod links;
+pub mod migrations;
+pub mod preferences;
+pub mod privacy;
+pub mod repository;
 pub mod routes;
+pub mod segment;
 pub mod startup;
+pub mod templates;
+pub mod tracking;
 
 use tracing::{Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
@@ -22,12 +40,10 @@ where
         sink
     );
 
-    let subscriber = Registry::default()
+    Registry::default()
         .with(env_filter)
         .with(JsonStorageLayer)
-        .with(formatting_layer);
-
-    subscriber
+        .with(formatting_layer)
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Sync + Send) {
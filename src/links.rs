@@ -0,0 +1,178 @@
+//! src/links.rs
+use std::net::IpAddr;
+
+use eyre::{eyre, Result};
+use url::Url;
+
+use crate::preferences::EmailChangeRequest;
+use crate::privacy::{PrivacyAction, SignedRequest};
+
+/// Builds absolute links to this application from its public base URL.
+///
+/// The base URL may carry a path prefix (e.g. `https://example.com/newsletter`)
+/// when we sit behind a reverse proxy; it is preserved, and every segment
+/// appended to it is percent-encoded.
+#[derive(Clone, Debug)]
+pub struct LinkBuilder {
+    base_url: Url,
+}
+
+impl LinkBuilder {
+    /// Fails unless `base_url` is an absolute `https` URL. Plain `http` is
+    /// accepted for loopback hosts so local development keeps working.
+    pub fn new(base_url: Url) -> Result<Self> {
+        if base_url.cannot_be_a_base() {
+            return Err(eyre!("Base URL {} is not an absolute URL", base_url));
+        }
+        let host = base_url
+            .host_str()
+            .ok_or_else(|| eyre!("Base URL {} has no host", base_url))?;
+        let is_loopback = host == "localhost"
+            || host
+                .trim_matches(['[', ']'])
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback());
+        match base_url.scheme() {
+            "https" => {}
+            "http" if is_loopback => {}
+            scheme => {
+                return Err(eyre!(
+                    "Base URL {} must use https, not {}",
+                    base_url,
+                    scheme
+                ))
+            }
+        }
+        if base_url.query().is_some() || base_url.fragment().is_some() {
+            return Err(eyre!("Base URL {} must not have a query or fragment", base_url));
+        }
+        Ok(Self { base_url })
+    }
+
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// `base_url` followed by `segments`, each percent-encoded.
+    pub fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("Checked in LinkBuilder::new")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    pub fn confirmation_link(&self, subscription_token: &str) -> Url {
+        self.url(&["subscriptions", "confirm", subscription_token])
+    }
+
+    /// `/privacy/<action>` with the signed request as its query string.
+    pub fn privacy_link(&self, action: PrivacyAction, request: &SignedRequest) -> Url {
+        let mut url = self.url(&["privacy", action.as_str()]);
+        url.query_pairs_mut()
+            .append_pair("subscriber_id", &request.subscriber_id.to_string())
+            .append_pair("expires", &request.expires.to_string())
+            .append_pair("signature", &request.signature);
+        url
+    }
+
+    /// The tracking pixel, click redirector or tracking opt-out page
+    /// (`kind` is `o`, `c` or `opt-out`) for a signed tracking token.
+    pub fn tracking_link(&self, kind: &str, token: &str) -> Url {
+        self.url(&["t", kind, token])
+    }
+
+    pub fn preferences_link(&self) -> Url {
+        self.url(&["preferences"])
+    }
+
+    /// `/preferences/email/confirm` with the signed request as its query
+    /// string.
+    pub fn email_change_link(&self, request: &EmailChangeRequest) -> Url {
+        let mut url = self.url(&["preferences", "email", "confirm"]);
+        url.query_pairs_mut()
+            .append_pair("subscriber_id", &request.subscriber_id.to_string())
+            .append_pair("email", &request.email)
+            .append_pair("from", &request.from)
+            .append_pair("expires", &request.expires.to_string())
+            .append_pair("signature", &request.signature);
+        url
+    }
+
+    /// A page of the public archive; the first one has no `page` query.
+    pub fn archive_page_link(&self, page: i64) -> Url {
+        let mut url = self.url(&["archive"]);
+        if page > 1 {
+            url.query_pairs_mut().append_pair("page", &page.to_string());
+        }
+        url
+    }
+
+    /// An issue in the public archive.
+    pub fn archive_link(&self, slug: &str) -> Url {
+        self.url(&["archive", slug])
+    }
+
+    pub fn archive_feed_link(&self) -> Url {
+        self.url(&["archive", "feed.atom"])
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod tests {
+    use crate::links::LinkBuilder;
+    use claim::{assert_err, assert_ok};
+    use url::Url;
+
+    fn links(base_url: &str) -> LinkBuilder {
+        LinkBuilder::new(Url::parse(base_url).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn https_urls_and_loopback_http_urls_are_accepted() {
+        for base_url in [
+            "https://example.com",
+            "http://127.0.0.1:8000",
+            "http://localhost",
+            "http://[::1]:8000",
+        ] {
+            assert_ok!(LinkBuilder::new(Url::parse(base_url).unwrap()));
+        }
+    }
+
+    #[test]
+    fn non_https_and_non_absolute_urls_are_rejected() {
+        for base_url in [
+            "http://example.com",
+            "ftp://example.com",
+            "mailto:ursula@example.com",
+            "https://example.com/?a=b",
+        ] {
+            assert_err!(LinkBuilder::new(Url::parse(base_url).unwrap()));
+        }
+    }
+
+    #[test]
+    fn path_prefixes_are_kept_with_or_without_a_trailing_slash() {
+        for base_url in ["https://example.com/newsletter", "https://example.com/newsletter/"] {
+            assert_eq!(
+                links(base_url).confirmation_link("abc").as_str(),
+                "https://example.com/newsletter/subscriptions/confirm/abc"
+            );
+        }
+        assert_eq!(
+            links("https://example.com").confirmation_link("abc").as_str(),
+            "https://example.com/subscriptions/confirm/abc"
+        );
+    }
+
+    #[test]
+    fn segments_are_percent_encoded() {
+        assert_eq!(
+            links("https://example.com").url(&["a b", "c/d?e"]).as_str(),
+            "https://example.com/a%20b/c%2Fd%3Fe"
+        );
+    }
+}
@@ -1,10 +1,22 @@
+use std::net::SocketAddr;
 use tokio::net::TcpListener;
+use zero_to_prod::cli::{Command, MigrateCommand};
+use zero_to_prod::export;
+use zero_to_prod::feeds;
+use zero_to_prod::import::{self, ImportOptions};
+use zero_to_prod::issues;
+use zero_to_prod::audit::EventContext;
+use zero_to_prod::repository::{SubscriberFilter, DEFAULT_LIST};
+use futures_util::TryStreamExt;
+use tokio::io::AsyncWriteExt;
 use zero_to_prod::configuration::get_configuration;
-use zero_to_prod::{startup::{router, build}, get_subscriber, init_subscriber};
+use zero_to_prod::migrations::SchemaConnection;
+use zero_to_prod::{startup::{router, build, prepare_database}, get_subscriber, init_subscriber};
 use eyre::Result;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let command = Command::parse(std::env::args().skip(1))?;
     init_subscriber(get_subscriber(
         "zero-to-prod".into(),
         "debug".into(),
@@ -12,9 +24,84 @@ async fn main() -> Result<()> {
     ));
 
     let configuration = get_configuration().expect("Failed to read configuration.");
-    let listener = TcpListener::bind(configuration.application.address()).await.unwrap();
-    let app = router(build(configuration)?);
-    tracing::info!("Starting zero-to-prod");
-    // run our app with hyper, listening globally on port 3000
-    Ok(axum::serve(listener, app).await?)
+    match command {
+        Command::Serve => {
+            prepare_database(&configuration.database).await?;
+            let listener = TcpListener::bind(configuration.application.address()).await.unwrap();
+            let scheduler = configuration.scheduler.clone();
+            let feeds = configuration.feeds.clone();
+            let state = build(configuration)?;
+            if scheduler.enabled {
+                tokio::spawn(issues::run_scheduler(state.clone(), scheduler.poll_interval()));
+            }
+            if feeds.enabled {
+                tokio::spawn(feeds::run_feed_poller(state.clone(), feeds));
+            }
+            let app = router(state);
+            let app = app.into_make_service_with_connect_info::<SocketAddr>();
+            tracing::info!("Starting zero-to-prod");
+            // run our app with hyper, listening globally on port 3000
+            Ok(axum::serve(listener, app).await?)
+        }
+        Command::Migrate(command) => {
+            let mut connection = SchemaConnection::connect(&configuration.database).await?;
+            match command {
+                MigrateCommand::Up => connection.up().await?,
+                MigrateCommand::DownTo(version) => connection.down_to(version).await?,
+                MigrateCommand::Status => {
+                    for migration in connection.status().await? {
+                        println!("{}", migration);
+                    }
+                }
+            }
+            connection.close().await
+        }
+        Command::Import(command) => {
+            prepare_database(&configuration.database).await?;
+            let state = build(configuration)?;
+            let slug = command.list.as_deref().unwrap_or(DEFAULT_LIST);
+            let list = state
+                .subscribers
+                .find_list(slug)
+                .await?
+                .ok_or_else(|| eyre::eyre!("There is no list `{}`", slug))?;
+            let file = tokio::fs::File::open(&command.path).await?;
+            let options = ImportOptions {
+                format: command.format,
+                list_id: list.id,
+                consent_source: command.consent_source,
+            };
+            let outcome = import::import_subscribers(
+                tokio::io::BufReader::new(file),
+                &options,
+                state.subscribers.as_ref(),
+                EventContext::default(),
+            )
+            .await
+            .map_err(|e| match e {
+                import::ImportError::InvalidInput(message) => eyre::eyre!(message),
+                import::ImportError::Unexpected(e) => e,
+            })?;
+            let sent = import::send_confirmations(&state, &list, outcome.pending_confirmations).await;
+            println!("{}", serde_json::to_string_pretty(&outcome.report)?);
+            tracing::info!(confirmation_emails_sent = sent, "Import finished");
+            Ok(())
+        }
+        Command::Export(command) => {
+            prepare_database(&configuration.database).await?;
+            let state = build(configuration)?;
+            let filter = SubscriberFilter {
+                status: command.status,
+                ..Default::default()
+            };
+            let subscribers = state.subscribers.export(&filter);
+            let mut lines = export::encode(command.format, command.columns, subscribers);
+            let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(&command.path).await?);
+            while let Some(line) = lines.try_next().await? {
+                file.write_all(&line).await?;
+            }
+            file.flush().await?;
+            Ok(())
+        }
+    }
 }
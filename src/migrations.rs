@@ -0,0 +1,161 @@
+//! src/migrations.rs
+use std::collections::HashMap;
+use std::fmt;
+
+use eyre::{eyre, Result};
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    Connection, PgConnection,
+};
+
+use crate::configuration::{DatabaseBackend, DatabaseSettings};
+
+/// The Postgres migrations in `migrations/`, embedded in the binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Their SQLite equivalents in `migrations_sqlite/`.
+#[cfg(feature = "sqlite")]
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations_sqlite");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the file has changed since.
+    Modified,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+impl fmt::Display for MigrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Modified => "modified",
+        };
+        write!(f, "{:<16}{:<10}{}", self.version, state, self.description)
+    }
+}
+
+/// A dedicated connection for managing the schema of the configured database.
+///
+/// On Postgres the migrator holds an advisory lock while it runs, so
+/// instances started together apply each migration once. The lock belongs
+/// to the session, so it is released when this connection is closed or
+/// dropped, even if a migration fails half-way. SQLite deployments are
+/// single-instance and are not locked.
+pub enum SchemaConnection {
+    Postgres(PgConnection),
+    #[cfg(feature = "sqlite")]
+    Sqlite(sqlx::SqliteConnection),
+}
+
+/// Runs `$body` with `$conn` and `$migrator` bound for either backend.
+macro_rules! with_connection {
+    ($connection:expr, |$conn:ident, $migrator:ident| $body:expr) => {
+        match $connection {
+            SchemaConnection::Postgres($conn) => {
+                let $migrator = &MIGRATOR;
+                $body
+            }
+            #[cfg(feature = "sqlite")]
+            SchemaConnection::Sqlite($conn) => {
+                let $migrator = &SQLITE_MIGRATOR;
+                $body
+            }
+        }
+    };
+}
+
+impl SchemaConnection {
+    pub async fn connect(settings: &DatabaseSettings) -> Result<Self> {
+        match settings.backend {
+            DatabaseBackend::Postgres => Ok(SchemaConnection::Postgres(
+                PgConnection::connect_with(&settings.with_db()).await?,
+            )),
+            #[cfg(feature = "sqlite")]
+            DatabaseBackend::Sqlite => Ok(SchemaConnection::Sqlite(
+                sqlx::SqliteConnection::connect_with(&settings.sqlite()).await?,
+            )),
+            #[cfg(not(feature = "sqlite"))]
+            DatabaseBackend::Sqlite => Err(eyre!(
+                "The sqlite database backend needs the `sqlite` feature"
+            )),
+        }
+    }
+
+    /// Applies every pending migration.
+    #[tracing::instrument(name = "Applying migrations", skip(self))]
+    pub async fn up(&mut self) -> Result<()> {
+        with_connection!(self, |conn, migrator| migrator.run(&mut *conn).await)?;
+        Ok(())
+    }
+
+    /// Reverts every applied migration newer than `version`; `0` reverts all.
+    #[tracing::instrument(name = "Reverting migrations", skip(self))]
+    pub async fn down_to(&mut self, version: i64) -> Result<()> {
+        with_connection!(self, |conn, migrator| {
+            if version != 0 && !migrator.version_exists(version) {
+                return Err(eyre!("There is no migration with version {}", version));
+            }
+            migrator.undo(&mut *conn, version).await
+        })?;
+        Ok(())
+    }
+
+    /// Every migration this binary knows about, oldest first.
+    pub async fn status(&mut self) -> Result<Vec<MigrationStatus>> {
+        with_connection!(self, |conn, migrator| {
+            conn.ensure_migrations_table().await?;
+            let applied: HashMap<_, _> = conn
+                .list_applied_migrations()
+                .await?
+                .into_iter()
+                .map(|m| (m.version, m.checksum))
+                .collect();
+            Ok(migrator
+                .iter()
+                .filter(|m| !m.migration_type.is_down_migration())
+                .map(|m| MigrationStatus {
+                    version: m.version,
+                    description: m.description.to_string(),
+                    state: match applied.get(&m.version) {
+                        None => MigrationState::Pending,
+                        Some(checksum) if *checksum != m.checksum => MigrationState::Modified,
+                        Some(_) => MigrationState::Applied,
+                    },
+                })
+                .collect())
+        })
+    }
+
+    /// Fails if the schema is older than this binary expects.
+    pub async fn ensure_current(&mut self) -> Result<()> {
+        let pending: Vec<_> = self
+            .status()
+            .await?
+            .into_iter()
+            .filter(|m| m.state == MigrationState::Pending)
+            .map(|m| m.version.to_string())
+            .collect();
+        if !pending.is_empty() {
+            return Err(eyre!(
+                "The database schema is behind this binary (pending migrations: {}); \
+                run `zero-to-prod migrate up` or set `database.run_migrations_on_startup`",
+                pending.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    pub async fn close(self) -> Result<()> {
+        with_connection!(self, |conn, _migrator| conn.close().await)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+//! src/preferences.rs
+//!
+//! The preference center, where subscribers change their name, lists and
+//! delivery, or unsubscribe. They get in through a privacy link (see
+//! [`crate::privacy`]) and stay in through a short-lived session, signed
+//! like those links and kept in a cookie, so no session is stored.
+//!
+//! A subscriber moving to a new address confirms it through a link sent
+//! there, signed with both addresses, so it is good for one change only.
+//! The old address is only in the link as a keyed fingerprint, so the link
+//! can be checked without it once the change is made.
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::privacy::LinkRejection;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SESSION_COOKIE: &str = "preferences_session";
+
+/// The longest a subscriber can pause delivery for at once.
+pub const MAX_PAUSE_WEEKS: i64 = 26;
+
+/// Signs and checks preference center sessions:
+/// `<subscriber id>.<expiry>.<signature>`, where the expiry is a Unix
+/// timestamp and the signature a hex-encoded HMAC-SHA256 of both.
+#[derive(Clone)]
+pub struct SessionSigner {
+    key: SecretString,
+    ttl: Duration,
+}
+
+impl SessionSigner {
+    pub fn new(key: SecretString, ttl: Duration) -> Self {
+        Self { key, ttl }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Prefixed, so that no privacy link signature is ever a valid session
+    /// signature, or the other way around.
+    fn mac(&self, subscriber_id: Uuid, expires: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("preferences-session:{}:{}", subscriber_id, expires).as_bytes());
+        mac
+    }
+
+    pub fn sign(&self, subscriber_id: Uuid, now: DateTime<Utc>) -> String {
+        let expires = (now + self.ttl).timestamp();
+        let signature = hex::encode(self.mac(subscriber_id, expires).finalize().into_bytes());
+        format!("{}.{}.{}", subscriber_id, expires, signature)
+    }
+
+    /// The subscriber the session is for, if it is genuine and current.
+    pub fn verify(&self, session: &str, now: DateTime<Utc>) -> Option<Uuid> {
+        let mut parts = session.split('.');
+        let subscriber_id = parts.next()?.parse().ok()?;
+        let expires: i64 = parts.next()?.parse().ok()?;
+        let signature = hex::decode(parts.next()?).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        self.mac(subscriber_id, expires).verify_slice(&signature).ok()?;
+        (expires >= now.timestamp()).then_some(subscriber_id)
+    }
+}
+
+/// The query string of a link confirming a new address.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EmailChangeRequest {
+    pub subscriber_id: Uuid,
+    /// The new address.
+    pub email: String,
+    /// Hex-encoded fingerprint of the address the link was issued from.
+    pub from: String,
+    /// Unix timestamp, in seconds.
+    pub expires: i64,
+    /// Hex-encoded HMAC-SHA256 of the subscriber id, the fingerprint, the
+    /// new address and the expiry.
+    pub signature: String,
+}
+
+/// Signs and checks links confirming a new address. The current address
+/// is signed but not sent, so a link stops working once it has been used,
+/// or the address changed some other way.
+#[derive(Clone)]
+pub struct EmailChangeSigner {
+    key: SecretString,
+    ttl: Duration,
+}
+
+impl EmailChangeSigner {
+    pub fn new(key: SecretString, ttl: Duration) -> Self {
+        Self { key, ttl }
+    }
+
+    fn mac(&self, subscriber_id: Uuid, from: &str, new_email: &str, expires: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        // Addresses cannot contain a newline, so the fields cannot run together.
+        mac.update(format!("email-change\n{}\n{}\n{}\n{}", subscriber_id, from, new_email, expires).as_bytes());
+        mac
+    }
+
+    /// Keyed, so the old address cannot be told from the link.
+    fn fingerprint(&self, subscriber_id: Uuid, old_email: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("email-change-from\n{}\n{}", subscriber_id, old_email).as_bytes());
+        mac
+    }
+
+    pub fn sign(&self, subscriber_id: Uuid, old_email: &str, new_email: &str, now: DateTime<Utc>) -> EmailChangeRequest {
+        let expires = (now + self.ttl).timestamp();
+        let from = hex::encode(self.fingerprint(subscriber_id, old_email).finalize().into_bytes());
+        let mac = self.mac(subscriber_id, &from, new_email, expires);
+        EmailChangeRequest {
+            subscriber_id,
+            email: new_email.to_owned(),
+            from,
+            expires,
+            signature: hex::encode(mac.finalize().into_bytes()),
+        }
+    }
+
+    /// Whether we signed the link, and it is still current.
+    pub fn verify(&self, request: &EmailChangeRequest, now: DateTime<Utc>) -> Result<(), LinkRejection> {
+        let signature = hex::decode(&request.signature).map_err(|_| LinkRejection::Invalid)?;
+        self.mac(request.subscriber_id, &request.from, &request.email, request.expires)
+            .verify_slice(&signature)
+            .map_err(|_| LinkRejection::Invalid)?;
+        if request.expires < now.timestamp() {
+            return Err(LinkRejection::Expired);
+        }
+        Ok(())
+    }
+
+    /// Whether the link was issued while the subscriber's address was
+    /// `old_email`.
+    pub fn issued_from(&self, request: &EmailChangeRequest, old_email: &str) -> bool {
+        hex::decode(&request.from).is_ok_and(|from| {
+            self.fingerprint(request.subscriber_id, old_email)
+                .verify_slice(&from)
+                .is_ok()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use crate::preferences::{EmailChangeSigner, SessionSigner};
+    use crate::privacy::LinkRejection;
+
+    fn signer(key: &str) -> SessionSigner {
+        SessionSigner::new(key.to_owned().into(), Duration::minutes(60))
+    }
+
+    #[test]
+    fn sessions_verify_until_they_expire() {
+        let signer = signer("key");
+        let now = Utc::now();
+        let id = Uuid::new_v4();
+        let session = signer.sign(id, now);
+
+        assert_eq!(signer.verify(&session, now), Some(id));
+        assert_eq!(signer.verify(&session, now + Duration::minutes(61)), None);
+    }
+
+    #[test]
+    fn sessions_are_bound_to_their_subscriber_expiry_and_key() {
+        let signer = signer("key");
+        let now = Utc::now();
+        let id = Uuid::new_v4();
+        let session = signer.sign(id, now);
+        let [_, expires, signature] = session.split('.').collect::<Vec<_>>()[..] else {
+            panic!("Unexpected session {}", session);
+        };
+
+        let other = format!("{}.{}.{}", Uuid::new_v4(), expires, signature);
+        assert_eq!(signer.verify(&other, now), None);
+        let extended = format!("{}.{}.{}", id, expires.parse::<i64>().unwrap() + 3600, signature);
+        assert_eq!(signer.verify(&extended, now), None);
+        assert_eq!(signer.verify(&format!("{}.extra", session), now), None);
+        assert_eq!(signer.verify("garbage", now), None);
+        assert_eq!(self::signer("other key").verify(&session, now), None);
+    }
+
+    #[test]
+    fn email_change_links_only_work_from_the_address_they_were_signed_for() {
+        let signer = EmailChangeSigner::new("key".to_owned().into(), Duration::hours(24));
+        let now = Utc::now();
+        let request = signer.sign(Uuid::new_v4(), "old@example.com", "new@example.com", now);
+
+        assert_eq!(signer.verify(&request, now), Ok(()));
+        assert_eq!(signer.verify(&request, now + Duration::hours(25)), Err(LinkRejection::Expired));
+        assert!(signer.issued_from(&request, "old@example.com"));
+        assert!(!signer.issued_from(&request, "new@example.com"));
+        assert!(!request.from.contains("old"));
+        let mut redirected = request.clone();
+        redirected.email = "attacker@example.com".into();
+        assert_eq!(signer.verify(&redirected, now), Err(LinkRejection::Invalid));
+        let mut moved = request.clone();
+        moved.from = signer.sign(request.subscriber_id, "other@example.com", "new@example.com", now).from;
+        assert_eq!(signer.verify(&moved, now), Err(LinkRejection::Invalid));
+    }
+}
@@ -0,0 +1,943 @@
+//! src/repository.rs
+//!
+//! Persistence for subscribers, their tags and attributes, the lists they
+//! are on, their confirmation tokens and the issues sent to those lists with their
+//! deliveries and tracked opens and clicks. Each aggregate has its own trait,
+//! and each backend a module per trait, so handlers can run against Postgres
+//! in production and against memory in unit tests.
+mod in_memory;
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use eyre::eyre;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::audit::{EventContext, EventKind, SubscriptionEvent};
+use crate::domain::NewSubscriber;
+use crate::segment::Segment;
+
+pub use in_memory::InMemorySubscriberRepository;
+pub use postgres::PostgresSubscriberRepository;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteSubscriberRepository;
+
+/// A subscriber as stored, whatever their status.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct Subscriber {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub locale: String,
+    pub subscribed_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    /// Set for subscribers who consented somewhere other than through us.
+    pub consent_source: Option<String>,
+}
+
+/// A confirmation token with what we need to know about its subscriber.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct SubscriptionToken {
+    pub subscriber_id: Uuid,
+    /// The list the token confirms the subscriber on.
+    pub list_id: Uuid,
+    pub locale: String,
+    /// Of the membership, not of the subscriber.
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What segments select subscribers by.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct SubscriberProfile {
+    pub tags: BTreeSet<String>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// Changes to a [`SubscriberProfile`]; the caller checks the keys and tags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileUpdate {
+    /// Attributes to set, or to remove when `None`; others stay as they are.
+    pub attributes: BTreeMap<String, Option<String>>,
+    pub add_tags: BTreeSet<String>,
+    pub remove_tags: BTreeSet<String>,
+}
+
+/// What a subscriber chose at the preference center; the caller checks
+/// the name and resolves the lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreferenceUpdate {
+    pub name: String,
+    /// The lists to be on; they leave any others.
+    pub list_ids: BTreeSet<Uuid>,
+    /// No issue reaches them until then.
+    pub paused_until: Option<DateTime<Utc>>,
+}
+
+impl SubscriberProfile {
+    pub fn apply(&mut self, update: &ProfileUpdate) {
+        for (key, value) in &update.attributes {
+            match value {
+                Some(value) => self.attributes.insert(key.clone(), value.clone()),
+                None => self.attributes.remove(key),
+            };
+        }
+        self.tags.extend(update.add_tags.iter().cloned());
+        self.tags.retain(|tag| !update.remove_tags.contains(tag));
+    }
+}
+
+/// Slug of the list everyone was on before there were several, and which
+/// subscriptions go to unless they name another.
+pub const DEFAULT_LIST: &str = "default";
+
+/// A publication people subscribe to.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct MailingList {
+    pub id: Uuid,
+    pub slug: String,
+    pub name: String,
+    /// Sends the list's emails instead of the configured sender.
+    pub sender_email: Option<String>,
+    pub sender_name: Option<String>,
+    /// Replace the default copy of the confirmation email.
+    pub confirmation_subject: Option<String>,
+    pub confirmation_intro: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MailingList {
+    /// The `From` of the list's emails: its own sender if it has one,
+    /// otherwise `default_sender`, with the list's sender name if any.
+    pub fn sender(&self, default_sender: &str) -> String {
+        let address = self.sender_email.as_deref().unwrap_or(default_sender);
+        match &self.sender_name {
+            Some(name) => format!("\"{}\" <{}>", name, address),
+            None => address.to_owned(),
+        }
+    }
+}
+
+/// What admins can change about a list; see [`MailingList`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct ListSettings {
+    pub name: String,
+    pub sender_email: Option<String>,
+    pub sender_name: Option<String>,
+    pub confirmation_subject: Option<String>,
+    pub confirmation_intro: Option<String>,
+}
+
+/// A subscriber's place on a list.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct Membership {
+    /// The list's slug.
+    pub list: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+/// An issue of a list's newsletter. Its status goes from `draft` to
+/// `scheduled`, `sending` and `sent`; only drafts and scheduled issues can
+/// still be changed.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct NewsletterIssue {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub list_id: Uuid,
+    /// The list's slug.
+    pub list: String,
+    pub title: String,
+    pub html_content: String,
+    pub text_content: String,
+    /// Narrows the recipients down, see [`Segment`]; everyone on the list
+    /// if `None`.
+    pub segment: Option<String>,
+    /// Whether opens and clicks are tracked, for subscribers who did not
+    /// opt out and if tracking is enabled at all.
+    pub tracking: bool,
+    /// Whether it shows in the public archive once sent.
+    pub public: bool,
+    /// Where it is in the public archive: `/archive/<slug>`. Follows the
+    /// title until the issue is sent.
+    pub slug: String,
+    pub status: String,
+    pub send_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+    /// How many subscribers it was sent to, once sent.
+    pub recipient_count: Option<i64>,
+}
+
+/// What editors write; see [`NewsletterIssue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssueContent {
+    pub list_id: Uuid,
+    pub title: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub segment: Option<String>,
+    pub tracking: bool,
+    pub public: bool,
+}
+
+/// An issue's delivery to one subscriber: `queued`, then `sent` or
+/// `failed`, and `bounced` if the provider reports it did not arrive.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct Delivery {
+    pub subscriber_id: Uuid,
+    pub email: String,
+    pub status: String,
+    /// The provider's id for the message, once sent.
+    pub message_id: Option<String>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An issue as delivered to one subscriber, as handed out on an access
+/// request.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct ReceivedIssue {
+    pub issue_id: Uuid,
+    pub title: String,
+    pub status: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An open or click recorded for one subscriber: `kind` is `open` or
+/// `click`, and clicks have the url they went to.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct TrackingEvent {
+    pub issue_id: Uuid,
+    pub kind: String,
+    pub url: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// An entry of one of the feeds digest issues are drafted from.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct FeedEntry {
+    pub id: Uuid,
+    pub feed_url: String,
+    pub guid: String,
+    pub title: String,
+    pub link: Option<String>,
+    /// Plain text.
+    pub summary: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// An entry as read from its feed; see [`FeedEntry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewFeedEntry {
+    /// The feed's id for the entry, which tells new entries from ones
+    /// stored before.
+    pub guid: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub summary: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// How one attempt at delivering an issue went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    Sent { message_id: Option<String> },
+    Failed { error: String },
+}
+
+/// How many of an issue's deliveries are in each state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct DeliveryCounts {
+    pub queued: i64,
+    pub sent: i64,
+    pub failed: i64,
+    pub bounced: i64,
+}
+
+impl FromIterator<(String, i64)> for DeliveryCounts {
+    /// Sums `(status, count)` pairs; unknown statuses are ignored.
+    fn from_iter<I: IntoIterator<Item = (String, i64)>>(counts: I) -> Self {
+        let mut totals = DeliveryCounts::default();
+        for (status, count) in counts {
+            match status.as_str() {
+                "queued" => totals.queued += count,
+                "sent" => totals.sent += count,
+                "failed" => totals.failed += count,
+                "bounced" => totals.bounced += count,
+                _ => {}
+            }
+        }
+        totals
+    }
+}
+
+/// Something a subscriber did with a tracked issue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackedAction {
+    /// Loaded the tracking pixel, which is all we can tell of an open.
+    Opened,
+    /// Followed a link in the issue to `url`.
+    Clicked { url: String },
+}
+
+impl TrackedAction {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TrackedAction::Opened => "open",
+            TrackedAction::Clicked { .. } => "click",
+        }
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            TrackedAction::Opened => None,
+            TrackedAction::Clicked { url } => Some(url),
+        }
+    }
+}
+
+/// How subscribers engaged with an issue. The unique counts are of
+/// subscribers; the others count every open and click.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Engagement {
+    pub opens: i64,
+    pub unique_opens: i64,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+    /// Most clicked first.
+    pub links: Vec<LinkClicks>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub struct LinkClicks {
+    pub url: String,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
+
+/// Narrows down a subscriber listing; every criterion is optional.
+#[derive(Debug, Clone, Default)]
+pub struct SubscriberFilter {
+    pub status: Option<String>,
+    /// Inclusive lower bound on `subscribed_at`.
+    pub subscribed_after: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on `subscribed_at`.
+    pub subscribed_before: Option<DateTime<Utc>>,
+    /// Case-insensitive prefix of the email or the name.
+    pub search: Option<String>,
+}
+
+/// Position in a listing, which is ordered by `subscribed_at` then `id`,
+/// newest first. Rendered as an opaque string for API clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    pub subscribed_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl PageCursor {
+    pub fn after(subscriber: &Subscriber) -> Self {
+        Self {
+            subscribed_at: subscriber.subscribed_at,
+            id: subscriber.id,
+        }
+    }
+}
+
+impl fmt::Display for PageCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = self.subscribed_at.timestamp_nanos_opt().ok_or(fmt::Error)?;
+        write!(f, "{}_{}", nanos, self.id.simple())
+    }
+}
+
+impl FromStr for PageCursor {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || eyre!("Invalid cursor {}", s);
+        let (nanos, id) = s.split_once('_').ok_or_else(invalid)?;
+        Ok(Self {
+            subscribed_at: DateTime::from_timestamp_nanos(nanos.parse().map_err(|_| invalid())?),
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A subscriber brought in by a bulk import.
+pub struct ImportedSubscriber {
+    pub new_subscriber: NewSubscriber,
+    pub status: ImportedStatus,
+}
+
+pub enum ImportedStatus {
+    /// Confirmed elsewhere; `consent_source` records where.
+    Confirmed { consent_source: String },
+    /// Needs to confirm through the link carrying `token`.
+    Pending { token: String },
+}
+
+/// How moving a subscriber to a new address went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailChange {
+    Changed,
+    /// Their address is no longer the one the change was asked from.
+    Stale,
+    /// Another subscriber has the new address.
+    Taken,
+    /// The new address was erased and may not be used again.
+    Suppressed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Keep the row with status `deleted`, so the subscriber stays on record.
+    Soft,
+    /// Remove the subscriber altogether.
+    Hard,
+}
+
+/// Subscribers and their confirmation tokens.
+#[async_trait]
+pub trait SubscriberRepository: Send + Sync {
+    /// Starts a unit of work; nothing written through it is visible to
+    /// others until it is committed, and dropping it rolls it back.
+    async fn begin(&self) -> eyre::Result<Box<dyn SubscriberTransaction>>;
+
+    async fn find_by_email(&self, email: &str) -> eyre::Result<Option<Subscriber>>;
+
+    async fn find_token(&self, token: &str) -> eyre::Result<Option<SubscriptionToken>>;
+
+    /// Marks the subscriber as confirmed on the list, and as a subscriber if
+    /// this is their first list, and records it in their trail.
+    async fn confirm(
+        &self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<()>;
+
+    async fn find_by_id(&self, subscriber_id: Uuid) -> eyre::Result<Option<Subscriber>>;
+
+    /// Up to `limit` subscribers matching `filter`, newest first, starting
+    /// after `cursor` when given.
+    async fn list(
+        &self,
+        filter: &SubscriberFilter,
+        cursor: Option<PageCursor>,
+        limit: i64,
+    ) -> eyre::Result<Vec<Subscriber>>;
+
+    /// When each of the subscriber's confirmation tokens was issued, newest first.
+    async fn token_history(&self, subscriber_id: Uuid) -> eyre::Result<Vec<DateTime<Utc>>>;
+
+    /// Replaces the subscriber's confirmation tokens for each list in
+    /// `tokens` with the one given for it, so only the newest link works;
+    /// `false`, changing nothing, if they were issued any token after
+    /// `issued_after`. Checked and written atomically.
+    async fn reissue_tokens(
+        &self,
+        subscriber_id: Uuid,
+        tokens: &[(Uuid, String)],
+        issued_after: DateTime<Utc>,
+    ) -> eyre::Result<bool>;
+
+    /// The subscriber's tags and attributes; empty if there is no such
+    /// subscriber.
+    async fn profile(&self, subscriber_id: Uuid) -> eyre::Result<SubscriberProfile>;
+
+    /// Until when the subscriber paused delivery, if they did.
+    async fn paused_until(&self, subscriber_id: Uuid) -> eyre::Result<Option<DateTime<Utc>>>;
+
+    /// Unsubscribes the subscriber from every list, drops their tokens and
+    /// records it in their trail; `false` if they were not pending or
+    /// confirmed.
+    async fn unsubscribe(&self, subscriber_id: Uuid, context: &EventContext) -> eyre::Result<bool>;
+
+    /// Moves the subscriber from `old_email` to `new_email`, whose
+    /// suppression hash is `suppression_hash`, keeping their lists, status
+    /// and trail, and records it there. Checked and written atomically, so
+    /// the address is never taken twice nor a suppressed one used.
+    async fn change_email(
+        &self,
+        subscriber_id: Uuid,
+        old_email: &str,
+        new_email: &str,
+        suppression_hash: &str,
+        context: &EventContext,
+    ) -> eyre::Result<EmailChange>;
+
+    /// Deletes the subscriber and their tokens; `false` if there was no
+    /// such subscriber. Soft deletes are recorded in the subscriber's trail.
+    async fn delete(
+        &self,
+        subscriber_id: Uuid,
+        mode: DeleteMode,
+        context: &EventContext,
+    ) -> eyre::Result<bool>;
+
+    /// Stores a batch of subscribers at once on `list_id`, skipping any whose
+    /// email is already subscribed, and records an `imported` event for each
+    /// one stored. Returns the emails that were stored.
+    async fn import(
+        &self,
+        subscribers: &[ImportedSubscriber],
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<HashSet<String>>;
+
+    /// Every subscriber matching `filter`, oldest first, all read from one
+    /// consistent snapshot. Rows are fetched as the stream is polled.
+    fn export(&self, filter: &SubscriberFilter) -> SubscriberStream;
+}
+
+/// Lists, and who is on which.
+#[async_trait]
+pub trait ListRepository: Send + Sync {
+    /// The lists the subscriber is on, in the order they joined them.
+    async fn memberships(&self, subscriber_id: Uuid) -> eyre::Result<Vec<Membership>>;
+
+    /// Applies what a confirmed subscriber chose at the preference center.
+    /// Lists they join are confirmed right away, since they proved they own
+    /// the address to get there; lists they leave are unsubscribed, and
+    /// their pending tokens dropped. Each is recorded in their trail.
+    /// `false` if there is no such confirmed subscriber.
+    async fn update_preferences(
+        &self,
+        subscriber_id: Uuid,
+        update: &PreferenceUpdate,
+        context: &EventContext,
+    ) -> eyre::Result<bool>;
+
+    /// Confirmed subscribers who confirmed `list_id`, match `segment` and
+    /// have not paused delivery, oldest first: whom an issue sent to the
+    /// list reaches.
+    async fn recipients(
+        &self,
+        list_id: Uuid,
+        segment: Option<&Segment>,
+    ) -> eyre::Result<Vec<Subscriber>>;
+
+    /// Every list, oldest first.
+    async fn lists(&self) -> eyre::Result<Vec<MailingList>>;
+
+    async fn find_list(&self, slug: &str) -> eyre::Result<Option<MailingList>>;
+
+    /// `None` if the slug is taken.
+    async fn create_list(
+        &self,
+        slug: &str,
+        settings: &ListSettings,
+    ) -> eyre::Result<Option<MailingList>>;
+
+    /// `None` if there is no such list.
+    async fn update_list(
+        &self,
+        slug: &str,
+        settings: &ListSettings,
+    ) -> eyre::Result<Option<MailingList>>;
+
+    /// Deletes the list with its memberships and their tokens; `false` if
+    /// there was no such list.
+    async fn delete_list(&self, slug: &str) -> eyre::Result<bool>;
+}
+
+/// Issues sent to lists, their deliveries, the opens and clicks tracked on
+/// them, and the public archive.
+#[async_trait]
+pub trait IssueRepository: Send + Sync {
+    /// Issues with `status`, or all of them, newest first.
+    async fn issues(&self, status: Option<&str>) -> eyre::Result<Vec<NewsletterIssue>>;
+
+    async fn find_issue(&self, issue_id: Uuid) -> eyre::Result<Option<NewsletterIssue>>;
+
+    /// Stores a new draft.
+    async fn create_issue(&self, content: &IssueContent) -> eyre::Result<NewsletterIssue>;
+
+    /// Replaces the content of a draft or scheduled issue, keeping its
+    /// schedule; `None` if there is no such issue or it is past that.
+    async fn update_issue(
+        &self,
+        issue_id: Uuid,
+        content: &IssueContent,
+    ) -> eyre::Result<Option<NewsletterIssue>>;
+
+    /// Schedules a draft or scheduled issue for `send_at`, or turns it back
+    /// into a draft when `None`; `None` if there is no such issue or it is
+    /// past that.
+    async fn schedule_issue(
+        &self,
+        issue_id: Uuid,
+        send_at: Option<DateTime<Utc>>,
+    ) -> eyre::Result<Option<NewsletterIssue>>;
+
+    /// Deletes a draft or scheduled issue; `false` if there is no such
+    /// issue or it is past that.
+    async fn delete_issue(&self, issue_id: Uuid) -> eyre::Result<bool>;
+
+    /// Moves the scheduled issue that was due first by `now` to `sending`
+    /// and returns it. Each issue is claimed by one caller only, however
+    /// many instances poll at once.
+    async fn claim_due_issue(&self, now: DateTime<Utc>) -> eyre::Result<Option<NewsletterIssue>>;
+
+    /// Claims again, as of `now`, the `sending` issue that has made no
+    /// progress since `stalled_before`: neither it nor any of its
+    /// deliveries changed since. Each is taken over by one caller only.
+    async fn claim_stalled_issue(
+        &self,
+        now: DateTime<Utc>,
+        stalled_before: DateTime<Utc>,
+    ) -> eyre::Result<Option<NewsletterIssue>>;
+
+    /// Marks a claimed issue as sent to `recipient_count` subscribers.
+    async fn finish_issue(&self, issue_id: Uuid, recipient_count: i64) -> eyre::Result<()>;
+
+    /// Turns a claimed issue that could not be sent to anyone back into a
+    /// draft, so it can be fixed and scheduled again.
+    async fn release_issue(&self, issue_id: Uuid) -> eyre::Result<()>;
+
+    /// Records the issue as queued for each of `subscriber_ids`; deliveries
+    /// already recorded stay as they are.
+    async fn queue_deliveries(&self, issue_id: Uuid, subscriber_ids: &[Uuid]) -> eyre::Result<()>;
+
+    /// The subscribers the issue is still queued for, in the order
+    /// [`recipients`](ListRepository::recipients) gives them.
+    async fn queued_recipients(&self, issue_id: Uuid) -> eyre::Result<Vec<Subscriber>>;
+
+    /// Records an attempt at delivering the issue to the subscriber. A
+    /// successful one keeps the error of any earlier attempt.
+    async fn record_delivery(
+        &self,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        outcome: &DeliveryOutcome,
+    ) -> eyre::Result<()>;
+
+    async fn delivery_counts(&self, issue_id: Uuid) -> eyre::Result<DeliveryCounts>;
+
+    /// Up to `limit` of the issue's failed or bounced deliveries, latest
+    /// first.
+    async fn failed_deliveries(&self, issue_id: Uuid, limit: i64) -> eyre::Result<Vec<Delivery>>;
+
+    /// Every issue delivered, or being delivered, to the subscriber, oldest
+    /// first.
+    async fn received_issues(&self, subscriber_id: Uuid) -> eyre::Result<Vec<ReceivedIssue>>;
+
+    /// Opts the subscriber out of open and click tracking, or back in;
+    /// `false` if there is no such subscriber.
+    async fn set_tracking_opt_out(&self, subscriber_id: Uuid, opt_out: bool) -> eyre::Result<bool>;
+
+    /// Those the issue is being delivered to who opted out of tracking.
+    async fn untracked_recipients(&self, issue_id: Uuid) -> eyre::Result<HashSet<Uuid>>;
+
+    /// Records what the subscriber did with the issue, unless they opted
+    /// out of tracking since it was sent; `false` if it was not recorded.
+    async fn record_tracked_action(
+        &self,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        action: &TrackedAction,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<bool>;
+
+    async fn engagement(&self, issue_id: Uuid) -> eyre::Result<Engagement>;
+
+    /// Every open and click recorded for the subscriber, oldest first.
+    async fn tracking_events(&self, subscriber_id: Uuid) -> eyre::Result<Vec<TrackingEvent>>;
+
+    /// Up to `limit` sent issues from the public archive, latest first,
+    /// after skipping `offset`.
+    async fn archived_issues(&self, limit: i64, offset: i64) -> eyre::Result<Vec<NewsletterIssue>>;
+
+    /// The issue at `slug`, if it was sent and is in the public archive.
+    async fn find_archived_issue(&self, slug: &str) -> eyre::Result<Option<NewsletterIssue>>;
+}
+
+/// Entries of the feeds that digests are made from.
+#[async_trait]
+pub trait FeedRepository: Send + Sync {
+    /// Stores the entries of the feed at `feed_url` not stored yet, and
+    /// returns how many there were. The entries of a feed stored for the
+    /// first time count as digested already, so that its back catalogue
+    /// does not end up in a digest.
+    async fn store_feed_entries(
+        &self,
+        feed_url: &str,
+        entries: &[NewFeedEntry],
+        fetched_at: DateTime<Utc>,
+    ) -> eyre::Result<u64>;
+
+    /// Marks every entry not digested yet as digested at `now`, and returns
+    /// them. Each entry is claimed by one caller only.
+    async fn claim_feed_entries(&self, now: DateTime<Utc>) -> eyre::Result<Vec<FeedEntry>>;
+
+    /// Returns claimed entries that could not be digested to the next
+    /// claim.
+    async fn release_feed_entries(&self, entry_ids: &[Uuid]) -> eyre::Result<()>;
+}
+
+/// Subscribers' audit trails, and erasure at their request.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    /// Irreversibly replaces the subscriber's personal data, drops their
+    /// tokens, tags and attributes and records `suppression_hash` (see
+    /// [`crate::privacy::suppression_hash`]). The row itself stays, so it
+    /// still counts towards aggregates, and so does the trail, minus the
+    /// addresses and user agents in it. `false` if there was no such
+    /// subscriber.
+    async fn erase(
+        &self,
+        subscriber_id: Uuid,
+        suppression_hash: &str,
+        context: &EventContext,
+    ) -> eyre::Result<bool>;
+
+    /// Which of `suppression_hashes` belong to erased addresses.
+    async fn suppressed(&self, suppression_hashes: &[String]) -> eyre::Result<HashSet<String>>;
+
+    /// The subscriber's audit trail, oldest first.
+    async fn events(&self, subscriber_id: Uuid) -> eyre::Result<Vec<SubscriptionEvent>>;
+}
+
+/// Every aggregate at once, as the handlers share it; each backend
+/// implements them all on one type.
+pub trait Repository:
+    SubscriberRepository + ListRepository + IssueRepository + FeedRepository + AuditRepository
+{
+}
+
+impl<T> Repository for T where
+    T: SubscriberRepository + ListRepository + IssueRepository + FeedRepository + AuditRepository
+{
+}
+
+/// The title in lowercase ASCII words joined by dashes, then the start of
+/// the id, which keeps slugs unique however titles repeat.
+fn issue_slug(title: &str, issue_id: Uuid) -> String {
+    let words: Vec<_> = title
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    let mut slug = String::new();
+    for word in words {
+        if slug.len() + word.len() > 60 {
+            break;
+        }
+        slug.push_str(&word);
+        slug.push('-');
+    }
+    if slug.is_empty() {
+        slug.push_str("issue-");
+    }
+    slug.push_str(&issue_id.simple().to_string()[..8]);
+    slug
+}
+
+/// How a subscriber's memberships change at the preference center.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct MembershipChanges {
+    /// Lists they are not on, or left: they subscribe and confirm at once.
+    join: Vec<Uuid>,
+    /// Lists they are pending on: they confirm.
+    confirm: Vec<Uuid>,
+    /// Lists they are pending or confirmed on but did not choose.
+    leave: Vec<Uuid>,
+}
+
+impl MembershipChanges {
+    /// From their current `(list id, status)` memberships to being on
+    /// `list_ids`.
+    fn new(current: &[(Uuid, String)], list_ids: &BTreeSet<Uuid>) -> Self {
+        let mut changes = MembershipChanges::default();
+        for list_id in list_ids {
+            match current.iter().find(|(id, _)| id == list_id) {
+                Some((_, status)) if status == "confirmed" => {}
+                Some((_, status)) if status == "pending" => changes.confirm.push(*list_id),
+                _ => changes.join.push(*list_id),
+            }
+        }
+        changes.leave = current
+            .iter()
+            .filter(|(id, status)| !list_ids.contains(id) && (status == "pending" || status == "confirmed"))
+            .map(|(id, _)| *id)
+            .collect();
+        changes
+    }
+
+    /// The trail of the changes, in the order they are recorded.
+    fn events(&self) -> Vec<(Uuid, EventKind)> {
+        let joined = self
+            .join
+            .iter()
+            .flat_map(|id| [(*id, EventKind::Subscribed), (*id, EventKind::Confirmed)]);
+        let confirmed = self.confirm.iter().map(|id| (*id, EventKind::Confirmed));
+        let left = self.leave.iter().map(|id| (*id, EventKind::Unsubscribed));
+        joined.chain(confirmed).chain(left).collect()
+    }
+}
+
+/// Stands in for the address of an erased subscriber; unique, like the
+/// address it replaces, and undeliverable.
+fn erased_email(subscriber_id: Uuid) -> String {
+    format!("erased-{}@erased.invalid", subscriber_id.simple())
+}
+
+pub type SubscriberStream = BoxStream<'static, eyre::Result<Subscriber>>;
+
+/// Rows fetched ahead of the consumer of an export.
+const EXPORT_BUFFER: usize = 64;
+
+/// Runs `produce` in its own task, which owns the connection for as long as
+/// the export lasts, and hands its rows over through a bounded channel so a
+/// slow consumer holds the producer back. Sending fails once the stream is
+/// dropped, which is the producer's cue to stop.
+fn spawn_export<F, Fut>(produce: F) -> SubscriberStream
+where
+    F: FnOnce(mpsc::Sender<eyre::Result<Subscriber>>) -> Fut,
+    Fut: Future<Output = eyre::Result<()>> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(EXPORT_BUFFER);
+    let export = produce(sender.clone());
+    tokio::spawn(async move {
+        if let Err(e) = export.await {
+            tracing::error!("Failed to export subscribers: {:?}", e);
+            let _ = sender.send(Err(e)).await;
+        }
+    });
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|row| (row, receiver))
+    })
+    .boxed()
+}
+
+#[async_trait]
+pub trait SubscriberTransaction: Send {
+    /// Stores `new_subscriber` as pending and returns their id; they are on
+    /// no list until they [join](SubscriberTransaction::join_list) one.
+    async fn insert_subscriber(&mut self, new_subscriber: &NewSubscriber) -> eyre::Result<Uuid>;
+
+    /// Brings a deleted or unsubscribed subscriber back as pending, with the
+    /// details from `new_subscriber`; they are on no list until they
+    /// [join](SubscriberTransaction::join_list) one again.
+    async fn reactivate_subscriber(
+        &mut self,
+        subscriber_id: Uuid,
+        new_subscriber: &NewSubscriber,
+    ) -> eyre::Result<()>;
+
+    /// Puts the subscriber on the list, pending confirmation, and records a
+    /// `subscribed` event; someone who left the list, or has not confirmed
+    /// yet, is pending on it again. Returns `false`, changing nothing, if
+    /// they are confirmed on it already.
+    async fn join_list(
+        &mut self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<bool>;
+
+    /// Stores a token confirming the subscriber on the list.
+    async fn store_token(
+        &mut self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        token: &str,
+    ) -> eyre::Result<()>;
+
+    /// Sets and removes the subscriber's attributes and tags.
+    async fn update_profile(
+        &mut self,
+        subscriber_id: Uuid,
+        update: &ProfileUpdate,
+    ) -> eyre::Result<()>;
+
+    async fn commit(self: Box<Self>) -> eyre::Result<()>;
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use claim::assert_err;
+    use uuid::Uuid;
+
+    use crate::audit::EventKind;
+    use crate::repository::{issue_slug, MembershipChanges, PageCursor};
+
+    #[test]
+    fn cursors_round_trip_through_their_string_form() {
+        let cursor = PageCursor {
+            subscribed_at: Utc.timestamp_opt(1_760_000_000, 123_456_789).unwrap(),
+            id: Uuid::new_v4(),
+        };
+        assert_eq!(cursor.to_string().parse::<PageCursor>().unwrap(), cursor);
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        for cursor in ["", "123", "abc_def", "123_not-a-uuid"] {
+            assert_err!(cursor.parse::<PageCursor>());
+        }
+    }
+
+    #[test]
+    fn membership_changes_take_subscribers_to_the_chosen_lists() {
+        let [kept, pending, left, rejoined, new, dropped] = [(); 6].map(|_| Uuid::new_v4());
+        let current = [
+            (kept, "confirmed".to_owned()),
+            (pending, "pending".to_owned()),
+            (left, "confirmed".to_owned()),
+            (rejoined, "unsubscribed".to_owned()),
+            (dropped, "unsubscribed".to_owned()),
+        ];
+
+        let changes = MembershipChanges::new(&current, &[kept, pending, rejoined, new].into());
+
+        let mut joined = vec![rejoined, new];
+        joined.sort();
+        assert_eq!(
+            changes,
+            MembershipChanges {
+                join: joined.clone(),
+                confirm: vec![pending],
+                leave: vec![left],
+            }
+        );
+        assert_eq!(
+            changes.events(),
+            [
+                (joined[0], EventKind::Subscribed),
+                (joined[0], EventKind::Confirmed),
+                (joined[1], EventKind::Subscribed),
+                (joined[1], EventKind::Confirmed),
+                (pending, EventKind::Confirmed),
+                (left, EventKind::Unsubscribed),
+            ]
+        );
+    }
+
+    #[test]
+    fn issue_slugs_follow_the_title_and_end_with_the_id() {
+        let issue_id = Uuid::parse_str("3f2a9c1b-0000-4000-8000-000000000000").unwrap();
+        assert_eq!(issue_slug("Issue #12: Rust & You!", issue_id), "issue-12-rust-you-3f2a9c1b");
+        assert_eq!(issue_slug("Ünïcödé", issue_id), "n-c-d-3f2a9c1b");
+        assert_eq!(issue_slug("???", issue_id), "issue-3f2a9c1b");
+        assert!(issue_slug(&"word ".repeat(50), issue_id).len() <= 70);
+    }
+}
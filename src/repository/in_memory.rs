@@ -0,0 +1,714 @@
+mod audit;
+mod feeds;
+mod issues;
+mod lists;
+mod subscribers;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use eyre::eyre;
+use uuid::Uuid;
+
+use super::{
+    Delivery, FeedEntry, MailingList, Membership, NewsletterIssue, ProfileUpdate, Subscriber, SubscriberFilter, SubscriberProfile,
+    SubscriberTransaction, TrackedAction, DEFAULT_LIST,
+};
+use crate::audit::{EventContext, EventKind, SubscriptionEvent};
+use crate::domain::NewSubscriber;
+
+#[derive(Default)]
+struct Store {
+    subscribers: HashMap<Uuid, Subscriber>,
+    lists: HashMap<Uuid, MailingList>,
+    /// (subscriber id, list id) -> membership, whose `list` is the slug.
+    memberships: HashMap<(Uuid, Uuid), Membership>,
+    /// Token -> (subscriber id, list id, creation time).
+    tokens: HashMap<String, (Uuid, Uuid, DateTime<Utc>)>,
+    suppressions: HashSet<String>,
+    /// In the order they were recorded.
+    events: Vec<(Uuid, SubscriptionEvent)>,
+    profiles: HashMap<Uuid, SubscriberProfile>,
+    /// Made in a transaction, and applied to `profiles` on commit.
+    profile_updates: Vec<(Uuid, ProfileUpdate)>,
+    issues: HashMap<Uuid, NewsletterIssue>,
+    /// (issue id, subscriber id) -> delivery, whose `email` is filled in
+    /// when read.
+    deliveries: HashMap<(Uuid, Uuid), Delivery>,
+    tracking_opt_outs: HashSet<Uuid>,
+    /// Subscriber id -> until when they paused delivery.
+    paused: HashMap<Uuid, DateTime<Utc>>,
+    /// (issue id, subscriber id, action, when), in the order they were
+    /// recorded.
+    tracking_events: Vec<(Uuid, Uuid, TrackedAction, DateTime<Utc>)>,
+    /// Each with when it was digested, if it was.
+    feed_entries: Vec<(FeedEntry, Option<DateTime<Utc>>)>,
+}
+
+impl Store {
+    fn slug(&self, list_id: Uuid) -> Option<String> {
+        self.lists.get(&list_id).map(|list| list.slug.clone())
+    }
+}
+
+fn event(kind: EventKind, list: Option<String>, context: &EventContext) -> SubscriptionEvent {
+    SubscriptionEvent {
+        kind: kind.as_str().to_owned(),
+        list,
+        occurred_at: Utc::now(),
+        ip: context.ip.clone(),
+        user_agent: context.user_agent.clone(),
+        request_id: context.request_id.clone(),
+        source: context.source.clone(),
+    }
+}
+
+/// Keeps everything in memory; meant for tests.
+///
+/// Mirrors the constraints of the Postgres schema (unique emails, tokens
+/// referencing existing subscribers) so handlers see the same failures.
+/// Starts out with the default list, like a migrated database.
+#[derive(Clone)]
+pub struct InMemorySubscriberRepository {
+    store: Arc<Mutex<Store>>,
+}
+
+impl Default for InMemorySubscriberRepository {
+    fn default() -> Self {
+        let id = Uuid::new_v4();
+        let default_list = MailingList {
+            id,
+            slug: DEFAULT_LIST.into(),
+            name: "Newsletter".into(),
+            sender_email: None,
+            sender_name: None,
+            confirmation_subject: None,
+            confirmation_intro: None,
+            created_at: Utc::now(),
+        };
+        let store = Store {
+            lists: HashMap::from([(id, default_list)]),
+            ..Store::default()
+        };
+        Self {
+            store: Arc::new(Mutex::new(store)),
+        }
+    }
+}
+
+impl InMemorySubscriberRepository {
+    /// All subscribers, in no particular order.
+    pub fn subscribers(&self) -> Vec<Subscriber> {
+        self.store.lock().unwrap().subscribers.values().cloned().collect()
+    }
+
+    /// Backdates `token`, to exercise expiry.
+    pub fn set_token_created_at(&self, token: &str, created_at: DateTime<Utc>) {
+        if let Some(entry) = self.store.lock().unwrap().tokens.get_mut(token) {
+            entry.2 = created_at;
+        }
+    }
+}
+
+/// Buffers its writes and merges them into the shared store on commit.
+pub struct InMemorySubscriberTransaction {
+    shared: Arc<Mutex<Store>>,
+    pending: Store,
+}
+
+
+fn matches(filter: &SubscriberFilter, subscriber: &Subscriber) -> bool {
+    let starts_with = |value: &str, prefix: &str| value.to_lowercase().starts_with(&prefix.to_lowercase());
+    filter.status.as_ref().is_none_or(|status| subscriber.status == *status)
+        && filter.subscribed_after.is_none_or(|after| subscriber.subscribed_at >= after)
+        && filter.subscribed_before.is_none_or(|before| subscriber.subscribed_at < before)
+        && filter.search.as_ref().is_none_or(|search| {
+            starts_with(&subscriber.email, search) || starts_with(&subscriber.name, search)
+        })
+}
+
+#[async_trait]
+impl SubscriberTransaction for InMemorySubscriberTransaction {
+    async fn insert_subscriber(&mut self, new_subscriber: &NewSubscriber) -> eyre::Result<Uuid> {
+        let email = new_subscriber.email.as_ref();
+        let shared = self.shared.lock().unwrap();
+        let mut existing = shared.subscribers.values().chain(self.pending.subscribers.values());
+        if existing.any(|s| s.email == email) {
+            return Err(eyre!("A subscriber with email {} already exists", email));
+        }
+        drop(shared);
+        let id = Uuid::new_v4();
+        self.pending.subscribers.insert(
+            id,
+            Subscriber {
+                id,
+                email: email.to_owned(),
+                name: new_subscriber.name.as_ref().to_owned(),
+                status: "pending".into(),
+                locale: new_subscriber.locale.clone(),
+                subscribed_at: Utc::now(),
+                confirmed_at: None,
+                consent_source: None,
+            },
+        );
+        Ok(id)
+    }
+
+    async fn reactivate_subscriber(
+        &mut self,
+        subscriber_id: Uuid,
+        new_subscriber: &NewSubscriber,
+    ) -> eyre::Result<()> {
+        let shared = self.shared.lock().unwrap();
+        let Some(subscriber) = self
+            .pending
+            .subscribers
+            .get(&subscriber_id)
+            .or_else(|| shared.subscribers.get(&subscriber_id))
+            .filter(|s| s.status == "deleted" || s.status == "unsubscribed")
+        else {
+            return Err(eyre!("Subscriber {} is not deleted or unsubscribed", subscriber_id));
+        };
+        let subscriber = Subscriber {
+            name: new_subscriber.name.as_ref().to_owned(),
+            status: "pending".into(),
+            locale: new_subscriber.locale.clone(),
+            subscribed_at: Utc::now(),
+            confirmed_at: None,
+            ..subscriber.clone()
+        };
+        let memberships: Vec<_> = shared
+            .memberships
+            .iter()
+            .filter(|((id, _), _)| *id == subscriber_id)
+            .map(|(key, membership)| {
+                let membership = Membership { status: "unsubscribed".into(), ..membership.clone() };
+                (*key, membership)
+            })
+            .collect();
+        drop(shared);
+        self.pending.subscribers.insert(subscriber_id, subscriber);
+        self.pending.memberships.extend(memberships);
+        Ok(())
+    }
+
+    async fn join_list(
+        &mut self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let shared = self.shared.lock().unwrap();
+        if !shared.subscribers.contains_key(&subscriber_id)
+            && !self.pending.subscribers.contains_key(&subscriber_id)
+        {
+            return Err(eyre!("Unknown subscriber {}", subscriber_id));
+        }
+        let Some(list) = shared.slug(list_id) else {
+            return Err(eyre!("Unknown list {}", list_id));
+        };
+        let key = (subscriber_id, list_id);
+        let existing = self.pending.memberships.get(&key).or_else(|| shared.memberships.get(&key));
+        if existing.is_some_and(|membership| membership.status == "confirmed") {
+            return Ok(false);
+        }
+        drop(shared);
+        let membership = Membership {
+            list: list.clone(),
+            status: "pending".into(),
+            subscribed_at: Utc::now(),
+            confirmed_at: None,
+        };
+        self.pending.memberships.insert(key, membership);
+        self.pending
+            .events
+            .push((subscriber_id, event(EventKind::Subscribed, Some(list), context)));
+        Ok(true)
+    }
+
+    async fn store_token(
+        &mut self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        token: &str,
+    ) -> eyre::Result<()> {
+        let shared = self.shared.lock().unwrap();
+        let key = (subscriber_id, list_id);
+        if !shared.memberships.contains_key(&key) && !self.pending.memberships.contains_key(&key) {
+            return Err(eyre!("Subscriber {} is not on list {}", subscriber_id, list_id));
+        }
+        if shared.tokens.contains_key(token) || self.pending.tokens.contains_key(token) {
+            return Err(eyre!("Duplicate subscription token"));
+        }
+        drop(shared);
+        self.pending
+            .tokens
+            .insert(token.to_owned(), (subscriber_id, list_id, Utc::now()));
+        Ok(())
+    }
+
+    async fn update_profile(
+        &mut self,
+        subscriber_id: Uuid,
+        update: &ProfileUpdate,
+    ) -> eyre::Result<()> {
+        self.pending.profile_updates.push((subscriber_id, update.clone()));
+        Ok(())
+    }
+
+    async fn commit(self: Box<Self>) -> eyre::Result<()> {
+        let mut shared = self.shared.lock().unwrap();
+        // Someone may have taken the email since we checked.
+        for subscriber in self.pending.subscribers.values() {
+            if shared.subscribers.values().any(|s| s.email == subscriber.email && s.id != subscriber.id) {
+                return Err(eyre!("A subscriber with email {} already exists", subscriber.email));
+            }
+        }
+        shared.subscribers.extend(self.pending.subscribers);
+        shared.memberships.extend(self.pending.memberships);
+        shared.tokens.extend(self.pending.tokens);
+        shared.events.extend(self.pending.events);
+        for (subscriber_id, update) in self.pending.profile_updates {
+            if shared.subscribers.contains_key(&subscriber_id) {
+                shared.profiles.entry(subscriber_id).or_default().apply(&update);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod tests {
+    use std::collections::HashSet;
+
+    use chrono::{Duration, Utc};
+    use claim::{assert_err, assert_none, assert_ok, assert_some};
+    use uuid::Uuid;
+
+    use crate::audit::EventContext;
+    use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+    use crate::repository::{
+        AuditRepository, DeleteMode, DeliveryOutcome, FeedRepository, InMemorySubscriberRepository, IssueContent,
+        IssueRepository, ListRepository, ListSettings, NewFeedEntry, PageCursor, ProfileUpdate, SubscriberFilter,
+        SubscriberRepository, TrackedAction, DEFAULT_LIST,
+    };
+    use crate::segment::Segment;
+
+    fn new_subscriber(email: &str) -> NewSubscriber {
+        NewSubscriber {
+            email: SubscriberEmail::parse(email.into()).unwrap(),
+            name: SubscriberName::parse("Ursula".into()).unwrap(),
+            locale: "en".into(),
+        }
+    }
+
+    /// Puts a new subscriber on the default list, pending, with `token`.
+    async fn subscribe(
+        repository: &InMemorySubscriberRepository,
+        email: &str,
+        token: &str,
+        context: &EventContext,
+    ) -> (Uuid, Uuid) {
+        let list = repository.find_list(DEFAULT_LIST).await.unwrap().unwrap();
+        let mut transaction = repository.begin().await.unwrap();
+        let id = transaction.insert_subscriber(&new_subscriber(email)).await.unwrap();
+        transaction.join_list(id, list.id, context).await.unwrap();
+        transaction.store_token(id, list.id, token).await.unwrap();
+        transaction.commit().await.unwrap();
+        (id, list.id)
+    }
+
+    #[tokio::test]
+    async fn nothing_is_visible_until_the_transaction_is_committed() {
+        let repository = InMemorySubscriberRepository::default();
+        let list = repository.find_list(DEFAULT_LIST).await.unwrap().unwrap();
+        let mut transaction = repository.begin().await.unwrap();
+        let id = transaction
+            .insert_subscriber(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap();
+        transaction.join_list(id, list.id, &EventContext::default()).await.unwrap();
+        transaction.store_token(id, list.id, "token").await.unwrap();
+        assert_none!(repository.find_token("token").await.unwrap());
+
+        transaction.commit().await.unwrap();
+        let token = assert_some!(repository.find_token("token").await.unwrap());
+        assert_eq!(token.subscriber_id, id);
+        assert_eq!(token.list_id, list.id);
+        assert_eq!(token.status, "pending");
+    }
+
+    #[tokio::test]
+    async fn dropping_a_transaction_rolls_it_back() {
+        let repository = InMemorySubscriberRepository::default();
+        let mut transaction = repository.begin().await.unwrap();
+        transaction
+            .insert_subscriber(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap();
+        drop(transaction);
+        assert!(repository.subscribers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn emails_are_unique() {
+        let repository = InMemorySubscriberRepository::default();
+        let mut transaction = repository.begin().await.unwrap();
+        assert_ok!(transaction.insert_subscriber(&new_subscriber("ursula@example.com")).await);
+        assert_err!(transaction.insert_subscriber(&new_subscriber("ursula@example.com")).await);
+    }
+
+    #[tokio::test]
+    async fn confirming_updates_the_status() {
+        let repository = InMemorySubscriberRepository::default();
+        let context = EventContext::default();
+        let (id, list_id) = subscribe(&repository, "ursula@example.com", "token", &context).await;
+
+        repository.confirm(id, list_id, &context).await.unwrap();
+        let subscriber = assert_some!(repository.find_by_email("ursula@example.com").await.unwrap());
+        assert_eq!(subscriber.status, "confirmed");
+        let events = repository.events(id).await.unwrap();
+        let kinds: Vec<_> = events.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(kinds, ["subscribed", "confirmed"]);
+        assert!(events.iter().all(|e| e.list.as_deref() == Some(DEFAULT_LIST)));
+    }
+
+    #[tokio::test]
+    async fn memberships_are_confirmed_per_list() {
+        let repository = InMemorySubscriberRepository::default();
+        let context = EventContext::default();
+        let (id, default_list) = subscribe(&repository, "ursula@example.com", "token", &context).await;
+        let settings = ListSettings {
+            name: "Weekly".into(),
+            sender_email: None,
+            sender_name: None,
+            confirmation_subject: None,
+            confirmation_intro: None,
+        };
+        let weekly = assert_some!(repository.create_list("weekly", &settings).await.unwrap());
+        assert_none!(repository.create_list("weekly", &settings).await.unwrap());
+        let mut transaction = repository.begin().await.unwrap();
+        transaction.join_list(id, weekly.id, &context).await.unwrap();
+        transaction.store_token(id, weekly.id, "weekly-token").await.unwrap();
+        transaction.commit().await.unwrap();
+
+        repository.confirm(id, weekly.id, &context).await.unwrap();
+        let statuses: Vec<_> = repository
+            .memberships(id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.list, m.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                (DEFAULT_LIST.to_owned(), "pending".to_owned()),
+                ("weekly".to_owned(), "confirmed".to_owned())
+            ]
+        );
+        assert_eq!(assert_some!(repository.find_token("token").await.unwrap()).list_id, default_list);
+
+        assert!(repository.delete_list("weekly").await.unwrap());
+        assert_none!(repository.find_token("weekly-token").await.unwrap());
+        assert_eq!(repository.memberships(id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn recipients_are_confirmed_members_matching_the_segment() {
+        let repository = InMemorySubscriberRepository::default();
+        let context = EventContext::default();
+        let mut ids = Vec::new();
+        for email in ["a@example.com", "b@example.com", "pending@example.com"] {
+            let (id, list_id) = subscribe(&repository, email, email, &context).await;
+            if !email.starts_with("pending") {
+                repository.confirm(id, list_id, &context).await.unwrap();
+            }
+            let mut transaction = repository.begin().await.unwrap();
+            let update = ProfileUpdate {
+                attributes: [("country".to_owned(), Some("de".to_owned()))].into(),
+                add_tags: ["rust".to_owned(), "go".to_owned()].into(),
+                ..Default::default()
+            };
+            transaction.update_profile(id, &update).await.unwrap();
+            transaction.commit().await.unwrap();
+            ids.push(id);
+        }
+        let mut transaction = repository.begin().await.unwrap();
+        let update = ProfileUpdate {
+            attributes: [("country".to_owned(), None)].into(),
+            remove_tags: ["go".to_owned()].into(),
+            ..Default::default()
+        };
+        transaction.update_profile(ids[1], &update).await.unwrap();
+        transaction.commit().await.unwrap();
+
+        let list = repository.find_list(DEFAULT_LIST).await.unwrap().unwrap();
+        let keys = ["country".to_owned()];
+        let emails = |segment: &str| {
+            let segment = Segment::parse(segment, &keys).unwrap();
+            let repository = repository.clone();
+            async move {
+                repository
+                    .recipients(list.id, Some(&segment))
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|s| s.email)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(emails("tag:rust").await, ["a@example.com", "b@example.com"]);
+        assert_eq!(emails("tag:go OR country:de").await, ["a@example.com"]);
+        assert_eq!(repository.recipients(list.id, None).await.unwrap().len(), 2);
+        let profile = repository.profile(ids[1]).await.unwrap();
+        assert!(profile.attributes.is_empty());
+        assert_eq!(profile.tags, ["rust".to_owned()].into());
+    }
+
+    #[tokio::test]
+    async fn listing_pages_through_matching_subscribers_newest_first() {
+        let repository = InMemorySubscriberRepository::default();
+        for email in ["a@example.com", "b@example.com", "c@example.com", "x@example.com"] {
+            let mut transaction = repository.begin().await.unwrap();
+            transaction.insert_subscriber(&new_subscriber(email)).await.unwrap();
+            transaction.commit().await.unwrap();
+        }
+        let filter = SubscriberFilter {
+            search: Some("A".into()),
+            ..Default::default()
+        };
+        assert_eq!(repository.list(&filter, None, 10).await.unwrap().len(), 1);
+
+        let filter = SubscriberFilter::default();
+        let first = repository.list(&filter, None, 3).await.unwrap();
+        let cursor = PageCursor::after(first.last().unwrap());
+        let second = repository.list(&filter, Some(cursor), 3).await.unwrap();
+        let emails: Vec<_> = first.iter().chain(&second).map(|s| s.email.as_str()).collect();
+        assert_eq!(
+            emails,
+            ["x@example.com", "c@example.com", "b@example.com", "a@example.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn deleting_removes_tokens_and_soft_deletes_keep_the_subscriber() {
+        let repository = InMemorySubscriberRepository::default();
+        let (id, _) = subscribe(&repository, "ursula@example.com", "token", &EventContext::default()).await;
+
+        assert!(repository.delete(id, DeleteMode::Soft, &EventContext::default()).await.unwrap());
+        assert_none!(repository.find_token("token").await.unwrap());
+        assert_eq!(repository.find_by_id(id).await.unwrap().unwrap().status, "deleted");
+
+        assert!(repository.delete(id, DeleteMode::Hard, &EventContext::default()).await.unwrap());
+        assert_none!(repository.find_by_id(id).await.unwrap());
+        assert!(!repository.delete(id, DeleteMode::Hard, &EventContext::default()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn erasing_replaces_personal_data_and_records_the_suppression() {
+        let repository = InMemorySubscriberRepository::default();
+        let context = EventContext {
+            ip: Some("192.0.2.1".into()),
+            user_agent: Some("Mozilla/5.0".into()),
+            ..Default::default()
+        };
+        let (id, _) = subscribe(&repository, "ursula@example.com", "token", &context).await;
+        let mut transaction = repository.begin().await.unwrap();
+        let update = ProfileUpdate {
+            add_tags: ["rust".to_owned()].into(),
+            ..Default::default()
+        };
+        transaction.update_profile(id, &update).await.unwrap();
+        transaction.commit().await.unwrap();
+
+        assert!(repository.erase(id, "hash", &context).await.unwrap());
+        let erased = repository.find_by_id(id).await.unwrap().unwrap();
+        assert_eq!(erased.status, "erased");
+        assert_ne!(erased.email, "ursula@example.com");
+        assert_eq!(erased.name, "");
+        assert_none!(repository.find_token("token").await.unwrap());
+        assert_eq!(repository.profile(id).await.unwrap(), Default::default());
+        let hashes = ["hash".to_owned(), "other".to_owned()];
+        assert_eq!(repository.suppressed(&hashes).await.unwrap(), HashSet::from(["hash".to_owned()]));
+        assert!(!repository.erase(Uuid::new_v4(), "hash", &context).await.unwrap());
+
+        let events = repository.events(id).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.ip.is_none() && e.user_agent.is_none()));
+    }
+
+    #[tokio::test]
+    async fn due_issues_are_claimed_once_and_in_order() {
+        let repository = InMemorySubscriberRepository::default();
+        let list = assert_some!(repository.find_list(DEFAULT_LIST).await.unwrap());
+        let content = |title: &str| IssueContent {
+            list_id: list.id,
+            title: title.into(),
+            html_content: "<p>Hello</p>".into(),
+            text_content: "Hello".into(),
+            segment: None,
+            tracking: false,
+            public: false,
+        };
+        let now = Utc::now();
+        let later = assert_ok!(repository.create_issue(&content("Later")).await);
+        let first = assert_ok!(repository.create_issue(&content("First")).await);
+        let draft = assert_ok!(repository.create_issue(&content("Draft")).await);
+        assert_eq!(draft.status, "draft");
+        repository.schedule_issue(later.id, Some(now + Duration::hours(2))).await.unwrap();
+        repository.schedule_issue(first.id, Some(now + Duration::hours(1))).await.unwrap();
+
+        assert_none!(repository.claim_due_issue(now).await.unwrap());
+        let claimed = assert_some!(repository.claim_due_issue(now + Duration::days(1)).await.unwrap());
+        assert_eq!((claimed.id, claimed.status.as_str()), (first.id, "sending"));
+        assert_none!(repository.update_issue(first.id, &content("Too late")).await.unwrap());
+        let claimed = assert_some!(repository.claim_due_issue(now + Duration::days(1)).await.unwrap());
+        assert_eq!(claimed.id, later.id);
+        assert_none!(repository.claim_due_issue(now + Duration::days(1)).await.unwrap());
+
+        repository.finish_issue(first.id, 3).await.unwrap();
+        repository.release_issue(later.id).await.unwrap();
+        let sent = assert_some!(repository.find_issue(first.id).await.unwrap());
+        assert_eq!((sent.status.as_str(), sent.recipient_count), ("sent", Some(3)));
+        assert!(!repository.delete_issue(first.id).await.unwrap());
+        let released = assert_some!(repository.find_issue(later.id).await.unwrap());
+        assert_eq!((released.status.as_str(), released.send_at), ("draft", None));
+        let drafts: Vec<_> = repository
+            .issues(Some("draft"))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|issue| issue.title)
+            .collect();
+        assert_eq!(drafts, ["Draft", "Later"]);
+    }
+
+    #[tokio::test]
+    async fn deliveries_count_attempts_and_keep_the_last_error() {
+        let repository = InMemorySubscriberRepository::default();
+        let list = assert_some!(repository.find_list(DEFAULT_LIST).await.unwrap());
+        let issue = assert_ok!(
+            repository
+                .create_issue(&IssueContent {
+                    list_id: list.id,
+                    title: "Issue #1".into(),
+                    html_content: "<p>Hello</p>".into(),
+                    text_content: "Hello".into(),
+                    segment: None,
+                    tracking: false,
+                    public: false,
+                })
+                .await
+        );
+        let context = EventContext::default();
+        let (ursula, _) = subscribe(&repository, "ursula@example.com", "token-1", &context).await;
+        let (octavia, _) = subscribe(&repository, "octavia@example.com", "token-2", &context).await;
+
+        repository.queue_deliveries(issue.id, &[ursula, octavia]).await.unwrap();
+        let failed = DeliveryOutcome::Failed { error: "timed out".into() };
+        repository.record_delivery(issue.id, ursula, &failed).await.unwrap();
+        repository.queue_deliveries(issue.id, &[ursula]).await.unwrap();
+        let sent = DeliveryOutcome::Sent { message_id: Some("message-1".into()) };
+        repository.record_delivery(issue.id, ursula, &sent).await.unwrap();
+        repository.record_delivery(issue.id, octavia, &failed).await.unwrap();
+
+        let counts = repository.delivery_counts(issue.id).await.unwrap();
+        assert_eq!((counts.queued, counts.sent, counts.failed), (0, 1, 1));
+        let failures = repository.failed_deliveries(issue.id, 10).await.unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].email, "octavia@example.com");
+        assert_eq!(failures[0].last_error.as_deref(), Some("timed out"));
+        let received = repository.received_issues(ursula).await.unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!((received[0].title.as_str(), received[0].status.as_str()), ("Issue #1", "sent"));
+    }
+
+    #[tokio::test]
+    async fn tracked_actions_are_summarised_unless_the_subscriber_opted_out() {
+        let repository = InMemorySubscriberRepository::default();
+        let list = assert_some!(repository.find_list(DEFAULT_LIST).await.unwrap());
+        let issue = assert_ok!(
+            repository
+                .create_issue(&IssueContent {
+                    list_id: list.id,
+                    title: "Issue #1".into(),
+                    html_content: "<p>Hello</p>".into(),
+                    text_content: "Hello".into(),
+                    segment: None,
+                    tracking: true,
+                    public: false,
+                })
+                .await
+        );
+        let context = EventContext::default();
+        let (ursula, _) = subscribe(&repository, "ursula@example.com", "token-1", &context).await;
+        let (octavia, _) = subscribe(&repository, "octavia@example.com", "token-2", &context).await;
+
+        repository.queue_deliveries(issue.id, &[ursula, octavia]).await.unwrap();
+        assert!(repository.set_tracking_opt_out(octavia, true).await.unwrap());
+        let click = |url: &str| TrackedAction::Clicked { url: url.into() };
+        let now = Utc::now();
+        for (subscriber_id, action) in [
+            (ursula, TrackedAction::Opened),
+            (ursula, TrackedAction::Opened),
+            (ursula, click("https://example.com/a")),
+            (ursula, click("https://example.com/a")),
+            (ursula, click("https://example.com/b")),
+        ] {
+            assert!(repository.record_tracked_action(issue.id, subscriber_id, &action, now).await.unwrap());
+        }
+        let recorded = repository
+            .record_tracked_action(issue.id, octavia, &TrackedAction::Opened, now)
+            .await
+            .unwrap();
+
+        assert!(!recorded);
+        assert_eq!(repository.untracked_recipients(issue.id).await.unwrap(), HashSet::from([octavia]));
+        let engagement = repository.engagement(issue.id).await.unwrap();
+        assert_eq!(
+            (engagement.opens, engagement.unique_opens, engagement.clicks, engagement.unique_clicks),
+            (2, 1, 3, 1)
+        );
+        let links: Vec<_> = engagement.links.iter().map(|link| (link.url.as_str(), link.clicks)).collect();
+        assert_eq!(links, [("https://example.com/a", 2), ("https://example.com/b", 1)]);
+        let events = repository.tracking_events(ursula).await.unwrap();
+        assert_eq!(events.len(), 5);
+        assert_eq!((events[2].kind.as_str(), events[2].url.as_deref()), ("click", Some("https://example.com/a")));
+        assert!(repository.tracking_events(octavia).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn feed_entries_are_stored_once_and_claimed_once() {
+        let repository = InMemorySubscriberRepository::default();
+        let entry = |guid: &str| NewFeedEntry {
+            guid: guid.into(),
+            title: format!("Post {}", guid),
+            link: Some(format!("https://blog.example.com/{}", guid)),
+            summary: None,
+            published_at: None,
+        };
+        let feed = "https://blog.example.com/feed.xml";
+        let now = Utc::now();
+
+        // The back catalogue of a new feed counts as digested already.
+        assert_eq!(repository.store_feed_entries(feed, &[entry("1"), entry("2")], now).await.unwrap(), 2);
+        assert!(repository.claim_feed_entries(now).await.unwrap().is_empty());
+
+        let stored = repository
+            .store_feed_entries(feed, &[entry("2"), entry("3"), entry("3")], now)
+            .await
+            .unwrap();
+        assert_eq!(stored, 1);
+        let claimed = repository.claim_feed_entries(now).await.unwrap();
+        let guids: Vec<_> = claimed.iter().map(|entry| entry.guid.as_str()).collect();
+        assert_eq!(guids, ["3"]);
+        assert!(repository.claim_feed_entries(now).await.unwrap().is_empty());
+
+        repository.release_feed_entries(&[claimed[0].id]).await.unwrap();
+        assert_eq!(repository.claim_feed_entries(now).await.unwrap(), claimed);
+    }
+}
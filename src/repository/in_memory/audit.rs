@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::{event, InMemorySubscriberRepository};
+use crate::audit::{EventContext, EventKind, SubscriptionEvent};
+use crate::repository::{erased_email, AuditRepository};
+
+#[async_trait]
+impl AuditRepository for InMemorySubscriberRepository {
+    async fn erase(
+        &self,
+        subscriber_id: Uuid,
+        suppression_hash: &str,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        let Some(subscriber) = store.subscribers.get_mut(&subscriber_id) else {
+            return Ok(false);
+        };
+        subscriber.email = erased_email(subscriber_id);
+        subscriber.name = String::new();
+        subscriber.status = "erased".into();
+        subscriber.consent_source = None;
+        store.tokens.retain(|_, (id, _, _)| *id != subscriber_id);
+        store.profiles.remove(&subscriber_id);
+        store.suppressions.insert(suppression_hash.to_owned());
+        for (_, event) in store
+            .events
+            .iter_mut()
+            .filter(|(id, _)| *id == subscriber_id)
+        {
+            event.ip = None;
+            event.user_agent = None;
+        }
+        store.events.push((
+            subscriber_id,
+            event(EventKind::Erased, None, &context.anonymized()),
+        ));
+        Ok(true)
+    }
+
+    async fn suppressed(&self, suppression_hashes: &[String]) -> eyre::Result<HashSet<String>> {
+        let store = self.store.lock().unwrap();
+        Ok(suppression_hashes
+            .iter()
+            .filter(|hash| store.suppressions.contains(*hash))
+            .cloned()
+            .collect())
+    }
+
+    async fn events(&self, subscriber_id: Uuid) -> eyre::Result<Vec<SubscriptionEvent>> {
+        let store = self.store.lock().unwrap();
+        Ok(store
+            .events
+            .iter()
+            .filter(|(id, _)| *id == subscriber_id)
+            .map(|(_, event)| event.clone())
+            .collect())
+    }
+}
@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::InMemorySubscriberRepository;
+use crate::repository::{FeedEntry, FeedRepository, NewFeedEntry};
+
+#[async_trait]
+impl FeedRepository for InMemorySubscriberRepository {
+    async fn store_feed_entries(
+        &self,
+        feed_url: &str,
+        entries: &[NewFeedEntry],
+        fetched_at: DateTime<Utc>,
+    ) -> eyre::Result<u64> {
+        let mut store = self.store.lock().unwrap();
+        let known = store.feed_entries.iter().any(|(entry, _)| entry.feed_url == feed_url);
+        let digested_at = (!known).then_some(fetched_at);
+        let mut stored = 0;
+        for new_entry in entries {
+            let seen = store
+                .feed_entries
+                .iter()
+                .any(|(entry, _)| entry.feed_url == feed_url && entry.guid == new_entry.guid);
+            if seen {
+                continue;
+            }
+            let entry = FeedEntry {
+                id: Uuid::new_v4(),
+                feed_url: feed_url.to_owned(),
+                guid: new_entry.guid.clone(),
+                title: new_entry.title.clone(),
+                link: new_entry.link.clone(),
+                summary: new_entry.summary.clone(),
+                published_at: new_entry.published_at,
+                fetched_at,
+            };
+            store.feed_entries.push((entry, digested_at));
+            stored += 1;
+        }
+        Ok(stored)
+    }
+
+    async fn claim_feed_entries(&self, now: DateTime<Utc>) -> eyre::Result<Vec<FeedEntry>> {
+        let mut store = self.store.lock().unwrap();
+        let mut claimed = Vec::new();
+        for (entry, digested_at) in &mut store.feed_entries {
+            if digested_at.is_none() {
+                *digested_at = Some(now);
+                claimed.push(entry.clone());
+            }
+        }
+        Ok(claimed)
+    }
+
+    async fn release_feed_entries(&self, entry_ids: &[Uuid]) -> eyre::Result<()> {
+        let mut store = self.store.lock().unwrap();
+        for (entry, digested_at) in &mut store.feed_entries {
+            if entry_ids.contains(&entry.id) {
+                *digested_at = None;
+            }
+        }
+        Ok(())
+    }
+}
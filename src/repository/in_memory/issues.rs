@@ -0,0 +1,412 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use eyre::eyre;
+use uuid::Uuid;
+
+use super::InMemorySubscriberRepository;
+use crate::repository::{
+    issue_slug, Delivery, DeliveryCounts, DeliveryOutcome, Engagement, IssueContent,
+    IssueRepository, LinkClicks, NewsletterIssue, ReceivedIssue, Subscriber, TrackedAction,
+    TrackingEvent,
+};
+
+/// Whether the issue is a draft or scheduled, and so can still change.
+fn is_editable(issue: &NewsletterIssue) -> bool {
+    issue.status == "draft" || issue.status == "scheduled"
+}
+
+#[async_trait]
+impl IssueRepository for InMemorySubscriberRepository {
+    async fn issues(&self, status: Option<&str>) -> eyre::Result<Vec<NewsletterIssue>> {
+        let store = self.store.lock().unwrap();
+        let mut issues: Vec<_> = store
+            .issues
+            .values()
+            .filter(|issue| status.is_none_or(|status| issue.status == status))
+            .cloned()
+            .collect();
+        issues.sort_by_key(|issue| (Reverse(issue.created_at), issue.id));
+        Ok(issues)
+    }
+
+    async fn find_issue(&self, issue_id: Uuid) -> eyre::Result<Option<NewsletterIssue>> {
+        Ok(self.store.lock().unwrap().issues.get(&issue_id).cloned())
+    }
+
+    async fn create_issue(&self, content: &IssueContent) -> eyre::Result<NewsletterIssue> {
+        let mut store = self.store.lock().unwrap();
+        let list = store
+            .slug(content.list_id)
+            .ok_or_else(|| eyre!("Foreign key violation: no list {}", content.list_id))?;
+        let now = Utc::now();
+        let issue_id = Uuid::new_v4();
+        let issue = NewsletterIssue {
+            id: issue_id,
+            list_id: content.list_id,
+            list,
+            title: content.title.clone(),
+            html_content: content.html_content.clone(),
+            text_content: content.text_content.clone(),
+            segment: content.segment.clone(),
+            tracking: content.tracking,
+            public: content.public,
+            slug: issue_slug(&content.title, issue_id),
+            status: "draft".into(),
+            send_at: None,
+            created_at: now,
+            updated_at: now,
+            sent_at: None,
+            recipient_count: None,
+        };
+        store.issues.insert(issue.id, issue.clone());
+        Ok(issue)
+    }
+
+    async fn update_issue(
+        &self,
+        issue_id: Uuid,
+        content: &IssueContent,
+    ) -> eyre::Result<Option<NewsletterIssue>> {
+        let mut store = self.store.lock().unwrap();
+        let list = store
+            .slug(content.list_id)
+            .ok_or_else(|| eyre!("Foreign key violation: no list {}", content.list_id))?;
+        let Some(issue) = store.issues.get_mut(&issue_id).filter(|issue| is_editable(issue)) else {
+            return Ok(None);
+        };
+        issue.list_id = content.list_id;
+        issue.list = list;
+        issue.title = content.title.clone();
+        issue.html_content = content.html_content.clone();
+        issue.text_content = content.text_content.clone();
+        issue.segment = content.segment.clone();
+        issue.tracking = content.tracking;
+        issue.public = content.public;
+        issue.slug = issue_slug(&content.title, issue_id);
+        issue.updated_at = Utc::now();
+        Ok(Some(issue.clone()))
+    }
+
+    async fn schedule_issue(
+        &self,
+        issue_id: Uuid,
+        send_at: Option<DateTime<Utc>>,
+    ) -> eyre::Result<Option<NewsletterIssue>> {
+        let mut store = self.store.lock().unwrap();
+        let Some(issue) = store.issues.get_mut(&issue_id).filter(|issue| is_editable(issue)) else {
+            return Ok(None);
+        };
+        issue.status = if send_at.is_some() { "scheduled" } else { "draft" }.into();
+        issue.send_at = send_at;
+        issue.updated_at = Utc::now();
+        Ok(Some(issue.clone()))
+    }
+
+    async fn delete_issue(&self, issue_id: Uuid) -> eyre::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        if !store.issues.get(&issue_id).is_some_and(is_editable) {
+            return Ok(false);
+        }
+        store.issues.remove(&issue_id);
+        store.deliveries.retain(|(id, _), _| *id != issue_id);
+        store.tracking_events.retain(|(id, ..)| *id != issue_id);
+        Ok(true)
+    }
+
+    async fn claim_due_issue(&self, now: DateTime<Utc>) -> eyre::Result<Option<NewsletterIssue>> {
+        let mut store = self.store.lock().unwrap();
+        let Some(issue) = store
+            .issues
+            .values_mut()
+            .filter(|issue| issue.status == "scheduled" && issue.send_at.is_some_and(|at| at <= now))
+            .min_by_key(|issue| (issue.send_at, issue.created_at))
+        else {
+            return Ok(None);
+        };
+        issue.status = "sending".into();
+        issue.updated_at = now;
+        Ok(Some(issue.clone()))
+    }
+
+    async fn claim_stalled_issue(
+        &self,
+        now: DateTime<Utc>,
+        stalled_before: DateTime<Utc>,
+    ) -> eyre::Result<Option<NewsletterIssue>> {
+        let mut store = self.store.lock().unwrap();
+        let progressed: HashSet<Uuid> = store
+            .deliveries
+            .iter()
+            .filter(|(_, delivery)| delivery.updated_at > stalled_before)
+            .map(|((issue_id, _), _)| *issue_id)
+            .collect();
+        let Some(issue) = store
+            .issues
+            .values_mut()
+            .filter(|issue| {
+                issue.status == "sending"
+                    && issue.updated_at <= stalled_before
+                    && !progressed.contains(&issue.id)
+            })
+            .min_by_key(|issue| (issue.updated_at, issue.created_at))
+        else {
+            return Ok(None);
+        };
+        issue.updated_at = now;
+        Ok(Some(issue.clone()))
+    }
+
+    async fn finish_issue(&self, issue_id: Uuid, recipient_count: i64) -> eyre::Result<()> {
+        let mut store = self.store.lock().unwrap();
+        if let Some(issue) = store.issues.get_mut(&issue_id).filter(|issue| issue.status == "sending") {
+            let now = Utc::now();
+            issue.status = "sent".into();
+            issue.sent_at = Some(now);
+            issue.updated_at = now;
+            issue.recipient_count = Some(recipient_count);
+        }
+        Ok(())
+    }
+
+    async fn release_issue(&self, issue_id: Uuid) -> eyre::Result<()> {
+        let mut store = self.store.lock().unwrap();
+        if let Some(issue) = store.issues.get_mut(&issue_id).filter(|issue| issue.status == "sending") {
+            issue.status = "draft".into();
+            issue.send_at = None;
+            issue.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+
+    async fn queue_deliveries(&self, issue_id: Uuid, subscriber_ids: &[Uuid]) -> eyre::Result<()> {
+        let mut store = self.store.lock().unwrap();
+        let now = Utc::now();
+        for subscriber_id in subscriber_ids {
+            store
+                .deliveries
+                .entry((issue_id, *subscriber_id))
+                .or_insert_with(|| Delivery {
+                    subscriber_id: *subscriber_id,
+                    email: String::new(),
+                    status: "queued".into(),
+                    message_id: None,
+                    attempts: 0,
+                    last_error: None,
+                    updated_at: now,
+                });
+        }
+        Ok(())
+    }
+
+    async fn queued_recipients(&self, issue_id: Uuid) -> eyre::Result<Vec<Subscriber>> {
+        let store = self.store.lock().unwrap();
+        let mut recipients: Vec<_> = store
+            .deliveries
+            .iter()
+            .filter(|((id, _), delivery)| *id == issue_id && delivery.status == "queued")
+            .filter_map(|((_, subscriber_id), _)| store.subscribers.get(subscriber_id))
+            .cloned()
+            .collect();
+        recipients.sort_by_key(|s| (s.subscribed_at, s.id));
+        Ok(recipients)
+    }
+
+    async fn record_delivery(
+        &self,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        outcome: &DeliveryOutcome,
+    ) -> eyre::Result<()> {
+        let mut store = self.store.lock().unwrap();
+        if let Some(delivery) = store.deliveries.get_mut(&(issue_id, subscriber_id)) {
+            match outcome {
+                DeliveryOutcome::Sent { message_id } => {
+                    delivery.status = "sent".into();
+                    delivery.message_id = message_id.clone();
+                }
+                DeliveryOutcome::Failed { error } => {
+                    delivery.status = "failed".into();
+                    delivery.message_id = None;
+                    delivery.last_error = Some(error.clone());
+                }
+            }
+            delivery.attempts += 1;
+            delivery.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+
+    async fn delivery_counts(&self, issue_id: Uuid) -> eyre::Result<DeliveryCounts> {
+        let store = self.store.lock().unwrap();
+        Ok(store
+            .deliveries
+            .iter()
+            .filter(|((id, _), _)| *id == issue_id)
+            .map(|(_, delivery)| (delivery.status.clone(), 1))
+            .collect())
+    }
+
+    async fn failed_deliveries(&self, issue_id: Uuid, limit: i64) -> eyre::Result<Vec<Delivery>> {
+        let store = self.store.lock().unwrap();
+        let mut deliveries: Vec<_> = store
+            .deliveries
+            .iter()
+            .filter(|((id, _), delivery)| {
+                *id == issue_id && (delivery.status == "failed" || delivery.status == "bounced")
+            })
+            .map(|(_, delivery)| Delivery {
+                email: store
+                    .subscribers
+                    .get(&delivery.subscriber_id)
+                    .map(|subscriber| subscriber.email.clone())
+                    .unwrap_or_default(),
+                ..delivery.clone()
+            })
+            .collect();
+        deliveries.sort_by_key(|delivery| (Reverse(delivery.updated_at), delivery.subscriber_id));
+        deliveries.truncate(usize::try_from(limit).unwrap_or(0));
+        Ok(deliveries)
+    }
+
+    async fn received_issues(&self, subscriber_id: Uuid) -> eyre::Result<Vec<ReceivedIssue>> {
+        let store = self.store.lock().unwrap();
+        let mut issues: Vec<_> = store
+            .deliveries
+            .iter()
+            .filter(|((_, id), _)| *id == subscriber_id)
+            .filter_map(|((issue_id, _), delivery)| {
+                let issue = store.issues.get(issue_id)?;
+                Some(ReceivedIssue {
+                    issue_id: *issue_id,
+                    title: issue.title.clone(),
+                    status: delivery.status.clone(),
+                    updated_at: delivery.updated_at,
+                })
+            })
+            .collect();
+        issues.sort_by_key(|issue| (issue.updated_at, issue.issue_id));
+        Ok(issues)
+    }
+
+    async fn set_tracking_opt_out(&self, subscriber_id: Uuid, opt_out: bool) -> eyre::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        if !store.subscribers.contains_key(&subscriber_id) {
+            return Ok(false);
+        }
+        if opt_out {
+            store.tracking_opt_outs.insert(subscriber_id);
+        } else {
+            store.tracking_opt_outs.remove(&subscriber_id);
+        }
+        Ok(true)
+    }
+
+    async fn untracked_recipients(&self, issue_id: Uuid) -> eyre::Result<HashSet<Uuid>> {
+        let store = self.store.lock().unwrap();
+        Ok(store
+            .deliveries
+            .keys()
+            .filter(|(id, subscriber_id)| *id == issue_id && store.tracking_opt_outs.contains(subscriber_id))
+            .map(|(_, subscriber_id)| *subscriber_id)
+            .collect())
+    }
+
+    async fn record_tracked_action(
+        &self,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        action: &TrackedAction,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        if !store.issues.contains_key(&issue_id)
+            || !store.subscribers.contains_key(&subscriber_id)
+            || store.tracking_opt_outs.contains(&subscriber_id)
+        {
+            return Ok(false);
+        }
+        store.tracking_events.push((issue_id, subscriber_id, action.clone(), at));
+        Ok(true)
+    }
+
+    async fn engagement(&self, issue_id: Uuid) -> eyre::Result<Engagement> {
+        let store = self.store.lock().unwrap();
+        let mut engagement = Engagement::default();
+        let mut opened = HashSet::new();
+        let mut clicked = HashSet::new();
+        // url -> (clicks, subscribers who clicked it)
+        let mut links: HashMap<&str, (i64, HashSet<Uuid>)> = HashMap::new();
+        for (_, subscriber_id, action, _) in store.tracking_events.iter().filter(|(id, ..)| *id == issue_id) {
+            match action {
+                TrackedAction::Opened => {
+                    engagement.opens += 1;
+                    opened.insert(*subscriber_id);
+                }
+                TrackedAction::Clicked { url } => {
+                    engagement.clicks += 1;
+                    clicked.insert(*subscriber_id);
+                    let (clicks, subscribers) = links.entry(url).or_default();
+                    *clicks += 1;
+                    subscribers.insert(*subscriber_id);
+                }
+            }
+        }
+        engagement.unique_opens = opened.len() as i64;
+        engagement.unique_clicks = clicked.len() as i64;
+        engagement.links = links
+            .into_iter()
+            .map(|(url, (clicks, subscribers))| LinkClicks {
+                url: url.to_owned(),
+                clicks,
+                unique_clicks: subscribers.len() as i64,
+            })
+            .collect();
+        engagement
+            .links
+            .sort_by(|a, b| b.clicks.cmp(&a.clicks).then_with(|| a.url.cmp(&b.url)));
+        Ok(engagement)
+    }
+
+    async fn tracking_events(&self, subscriber_id: Uuid) -> eyre::Result<Vec<TrackingEvent>> {
+        let store = self.store.lock().unwrap();
+        Ok(store
+            .tracking_events
+            .iter()
+            .filter(|(_, id, ..)| *id == subscriber_id)
+            .map(|(issue_id, _, action, at)| TrackingEvent {
+                issue_id: *issue_id,
+                kind: action.kind().to_owned(),
+                url: action.url().map(str::to_owned),
+                occurred_at: *at,
+            })
+            .collect())
+    }
+
+    async fn archived_issues(&self, limit: i64, offset: i64) -> eyre::Result<Vec<NewsletterIssue>> {
+        let store = self.store.lock().unwrap();
+        let mut issues: Vec<_> = store
+            .issues
+            .values()
+            .filter(|issue| issue.public && issue.status == "sent")
+            .cloned()
+            .collect();
+        issues.sort_by_key(|issue| (Reverse(issue.sent_at), issue.id));
+        Ok(issues
+            .into_iter()
+            .skip(offset.try_into()?)
+            .take(limit.try_into()?)
+            .collect())
+    }
+
+    async fn find_archived_issue(&self, slug: &str) -> eyre::Result<Option<NewsletterIssue>> {
+        let store = self.store.lock().unwrap();
+        Ok(store
+            .issues
+            .values()
+            .find(|issue| issue.slug == slug && issue.public && issue.status == "sent")
+            .cloned())
+    }
+}
@@ -0,0 +1,183 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use eyre::eyre;
+use uuid::Uuid;
+
+use super::{event, InMemorySubscriberRepository, Store};
+use crate::audit::EventContext;
+use crate::repository::{
+    ListRepository, ListSettings, MailingList, Membership, MembershipChanges, PreferenceUpdate,
+    Subscriber, SubscriberProfile,
+};
+use crate::segment::Segment;
+
+#[async_trait]
+impl ListRepository for InMemorySubscriberRepository {
+    async fn memberships(&self, subscriber_id: Uuid) -> eyre::Result<Vec<Membership>> {
+        let store = self.store.lock().unwrap();
+        let mut memberships: Vec<_> = store
+            .memberships
+            .iter()
+            .filter(|((id, _), _)| *id == subscriber_id)
+            .map(|(_, membership)| membership.clone())
+            .collect();
+        memberships.sort_by(|a, b| (a.subscribed_at, &a.list).cmp(&(b.subscribed_at, &b.list)));
+        Ok(memberships)
+    }
+
+    async fn update_preferences(
+        &self,
+        subscriber_id: Uuid,
+        update: &PreferenceUpdate,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        let Some(subscriber) = store
+            .subscribers
+            .get_mut(&subscriber_id)
+            .filter(|subscriber| subscriber.status == "confirmed")
+        else {
+            return Ok(false);
+        };
+        subscriber.name = update.name.clone();
+        match update.paused_until {
+            Some(paused_until) => store.paused.insert(subscriber_id, paused_until),
+            None => store.paused.remove(&subscriber_id),
+        };
+        let current: Vec<_> = store
+            .memberships
+            .iter()
+            .filter(|((id, _), _)| *id == subscriber_id)
+            .map(|((_, list_id), membership)| (*list_id, membership.status.clone()))
+            .collect();
+        let changes = MembershipChanges::new(&current, &update.list_ids);
+        let now = Utc::now();
+        for list_id in changes.join.iter().chain(&changes.confirm) {
+            let slug = store.slug(*list_id).ok_or_else(|| eyre!("There is no list {}", list_id))?;
+            let membership = store
+                .memberships
+                .entry((subscriber_id, *list_id))
+                .or_insert_with(|| Membership {
+                    list: slug,
+                    status: String::new(),
+                    subscribed_at: now,
+                    confirmed_at: None,
+                });
+            membership.status = "confirmed".into();
+            membership.confirmed_at = Some(now);
+        }
+        for list_id in &changes.leave {
+            if let Some(membership) = store.memberships.get_mut(&(subscriber_id, *list_id)) {
+                membership.status = "unsubscribed".into();
+            }
+            store
+                .tokens
+                .retain(|_, (id, token_list_id, _)| *id != subscriber_id || token_list_id != list_id);
+        }
+        for (list_id, kind) in changes.events() {
+            let slug = store.slug(list_id);
+            store.events.push((subscriber_id, event(kind, slug, context)));
+        }
+        Ok(true)
+    }
+
+    async fn recipients(
+        &self,
+        list_id: Uuid,
+        segment: Option<&Segment>,
+    ) -> eyre::Result<Vec<Subscriber>> {
+        let store = self.store.lock().unwrap();
+        let now = Utc::now();
+        let empty = SubscriberProfile::default();
+        let mut recipients: Vec<_> = store
+            .subscribers
+            .values()
+            .filter(|s| s.status == "confirmed")
+            .filter(|s| store.paused.get(&s.id).is_none_or(|paused_until| *paused_until <= now))
+            .filter(|s| {
+                store
+                    .memberships
+                    .get(&(s.id, list_id))
+                    .is_some_and(|m| m.status == "confirmed")
+            })
+            .filter(|s| {
+                let profile = store.profiles.get(&s.id).unwrap_or(&empty);
+                segment.is_none_or(|segment| segment.matches(&profile.tags, &profile.attributes))
+            })
+            .cloned()
+            .collect();
+        recipients.sort_by_key(|s| (s.subscribed_at, s.id));
+        Ok(recipients)
+    }
+
+    async fn lists(&self) -> eyre::Result<Vec<MailingList>> {
+        let mut lists: Vec<_> = self.store.lock().unwrap().lists.values().cloned().collect();
+        lists.sort_by(|a, b| (a.created_at, &a.slug).cmp(&(b.created_at, &b.slug)));
+        Ok(lists)
+    }
+
+    async fn find_list(&self, slug: &str) -> eyre::Result<Option<MailingList>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.lists.values().find(|list| list.slug == slug).cloned())
+    }
+
+    async fn create_list(
+        &self,
+        slug: &str,
+        settings: &ListSettings,
+    ) -> eyre::Result<Option<MailingList>> {
+        let mut store = self.store.lock().unwrap();
+        if store.lists.values().any(|list| list.slug == slug) {
+            return Ok(None);
+        }
+        let list = MailingList {
+            id: Uuid::new_v4(),
+            slug: slug.to_owned(),
+            name: settings.name.clone(),
+            sender_email: settings.sender_email.clone(),
+            sender_name: settings.sender_name.clone(),
+            confirmation_subject: settings.confirmation_subject.clone(),
+            confirmation_intro: settings.confirmation_intro.clone(),
+            created_at: Utc::now(),
+        };
+        store.lists.insert(list.id, list.clone());
+        Ok(Some(list))
+    }
+
+    async fn update_list(
+        &self,
+        slug: &str,
+        settings: &ListSettings,
+    ) -> eyre::Result<Option<MailingList>> {
+        let mut store = self.store.lock().unwrap();
+        let Some(list) = store.lists.values_mut().find(|list| list.slug == slug) else {
+            return Ok(None);
+        };
+        list.name = settings.name.clone();
+        list.sender_email = settings.sender_email.clone();
+        list.sender_name = settings.sender_name.clone();
+        list.confirmation_subject = settings.confirmation_subject.clone();
+        list.confirmation_intro = settings.confirmation_intro.clone();
+        Ok(Some(list.clone()))
+    }
+
+    async fn delete_list(&self, slug: &str) -> eyre::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        let Some(list_id) = store.lists.values().find(|list| list.slug == slug).map(|list| list.id) else {
+            return Ok(false);
+        };
+        store.lists.remove(&list_id);
+        store.memberships.retain(|(_, id), _| *id != list_id);
+        store.tokens.retain(|_, (_, id, _)| *id != list_id);
+        store.issues.retain(|_, issue| issue.list_id != list_id);
+        let Store { issues, deliveries, tracking_events, .. } = &mut *store;
+        deliveries.retain(|(issue_id, _), _| issues.contains_key(issue_id));
+        tracking_events.retain(|(issue_id, ..)| issues.contains_key(issue_id));
+        for (_, event) in store.events.iter_mut() {
+            if event.list.as_deref() == Some(slug) {
+                event.list = None;
+            }
+        }
+        Ok(true)
+    }
+}
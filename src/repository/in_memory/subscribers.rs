@@ -0,0 +1,288 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use eyre::eyre;
+use futures_util::stream::{self, StreamExt};
+use uuid::Uuid;
+
+use super::{event, matches, InMemorySubscriberRepository, InMemorySubscriberTransaction, Store};
+use crate::audit::{EventContext, EventKind};
+use crate::repository::{
+    DeleteMode, EmailChange, ImportedStatus, ImportedSubscriber, Membership, PageCursor, Subscriber,
+    SubscriberFilter, SubscriberProfile, SubscriberRepository, SubscriberStream,
+    SubscriberTransaction, SubscriptionToken,
+};
+
+#[async_trait]
+impl SubscriberRepository for InMemorySubscriberRepository {
+    async fn begin(&self) -> eyre::Result<Box<dyn SubscriberTransaction>> {
+        Ok(Box::new(InMemorySubscriberTransaction {
+            shared: self.store.clone(),
+            pending: Store::default(),
+        }))
+    }
+
+    async fn find_by_email(&self, email: &str) -> eyre::Result<Option<Subscriber>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.subscribers.values().find(|s| s.email == email).cloned())
+    }
+
+    async fn find_token(&self, token: &str) -> eyre::Result<Option<SubscriptionToken>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.tokens.get(token).map(|(subscriber_id, list_id, created_at)| {
+            SubscriptionToken {
+                subscriber_id: *subscriber_id,
+                list_id: *list_id,
+                locale: store.subscribers[subscriber_id].locale.clone(),
+                status: store.memberships[&(*subscriber_id, *list_id)].status.clone(),
+                created_at: *created_at,
+            }
+        }))
+    }
+
+    async fn confirm(
+        &self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<()> {
+        let mut store = self.store.lock().unwrap();
+        let now = Utc::now();
+        if let Some(membership) = store.memberships.get_mut(&(subscriber_id, list_id)) {
+            membership.status = "confirmed".into();
+            membership.confirmed_at = Some(now);
+        }
+        if let Some(subscriber) = store.subscribers.get_mut(&subscriber_id) {
+            if subscriber.status == "pending" {
+                subscriber.status = "confirmed".into();
+                subscriber.confirmed_at = Some(now);
+            }
+            let confirmed = event(EventKind::Confirmed, store.slug(list_id), context);
+            store.events.push((subscriber_id, confirmed));
+        }
+        Ok(())
+    }
+
+    async fn find_by_id(&self, subscriber_id: Uuid) -> eyre::Result<Option<Subscriber>> {
+        Ok(self.store.lock().unwrap().subscribers.get(&subscriber_id).cloned())
+    }
+
+    async fn list(
+        &self,
+        filter: &SubscriberFilter,
+        cursor: Option<PageCursor>,
+        limit: i64,
+    ) -> eyre::Result<Vec<Subscriber>> {
+        let store = self.store.lock().unwrap();
+        let mut subscribers: Vec<_> = store
+            .subscribers
+            .values()
+            .filter(|s| matches(filter, s))
+            .filter(|s| cursor.is_none_or(|c| (s.subscribed_at, s.id) < (c.subscribed_at, c.id)))
+            .cloned()
+            .collect();
+        subscribers.sort_by_key(|s| Reverse((s.subscribed_at, s.id)));
+        subscribers.truncate(limit.try_into().unwrap_or(0));
+        Ok(subscribers)
+    }
+
+    async fn token_history(&self, subscriber_id: Uuid) -> eyre::Result<Vec<DateTime<Utc>>> {
+        let store = self.store.lock().unwrap();
+        let mut created_at: Vec<_> = store
+            .tokens
+            .values()
+            .filter(|(id, _, _)| *id == subscriber_id)
+            .map(|(_, _, created_at)| *created_at)
+            .collect();
+        created_at.sort_by_key(|at| Reverse(*at));
+        Ok(created_at)
+    }
+
+    async fn reissue_tokens(
+        &self,
+        subscriber_id: Uuid,
+        tokens: &[(Uuid, String)],
+        issued_after: DateTime<Utc>,
+    ) -> eyre::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        if store
+            .tokens
+            .values()
+            .any(|(id, _, created_at)| *id == subscriber_id && *created_at > issued_after)
+        {
+            return Ok(false);
+        }
+        for (list_id, token) in tokens {
+            store
+                .tokens
+                .retain(|_, (id, list, _)| !(*id == subscriber_id && list == list_id));
+            store
+                .tokens
+                .insert(token.clone(), (subscriber_id, *list_id, Utc::now()));
+        }
+        Ok(true)
+    }
+
+    async fn profile(&self, subscriber_id: Uuid) -> eyre::Result<SubscriberProfile> {
+        let store = self.store.lock().unwrap();
+        Ok(store.profiles.get(&subscriber_id).cloned().unwrap_or_default())
+    }
+
+    async fn paused_until(&self, subscriber_id: Uuid) -> eyre::Result<Option<DateTime<Utc>>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.paused.get(&subscriber_id).copied())
+    }
+
+    async fn unsubscribe(&self, subscriber_id: Uuid, context: &EventContext) -> eyre::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        let Some(subscriber) = store
+            .subscribers
+            .get_mut(&subscriber_id)
+            .filter(|subscriber| subscriber.status == "pending" || subscriber.status == "confirmed")
+        else {
+            return Ok(false);
+        };
+        subscriber.status = "unsubscribed".into();
+        for ((id, _), membership) in store.memberships.iter_mut() {
+            if *id == subscriber_id {
+                membership.status = "unsubscribed".into();
+            }
+        }
+        store.tokens.retain(|_, (id, _, _)| *id != subscriber_id);
+        store
+            .events
+            .push((subscriber_id, event(EventKind::Unsubscribed, None, context)));
+        Ok(true)
+    }
+
+    async fn change_email(
+        &self,
+        subscriber_id: Uuid,
+        old_email: &str,
+        new_email: &str,
+        suppression_hash: &str,
+        context: &EventContext,
+    ) -> eyre::Result<EmailChange> {
+        let mut store = self.store.lock().unwrap();
+        if store.suppressions.contains(suppression_hash) {
+            return Ok(EmailChange::Suppressed);
+        }
+        if store.subscribers.values().any(|s| s.email == new_email && s.id != subscriber_id) {
+            return Ok(EmailChange::Taken);
+        }
+        let Some(subscriber) = store
+            .subscribers
+            .get_mut(&subscriber_id)
+            .filter(|subscriber| subscriber.email == old_email)
+        else {
+            return Ok(EmailChange::Stale);
+        };
+        subscriber.email = new_email.to_owned();
+        store
+            .events
+            .push((subscriber_id, event(EventKind::EmailChanged, None, context)));
+        Ok(EmailChange::Changed)
+    }
+
+    async fn delete(
+        &self,
+        subscriber_id: Uuid,
+        mode: DeleteMode,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let mut store = self.store.lock().unwrap();
+        store.tokens.retain(|_, (id, _, _)| *id != subscriber_id);
+        Ok(match mode {
+            DeleteMode::Hard => {
+                store.profiles.remove(&subscriber_id);
+                store.events.retain(|(id, _)| *id != subscriber_id);
+                store.memberships.retain(|(id, _), _| *id != subscriber_id);
+                store.deliveries.retain(|(_, id), _| *id != subscriber_id);
+                store.tracking_events.retain(|(_, id, ..)| *id != subscriber_id);
+                store.tracking_opt_outs.remove(&subscriber_id);
+                store.paused.remove(&subscriber_id);
+                store.subscribers.remove(&subscriber_id).is_some()
+            }
+            DeleteMode::Soft => match store.subscribers.get_mut(&subscriber_id) {
+                Some(subscriber) => {
+                    subscriber.status = "deleted".into();
+                    store
+                        .events
+                        .push((subscriber_id, event(EventKind::Deleted, None, context)));
+                    true
+                }
+                None => false,
+            },
+        })
+    }
+
+    async fn import(
+        &self,
+        subscribers: &[ImportedSubscriber],
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<HashSet<String>> {
+        let mut store = self.store.lock().unwrap();
+        let Some(list) = store.slug(list_id) else {
+            return Err(eyre!("Unknown list {}", list_id));
+        };
+        let mut inserted = HashSet::new();
+        let now = Utc::now();
+        for imported in subscribers {
+            let email = imported.new_subscriber.email.as_ref();
+            if store.subscribers.values().any(|s| s.email == email) {
+                continue;
+            }
+            let id = Uuid::new_v4();
+            let (status, confirmed_at, consent_source) = match &imported.status {
+                ImportedStatus::Confirmed { consent_source } => {
+                    ("confirmed", Some(now), Some(consent_source.clone()))
+                }
+                ImportedStatus::Pending { token } => {
+                    store.tokens.insert(token.clone(), (id, list_id, now));
+                    ("pending", None, None)
+                }
+            };
+            let membership = Membership {
+                list: list.clone(),
+                status: status.into(),
+                subscribed_at: now,
+                confirmed_at,
+            };
+            store.memberships.insert((id, list_id), membership);
+            store.subscribers.insert(
+                id,
+                Subscriber {
+                    id,
+                    email: email.to_owned(),
+                    name: imported.new_subscriber.name.as_ref().to_owned(),
+                    status: status.into(),
+                    locale: imported.new_subscriber.locale.clone(),
+                    subscribed_at: now,
+                    confirmed_at,
+                    consent_source,
+                },
+            );
+            store
+                .events
+                .push((id, event(EventKind::Imported, Some(list.clone()), context)));
+            inserted.insert(email.to_owned());
+        }
+        Ok(inserted)
+    }
+
+    /// The snapshot is a copy of the matching subscribers.
+    fn export(&self, filter: &SubscriberFilter) -> SubscriberStream {
+        let store = self.store.lock().unwrap();
+        let mut subscribers: Vec<_> = store
+            .subscribers
+            .values()
+            .filter(|s| matches(filter, s))
+            .cloned()
+            .collect();
+        subscribers.sort_by_key(|s| (s.subscribed_at, s.id));
+        stream::iter(subscribers.into_iter().map(Ok)).boxed()
+    }
+}
@@ -0,0 +1,238 @@
+mod audit;
+mod feeds;
+mod issues;
+mod lists;
+mod subscribers;
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{types::Json, PgConnection, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{ProfileUpdate, SubscriberTransaction};
+use crate::audit::{EventContext, EventKind};
+use crate::domain::NewSubscriber;
+
+#[derive(Clone)]
+pub struct PostgresSubscriberRepository {
+    pool: PgPool,
+}
+
+impl PostgresSubscriberRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Appends to the subscriber's audit trail; `list_id` is for events about
+/// one list.
+async fn record_event(
+    connection: &mut PgConnection,
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+    kind: EventKind,
+    context: &EventContext,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO subscription_events
+            (subscriber_id, list_id, kind, occurred_at, ip, user_agent, request_id, source)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+        subscriber_id,
+        list_id,
+        kind.as_str(),
+        Utc::now(),
+        context.ip,
+        context.user_agent,
+        context.request_id,
+        context.source,
+    )
+    .execute(connection)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
+
+pub struct PostgresSubscriberTransaction {
+    transaction: Transaction<'static, Postgres>,
+}
+
+
+#[async_trait]
+impl SubscriberTransaction for PostgresSubscriberTransaction {
+    #[tracing::instrument(
+        name = "Saving new subscriber details in the database",
+        skip(self, new_subscriber)
+    )]
+    async fn insert_subscriber(&mut self, new_subscriber: &NewSubscriber) -> eyre::Result<Uuid> {
+        let subscriber_id = Uuid::new_v4();
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, locale)
+            VALUES ($1, $2, $3, $4, 'pending', $5)
+        "#,
+            subscriber_id,
+            new_subscriber.email.as_ref(),
+            new_subscriber.name.as_ref(),
+            Utc::now(),
+            new_subscriber.locale,
+        )
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        tracing::info!("New subscriber {} saved", new_subscriber.email.as_ref());
+        Ok(subscriber_id)
+    }
+
+    #[tracing::instrument(name = "Reactivate subscriber", skip(self, new_subscriber))]
+    async fn reactivate_subscriber(
+        &mut self,
+        subscriber_id: Uuid,
+        new_subscriber: &NewSubscriber,
+    ) -> eyre::Result<()> {
+        let result = sqlx::query!(
+            r#"UPDATE subscriptions
+            SET name = $2, locale = $3, status = 'pending', subscribed_at = $4, confirmed_at = NULL
+            WHERE id = $1 AND status IN ('deleted', 'unsubscribed')"#,
+            subscriber_id,
+            new_subscriber.name.as_ref(),
+            new_subscriber.locale,
+            Utc::now(),
+        )
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(eyre::eyre!("Subscriber {} is not deleted or unsubscribed", subscriber_id));
+        }
+        sqlx::query!(
+            r#"UPDATE list_memberships SET status = 'unsubscribed' WHERE subscriber_id = $1"#,
+            subscriber_id,
+        )
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Join mailing list", skip(self, context))]
+    async fn join_list(
+        &mut self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let result = sqlx::query!(
+            r#"INSERT INTO list_memberships (subscriber_id, list_id, status, subscribed_at)
+            VALUES ($1, $2, 'pending', $3)
+            ON CONFLICT (subscriber_id, list_id) DO UPDATE
+            SET status = 'pending', subscribed_at = EXCLUDED.subscribed_at, confirmed_at = NULL
+            WHERE list_memberships.status <> 'confirmed'"#,
+            subscriber_id,
+            list_id,
+            Utc::now(),
+        )
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        record_event(
+            &mut self.transaction,
+            subscriber_id,
+            Some(list_id),
+            EventKind::Subscribed,
+            context,
+        )
+        .await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "Update subscriber profile", skip(self))]
+    async fn update_profile(
+        &mut self,
+        subscriber_id: Uuid,
+        update: &ProfileUpdate,
+    ) -> eyre::Result<()> {
+        let (set, removed): (BTreeMap<_, _>, BTreeMap<_, _>) =
+            update.attributes.iter().partition(|(_, value)| value.is_some());
+        let removed: Vec<String> = removed.into_keys().cloned().collect();
+        sqlx::query!(
+            r#"UPDATE subscriptions SET attributes = (attributes || $2) - $3::text[]
+            WHERE id = $1"#,
+            subscriber_id,
+            Json(set) as _,
+            &removed,
+        )
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        let added: Vec<String> = update.add_tags.iter().cloned().collect();
+        sqlx::query!(
+            r#"INSERT INTO subscriber_tags (subscriber_id, tag)
+            SELECT $1, tag FROM unnest($2::text[]) AS tag
+            ON CONFLICT DO NOTHING"#,
+            subscriber_id,
+            &added,
+        )
+        .execute(&mut *self.transaction)
+        .await?;
+        let removed: Vec<String> = update.remove_tags.iter().cloned().collect();
+        sqlx::query!(
+            r#"DELETE FROM subscriber_tags WHERE subscriber_id = $1 AND tag = ANY($2)"#,
+            subscriber_id,
+            &removed,
+        )
+        .execute(&mut *self.transaction)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Store subscription token", skip(self, token))]
+    async fn store_token(
+        &mut self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        token: &str,
+    ) -> eyre::Result<()> {
+        sqlx::query!(
+            r#"INSERT INTO subscription_tokens (token, subscriber_id, list_id)
+            VALUES ($1, $2, $3)"#,
+            token,
+            subscriber_id,
+            list_id,
+        )
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    async fn commit(self: Box<Self>) -> eyre::Result<()> {
+        self.transaction.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::{record_event, PostgresSubscriberRepository};
+use crate::audit::{EventContext, EventKind, SubscriptionEvent};
+use crate::repository::{erased_email, AuditRepository};
+
+#[async_trait]
+impl AuditRepository for PostgresSubscriberRepository {
+    #[tracing::instrument(name = "Erase subscriber", skip(self, suppression_hash, context))]
+    async fn erase(
+        &self,
+        subscriber_id: Uuid,
+        suppression_hash: &str,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+            subscriber_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"DELETE FROM subscriber_tags WHERE subscriber_id = $1"#,
+            subscriber_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        let result = sqlx::query!(
+            r#"UPDATE subscriptions
+            SET email = $2, name = '', status = 'erased', consent_source = NULL, attributes = '{}'
+            WHERE id = $1"#,
+            subscriber_id,
+            erased_email(subscriber_id),
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query!(
+            r#"INSERT INTO suppressions (email_hash, created_at) VALUES ($1, $2)
+            ON CONFLICT (email_hash) DO NOTHING"#,
+            suppression_hash,
+            Utc::now(),
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"UPDATE subscription_events SET ip = NULL, user_agent = NULL WHERE subscriber_id = $1"#,
+            subscriber_id,
+        )
+        .execute(&mut *transaction)
+        .await?;
+        record_event(
+            &mut transaction,
+            subscriber_id,
+            None,
+            EventKind::Erased,
+            &context.anonymized(),
+        )
+        .await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn suppressed(&self, suppression_hashes: &[String]) -> eyre::Result<HashSet<String>> {
+        let suppressed = sqlx::query_scalar!(
+            r#"SELECT email_hash FROM suppressions WHERE email_hash = ANY($1)"#,
+            suppression_hashes,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(suppressed.into_iter().collect())
+    }
+
+    #[tracing::instrument(name = "Get subscription events", skip(self))]
+    async fn events(&self, subscriber_id: Uuid) -> eyre::Result<Vec<SubscriptionEvent>> {
+        let events = sqlx::query_as!(
+            SubscriptionEvent,
+            r#"SELECT e.kind, l.slug AS "list?", e.occurred_at, e.ip, e.user_agent, e.request_id, e.source
+            FROM subscription_events e LEFT JOIN lists l ON l.id = e.list_id
+            WHERE e.subscriber_id = $1 ORDER BY e.id"#,
+            subscriber_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(events)
+    }
+}
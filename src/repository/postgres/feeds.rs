@@ -0,0 +1,81 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::PostgresSubscriberRepository;
+use crate::repository::{FeedEntry, FeedRepository, NewFeedEntry};
+
+#[async_trait]
+impl FeedRepository for PostgresSubscriberRepository {
+    #[tracing::instrument(name = "Store feed entries", skip(self, entries))]
+    async fn store_feed_entries(
+        &self,
+        feed_url: &str,
+        entries: &[NewFeedEntry],
+        fetched_at: DateTime<Utc>,
+    ) -> eyre::Result<u64> {
+        let ids: Vec<Uuid> = entries.iter().map(|_| Uuid::new_v4()).collect();
+        let guids: Vec<String> = entries.iter().map(|entry| entry.guid.clone()).collect();
+        let titles: Vec<String> = entries.iter().map(|entry| entry.title.clone()).collect();
+        let links: Vec<Option<String>> = entries.iter().map(|entry| entry.link.clone()).collect();
+        let summaries: Vec<Option<String>> = entries.iter().map(|entry| entry.summary.clone()).collect();
+        let published_at: Vec<Option<DateTime<Utc>>> = entries.iter().map(|entry| entry.published_at).collect();
+        let result = sqlx::query!(
+            r#"INSERT INTO feed_entries
+                (id, feed_url, guid, title, link, summary, published_at, fetched_at, digested_at)
+            SELECT e.id, $1, e.guid, e.title, e.link, e.summary, e.published_at, $2::timestamptz,
+                CASE WHEN EXISTS (SELECT 1 FROM feed_entries WHERE feed_url = $1) THEN NULL ELSE $2::timestamptz END
+            FROM UNNEST($3::uuid[], $4::text[], $5::text[], $6::text[], $7::text[], $8::timestamptz[])
+                AS e(id, guid, title, link, summary, published_at)
+            ON CONFLICT (feed_url, guid) DO NOTHING"#,
+            feed_url,
+            fetched_at,
+            &ids,
+            &guids,
+            &titles,
+            &links as &[Option<String>],
+            &summaries as &[Option<String>],
+            &published_at as &[Option<DateTime<Utc>>],
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Claim feed entries", skip(self))]
+    async fn claim_feed_entries(&self, now: DateTime<Utc>) -> eyre::Result<Vec<FeedEntry>> {
+        let entries = sqlx::query_as!(
+            FeedEntry,
+            r#"UPDATE feed_entries SET digested_at = $1
+            WHERE digested_at IS NULL
+            RETURNING id, feed_url, guid, title, link, summary, published_at, fetched_at"#,
+            now,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(entries)
+    }
+
+    #[tracing::instrument(name = "Release feed entries", skip(self))]
+    async fn release_feed_entries(&self, entry_ids: &[Uuid]) -> eyre::Result<()> {
+        sqlx::query!(
+            "UPDATE feed_entries SET digested_at = NULL WHERE id = ANY($1)",
+            entry_ids,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,550 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use eyre::eyre;
+use uuid::Uuid;
+
+use super::PostgresSubscriberRepository;
+use crate::repository::{
+    issue_slug, Delivery, DeliveryCounts, DeliveryOutcome, Engagement, IssueContent,
+    IssueRepository, LinkClicks, NewsletterIssue, ReceivedIssue, Subscriber, TrackedAction,
+    TrackingEvent,
+};
+
+#[async_trait]
+impl IssueRepository for PostgresSubscriberRepository {
+    #[tracing::instrument(name = "List newsletter issues", skip(self))]
+    async fn issues(&self, status: Option<&str>) -> eyre::Result<Vec<NewsletterIssue>> {
+        let issues = sqlx::query_as!(
+            NewsletterIssue,
+            r#"SELECT i.id, i.list_id, l.slug AS list, i.title, i.html_content, i.text_content,
+                i.segment, i.tracking, i.public, i.slug, i.status, i.send_at, i.created_at, i.updated_at, i.sent_at, i.recipient_count
+            FROM newsletter_issues i JOIN lists l ON l.id = i.list_id
+            WHERE $1::text IS NULL OR i.status = $1
+            ORDER BY i.created_at DESC, i.id"#,
+            status,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issues)
+    }
+
+    #[tracing::instrument(name = "Find newsletter issue", skip(self))]
+    async fn find_issue(&self, issue_id: Uuid) -> eyre::Result<Option<NewsletterIssue>> {
+        let issue = sqlx::query_as!(
+            NewsletterIssue,
+            r#"SELECT i.id, i.list_id, l.slug AS list, i.title, i.html_content, i.text_content,
+                i.segment, i.tracking, i.public, i.slug, i.status, i.send_at, i.created_at, i.updated_at, i.sent_at, i.recipient_count
+            FROM newsletter_issues i JOIN lists l ON l.id = i.list_id
+            WHERE i.id = $1"#,
+            issue_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issue)
+    }
+
+    #[tracing::instrument(name = "Create newsletter issue", skip(self, content))]
+    async fn create_issue(&self, content: &IssueContent) -> eyre::Result<NewsletterIssue> {
+        let issue_id = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query!(
+            r#"INSERT INTO newsletter_issues (id, list_id, title, html_content, text_content,
+                segment, tracking, public, slug, status, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'draft', $10, $10)"#,
+            issue_id,
+            content.list_id,
+            content.title,
+            content.html_content,
+            content.text_content,
+            content.segment,
+            content.tracking,
+            content.public,
+            issue_slug(&content.title, issue_id),
+            now,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        self.find_issue(issue_id)
+            .await?
+            .ok_or_else(|| eyre!("Newsletter issue {} vanished after being created", issue_id))
+    }
+
+    #[tracing::instrument(name = "Update newsletter issue", skip(self, content))]
+    async fn update_issue(
+        &self,
+        issue_id: Uuid,
+        content: &IssueContent,
+    ) -> eyre::Result<Option<NewsletterIssue>> {
+        let issue = sqlx::query_as!(
+            NewsletterIssue,
+            r#"UPDATE newsletter_issues i SET list_id = $2, title = $3, html_content = $4,
+                text_content = $5, segment = $6, tracking = $7, public = $8, slug = $9, updated_at = $10
+            FROM lists l
+            WHERE i.id = $1 AND i.status IN ('draft', 'scheduled') AND l.id = $2
+            RETURNING i.id, i.list_id, l.slug AS list, i.title, i.html_content, i.text_content,
+                i.segment, i.tracking, i.public, i.slug, i.status, i.send_at, i.created_at, i.updated_at, i.sent_at, i.recipient_count"#,
+            issue_id,
+            content.list_id,
+            content.title,
+            content.html_content,
+            content.text_content,
+            content.segment,
+            content.tracking,
+            content.public,
+            issue_slug(&content.title, issue_id),
+            Utc::now(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issue)
+    }
+
+    #[tracing::instrument(name = "Schedule newsletter issue", skip(self))]
+    async fn schedule_issue(
+        &self,
+        issue_id: Uuid,
+        send_at: Option<DateTime<Utc>>,
+    ) -> eyre::Result<Option<NewsletterIssue>> {
+        let issue = sqlx::query_as!(
+            NewsletterIssue,
+            r#"UPDATE newsletter_issues i
+            SET status = CASE WHEN $2::timestamptz IS NULL THEN 'draft' ELSE 'scheduled' END,
+                send_at = $2, updated_at = $3
+            FROM lists l
+            WHERE i.id = $1 AND i.status IN ('draft', 'scheduled') AND l.id = i.list_id
+            RETURNING i.id, i.list_id, l.slug AS list, i.title, i.html_content, i.text_content,
+                i.segment, i.tracking, i.public, i.slug, i.status, i.send_at, i.created_at, i.updated_at, i.sent_at, i.recipient_count"#,
+            issue_id,
+            send_at,
+            Utc::now(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issue)
+    }
+
+    #[tracing::instrument(name = "Delete newsletter issue", skip(self))]
+    async fn delete_issue(&self, issue_id: Uuid) -> eyre::Result<bool> {
+        let result = sqlx::query!(
+            r#"DELETE FROM newsletter_issues WHERE id = $1 AND status IN ('draft', 'scheduled')"#,
+            issue_id,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Instances polling at the same time skip the rows others have locked
+    /// instead of waiting for them, and the status check after the lock
+    /// keeps a row claimed in between from being claimed twice.
+    #[tracing::instrument(name = "Claim due newsletter issue", skip(self))]
+    async fn claim_due_issue(&self, now: DateTime<Utc>) -> eyre::Result<Option<NewsletterIssue>> {
+        let issue = sqlx::query_as!(
+            NewsletterIssue,
+            r#"UPDATE newsletter_issues i SET status = 'sending', updated_at = $1
+            FROM lists l
+            WHERE l.id = i.list_id AND i.status = 'scheduled' AND i.id = (
+                SELECT id FROM newsletter_issues
+                WHERE status = 'scheduled' AND send_at <= $1
+                ORDER BY send_at, created_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING i.id, i.list_id, l.slug AS list, i.title, i.html_content, i.text_content,
+                i.segment, i.tracking, i.public, i.slug, i.status, i.send_at, i.created_at, i.updated_at, i.sent_at, i.recipient_count"#,
+            now,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issue)
+    }
+
+    /// Like [`claim_due_issue`](Self::claim_due_issue); the `updated_at`
+    /// check after the lock keeps an issue taken over in between from
+    /// being taken over twice.
+    #[tracing::instrument(name = "Claim stalled newsletter issue", skip(self))]
+    async fn claim_stalled_issue(
+        &self,
+        now: DateTime<Utc>,
+        stalled_before: DateTime<Utc>,
+    ) -> eyre::Result<Option<NewsletterIssue>> {
+        let issue = sqlx::query_as!(
+            NewsletterIssue,
+            r#"UPDATE newsletter_issues i SET updated_at = $1
+            FROM lists l
+            WHERE l.id = i.list_id AND i.status = 'sending' AND i.updated_at <= $2 AND i.id = (
+                SELECT n.id FROM newsletter_issues n
+                WHERE n.status = 'sending' AND n.updated_at <= $2 AND NOT EXISTS (
+                    SELECT 1 FROM deliveries d WHERE d.issue_id = n.id AND d.updated_at > $2
+                )
+                ORDER BY n.updated_at, n.created_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING i.id, i.list_id, l.slug AS list, i.title, i.html_content, i.text_content,
+                i.segment, i.tracking, i.public, i.slug, i.status, i.send_at, i.created_at, i.updated_at, i.sent_at, i.recipient_count"#,
+            now,
+            stalled_before,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issue)
+    }
+
+    #[tracing::instrument(name = "Finish newsletter issue", skip(self))]
+    async fn finish_issue(&self, issue_id: Uuid, recipient_count: i64) -> eyre::Result<()> {
+        let now = Utc::now();
+        sqlx::query!(
+            r#"UPDATE newsletter_issues
+            SET status = 'sent', sent_at = $2, updated_at = $2, recipient_count = $3
+            WHERE id = $1 AND status = 'sending'"#,
+            issue_id,
+            now,
+            recipient_count,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Release newsletter issue", skip(self))]
+    async fn release_issue(&self, issue_id: Uuid) -> eyre::Result<()> {
+        sqlx::query!(
+            r#"UPDATE newsletter_issues SET status = 'draft', send_at = NULL, updated_at = $2
+            WHERE id = $1 AND status = 'sending'"#,
+            issue_id,
+            Utc::now(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Queue deliveries", skip(self, subscriber_ids), fields(count = subscriber_ids.len()))]
+    async fn queue_deliveries(&self, issue_id: Uuid, subscriber_ids: &[Uuid]) -> eyre::Result<()> {
+        sqlx::query!(
+            r#"INSERT INTO deliveries (issue_id, subscriber_id, status, created_at, updated_at)
+            SELECT $1, subscriber_id, 'queued', $3, $3 FROM UNNEST($2::uuid[]) AS subscriber_id
+            ON CONFLICT (issue_id, subscriber_id) DO NOTHING"#,
+            issue_id,
+            subscriber_ids,
+            Utc::now(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Find queued recipients", skip(self))]
+    async fn queued_recipients(&self, issue_id: Uuid) -> eyre::Result<Vec<Subscriber>> {
+        let recipients = sqlx::query_as!(
+            Subscriber,
+            r#"SELECT s.id, s.email, s.name, s.status, s.locale, s.subscribed_at, s.confirmed_at, s.consent_source
+            FROM subscriptions s JOIN deliveries d ON d.subscriber_id = s.id
+            WHERE d.issue_id = $1 AND d.status = 'queued'
+            ORDER BY s.subscribed_at, s.id"#,
+            issue_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(recipients)
+    }
+
+    #[tracing::instrument(name = "Record delivery", skip(self))]
+    async fn record_delivery(
+        &self,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        outcome: &DeliveryOutcome,
+    ) -> eyre::Result<()> {
+        let (status, message_id, error) = match outcome {
+            DeliveryOutcome::Sent { message_id } => ("sent", message_id.as_deref(), None),
+            DeliveryOutcome::Failed { error } => ("failed", None, Some(error.as_str())),
+        };
+        sqlx::query!(
+            r#"UPDATE deliveries SET status = $3, message_id = $4,
+                last_error = COALESCE($5, last_error), attempts = attempts + 1, updated_at = $6
+            WHERE issue_id = $1 AND subscriber_id = $2"#,
+            issue_id,
+            subscriber_id,
+            status,
+            message_id,
+            error,
+            Utc::now(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Count deliveries", skip(self))]
+    async fn delivery_counts(&self, issue_id: Uuid) -> eyre::Result<DeliveryCounts> {
+        let counts = sqlx::query!(
+            r#"SELECT status, COUNT(*) AS "count!" FROM deliveries
+            WHERE issue_id = $1 GROUP BY status"#,
+            issue_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(counts.into_iter().map(|row| (row.status, row.count)).collect())
+    }
+
+    #[tracing::instrument(name = "List failed deliveries", skip(self))]
+    async fn failed_deliveries(&self, issue_id: Uuid, limit: i64) -> eyre::Result<Vec<Delivery>> {
+        let deliveries = sqlx::query_as!(
+            Delivery,
+            r#"SELECT d.subscriber_id, s.email, d.status, d.message_id, d.attempts, d.last_error,
+                d.updated_at
+            FROM deliveries d JOIN subscriptions s ON s.id = d.subscriber_id
+            WHERE d.issue_id = $1 AND d.status IN ('failed', 'bounced')
+            ORDER BY d.updated_at DESC, d.subscriber_id
+            LIMIT $2"#,
+            issue_id,
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(deliveries)
+    }
+
+    #[tracing::instrument(name = "List a subscriber's received issues", skip(self))]
+    async fn received_issues(&self, subscriber_id: Uuid) -> eyre::Result<Vec<ReceivedIssue>> {
+        let issues = sqlx::query_as!(
+            ReceivedIssue,
+            r#"SELECT d.issue_id, i.title, d.status, d.updated_at
+            FROM deliveries d JOIN newsletter_issues i ON i.id = d.issue_id
+            WHERE d.subscriber_id = $1
+            ORDER BY d.created_at, d.issue_id"#,
+            subscriber_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issues)
+    }
+
+    #[tracing::instrument(name = "Set tracking opt-out", skip(self))]
+    async fn set_tracking_opt_out(&self, subscriber_id: Uuid, opt_out: bool) -> eyre::Result<bool> {
+        let result = sqlx::query!(
+            r#"UPDATE subscriptions SET tracking_opt_out = $2 WHERE id = $1"#,
+            subscriber_id,
+            opt_out,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "List untracked recipients", skip(self))]
+    async fn untracked_recipients(&self, issue_id: Uuid) -> eyre::Result<HashSet<Uuid>> {
+        let subscriber_ids = sqlx::query_scalar!(
+            r#"SELECT d.subscriber_id FROM deliveries d
+            JOIN subscriptions s ON s.id = d.subscriber_id
+            WHERE d.issue_id = $1 AND s.tracking_opt_out"#,
+            issue_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(subscriber_ids.into_iter().collect())
+    }
+
+    #[tracing::instrument(name = "Record tracked action", skip(self))]
+    async fn record_tracked_action(
+        &self,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        action: &TrackedAction,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<bool> {
+        let result = sqlx::query!(
+            r#"INSERT INTO tracking_events (issue_id, subscriber_id, kind, url, occurred_at)
+            SELECT i.id, s.id, $3, $4, $5 FROM newsletter_issues i, subscriptions s
+            WHERE i.id = $1 AND s.id = $2 AND NOT s.tracking_opt_out"#,
+            issue_id,
+            subscriber_id,
+            action.kind(),
+            action.url(),
+            at,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Summarise engagement", skip(self))]
+    async fn engagement(&self, issue_id: Uuid) -> eyre::Result<Engagement> {
+        let totals = sqlx::query!(
+            r#"SELECT
+                COUNT(*) FILTER (WHERE kind = 'open') AS "opens!",
+                COUNT(DISTINCT subscriber_id) FILTER (WHERE kind = 'open') AS "unique_opens!",
+                COUNT(*) FILTER (WHERE kind = 'click') AS "clicks!",
+                COUNT(DISTINCT subscriber_id) FILTER (WHERE kind = 'click') AS "unique_clicks!"
+            FROM tracking_events WHERE issue_id = $1"#,
+            issue_id,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        let links = sqlx::query_as!(
+            LinkClicks,
+            r#"SELECT url AS "url!", COUNT(*) AS "clicks!",
+                COUNT(DISTINCT subscriber_id) AS "unique_clicks!"
+            FROM tracking_events WHERE issue_id = $1 AND kind = 'click'
+            GROUP BY url
+            ORDER BY 2 DESC, url"#,
+            issue_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(Engagement {
+            opens: totals.opens,
+            unique_opens: totals.unique_opens,
+            clicks: totals.clicks,
+            unique_clicks: totals.unique_clicks,
+            links,
+        })
+    }
+
+    #[tracing::instrument(name = "List a subscriber's tracking events", skip(self))]
+    async fn tracking_events(&self, subscriber_id: Uuid) -> eyre::Result<Vec<TrackingEvent>> {
+        let events = sqlx::query_as!(
+            TrackingEvent,
+            r#"SELECT issue_id, kind, url, occurred_at FROM tracking_events
+            WHERE subscriber_id = $1
+            ORDER BY id"#,
+            subscriber_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(events)
+    }
+
+    #[tracing::instrument(name = "List archived newsletter issues", skip(self))]
+    async fn archived_issues(&self, limit: i64, offset: i64) -> eyre::Result<Vec<NewsletterIssue>> {
+        let issues = sqlx::query_as!(
+            NewsletterIssue,
+            r#"SELECT i.id, i.list_id, l.slug AS list, i.title, i.html_content, i.text_content,
+                i.segment, i.tracking, i.public, i.slug, i.status, i.send_at, i.created_at, i.updated_at, i.sent_at, i.recipient_count
+            FROM newsletter_issues i JOIN lists l ON l.id = i.list_id
+            WHERE i.public AND i.status = 'sent'
+            ORDER BY i.sent_at DESC, i.id
+            LIMIT $1 OFFSET $2"#,
+            limit,
+            offset,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issues)
+    }
+
+    #[tracing::instrument(name = "Find archived newsletter issue", skip(self))]
+    async fn find_archived_issue(&self, slug: &str) -> eyre::Result<Option<NewsletterIssue>> {
+        let issue = sqlx::query_as!(
+            NewsletterIssue,
+            r#"SELECT i.id, i.list_id, l.slug AS list, i.title, i.html_content, i.text_content,
+                i.segment, i.tracking, i.public, i.slug, i.status, i.send_at, i.created_at, i.updated_at, i.sent_at, i.recipient_count
+            FROM newsletter_issues i JOIN lists l ON l.id = i.list_id
+            WHERE i.slug = $1 AND i.public AND i.status = 'sent'"#,
+            slug,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issue)
+    }
+}
@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use super::{record_event, PostgresSubscriberRepository};
+use crate::audit::EventContext;
+use crate::repository::{
+    ListRepository, ListSettings, MailingList, Membership, MembershipChanges, PreferenceUpdate,
+    Subscriber,
+};
+use crate::segment::{Dialect, Segment};
+
+#[async_trait]
+impl ListRepository for PostgresSubscriberRepository {
+    #[tracing::instrument(name = "Get list memberships", skip(self))]
+    async fn memberships(&self, subscriber_id: Uuid) -> eyre::Result<Vec<Membership>> {
+        let memberships = sqlx::query_as!(
+            Membership,
+            r#"SELECT l.slug AS list, m.status, m.subscribed_at, m.confirmed_at
+            FROM list_memberships m JOIN lists l ON l.id = m.list_id
+            WHERE m.subscriber_id = $1
+            ORDER BY m.subscribed_at, l.slug"#,
+            subscriber_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(memberships)
+    }
+
+    #[tracing::instrument(name = "Update subscriber preferences", skip(self, update, context))]
+    async fn update_preferences(
+        &self,
+        subscriber_id: Uuid,
+        update: &PreferenceUpdate,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        let result = sqlx::query!(
+            r#"UPDATE subscriptions SET name = $2, paused_until = $3
+            WHERE id = $1 AND status = 'confirmed'"#,
+            subscriber_id,
+            update.name,
+            update.paused_until,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        // Locked by the update above, so the memberships cannot change
+        // under us.
+        let current: Vec<_> = sqlx::query!(
+            r#"SELECT list_id, status FROM list_memberships WHERE subscriber_id = $1"#,
+            subscriber_id,
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+        .into_iter()
+        .map(|row| (row.list_id, row.status))
+        .collect();
+        let changes = MembershipChanges::new(&current, &update.list_ids);
+        let confirmed: Vec<_> = changes.join.iter().chain(&changes.confirm).copied().collect();
+        sqlx::query!(
+            r#"INSERT INTO list_memberships (subscriber_id, list_id, status, subscribed_at, confirmed_at)
+            SELECT $1, list_id, 'confirmed', now(), now() FROM UNNEST($2::uuid[]) AS list_id
+            ON CONFLICT (subscriber_id, list_id)
+                DO UPDATE SET status = 'confirmed', confirmed_at = now()"#,
+            subscriber_id,
+            &confirmed,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        sqlx::query!(
+            r#"UPDATE list_memberships SET status = 'unsubscribed'
+            WHERE subscriber_id = $1 AND list_id = ANY($2)"#,
+            subscriber_id,
+            &changes.leave,
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1 AND list_id = ANY($2)"#,
+            subscriber_id,
+            &changes.leave,
+        )
+        .execute(&mut *transaction)
+        .await?;
+        for (list_id, kind) in changes.events() {
+            record_event(&mut transaction, subscriber_id, Some(list_id), kind, context).await?;
+        }
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "Get list recipients", skip(self))]
+    async fn recipients(
+        &self,
+        list_id: Uuid,
+        segment: Option<&Segment>,
+    ) -> eyre::Result<Vec<Subscriber>> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"SELECT s.id, s.email, s.name, s.status, s.locale, s.subscribed_at, s.confirmed_at, s.consent_source
+            FROM subscriptions s JOIN list_memberships m ON m.subscriber_id = s.id
+            WHERE s.status = 'confirmed' AND m.status = 'confirmed'
+                AND (s.paused_until IS NULL OR s.paused_until <= now()) AND m.list_id = "#,
+        );
+        query.push_bind(list_id);
+        if let Some(segment) = segment {
+            query.push(" AND ");
+            segment.push_sql(&mut query, Dialect::Postgres);
+        }
+        query.push(" ORDER BY s.subscribed_at, s.id");
+        let recipients = query
+            .build_query_as::<Subscriber>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        Ok(recipients)
+    }
+
+    #[tracing::instrument(name = "List mailing lists", skip(self))]
+    async fn lists(&self) -> eyre::Result<Vec<MailingList>> {
+        let lists = sqlx::query_as!(
+            MailingList,
+            r#"SELECT id, slug, name, sender_email, sender_name, confirmation_subject,
+                confirmation_intro, created_at
+            FROM lists ORDER BY created_at, slug"#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(lists)
+    }
+
+    #[tracing::instrument(name = "Find mailing list", skip(self))]
+    async fn find_list(&self, slug: &str) -> eyre::Result<Option<MailingList>> {
+        let list = sqlx::query_as!(
+            MailingList,
+            r#"SELECT id, slug, name, sender_email, sender_name, confirmation_subject,
+                confirmation_intro, created_at
+            FROM lists WHERE slug = $1"#,
+            slug,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(list)
+    }
+
+    #[tracing::instrument(name = "Create mailing list", skip(self, settings))]
+    async fn create_list(
+        &self,
+        slug: &str,
+        settings: &ListSettings,
+    ) -> eyre::Result<Option<MailingList>> {
+        let list = sqlx::query_as!(
+            MailingList,
+            r#"INSERT INTO lists (id, slug, name, sender_email, sender_name,
+                confirmation_subject, confirmation_intro, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (slug) DO NOTHING
+            RETURNING id, slug, name, sender_email, sender_name, confirmation_subject,
+                confirmation_intro, created_at"#,
+            Uuid::new_v4(),
+            slug,
+            settings.name,
+            settings.sender_email,
+            settings.sender_name,
+            settings.confirmation_subject,
+            settings.confirmation_intro,
+            Utc::now(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(list)
+    }
+
+    #[tracing::instrument(name = "Update mailing list", skip(self, settings))]
+    async fn update_list(
+        &self,
+        slug: &str,
+        settings: &ListSettings,
+    ) -> eyre::Result<Option<MailingList>> {
+        let list = sqlx::query_as!(
+            MailingList,
+            r#"UPDATE lists SET name = $2, sender_email = $3, sender_name = $4,
+                confirmation_subject = $5, confirmation_intro = $6
+            WHERE slug = $1
+            RETURNING id, slug, name, sender_email, sender_name, confirmation_subject,
+                confirmation_intro, created_at"#,
+            slug,
+            settings.name,
+            settings.sender_email,
+            settings.sender_name,
+            settings.confirmation_subject,
+            settings.confirmation_intro,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(list)
+    }
+
+    /// Memberships and tokens go through `ON DELETE CASCADE`.
+    #[tracing::instrument(name = "Delete mailing list", skip(self))]
+    async fn delete_list(&self, slug: &str) -> eyre::Result<bool> {
+        let result = sqlx::query!(r#"DELETE FROM lists WHERE slug = $1"#, slug)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        Ok(result.rows_affected() > 0)
+    }
+}
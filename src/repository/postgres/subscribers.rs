@@ -0,0 +1,552 @@
+use std::collections::{BTreeMap, HashSet};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use sqlx::{types::Json, Executor};
+use uuid::Uuid;
+
+use super::{record_event, PostgresSubscriberRepository, PostgresSubscriberTransaction};
+use crate::audit::{EventContext, EventKind};
+use crate::repository::{
+    spawn_export, DeleteMode, EmailChange, ImportedStatus, ImportedSubscriber, PageCursor,
+    Subscriber, SubscriberFilter, SubscriberProfile, SubscriberRepository, SubscriberStream,
+    SubscriberTransaction, SubscriptionToken,
+};
+
+#[async_trait]
+impl SubscriberRepository for PostgresSubscriberRepository {
+    async fn begin(&self) -> eyre::Result<Box<dyn SubscriberTransaction>> {
+        let transaction = self.pool.begin().await?;
+        Ok(Box::new(PostgresSubscriberTransaction { transaction }))
+    }
+
+    #[tracing::instrument(name = "Find subscriber by email", skip(self, email))]
+    async fn find_by_email(&self, email: &str) -> eyre::Result<Option<Subscriber>> {
+        let subscriber = sqlx::query_as!(
+            Subscriber,
+            r#"SELECT id, email, name, status, locale, subscribed_at, confirmed_at, consent_source
+            FROM subscriptions WHERE email = $1"#,
+            email,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(subscriber)
+    }
+
+    #[tracing::instrument(name = "Get subscription token", skip(self, token))]
+    async fn find_token(&self, token: &str) -> eyre::Result<Option<SubscriptionToken>> {
+        let token = sqlx::query_as!(
+            SubscriptionToken,
+            r#"SELECT t.subscriber_id, t.list_id, s.locale, m.status, t.created_at
+            FROM subscription_tokens t
+            JOIN subscriptions s ON s.id = t.subscriber_id
+            JOIN list_memberships m
+                ON m.subscriber_id = t.subscriber_id AND m.list_id = t.list_id
+            WHERE t.token = $1"#,
+            token,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(token)
+    }
+
+    #[tracing::instrument(name = "Mark subscriber as confirmed", skip(self, context))]
+    async fn confirm(
+        &self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"UPDATE list_memberships SET status = 'confirmed', confirmed_at = now()
+            WHERE subscriber_id = $1 AND list_id = $2"#,
+            subscriber_id,
+            list_id,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        sqlx::query!(
+            r#"UPDATE subscriptions SET status = 'confirmed', confirmed_at = now()
+            WHERE id = $1 AND status = 'pending'"#,
+            subscriber_id,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        record_event(
+            &mut transaction,
+            subscriber_id,
+            Some(list_id),
+            EventKind::Confirmed,
+            context,
+        )
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Find subscriber by id", skip(self))]
+    async fn find_by_id(&self, subscriber_id: Uuid) -> eyre::Result<Option<Subscriber>> {
+        let subscriber = sqlx::query_as!(
+            Subscriber,
+            r#"SELECT id, email, name, status, locale, subscribed_at, confirmed_at, consent_source
+            FROM subscriptions WHERE id = $1"#,
+            subscriber_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(subscriber)
+    }
+
+    #[tracing::instrument(name = "List subscribers", skip(self))]
+    async fn list(
+        &self,
+        filter: &SubscriberFilter,
+        cursor: Option<PageCursor>,
+        limit: i64,
+    ) -> eyre::Result<Vec<Subscriber>> {
+        let subscribers = sqlx::query_as!(
+            Subscriber,
+            r#"SELECT id, email, name, status, locale, subscribed_at, confirmed_at, consent_source
+            FROM subscriptions
+            WHERE ($1::text IS NULL OR status = $1)
+                AND ($2::timestamptz IS NULL OR subscribed_at >= $2)
+                AND ($3::timestamptz IS NULL OR subscribed_at < $3)
+                AND ($4::text IS NULL
+                    OR starts_with(lower(email), lower($4))
+                    OR starts_with(lower(name), lower($4)))
+                AND ($5::timestamptz IS NULL OR (subscribed_at, id) < ($5, $6::uuid))
+            ORDER BY subscribed_at DESC, id DESC
+            LIMIT $7"#,
+            filter.status,
+            filter.subscribed_after,
+            filter.subscribed_before,
+            filter.search,
+            cursor.map(|c| c.subscribed_at),
+            cursor.map(|c| c.id),
+            limit,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(subscribers)
+    }
+
+    #[tracing::instrument(name = "Get subscription token history", skip(self))]
+    async fn token_history(&self, subscriber_id: Uuid) -> eyre::Result<Vec<DateTime<Utc>>> {
+        let created_at = sqlx::query_scalar!(
+            r#"SELECT created_at FROM subscription_tokens
+            WHERE subscriber_id = $1 ORDER BY created_at DESC"#,
+            subscriber_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(created_at)
+    }
+
+    #[tracing::instrument(name = "Delete subscriber", skip(self))]
+    async fn delete(
+        &self,
+        subscriber_id: Uuid,
+        mode: DeleteMode,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let result = match mode {
+            // Tokens go through `ON DELETE CASCADE`.
+            DeleteMode::Hard => {
+                sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, subscriber_id)
+                    .execute(&self.pool)
+                    .await
+            }
+            DeleteMode::Soft => {
+                let mut transaction = self.pool.begin().await?;
+                sqlx::query!(
+                    r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+                    subscriber_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+                let result = sqlx::query!(
+                    r#"UPDATE subscriptions SET status = 'deleted' WHERE id = $1"#,
+                    subscriber_id
+                )
+                .execute(&mut *transaction)
+                .await;
+                if result
+                    .as_ref()
+                    .is_ok_and(|result| result.rows_affected() > 0)
+                {
+                    record_event(&mut transaction, subscriber_id, None, EventKind::Deleted, context)
+                        .await?;
+                }
+                transaction.commit().await?;
+                result
+            }
+        }
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Import subscribers", skip(self, subscribers, context), fields(count = subscribers.len()))]
+    async fn import(
+        &self,
+        subscribers: &[ImportedSubscriber],
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<HashSet<String>> {
+        let now = Utc::now();
+        let mut ids = Vec::with_capacity(subscribers.len());
+        let mut emails = Vec::with_capacity(subscribers.len());
+        let mut names = Vec::with_capacity(subscribers.len());
+        let mut statuses = Vec::with_capacity(subscribers.len());
+        let mut locales = Vec::with_capacity(subscribers.len());
+        let mut confirmed_at = Vec::with_capacity(subscribers.len());
+        let mut consent_sources = Vec::with_capacity(subscribers.len());
+        for subscriber in subscribers {
+            ids.push(Uuid::new_v4());
+            emails.push(subscriber.new_subscriber.email.as_ref().to_owned());
+            names.push(subscriber.new_subscriber.name.as_ref().to_owned());
+            locales.push(subscriber.new_subscriber.locale.clone());
+            match &subscriber.status {
+                ImportedStatus::Confirmed { consent_source } => {
+                    statuses.push("confirmed".to_owned());
+                    confirmed_at.push(Some(now));
+                    consent_sources.push(Some(consent_source.clone()));
+                }
+                ImportedStatus::Pending { .. } => {
+                    statuses.push("pending".to_owned());
+                    confirmed_at.push(None);
+                    consent_sources.push(None);
+                }
+            }
+        }
+
+        let mut transaction = self.pool.begin().await?;
+        let inserted: HashSet<Uuid> = sqlx::query_scalar!(
+            r#"INSERT INTO subscriptions
+                (id, email, name, status, locale, confirmed_at, consent_source, subscribed_at)
+            SELECT *, $8::timestamptz FROM UNNEST(
+                $1::uuid[], $2::text[], $3::text[], $4::text[], $5::text[],
+                $6::timestamptz[], $7::text[]
+            )
+            ON CONFLICT (email) DO NOTHING
+            RETURNING id"#,
+            &ids,
+            &emails,
+            &names,
+            &statuses,
+            &locales,
+            &confirmed_at as &[Option<DateTime<Utc>>],
+            &consent_sources as &[Option<String>],
+            now,
+        )
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?
+        .into_iter()
+        .collect();
+        sqlx::query!(
+            r#"INSERT INTO list_memberships (subscriber_id, list_id, status, subscribed_at, confirmed_at)
+            SELECT id, $2, status, subscribed_at, confirmed_at
+            FROM subscriptions WHERE id = ANY($1)"#,
+            &inserted.iter().copied().collect::<Vec<_>>(),
+            list_id,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+
+        let (token_ids, tokens): (Vec<Uuid>, Vec<String>) = ids
+            .iter()
+            .zip(subscribers)
+            .filter(|(id, _)| inserted.contains(id))
+            .filter_map(|(id, subscriber)| match &subscriber.status {
+                ImportedStatus::Pending { token } => Some((*id, token.clone())),
+                ImportedStatus::Confirmed { .. } => None,
+            })
+            .unzip();
+        sqlx::query!(
+            r#"INSERT INTO subscription_tokens (subscriber_id, token, list_id)
+            SELECT *, $3::uuid FROM UNNEST($1::uuid[], $2::text[])"#,
+            &token_ids,
+            &tokens,
+            list_id,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        let inserted_ids: Vec<Uuid> = inserted.iter().copied().collect();
+        sqlx::query!(
+            r#"INSERT INTO subscription_events
+                (subscriber_id, list_id, kind, occurred_at, ip, user_agent, request_id, source)
+            SELECT id, $8, $2, $3, $4, $5, $6, $7 FROM UNNEST($1::uuid[]) AS id"#,
+            &inserted_ids,
+            EventKind::Imported.as_str(),
+            now,
+            context.ip,
+            context.user_agent,
+            context.request_id,
+            context.source,
+            list_id,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        transaction.commit().await?;
+
+        Ok(ids
+            .iter()
+            .zip(emails)
+            .filter(|(id, _)| inserted.contains(id))
+            .map(|(_, email)| email)
+            .collect())
+    }
+
+    fn export(&self, filter: &SubscriberFilter) -> SubscriberStream {
+        let pool = self.pool.clone();
+        let filter = filter.clone();
+        spawn_export(|sender| async move {
+            let mut transaction = pool.begin().await?;
+            transaction
+                .execute("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+                .await?;
+            let mut rows = sqlx::query_as!(
+                Subscriber,
+                r#"SELECT id, email, name, status, locale, subscribed_at, confirmed_at, consent_source
+                FROM subscriptions
+                WHERE ($1::text IS NULL OR status = $1)
+                    AND ($2::timestamptz IS NULL OR subscribed_at >= $2)
+                    AND ($3::timestamptz IS NULL OR subscribed_at < $3)
+                    AND ($4::text IS NULL
+                        OR starts_with(lower(email), lower($4))
+                        OR starts_with(lower(name), lower($4)))
+                ORDER BY subscribed_at, id"#,
+                filter.status,
+                filter.subscribed_after,
+                filter.subscribed_before,
+                filter.search,
+            )
+            .fetch(&mut *transaction);
+            while let Some(subscriber) = rows.try_next().await? {
+                if sender.send(Ok(subscriber)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    #[tracing::instrument(name = "Get subscriber profile", skip(self))]
+    async fn profile(&self, subscriber_id: Uuid) -> eyre::Result<SubscriberProfile> {
+        let attributes = sqlx::query_scalar!(
+            r#"SELECT attributes AS "attributes: Json<BTreeMap<String, String>>"
+            FROM subscriptions WHERE id = $1"#,
+            subscriber_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        let tags = sqlx::query_scalar!(
+            r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1"#,
+            subscriber_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(SubscriberProfile {
+            tags: tags.into_iter().collect(),
+            attributes: attributes.map(|attributes| attributes.0).unwrap_or_default(),
+        })
+    }
+
+    #[tracing::instrument(name = "Get paused delivery", skip(self))]
+    async fn paused_until(&self, subscriber_id: Uuid) -> eyre::Result<Option<DateTime<Utc>>> {
+        let paused_until = sqlx::query_scalar!(
+            r#"SELECT paused_until FROM subscriptions WHERE id = $1"#,
+            subscriber_id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(paused_until.flatten())
+    }
+
+    #[tracing::instrument(name = "Unsubscribe subscriber", skip(self, context))]
+    async fn unsubscribe(&self, subscriber_id: Uuid, context: &EventContext) -> eyre::Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        let result = sqlx::query!(
+            r#"UPDATE subscriptions SET status = 'unsubscribed'
+            WHERE id = $1 AND status IN ('pending', 'confirmed')"#,
+            subscriber_id,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query!(
+            r#"UPDATE list_memberships SET status = 'unsubscribed' WHERE subscriber_id = $1"#,
+            subscriber_id,
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+            subscriber_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        record_event(&mut transaction, subscriber_id, None, EventKind::Unsubscribed, context).await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "Change subscriber email", skip(self, old_email, new_email, suppression_hash, context))]
+    async fn change_email(
+        &self,
+        subscriber_id: Uuid,
+        old_email: &str,
+        new_email: &str,
+        suppression_hash: &str,
+        context: &EventContext,
+    ) -> eyre::Result<EmailChange> {
+        let mut transaction = self.pool.begin().await?;
+        let suppressed = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM suppressions WHERE email_hash = $1) AS "suppressed!""#,
+            suppression_hash,
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        if suppressed {
+            return Ok(EmailChange::Suppressed);
+        }
+        // The unique constraint on the address settles races with signups.
+        let result = sqlx::query!(
+            r#"UPDATE subscriptions SET email = $3 WHERE id = $1 AND email = $2"#,
+            subscriber_id,
+            old_email,
+            new_email,
+        )
+        .execute(&mut *transaction)
+        .await;
+        match result {
+            Ok(result) if result.rows_affected() == 0 => return Ok(EmailChange::Stale),
+            Ok(_) => {}
+            Err(e) if e.as_database_error().is_some_and(|e| e.is_unique_violation()) => {
+                return Ok(EmailChange::Taken)
+            }
+            Err(e) => {
+                tracing::error!("Failed to execute query: {:?}", e);
+                return Err(e.into());
+            }
+        }
+        record_event(&mut transaction, subscriber_id, None, EventKind::EmailChanged, context).await?;
+        transaction.commit().await?;
+        Ok(EmailChange::Changed)
+    }
+
+    #[tracing::instrument(name = "Reissue confirmation tokens", skip(self, tokens))]
+    async fn reissue_tokens(
+        &self,
+        subscriber_id: Uuid,
+        tokens: &[(Uuid, String)],
+        issued_after: DateTime<Utc>,
+    ) -> eyre::Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        // Locks the subscriber, so concurrent requests take turns at the check.
+        sqlx::query!(
+            r#"SELECT id FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+            subscriber_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        let recent = sqlx::query_scalar!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM subscription_tokens WHERE subscriber_id = $1 AND created_at > $2
+            ) AS "recent!""#,
+            subscriber_id,
+            issued_after,
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        if recent {
+            return Ok(false);
+        }
+        for (list_id, token) in tokens {
+            sqlx::query!(
+                r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1 AND list_id = $2"#,
+                subscriber_id,
+                list_id,
+            )
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query!(
+                r#"INSERT INTO subscription_tokens (token, subscriber_id, list_id)
+                VALUES ($1, $2, $3)"#,
+                token,
+                subscriber_id,
+                list_id,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(true)
+    }
+}
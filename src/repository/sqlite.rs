@@ -0,0 +1,519 @@
+//! The same queries as the Postgres backend, checked at runtime: the
+//! `query!` macros can only check against one database at compile time,
+//! and that is Postgres.
+mod audit;
+mod feeds;
+mod issues;
+mod lists;
+mod subscribers;
+
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::{types::Json, QueryBuilder, Sqlite, SqliteConnection, SqlitePool, Transaction};
+use uuid::Uuid;
+
+use super::{ProfileUpdate, SubscriberTransaction};
+use crate::audit::{EventContext, EventKind};
+use crate::domain::NewSubscriber;
+
+/// Timestamps are stored as text, so they are written with a fixed number
+/// of fractional digits to keep comparisons and ordering chronological.
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Appends to the subscriber's audit trail; `list_id` is for events about
+/// one list.
+async fn record_event(
+    connection: &mut SqliteConnection,
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+    kind: EventKind,
+    context: &EventContext,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"INSERT INTO subscription_events
+            (subscriber_id, list_id, kind, occurred_at, ip, user_agent, request_id, source)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+    )
+    .bind(subscriber_id)
+    .bind(list_id)
+    .bind(kind.as_str())
+    .bind(timestamp(Utc::now()))
+    .bind(&context.ip)
+    .bind(&context.user_agent)
+    .bind(&context.request_id)
+    .bind(&context.source)
+    .execute(connection)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct SqliteSubscriberRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteSubscriberRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+pub struct SqliteSubscriberTransaction {
+    transaction: Transaction<'static, Sqlite>,
+}
+
+
+#[async_trait]
+impl SubscriberTransaction for SqliteSubscriberTransaction {
+    #[tracing::instrument(
+        name = "Saving new subscriber details in the database",
+        skip(self, new_subscriber)
+    )]
+    async fn insert_subscriber(&mut self, new_subscriber: &NewSubscriber) -> eyre::Result<Uuid> {
+        let subscriber_id = Uuid::new_v4();
+        sqlx::query(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, locale)
+            VALUES ($1, $2, $3, $4, 'pending', $5)
+        "#,
+        )
+        .bind(subscriber_id)
+        .bind(new_subscriber.email.as_ref())
+        .bind(new_subscriber.name.as_ref())
+        .bind(timestamp(Utc::now()))
+        .bind(&new_subscriber.locale)
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        tracing::info!("New subscriber {} saved", new_subscriber.email.as_ref());
+        Ok(subscriber_id)
+    }
+
+    #[tracing::instrument(name = "Reactivate subscriber", skip(self, new_subscriber))]
+    async fn reactivate_subscriber(
+        &mut self,
+        subscriber_id: Uuid,
+        new_subscriber: &NewSubscriber,
+    ) -> eyre::Result<()> {
+        let result = sqlx::query(
+            r#"UPDATE subscriptions
+            SET name = $2, locale = $3, status = 'pending', subscribed_at = $4, confirmed_at = NULL
+            WHERE id = $1 AND status IN ('deleted', 'unsubscribed')"#,
+        )
+        .bind(subscriber_id)
+        .bind(new_subscriber.name.as_ref())
+        .bind(&new_subscriber.locale)
+        .bind(timestamp(Utc::now()))
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(eyre::eyre!("Subscriber {} is not deleted or unsubscribed", subscriber_id));
+        }
+        sqlx::query(r#"UPDATE list_memberships SET status = 'unsubscribed' WHERE subscriber_id = $1"#)
+            .bind(subscriber_id)
+            .execute(&mut *self.transaction)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Join mailing list", skip(self, context))]
+    async fn join_list(
+        &mut self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let result = sqlx::query(
+            r#"INSERT INTO list_memberships (subscriber_id, list_id, status, subscribed_at)
+            VALUES ($1, $2, 'pending', $3)
+            ON CONFLICT (subscriber_id, list_id) DO UPDATE
+            SET status = 'pending', subscribed_at = excluded.subscribed_at, confirmed_at = NULL
+            WHERE list_memberships.status <> 'confirmed'"#,
+        )
+        .bind(subscriber_id)
+        .bind(list_id)
+        .bind(timestamp(Utc::now()))
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        record_event(
+            &mut self.transaction,
+            subscriber_id,
+            Some(list_id),
+            EventKind::Subscribed,
+            context,
+        )
+        .await?;
+        Ok(true)
+    }
+
+    /// Removals are sent as `null`s, which `json_patch` drops.
+    #[tracing::instrument(name = "Update subscriber profile", skip(self))]
+    async fn update_profile(
+        &mut self,
+        subscriber_id: Uuid,
+        update: &ProfileUpdate,
+    ) -> eyre::Result<()> {
+        sqlx::query(r#"UPDATE subscriptions SET attributes = json_patch(attributes, $2) WHERE id = $1"#)
+            .bind(subscriber_id)
+            .bind(Json(&update.attributes))
+            .execute(&mut *self.transaction)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        if !update.add_tags.is_empty() {
+            let mut insert = QueryBuilder::<Sqlite>::new("INSERT OR IGNORE INTO subscriber_tags (subscriber_id, tag) ");
+            insert.push_values(&update.add_tags, |mut row, tag| {
+                row.push_bind(subscriber_id).push_bind(tag);
+            });
+            insert.build().execute(&mut *self.transaction).await?;
+        }
+        if !update.remove_tags.is_empty() {
+            let mut delete = QueryBuilder::<Sqlite>::new("DELETE FROM subscriber_tags WHERE subscriber_id = ");
+            delete.push_bind(subscriber_id);
+            delete.push(" AND tag IN (");
+            let mut tags = delete.separated(", ");
+            for tag in &update.remove_tags {
+                tags.push_bind(tag);
+            }
+            delete.push(")");
+            delete.build().execute(&mut *self.transaction).await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Store subscription token", skip(self, token))]
+    async fn store_token(
+        &mut self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        token: &str,
+    ) -> eyre::Result<()> {
+        sqlx::query(
+            r#"INSERT INTO subscription_tokens (token, subscriber_id, list_id, created_at)
+            VALUES ($1, $2, $3, $4)"#,
+        )
+        .bind(token)
+        .bind(subscriber_id)
+        .bind(list_id)
+        .bind(timestamp(Utc::now()))
+        .execute(&mut *self.transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    async fn commit(self: Box<Self>) -> eyre::Result<()> {
+        self.transaction.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use chrono::{Duration, Utc};
+    use claim::{assert_none, assert_ok, assert_some};
+    use sqlx::SqlitePool;
+
+    use crate::audit::EventContext;
+    use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+    use crate::repository::{
+        AuditRepository, DeliveryOutcome, FeedRepository, IssueContent, IssueRepository, ListRepository, NewFeedEntry,
+        ProfileUpdate, SqliteSubscriberRepository, SubscriberRepository, TrackedAction, DEFAULT_LIST,
+    };
+    use crate::segment::Segment;
+
+    async fn repository() -> SqliteSubscriberRepository {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::migrate!("./migrations_sqlite").run(&pool).await.unwrap();
+        SqliteSubscriberRepository::new(pool)
+    }
+
+    #[tokio::test]
+    async fn subscribers_can_be_stored_looked_up_and_confirmed() {
+        let repository = repository().await;
+        let list = assert_some!(repository.find_list(DEFAULT_LIST).await.unwrap());
+        let mut transaction = repository.begin().await.unwrap();
+        let context = EventContext {
+            ip: Some("192.0.2.1".into()),
+            ..Default::default()
+        };
+        let id = transaction
+            .insert_subscriber(&NewSubscriber {
+                email: SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+                name: SubscriberName::parse("Ursula".into()).unwrap(),
+                locale: "de".into(),
+            })
+            .await
+            .unwrap();
+        transaction.join_list(id, list.id, &context).await.unwrap();
+        transaction.store_token(id, list.id, "token").await.unwrap();
+        transaction.commit().await.unwrap();
+
+        let token = assert_some!(repository.find_token("token").await.unwrap());
+        assert_eq!(token.subscriber_id, id);
+        assert_eq!(token.list_id, list.id);
+        assert_eq!(token.locale, "de");
+        assert_eq!(token.status, "pending");
+        assert_none!(repository.find_token("other").await.unwrap());
+
+        repository.confirm(id, list.id, &context).await.unwrap();
+        let subscriber = assert_some!(repository.find_by_email("ursula@example.com").await.unwrap());
+        assert_eq!(subscriber.status, "confirmed");
+        let memberships = repository.memberships(id).await.unwrap();
+        assert_eq!(memberships.len(), 1);
+        assert_eq!(memberships[0].list, DEFAULT_LIST);
+        assert_eq!(memberships[0].status, "confirmed");
+
+        let events = repository.events(id).await.unwrap();
+        let kinds: Vec<_> = events.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(kinds, ["subscribed", "confirmed"]);
+        assert_eq!(events[0].ip.as_deref(), Some("192.0.2.1"));
+        assert_eq!(events[0].list.as_deref(), Some(DEFAULT_LIST));
+
+        let mut transaction = repository.begin().await.unwrap();
+        let update = ProfileUpdate {
+            attributes: [("country".to_owned(), Some("de".to_owned())), ("plan".to_owned(), Some("pro".to_owned()))].into(),
+            add_tags: ["rust".to_owned(), "go".to_owned()].into(),
+            ..Default::default()
+        };
+        transaction.update_profile(id, &update).await.unwrap();
+        let update = ProfileUpdate {
+            attributes: [("plan".to_owned(), None)].into(),
+            remove_tags: ["go".to_owned()].into(),
+            ..Default::default()
+        };
+        transaction.update_profile(id, &update).await.unwrap();
+        transaction.commit().await.unwrap();
+        let profile = repository.profile(id).await.unwrap();
+        assert_eq!(profile.attributes, [("country".to_owned(), "de".to_owned())].into());
+        assert_eq!(profile.tags, ["rust".to_owned()].into());
+
+        let keys = ["country".to_owned()];
+        for (segment, expected) in [("tag:rust AND country:de", 1), ("tag:go OR NOT country:de", 0)] {
+            let segment = Segment::parse(segment, &keys).unwrap();
+            let recipients = repository.recipients(list.id, Some(&segment)).await.unwrap();
+            assert_eq!(recipients.len(), expected, "{:?}", segment);
+        }
+    }
+
+    #[tokio::test]
+    async fn due_issues_are_claimed_once_and_in_order() {
+        let repository = repository().await;
+        let list = assert_some!(repository.find_list(DEFAULT_LIST).await.unwrap());
+        let content = |title: &str| IssueContent {
+            list_id: list.id,
+            title: title.into(),
+            html_content: "<p>Hello</p>".into(),
+            text_content: "Hello".into(),
+            segment: None,
+            tracking: false,
+            public: false,
+        };
+        let now = Utc::now();
+        let later = assert_ok!(repository.create_issue(&content("Later")).await);
+        let first = assert_ok!(repository.create_issue(&content("First")).await);
+        let draft = assert_ok!(repository.create_issue(&content("Draft")).await);
+        assert_eq!(draft.status, "draft");
+        repository.schedule_issue(later.id, Some(now + Duration::hours(2))).await.unwrap();
+        repository.schedule_issue(first.id, Some(now + Duration::hours(1))).await.unwrap();
+
+        assert_none!(repository.claim_due_issue(now).await.unwrap());
+        let claimed = assert_some!(repository.claim_due_issue(now + Duration::days(1)).await.unwrap());
+        assert_eq!((claimed.id, claimed.status.as_str()), (first.id, "sending"));
+        assert_none!(repository.update_issue(first.id, &content("Too late")).await.unwrap());
+        let claimed = assert_some!(repository.claim_due_issue(now + Duration::days(1)).await.unwrap());
+        assert_eq!(claimed.id, later.id);
+        assert_none!(repository.claim_due_issue(now + Duration::days(1)).await.unwrap());
+
+        repository.finish_issue(first.id, 3).await.unwrap();
+        repository.release_issue(later.id).await.unwrap();
+        let sent = assert_some!(repository.find_issue(first.id).await.unwrap());
+        assert_eq!((sent.status.as_str(), sent.recipient_count), ("sent", Some(3)));
+        assert!(!repository.delete_issue(first.id).await.unwrap());
+        let released = assert_some!(repository.find_issue(later.id).await.unwrap());
+        assert_eq!((released.status.as_str(), released.send_at), ("draft", None));
+        let drafts: Vec<_> = repository
+            .issues(Some("draft"))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|issue| issue.title)
+            .collect();
+        assert_eq!(drafts, ["Draft", "Later"]);
+    }
+
+    #[tokio::test]
+    async fn deliveries_count_attempts_and_keep_the_last_error() {
+        let repository = repository().await;
+        let list = assert_some!(repository.find_list(DEFAULT_LIST).await.unwrap());
+        let issue = assert_ok!(
+            repository
+                .create_issue(&IssueContent {
+                    list_id: list.id,
+                    title: "Issue #1".into(),
+                    html_content: "<p>Hello</p>".into(),
+                    text_content: "Hello".into(),
+                    segment: None,
+                    tracking: false,
+                    public: false,
+                })
+                .await
+        );
+        let mut transaction = repository.begin().await.unwrap();
+        let mut subscribe = async |email: &str| {
+            let id = transaction
+                .insert_subscriber(&NewSubscriber {
+                    email: SubscriberEmail::parse(email.into()).unwrap(),
+                    name: SubscriberName::parse("Ursula".into()).unwrap(),
+                    locale: "en".into(),
+                })
+                .await
+                .unwrap();
+            transaction.join_list(id, list.id, &EventContext::default()).await.unwrap();
+            id
+        };
+        let ursula = subscribe("ursula@example.com").await;
+        let octavia = subscribe("octavia@example.com").await;
+        transaction.commit().await.unwrap();
+
+        repository.queue_deliveries(issue.id, &[ursula, octavia]).await.unwrap();
+        let failed = DeliveryOutcome::Failed { error: "timed out".into() };
+        repository.record_delivery(issue.id, ursula, &failed).await.unwrap();
+        repository.queue_deliveries(issue.id, &[ursula]).await.unwrap();
+        let sent = DeliveryOutcome::Sent { message_id: Some("message-1".into()) };
+        repository.record_delivery(issue.id, ursula, &sent).await.unwrap();
+        repository.record_delivery(issue.id, octavia, &failed).await.unwrap();
+
+        let counts = repository.delivery_counts(issue.id).await.unwrap();
+        assert_eq!((counts.queued, counts.sent, counts.failed), (0, 1, 1));
+        let failures = repository.failed_deliveries(issue.id, 10).await.unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].email, "octavia@example.com");
+        assert_eq!(failures[0].last_error.as_deref(), Some("timed out"));
+    }
+
+    #[tokio::test]
+    async fn tracked_actions_are_summarised_unless_the_subscriber_opted_out() {
+        let repository = repository().await;
+        let list = assert_some!(repository.find_list(DEFAULT_LIST).await.unwrap());
+        let issue = assert_ok!(
+            repository
+                .create_issue(&IssueContent {
+                    list_id: list.id,
+                    title: "Issue #1".into(),
+                    html_content: "<p>Hello</p>".into(),
+                    text_content: "Hello".into(),
+                    segment: None,
+                    tracking: true,
+                    public: false,
+                })
+                .await
+        );
+        let mut transaction = repository.begin().await.unwrap();
+        let mut subscribe = async |email: &str| {
+            let id = transaction
+                .insert_subscriber(&NewSubscriber {
+                    email: SubscriberEmail::parse(email.into()).unwrap(),
+                    name: SubscriberName::parse("Ursula".into()).unwrap(),
+                    locale: "en".into(),
+                })
+                .await
+                .unwrap();
+            transaction.join_list(id, list.id, &EventContext::default()).await.unwrap();
+            id
+        };
+        let ursula = subscribe("ursula@example.com").await;
+        let octavia = subscribe("octavia@example.com").await;
+        transaction.commit().await.unwrap();
+
+        repository.queue_deliveries(issue.id, &[ursula, octavia]).await.unwrap();
+        assert!(repository.set_tracking_opt_out(octavia, true).await.unwrap());
+        let click = |url: &str| TrackedAction::Clicked { url: url.into() };
+        let now = Utc::now();
+        for (subscriber_id, action) in [
+            (ursula, TrackedAction::Opened),
+            (ursula, TrackedAction::Opened),
+            (ursula, click("https://example.com/a")),
+            (ursula, click("https://example.com/a")),
+            (ursula, click("https://example.com/b")),
+        ] {
+            assert!(repository.record_tracked_action(issue.id, subscriber_id, &action, now).await.unwrap());
+        }
+        let recorded = repository
+            .record_tracked_action(issue.id, octavia, &TrackedAction::Opened, now)
+            .await
+            .unwrap();
+
+        assert!(!recorded);
+        assert_eq!(repository.untracked_recipients(issue.id).await.unwrap(), HashSet::from([octavia]));
+        let engagement = repository.engagement(issue.id).await.unwrap();
+        assert_eq!(
+            (engagement.opens, engagement.unique_opens, engagement.clicks, engagement.unique_clicks),
+            (2, 1, 3, 1)
+        );
+        let links: Vec<_> = engagement.links.iter().map(|link| (link.url.as_str(), link.clicks)).collect();
+        assert_eq!(links, [("https://example.com/a", 2), ("https://example.com/b", 1)]);
+    }
+
+    #[tokio::test]
+    async fn feed_entries_are_stored_once_and_claimed_once() {
+        let repository = repository().await;
+        let entry = |guid: &str| NewFeedEntry {
+            guid: guid.into(),
+            title: format!("Post {}", guid),
+            link: Some(format!("https://blog.example.com/{}", guid)),
+            summary: None,
+            published_at: None,
+        };
+        let feed = "https://blog.example.com/feed.xml";
+        let now = Utc::now();
+
+        // The back catalogue of a new feed counts as digested already.
+        assert_eq!(repository.store_feed_entries(feed, &[entry("1"), entry("2")], now).await.unwrap(), 2);
+        assert!(repository.claim_feed_entries(now).await.unwrap().is_empty());
+
+        let stored = repository
+            .store_feed_entries(feed, &[entry("2"), entry("3"), entry("3")], now)
+            .await
+            .unwrap();
+        assert_eq!(stored, 1);
+        let claimed = repository.claim_feed_entries(now).await.unwrap();
+        let guids: Vec<_> = claimed.iter().map(|entry| entry.guid.as_str()).collect();
+        assert_eq!(guids, ["3"]);
+        assert!(repository.claim_feed_entries(now).await.unwrap().is_empty());
+
+        repository.release_feed_entries(&[claimed[0].id]).await.unwrap();
+        assert_eq!(repository.claim_feed_entries(now).await.unwrap(), claimed);
+    }
+}
@@ -0,0 +1,102 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{QueryBuilder, Sqlite};
+use uuid::Uuid;
+
+use super::{timestamp, SqliteSubscriberRepository};
+use crate::repository::{FeedEntry, FeedRepository, NewFeedEntry};
+
+/// Feed entries stored per statement, for the same reason.
+const FEED_ENTRY_BATCH_SIZE: usize = 500;
+
+#[async_trait]
+impl FeedRepository for SqliteSubscriberRepository {
+    #[tracing::instrument(name = "Store feed entries", skip(self, entries))]
+    async fn store_feed_entries(
+        &self,
+        feed_url: &str,
+        entries: &[NewFeedEntry],
+        fetched_at: DateTime<Utc>,
+    ) -> eyre::Result<u64> {
+        let fetched_at = timestamp(fetched_at);
+        let mut transaction = self.pool.begin().await?;
+        let known: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM feed_entries WHERE feed_url = $1)")
+            .bind(feed_url)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        let digested_at = (!known).then(|| fetched_at.clone());
+        let mut stored = 0;
+        for batch in entries.chunks(FEED_ENTRY_BATCH_SIZE) {
+            let mut insert = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO feed_entries \
+                (id, feed_url, guid, title, link, summary, published_at, fetched_at, digested_at) ",
+            );
+            insert.push_values(batch, |mut row, entry| {
+                row.push_bind(Uuid::new_v4())
+                    .push_bind(feed_url)
+                    .push_bind(&entry.guid)
+                    .push_bind(&entry.title)
+                    .push_bind(&entry.link)
+                    .push_bind(&entry.summary)
+                    .push_bind(entry.published_at.map(timestamp))
+                    .push_bind(fetched_at.clone())
+                    .push_bind(digested_at.clone());
+            });
+            insert.push(" ON CONFLICT (feed_url, guid) DO NOTHING");
+            stored += insert
+                .build()
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to execute query: {:?}", e);
+                    e
+                })?
+                .rows_affected();
+        }
+        transaction.commit().await?;
+        Ok(stored)
+    }
+
+    #[tracing::instrument(name = "Claim feed entries", skip(self))]
+    async fn claim_feed_entries(&self, now: DateTime<Utc>) -> eyre::Result<Vec<FeedEntry>> {
+        let entries = sqlx::query_as::<_, FeedEntry>(
+            r#"UPDATE feed_entries SET digested_at = $1
+            WHERE digested_at IS NULL
+            RETURNING id, feed_url, guid, title, link, summary, published_at, fetched_at"#,
+        )
+        .bind(timestamp(now))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(entries)
+    }
+
+    #[tracing::instrument(name = "Release feed entries", skip(self))]
+    async fn release_feed_entries(&self, entry_ids: &[Uuid]) -> eyre::Result<()> {
+        if entry_ids.is_empty() {
+            return Ok(());
+        }
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE feed_entries SET digested_at = NULL WHERE id IN (");
+        let mut ids = query.separated(", ");
+        for entry_id in entry_ids {
+            ids.push_bind(*entry_id);
+        }
+        query.push(")");
+        query
+            .build()
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        Ok(())
+    }
+}
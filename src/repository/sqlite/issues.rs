@@ -0,0 +1,538 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use eyre::eyre;
+use sqlx::{QueryBuilder, Sqlite};
+use uuid::Uuid;
+
+use super::{timestamp, SqliteSubscriberRepository};
+use crate::repository::{
+    issue_slug, Delivery, DeliveryCounts, DeliveryOutcome, Engagement, IssueContent,
+    IssueRepository, LinkClicks, NewsletterIssue, ReceivedIssue, Subscriber, TrackedAction,
+    TrackingEvent,
+};
+
+/// Deliveries queued per statement, well within SQLite's limit on
+/// parameters.
+const DELIVERY_BATCH_SIZE: usize = 1000;
+
+const ISSUE_COLUMNS: &str = r#"SELECT i.id, i.list_id, l.slug AS list, i.title, i.html_content,
+    i.text_content, i.segment, i.tracking, i.public, i.slug, i.status, i.send_at, i.created_at, i.updated_at, i.sent_at,
+    i.recipient_count
+FROM newsletter_issues i JOIN lists l ON l.id = i.list_id"#;
+
+#[async_trait]
+impl IssueRepository for SqliteSubscriberRepository {
+    #[tracing::instrument(name = "List newsletter issues", skip(self))]
+    async fn issues(&self, status: Option<&str>) -> eyre::Result<Vec<NewsletterIssue>> {
+        let issues = sqlx::query_as::<_, NewsletterIssue>(&format!(
+            "{} WHERE $1 IS NULL OR i.status = $1 ORDER BY i.created_at DESC, i.id",
+            ISSUE_COLUMNS
+        ))
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issues)
+    }
+
+    #[tracing::instrument(name = "Find newsletter issue", skip(self))]
+    async fn find_issue(&self, issue_id: Uuid) -> eyre::Result<Option<NewsletterIssue>> {
+        let issue = sqlx::query_as::<_, NewsletterIssue>(&format!("{} WHERE i.id = $1", ISSUE_COLUMNS))
+            .bind(issue_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        Ok(issue)
+    }
+
+    #[tracing::instrument(name = "Create newsletter issue", skip(self, content))]
+    async fn create_issue(&self, content: &IssueContent) -> eyre::Result<NewsletterIssue> {
+        let issue_id = Uuid::new_v4();
+        let now = timestamp(Utc::now());
+        sqlx::query(
+            r#"INSERT INTO newsletter_issues (id, list_id, title, html_content, text_content,
+                segment, tracking, public, slug, status, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'draft', $10, $10)"#,
+        )
+        .bind(issue_id)
+        .bind(content.list_id)
+        .bind(&content.title)
+        .bind(&content.html_content)
+        .bind(&content.text_content)
+        .bind(&content.segment)
+        .bind(content.tracking)
+        .bind(content.public)
+        .bind(issue_slug(&content.title, issue_id))
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        self.find_issue(issue_id)
+            .await?
+            .ok_or_else(|| eyre!("Newsletter issue {} vanished after being created", issue_id))
+    }
+
+    /// SQLite's `RETURNING` cannot reach the list's slug, so the updates
+    /// here return the id and the issue is read again.
+    #[tracing::instrument(name = "Update newsletter issue", skip(self, content))]
+    async fn update_issue(
+        &self,
+        issue_id: Uuid,
+        content: &IssueContent,
+    ) -> eyre::Result<Option<NewsletterIssue>> {
+        let updated = sqlx::query_scalar::<_, Uuid>(
+            r#"UPDATE newsletter_issues SET list_id = $2, title = $3, html_content = $4,
+                text_content = $5, segment = $6, tracking = $7, public = $8, slug = $9, updated_at = $10
+            WHERE id = $1 AND status IN ('draft', 'scheduled')
+            RETURNING id"#,
+        )
+        .bind(issue_id)
+        .bind(content.list_id)
+        .bind(&content.title)
+        .bind(&content.html_content)
+        .bind(&content.text_content)
+        .bind(&content.segment)
+        .bind(content.tracking)
+        .bind(content.public)
+        .bind(issue_slug(&content.title, issue_id))
+        .bind(timestamp(Utc::now()))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        match updated {
+            Some(issue_id) => self.find_issue(issue_id).await,
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(name = "Schedule newsletter issue", skip(self))]
+    async fn schedule_issue(
+        &self,
+        issue_id: Uuid,
+        send_at: Option<DateTime<Utc>>,
+    ) -> eyre::Result<Option<NewsletterIssue>> {
+        let updated = sqlx::query_scalar::<_, Uuid>(
+            r#"UPDATE newsletter_issues
+            SET status = CASE WHEN $2 IS NULL THEN 'draft' ELSE 'scheduled' END,
+                send_at = $2, updated_at = $3
+            WHERE id = $1 AND status IN ('draft', 'scheduled')
+            RETURNING id"#,
+        )
+        .bind(issue_id)
+        .bind(send_at.map(timestamp))
+        .bind(timestamp(Utc::now()))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        match updated {
+            Some(issue_id) => self.find_issue(issue_id).await,
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(name = "Delete newsletter issue", skip(self))]
+    async fn delete_issue(&self, issue_id: Uuid) -> eyre::Result<bool> {
+        let result = sqlx::query(
+            r#"DELETE FROM newsletter_issues WHERE id = $1 AND status IN ('draft', 'scheduled')"#,
+        )
+        .bind(issue_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// SQLite runs one write at a time, so a single `UPDATE` claims the
+    /// issue atomically.
+    #[tracing::instrument(name = "Claim due newsletter issue", skip(self))]
+    async fn claim_due_issue(&self, now: DateTime<Utc>) -> eyre::Result<Option<NewsletterIssue>> {
+        let claimed = sqlx::query_scalar::<_, Uuid>(
+            r#"UPDATE newsletter_issues SET status = 'sending', updated_at = $1
+            WHERE status = 'scheduled' AND id = (
+                SELECT id FROM newsletter_issues
+                WHERE status = 'scheduled' AND send_at <= $1
+                ORDER BY send_at, created_at
+                LIMIT 1
+            )
+            RETURNING id"#,
+        )
+        .bind(timestamp(now))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        match claimed {
+            Some(issue_id) => self.find_issue(issue_id).await,
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(name = "Claim stalled newsletter issue", skip(self))]
+    async fn claim_stalled_issue(
+        &self,
+        now: DateTime<Utc>,
+        stalled_before: DateTime<Utc>,
+    ) -> eyre::Result<Option<NewsletterIssue>> {
+        let claimed = sqlx::query_scalar::<_, Uuid>(
+            r#"UPDATE newsletter_issues SET updated_at = $1
+            WHERE status = 'sending' AND id = (
+                SELECT n.id FROM newsletter_issues n
+                WHERE n.status = 'sending' AND n.updated_at <= $2 AND NOT EXISTS (
+                    SELECT 1 FROM deliveries d WHERE d.issue_id = n.id AND d.updated_at > $2
+                )
+                ORDER BY n.updated_at, n.created_at
+                LIMIT 1
+            )
+            RETURNING id"#,
+        )
+        .bind(timestamp(now))
+        .bind(timestamp(stalled_before))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        match claimed {
+            Some(issue_id) => self.find_issue(issue_id).await,
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(name = "Finish newsletter issue", skip(self))]
+    async fn finish_issue(&self, issue_id: Uuid, recipient_count: i64) -> eyre::Result<()> {
+        sqlx::query(
+            r#"UPDATE newsletter_issues
+            SET status = 'sent', sent_at = $2, updated_at = $2, recipient_count = $3
+            WHERE id = $1 AND status = 'sending'"#,
+        )
+        .bind(issue_id)
+        .bind(timestamp(Utc::now()))
+        .bind(recipient_count)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Release newsletter issue", skip(self))]
+    async fn release_issue(&self, issue_id: Uuid) -> eyre::Result<()> {
+        sqlx::query(
+            r#"UPDATE newsletter_issues SET status = 'draft', send_at = NULL, updated_at = $2
+            WHERE id = $1 AND status = 'sending'"#,
+        )
+        .bind(issue_id)
+        .bind(timestamp(Utc::now()))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Queue deliveries", skip(self, subscriber_ids), fields(count = subscriber_ids.len()))]
+    async fn queue_deliveries(&self, issue_id: Uuid, subscriber_ids: &[Uuid]) -> eyre::Result<()> {
+        let now = timestamp(Utc::now());
+        let mut transaction = self.pool.begin().await?;
+        for batch in subscriber_ids.chunks(DELIVERY_BATCH_SIZE) {
+            let mut insert = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO deliveries (issue_id, subscriber_id, status, created_at, updated_at) ",
+            );
+            insert.push_values(batch, |mut row, subscriber_id| {
+                row.push_bind(issue_id)
+                    .push_bind(*subscriber_id)
+                    .push_bind("queued")
+                    .push_bind(now.clone())
+                    .push_bind(now.clone());
+            });
+            insert.push(" ON CONFLICT (issue_id, subscriber_id) DO NOTHING");
+            insert
+                .build()
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to execute query: {:?}", e);
+                    e
+                })?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Find queued recipients", skip(self))]
+    async fn queued_recipients(&self, issue_id: Uuid) -> eyre::Result<Vec<Subscriber>> {
+        let recipients = sqlx::query_as::<_, Subscriber>(
+            r#"SELECT s.id, s.email, s.name, s.status, s.locale, s.subscribed_at, s.confirmed_at, s.consent_source
+            FROM subscriptions s JOIN deliveries d ON d.subscriber_id = s.id
+            WHERE d.issue_id = $1 AND d.status = 'queued'
+            ORDER BY s.subscribed_at, s.id"#,
+        )
+        .bind(issue_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(recipients)
+    }
+
+    #[tracing::instrument(name = "Record delivery", skip(self))]
+    async fn record_delivery(
+        &self,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        outcome: &DeliveryOutcome,
+    ) -> eyre::Result<()> {
+        let (status, message_id, error) = match outcome {
+            DeliveryOutcome::Sent { message_id } => ("sent", message_id.as_deref(), None),
+            DeliveryOutcome::Failed { error } => ("failed", None, Some(error.as_str())),
+        };
+        sqlx::query(
+            r#"UPDATE deliveries SET status = $3, message_id = $4,
+                last_error = COALESCE($5, last_error), attempts = attempts + 1, updated_at = $6
+            WHERE issue_id = $1 AND subscriber_id = $2"#,
+        )
+        .bind(issue_id)
+        .bind(subscriber_id)
+        .bind(status)
+        .bind(message_id)
+        .bind(error)
+        .bind(timestamp(Utc::now()))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Count deliveries", skip(self))]
+    async fn delivery_counts(&self, issue_id: Uuid) -> eyre::Result<DeliveryCounts> {
+        let counts = sqlx::query_as::<_, (String, i64)>(
+            r#"SELECT status, COUNT(*) FROM deliveries WHERE issue_id = $1 GROUP BY status"#,
+        )
+        .bind(issue_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(counts.into_iter().collect())
+    }
+
+    #[tracing::instrument(name = "List failed deliveries", skip(self))]
+    async fn failed_deliveries(&self, issue_id: Uuid, limit: i64) -> eyre::Result<Vec<Delivery>> {
+        let deliveries = sqlx::query_as::<_, Delivery>(
+            r#"SELECT d.subscriber_id, s.email, d.status, d.message_id, d.attempts, d.last_error,
+                d.updated_at
+            FROM deliveries d JOIN subscriptions s ON s.id = d.subscriber_id
+            WHERE d.issue_id = $1 AND d.status IN ('failed', 'bounced')
+            ORDER BY d.updated_at DESC, d.subscriber_id
+            LIMIT $2"#,
+        )
+        .bind(issue_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(deliveries)
+    }
+
+    #[tracing::instrument(name = "List a subscriber's received issues", skip(self))]
+    async fn received_issues(&self, subscriber_id: Uuid) -> eyre::Result<Vec<ReceivedIssue>> {
+        let issues = sqlx::query_as::<_, ReceivedIssue>(
+            r#"SELECT d.issue_id, i.title, d.status, d.updated_at
+            FROM deliveries d JOIN newsletter_issues i ON i.id = d.issue_id
+            WHERE d.subscriber_id = $1
+            ORDER BY d.created_at, d.issue_id"#,
+        )
+        .bind(subscriber_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issues)
+    }
+
+    #[tracing::instrument(name = "Set tracking opt-out", skip(self))]
+    async fn set_tracking_opt_out(&self, subscriber_id: Uuid, opt_out: bool) -> eyre::Result<bool> {
+        let result = sqlx::query(r#"UPDATE subscriptions SET tracking_opt_out = $2 WHERE id = $1"#)
+            .bind(subscriber_id)
+            .bind(opt_out)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "List untracked recipients", skip(self))]
+    async fn untracked_recipients(&self, issue_id: Uuid) -> eyre::Result<HashSet<Uuid>> {
+        let subscriber_ids = sqlx::query_scalar::<_, Uuid>(
+            r#"SELECT d.subscriber_id FROM deliveries d
+            JOIN subscriptions s ON s.id = d.subscriber_id
+            WHERE d.issue_id = $1 AND s.tracking_opt_out"#,
+        )
+        .bind(issue_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(subscriber_ids.into_iter().collect())
+    }
+
+    #[tracing::instrument(name = "Record tracked action", skip(self))]
+    async fn record_tracked_action(
+        &self,
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        action: &TrackedAction,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<bool> {
+        let result = sqlx::query(
+            r#"INSERT INTO tracking_events (issue_id, subscriber_id, kind, url, occurred_at)
+            SELECT i.id, s.id, $3, $4, $5 FROM newsletter_issues i, subscriptions s
+            WHERE i.id = $1 AND s.id = $2 AND NOT s.tracking_opt_out"#,
+        )
+        .bind(issue_id)
+        .bind(subscriber_id)
+        .bind(action.kind())
+        .bind(action.url())
+        .bind(timestamp(at))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Summarise engagement", skip(self))]
+    async fn engagement(&self, issue_id: Uuid) -> eyre::Result<Engagement> {
+        let (opens, unique_opens, clicks, unique_clicks) = sqlx::query_as::<_, (i64, i64, i64, i64)>(
+            r#"SELECT
+                COUNT(CASE WHEN kind = 'open' THEN 1 END),
+                COUNT(DISTINCT CASE WHEN kind = 'open' THEN subscriber_id END),
+                COUNT(CASE WHEN kind = 'click' THEN 1 END),
+                COUNT(DISTINCT CASE WHEN kind = 'click' THEN subscriber_id END)
+            FROM tracking_events WHERE issue_id = $1"#,
+        )
+        .bind(issue_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        let links = sqlx::query_as::<_, LinkClicks>(
+            r#"SELECT url, COUNT(*) AS clicks, COUNT(DISTINCT subscriber_id) AS unique_clicks
+            FROM tracking_events WHERE issue_id = $1 AND kind = 'click'
+            GROUP BY url
+            ORDER BY 2 DESC, url"#,
+        )
+        .bind(issue_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(Engagement {
+            opens,
+            unique_opens,
+            clicks,
+            unique_clicks,
+            links,
+        })
+    }
+
+    #[tracing::instrument(name = "List a subscriber's tracking events", skip(self))]
+    async fn tracking_events(&self, subscriber_id: Uuid) -> eyre::Result<Vec<TrackingEvent>> {
+        let events = sqlx::query_as::<_, TrackingEvent>(
+            r#"SELECT issue_id, kind, url, occurred_at FROM tracking_events
+            WHERE subscriber_id = $1
+            ORDER BY id"#,
+        )
+        .bind(subscriber_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(events)
+    }
+
+    #[tracing::instrument(name = "List archived newsletter issues", skip(self))]
+    async fn archived_issues(&self, limit: i64, offset: i64) -> eyre::Result<Vec<NewsletterIssue>> {
+        let issues = sqlx::query_as::<_, NewsletterIssue>(&format!(
+            "{} WHERE i.public AND i.status = 'sent' ORDER BY i.sent_at DESC, i.id LIMIT $1 OFFSET $2",
+            ISSUE_COLUMNS
+        ))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issues)
+    }
+
+    #[tracing::instrument(name = "Find archived newsletter issue", skip(self))]
+    async fn find_archived_issue(&self, slug: &str) -> eyre::Result<Option<NewsletterIssue>> {
+        let issue = sqlx::query_as::<_, NewsletterIssue>(&format!(
+            "{} WHERE i.slug = $1 AND i.public AND i.status = 'sent'",
+            ISSUE_COLUMNS
+        ))
+        .bind(slug)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(issue)
+    }
+}
@@ -0,0 +1,240 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{QueryBuilder, Sqlite};
+use uuid::Uuid;
+
+use super::{record_event, timestamp, SqliteSubscriberRepository};
+use crate::audit::EventContext;
+use crate::repository::{
+    ListRepository, ListSettings, MailingList, Membership, MembershipChanges, PreferenceUpdate,
+    Subscriber,
+};
+use crate::segment::{Dialect, Segment};
+
+#[async_trait]
+impl ListRepository for SqliteSubscriberRepository {
+    #[tracing::instrument(name = "Get list memberships", skip(self))]
+    async fn memberships(&self, subscriber_id: Uuid) -> eyre::Result<Vec<Membership>> {
+        let memberships = sqlx::query_as::<_, Membership>(
+            r#"SELECT l.slug AS list, m.status, m.subscribed_at, m.confirmed_at
+            FROM list_memberships m JOIN lists l ON l.id = m.list_id
+            WHERE m.subscriber_id = $1
+            ORDER BY m.subscribed_at, l.slug"#,
+        )
+        .bind(subscriber_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(memberships)
+    }
+
+    #[tracing::instrument(name = "Update subscriber preferences", skip(self, update, context))]
+    async fn update_preferences(
+        &self,
+        subscriber_id: Uuid,
+        update: &PreferenceUpdate,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let now = timestamp(Utc::now());
+        let mut transaction = self.pool.begin().await?;
+        let result = sqlx::query(
+            r#"UPDATE subscriptions SET name = $2, paused_until = $3
+            WHERE id = $1 AND status = 'confirmed'"#,
+        )
+        .bind(subscriber_id)
+        .bind(&update.name)
+        .bind(update.paused_until.map(timestamp))
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        let current: Vec<(Uuid, String)> =
+            sqlx::query_as(r#"SELECT list_id, status FROM list_memberships WHERE subscriber_id = $1"#)
+                .bind(subscriber_id)
+                .fetch_all(&mut *transaction)
+                .await?;
+        let changes = MembershipChanges::new(&current, &update.list_ids);
+        for list_id in changes.join.iter().chain(&changes.confirm) {
+            sqlx::query(
+                r#"INSERT INTO list_memberships (subscriber_id, list_id, status, subscribed_at, confirmed_at)
+                VALUES ($1, $2, 'confirmed', $3, $3)
+                ON CONFLICT (subscriber_id, list_id)
+                    DO UPDATE SET status = 'confirmed', confirmed_at = excluded.confirmed_at"#,
+            )
+            .bind(subscriber_id)
+            .bind(list_id)
+            .bind(&now)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        }
+        for list_id in &changes.leave {
+            sqlx::query(
+                r#"UPDATE list_memberships SET status = 'unsubscribed'
+                WHERE subscriber_id = $1 AND list_id = $2"#,
+            )
+            .bind(subscriber_id)
+            .bind(list_id)
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query(r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1 AND list_id = $2"#)
+                .bind(subscriber_id)
+                .bind(list_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        for (list_id, kind) in changes.events() {
+            record_event(&mut transaction, subscriber_id, Some(list_id), kind, context).await?;
+        }
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "Get list recipients", skip(self))]
+    async fn recipients(
+        &self,
+        list_id: Uuid,
+        segment: Option<&Segment>,
+    ) -> eyre::Result<Vec<Subscriber>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"SELECT s.id, s.email, s.name, s.status, s.locale, s.subscribed_at, s.confirmed_at, s.consent_source
+            FROM subscriptions s JOIN list_memberships m ON m.subscriber_id = s.id
+            WHERE s.status = 'confirmed' AND m.status = 'confirmed'
+                AND (s.paused_until IS NULL OR s.paused_until <= "#,
+        );
+        query.push_bind(timestamp(Utc::now()));
+        query.push(") AND m.list_id = ");
+        query.push_bind(list_id);
+        if let Some(segment) = segment {
+            query.push(" AND ");
+            segment.push_sql(&mut query, Dialect::Sqlite);
+        }
+        query.push(" ORDER BY s.subscribed_at, s.id");
+        let recipients = query
+            .build_query_as::<Subscriber>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        Ok(recipients)
+    }
+
+    #[tracing::instrument(name = "List mailing lists", skip(self))]
+    async fn lists(&self) -> eyre::Result<Vec<MailingList>> {
+        let lists = sqlx::query_as::<_, MailingList>(
+            r#"SELECT id, slug, name, sender_email, sender_name, confirmation_subject,
+                confirmation_intro, created_at
+            FROM lists ORDER BY created_at, slug"#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(lists)
+    }
+
+    #[tracing::instrument(name = "Find mailing list", skip(self))]
+    async fn find_list(&self, slug: &str) -> eyre::Result<Option<MailingList>> {
+        let list = sqlx::query_as::<_, MailingList>(
+            r#"SELECT id, slug, name, sender_email, sender_name, confirmation_subject,
+                confirmation_intro, created_at
+            FROM lists WHERE slug = $1"#,
+        )
+        .bind(slug)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(list)
+    }
+
+    #[tracing::instrument(name = "Create mailing list", skip(self, settings))]
+    async fn create_list(
+        &self,
+        slug: &str,
+        settings: &ListSettings,
+    ) -> eyre::Result<Option<MailingList>> {
+        let list = sqlx::query_as::<_, MailingList>(
+            r#"INSERT INTO lists (id, slug, name, sender_email, sender_name,
+                confirmation_subject, confirmation_intro, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (slug) DO NOTHING
+            RETURNING id, slug, name, sender_email, sender_name, confirmation_subject,
+                confirmation_intro, created_at"#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(slug)
+        .bind(&settings.name)
+        .bind(&settings.sender_email)
+        .bind(&settings.sender_name)
+        .bind(&settings.confirmation_subject)
+        .bind(&settings.confirmation_intro)
+        .bind(timestamp(Utc::now()))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(list)
+    }
+
+    #[tracing::instrument(name = "Update mailing list", skip(self, settings))]
+    async fn update_list(
+        &self,
+        slug: &str,
+        settings: &ListSettings,
+    ) -> eyre::Result<Option<MailingList>> {
+        let list = sqlx::query_as::<_, MailingList>(
+            r#"UPDATE lists SET name = $2, sender_email = $3, sender_name = $4,
+                confirmation_subject = $5, confirmation_intro = $6
+            WHERE slug = $1
+            RETURNING id, slug, name, sender_email, sender_name, confirmation_subject,
+                confirmation_intro, created_at"#,
+        )
+        .bind(slug)
+        .bind(&settings.name)
+        .bind(&settings.sender_email)
+        .bind(&settings.sender_name)
+        .bind(&settings.confirmation_subject)
+        .bind(&settings.confirmation_intro)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(list)
+    }
+
+    /// Memberships and tokens go through `ON DELETE CASCADE`.
+    #[tracing::instrument(name = "Delete mailing list", skip(self))]
+    async fn delete_list(&self, slug: &str) -> eyre::Result<bool> {
+        let result = sqlx::query(r#"DELETE FROM lists WHERE slug = $1"#)
+            .bind(slug)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,525 @@
+use std::collections::{BTreeMap, HashSet};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use sqlx::{types::Json, QueryBuilder, Sqlite};
+use uuid::Uuid;
+
+use super::{record_event, timestamp, SqliteSubscriberRepository, SqliteSubscriberTransaction};
+use crate::audit::{EventContext, EventKind};
+use crate::repository::{
+    spawn_export, DeleteMode, EmailChange, ImportedStatus, ImportedSubscriber, PageCursor,
+    Subscriber, SubscriberFilter, SubscriberProfile, SubscriberRepository, SubscriberStream,
+    SubscriberTransaction, SubscriptionToken,
+};
+
+#[async_trait]
+impl SubscriberRepository for SqliteSubscriberRepository {
+    async fn begin(&self) -> eyre::Result<Box<dyn SubscriberTransaction>> {
+        let transaction = self.pool.begin().await?;
+        Ok(Box::new(SqliteSubscriberTransaction { transaction }))
+    }
+
+    #[tracing::instrument(name = "Find subscriber by email", skip(self, email))]
+    async fn find_by_email(&self, email: &str) -> eyre::Result<Option<Subscriber>> {
+        let subscriber = sqlx::query_as::<_, Subscriber>(
+            r#"SELECT id, email, name, status, locale, subscribed_at, confirmed_at, consent_source
+            FROM subscriptions WHERE email = $1"#,
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(subscriber)
+    }
+
+    #[tracing::instrument(name = "Get subscription token", skip(self, token))]
+    async fn find_token(&self, token: &str) -> eyre::Result<Option<SubscriptionToken>> {
+        let token = sqlx::query_as::<_, SubscriptionToken>(
+            r#"SELECT t.subscriber_id, t.list_id, s.locale, m.status, t.created_at
+            FROM subscription_tokens t
+            JOIN subscriptions s ON s.id = t.subscriber_id
+            JOIN list_memberships m
+                ON m.subscriber_id = t.subscriber_id AND m.list_id = t.list_id
+            WHERE t.token = $1"#,
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(token)
+    }
+
+    #[tracing::instrument(name = "Mark subscriber as confirmed", skip(self, context))]
+    async fn confirm(
+        &self,
+        subscriber_id: Uuid,
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<()> {
+        let now = timestamp(Utc::now());
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query(
+            r#"UPDATE list_memberships SET status = 'confirmed', confirmed_at = $3
+            WHERE subscriber_id = $1 AND list_id = $2"#,
+        )
+        .bind(subscriber_id)
+        .bind(list_id)
+        .bind(&now)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        sqlx::query(
+            r#"UPDATE subscriptions SET status = 'confirmed', confirmed_at = $2
+            WHERE id = $1 AND status = 'pending'"#,
+        )
+        .bind(subscriber_id)
+        .bind(&now)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        record_event(
+            &mut transaction,
+            subscriber_id,
+            Some(list_id),
+            EventKind::Confirmed,
+            context,
+        )
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Find subscriber by id", skip(self))]
+    async fn find_by_id(&self, subscriber_id: Uuid) -> eyre::Result<Option<Subscriber>> {
+        let subscriber = sqlx::query_as::<_, Subscriber>(
+            r#"SELECT id, email, name, status, locale, subscribed_at, confirmed_at, consent_source
+            FROM subscriptions WHERE id = $1"#,
+        )
+        .bind(subscriber_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(subscriber)
+    }
+
+    #[tracing::instrument(name = "List subscribers", skip(self))]
+    async fn list(
+        &self,
+        filter: &SubscriberFilter,
+        cursor: Option<PageCursor>,
+        limit: i64,
+    ) -> eyre::Result<Vec<Subscriber>> {
+        let subscribers = sqlx::query_as::<_, Subscriber>(
+            r#"SELECT id, email, name, status, locale, subscribed_at, confirmed_at, consent_source
+            FROM subscriptions
+            WHERE ($1 IS NULL OR status = $1)
+                AND ($2 IS NULL OR subscribed_at >= $2)
+                AND ($3 IS NULL OR subscribed_at < $3)
+                AND ($4 IS NULL
+                    OR substr(lower(email), 1, length($4)) = lower($4)
+                    OR substr(lower(name), 1, length($4)) = lower($4))
+                AND ($5 IS NULL OR (subscribed_at, id) < ($5, $6))
+            ORDER BY subscribed_at DESC, id DESC
+            LIMIT $7"#,
+        )
+        .bind(&filter.status)
+        .bind(filter.subscribed_after.map(timestamp))
+        .bind(filter.subscribed_before.map(timestamp))
+        .bind(&filter.search)
+        .bind(cursor.map(|c| timestamp(c.subscribed_at)))
+        .bind(cursor.map(|c| c.id))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(subscribers)
+    }
+
+    #[tracing::instrument(name = "Get subscription token history", skip(self))]
+    async fn token_history(&self, subscriber_id: Uuid) -> eyre::Result<Vec<DateTime<Utc>>> {
+        let created_at = sqlx::query_scalar(
+            r#"SELECT created_at FROM subscription_tokens
+            WHERE subscriber_id = $1 ORDER BY created_at DESC"#,
+        )
+        .bind(subscriber_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(created_at)
+    }
+
+    #[tracing::instrument(name = "Delete subscriber", skip(self))]
+    async fn delete(
+        &self,
+        subscriber_id: Uuid,
+        mode: DeleteMode,
+        context: &EventContext,
+    ) -> eyre::Result<bool> {
+        let result = match mode {
+            // Tokens go through `ON DELETE CASCADE`.
+            DeleteMode::Hard => {
+                sqlx::query(r#"DELETE FROM subscriptions WHERE id = $1"#)
+                    .bind(subscriber_id)
+                    .execute(&self.pool)
+                    .await
+            }
+            DeleteMode::Soft => {
+                let mut transaction = self.pool.begin().await?;
+                sqlx::query(r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#)
+                    .bind(subscriber_id)
+                    .execute(&mut *transaction)
+                    .await?;
+                let result = sqlx::query(r#"UPDATE subscriptions SET status = 'deleted' WHERE id = $1"#)
+                    .bind(subscriber_id)
+                    .execute(&mut *transaction)
+                    .await;
+                if result.as_ref().is_ok_and(|result| result.rows_affected() > 0) {
+                    record_event(&mut transaction, subscriber_id, None, EventKind::Deleted, context)
+                        .await?;
+                }
+                transaction.commit().await?;
+                result
+            }
+        }
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Import subscribers", skip(self, subscribers, context), fields(count = subscribers.len()))]
+    async fn import(
+        &self,
+        subscribers: &[ImportedSubscriber],
+        list_id: Uuid,
+        context: &EventContext,
+    ) -> eyre::Result<HashSet<String>> {
+        if subscribers.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let now = timestamp(Utc::now());
+        let ids: Vec<Uuid> = subscribers.iter().map(|_| Uuid::new_v4()).collect();
+        let mut transaction = self.pool.begin().await?;
+
+        let mut insert = QueryBuilder::<Sqlite>::new(
+            "INSERT INTO subscriptions \
+            (id, email, name, status, locale, confirmed_at, consent_source, subscribed_at) ",
+        );
+        insert.push_values(ids.iter().zip(subscribers), |mut row, (id, subscriber)| {
+            let (status, confirmed_at, consent_source) = match &subscriber.status {
+                ImportedStatus::Confirmed { consent_source } => {
+                    ("confirmed", Some(now.clone()), Some(consent_source.clone()))
+                }
+                ImportedStatus::Pending { .. } => ("pending", None, None),
+            };
+            row.push_bind(*id)
+                .push_bind(subscriber.new_subscriber.email.as_ref().to_owned())
+                .push_bind(subscriber.new_subscriber.name.as_ref().to_owned())
+                .push_bind(status)
+                .push_bind(subscriber.new_subscriber.locale.clone())
+                .push_bind(confirmed_at)
+                .push_bind(consent_source)
+                .push_bind(now.clone());
+        });
+        insert.push(" ON CONFLICT (email) DO NOTHING RETURNING id");
+        let inserted: HashSet<Uuid> = insert
+            .build_query_scalar()
+            .fetch_all(&mut *transaction)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute query: {:?}", e);
+                e
+            })?
+            .into_iter()
+            .collect();
+        if !inserted.is_empty() {
+            let mut insert = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO list_memberships (subscriber_id, list_id, status, subscribed_at, confirmed_at) \
+                SELECT id, ",
+            );
+            insert.push_bind(list_id);
+            insert.push(", status, subscribed_at, confirmed_at FROM subscriptions WHERE id IN (");
+            let mut ids = insert.separated(", ");
+            for id in &inserted {
+                ids.push_bind(*id);
+            }
+            insert.push(")");
+            insert
+                .build()
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to execute query: {:?}", e);
+                    e
+                })?;
+        }
+
+        let tokens: Vec<(Uuid, &str)> = ids
+            .iter()
+            .zip(subscribers)
+            .filter(|(id, _)| inserted.contains(id))
+            .filter_map(|(id, subscriber)| match &subscriber.status {
+                ImportedStatus::Pending { token } => Some((*id, token.as_str())),
+                ImportedStatus::Confirmed { .. } => None,
+            })
+            .collect();
+        if !tokens.is_empty() {
+            let mut insert = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO subscription_tokens (subscriber_id, list_id, token, created_at) ",
+            );
+            insert.push_values(tokens, |mut row, (id, token)| {
+                row.push_bind(id)
+                    .push_bind(list_id)
+                    .push_bind(token)
+                    .push_bind(now.clone());
+            });
+            insert
+                .build()
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to execute query: {:?}", e);
+                    e
+                })?;
+        }
+        if !inserted.is_empty() {
+            let mut insert = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO subscription_events \
+                (subscriber_id, list_id, kind, occurred_at, ip, user_agent, request_id, source) ",
+            );
+            insert.push_values(&inserted, |mut row, id| {
+                row.push_bind(*id)
+                    .push_bind(list_id)
+                    .push_bind(EventKind::Imported.as_str())
+                    .push_bind(now.clone())
+                    .push_bind(context.ip.clone())
+                    .push_bind(context.user_agent.clone())
+                    .push_bind(context.request_id.clone())
+                    .push_bind(context.source.clone());
+            });
+            insert
+                .build()
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to execute query: {:?}", e);
+                    e
+                })?;
+        }
+        transaction.commit().await?;
+
+        Ok(ids
+            .iter()
+            .zip(subscribers)
+            .filter(|(id, _)| inserted.contains(id))
+            .map(|(_, subscriber)| subscriber.new_subscriber.email.as_ref().to_owned())
+            .collect())
+    }
+
+    /// Reads inside a transaction, which in WAL mode sees a snapshot taken
+    /// at its first read.
+    fn export(&self, filter: &SubscriberFilter) -> SubscriberStream {
+        let pool = self.pool.clone();
+        let filter = filter.clone();
+        spawn_export(|sender| async move {
+            let mut transaction = pool.begin().await?;
+            let mut rows = sqlx::query_as::<_, Subscriber>(
+                r#"SELECT id, email, name, status, locale, subscribed_at, confirmed_at, consent_source
+                FROM subscriptions
+                WHERE ($1 IS NULL OR status = $1)
+                    AND ($2 IS NULL OR subscribed_at >= $2)
+                    AND ($3 IS NULL OR subscribed_at < $3)
+                    AND ($4 IS NULL
+                        OR substr(lower(email), 1, length($4)) = lower($4)
+                        OR substr(lower(name), 1, length($4)) = lower($4))
+                ORDER BY subscribed_at, id"#,
+            )
+            .bind(filter.status)
+            .bind(filter.subscribed_after.map(timestamp))
+            .bind(filter.subscribed_before.map(timestamp))
+            .bind(filter.search)
+            .fetch(&mut *transaction);
+            while let Some(subscriber) = rows.try_next().await? {
+                if sender.send(Ok(subscriber)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    #[tracing::instrument(name = "Get subscriber profile", skip(self))]
+    async fn profile(&self, subscriber_id: Uuid) -> eyre::Result<SubscriberProfile> {
+        let attributes: Option<Json<BTreeMap<String, String>>> =
+            sqlx::query_scalar(r#"SELECT attributes FROM subscriptions WHERE id = $1"#)
+                .bind(subscriber_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to execute query: {:?}", e);
+                    e
+                })?;
+        let tags: Vec<String> = sqlx::query_scalar(r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1"#)
+            .bind(subscriber_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(SubscriberProfile {
+            tags: tags.into_iter().collect(),
+            attributes: attributes.map(|attributes| attributes.0).unwrap_or_default(),
+        })
+    }
+
+    #[tracing::instrument(name = "Get paused delivery", skip(self))]
+    async fn paused_until(&self, subscriber_id: Uuid) -> eyre::Result<Option<DateTime<Utc>>> {
+        let paused_until: Option<Option<DateTime<Utc>>> =
+            sqlx::query_scalar(r#"SELECT paused_until FROM subscriptions WHERE id = $1"#)
+                .bind(subscriber_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to execute query: {:?}", e);
+                    e
+                })?;
+        Ok(paused_until.flatten())
+    }
+
+    #[tracing::instrument(name = "Unsubscribe subscriber", skip(self, context))]
+    async fn unsubscribe(&self, subscriber_id: Uuid, context: &EventContext) -> eyre::Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        let result = sqlx::query(
+            r#"UPDATE subscriptions SET status = 'unsubscribed'
+            WHERE id = $1 AND status IN ('pending', 'confirmed')"#,
+        )
+        .bind(subscriber_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query(r#"UPDATE list_memberships SET status = 'unsubscribed' WHERE subscriber_id = $1"#)
+            .bind(subscriber_id)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query(r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#)
+            .bind(subscriber_id)
+            .execute(&mut *transaction)
+            .await?;
+        record_event(&mut transaction, subscriber_id, None, EventKind::Unsubscribed, context).await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "Change subscriber email", skip(self, old_email, new_email, suppression_hash, context))]
+    async fn change_email(
+        &self,
+        subscriber_id: Uuid,
+        old_email: &str,
+        new_email: &str,
+        suppression_hash: &str,
+        context: &EventContext,
+    ) -> eyre::Result<EmailChange> {
+        let mut transaction = self.pool.begin().await?;
+        let suppressed: bool =
+            sqlx::query_scalar(r#"SELECT EXISTS (SELECT 1 FROM suppressions WHERE email_hash = $1)"#)
+                .bind(suppression_hash)
+                .fetch_one(&mut *transaction)
+                .await?;
+        if suppressed {
+            return Ok(EmailChange::Suppressed);
+        }
+        // The unique constraint on the address settles races with signups.
+        let result = sqlx::query(r#"UPDATE subscriptions SET email = $3 WHERE id = $1 AND email = $2"#)
+            .bind(subscriber_id)
+            .bind(old_email)
+            .bind(new_email)
+            .execute(&mut *transaction)
+            .await;
+        match result {
+            Ok(result) if result.rows_affected() == 0 => return Ok(EmailChange::Stale),
+            Ok(_) => {}
+            Err(e) if e.as_database_error().is_some_and(|e| e.is_unique_violation()) => {
+                return Ok(EmailChange::Taken)
+            }
+            Err(e) => {
+                tracing::error!("Failed to execute query: {:?}", e);
+                return Err(e.into());
+            }
+        }
+        record_event(&mut transaction, subscriber_id, None, EventKind::EmailChanged, context).await?;
+        transaction.commit().await?;
+        Ok(EmailChange::Changed)
+    }
+
+    #[tracing::instrument(name = "Reissue confirmation tokens", skip(self, tokens))]
+    async fn reissue_tokens(
+        &self,
+        subscriber_id: Uuid,
+        tokens: &[(Uuid, String)],
+        issued_after: DateTime<Utc>,
+    ) -> eyre::Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        let recent: bool = sqlx::query_scalar(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM subscription_tokens WHERE subscriber_id = $1 AND created_at > $2
+            )"#,
+        )
+        .bind(subscriber_id)
+        .bind(timestamp(issued_after))
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute query: {:?}", e);
+            e
+        })?;
+        if recent {
+            return Ok(false);
+        }
+        for (list_id, token) in tokens {
+            sqlx::query(r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1 AND list_id = $2"#)
+                .bind(subscriber_id)
+                .bind(list_id)
+                .execute(&mut *transaction)
+                .await?;
+            sqlx::query(
+                r#"INSERT INTO subscription_tokens (token, subscriber_id, list_id, created_at)
+                VALUES ($1, $2, $3, $4)"#,
+            )
+            .bind(token)
+            .bind(subscriber_id)
+            .bind(list_id)
+            .bind(timestamp(Utc::now()))
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(true)
+    }
+}
@@ -1,8 +1,28 @@
-//! src/routes/mod.rs
-mod health_check;
-mod subscriptions;
-mod subscriptions_confirm;
-
-pub use health_check::*;
-pub use subscriptions::*;
-pub use subscriptions_confirm::*;
\ No newline at end of file
+//! src/routes/mod.rs
+mod admin;
+mod admin_export;
+mod admin_import;
+mod admin_issues;
+mod admin_lists;
+mod admin_subscribers;
+mod archive;
+mod health_check;
+mod preferences;
+mod privacy;
+mod subscriptions;
+mod subscriptions_confirm;
+mod tracking;
+
+pub use admin::*;
+pub use admin_export::*;
+pub use admin_import::*;
+pub use admin_issues::*;
+pub use admin_lists::*;
+pub use admin_subscribers::*;
+pub use archive::*;
+pub use health_check::*;
+pub use preferences::*;
+pub use privacy::*;
+pub use subscriptions::*;
+pub use subscriptions_confirm::*;
+pub use tracking::*;
\ No newline at end of file
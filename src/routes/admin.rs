@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use secrecy::ExposeSecret;
+
+use crate::app_state::AppState;
+
+/// Lets a request through only if it carries `Authorization: Bearer <token>`
+/// with the configured admin token.
+pub async fn require_admin_token(
+    State(app_state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = app_state.admin.api_token.as_ref() else {
+        tracing::warn!("Rejected an admin request: no admin token is configured");
+        return unauthorized();
+    };
+    let provided = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match provided {
+        Some(provided) if constant_time_eq(provided, expected.expose_secret()) => {
+            next.run(request).await
+        }
+        _ => unauthorized(),
+    }
+}
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(WWW_AUTHENTICATE, "Bearer")],
+        Json(serde_json::json!({ "error": "unauthorized" })),
+    )
+        .into_response()
+}
+
+/// Compares without returning early, so response times do not reveal how
+/// much of the token was right.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a
+            .bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (x, y)| difference | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::constant_time_eq;
+
+    #[test]
+    fn only_identical_tokens_are_equal() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secreT"));
+        assert!(!constant_time_eq("secret", "secret2"));
+        assert!(!constant_time_eq("", "secret"));
+    }
+}
@@ -0,0 +1,95 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    Json,
+};
+use futures_util::TryStreamExt;
+use tokio_util::io::StreamReader;
+
+use crate::{
+    app_state::AppState,
+    audit::EventContext,
+    import::{self, ImportError, ImportFormat, ImportOptions, ImportReport},
+    repository::DEFAULT_LIST,
+    routes::AdminError,
+};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ImportParameters {
+    #[serde(default)]
+    format: ImportFormat,
+    /// Slug of the list to import into; the default list if absent.
+    list: Option<String>,
+    /// Import everyone as already confirmed instead of emailing them.
+    #[serde(default)]
+    confirmed: bool,
+    /// Where the imported subscribers gave their consent; required with
+    /// `confirmed`.
+    consent_source: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ImportResponse {
+    #[serde(flatten)]
+    report: ImportReport,
+    /// Sent in the background after the response.
+    confirmation_emails_queued: usize,
+}
+
+impl From<ImportError> for AdminError {
+    fn from(e: ImportError) -> Self {
+        match e {
+            ImportError::InvalidInput(message) => AdminError::BadRequest(message),
+            ImportError::Unexpected(e) => AdminError::Unexpected(e),
+        }
+    }
+}
+
+/// Streams the request body into the database; the body is never held in
+/// memory as a whole.
+#[tracing::instrument(
+    name = "Importing subscribers over HTTP",
+    skip(app_state, context, body)
+)]
+pub async fn import_subscribers(
+    State(app_state): State<AppState>,
+    context: EventContext,
+    Query(parameters): Query<ImportParameters>,
+    body: Body,
+) -> Result<Json<ImportResponse>, AdminError> {
+    let consent_source = match (parameters.confirmed, parameters.consent_source) {
+        (false, _) => None,
+        (true, Some(source)) if !source.trim().is_empty() => Some(source.trim().to_owned()),
+        (true, _) => {
+            return Err(AdminError::BadRequest(
+                "`consent_source` is required when importing confirmed subscribers".into(),
+            ))
+        }
+    };
+    let slug = parameters.list.as_deref().unwrap_or(DEFAULT_LIST);
+    let list = app_state
+        .subscribers
+        .find_list(slug)
+        .await?
+        .ok_or_else(|| AdminError::BadRequest(format!("There is no list `{}`", slug)))?;
+    let options = ImportOptions {
+        format: parameters.format,
+        list_id: list.id,
+        consent_source,
+    };
+    let reader = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+    let outcome = import::import_subscribers(reader, &options, app_state.subscribers.as_ref(), context)
+            .await?;
+
+    let confirmation_emails_queued = outcome.pending_confirmations.len();
+    if confirmation_emails_queued > 0 {
+        let pending = outcome.pending_confirmations;
+        tokio::spawn(async move {
+            import::send_confirmations(&app_state, &list, pending).await;
+        });
+    }
+    Ok(Json(ImportResponse {
+        report: outcome.report,
+        confirmation_emails_queued,
+    }))
+}
@@ -0,0 +1,304 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::SubscriberEmail,
+    i18n::DEFAULT_LOCALE,
+    issues::{render_issue, send_test},
+    repository::{Delivery, DeliveryCounts, Engagement, IssueContent, NewsletterIssue, DEFAULT_LIST},
+    routes::AdminError,
+    segment::Segment,
+};
+
+const ISSUE_STATUSES: [&str; 4] = ["draft", "scheduled", "sending", "sent"];
+/// Failed deliveries listed in a report; the counts cover all of them.
+const REPORTED_FAILURES: i64 = 100;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct IssueForm {
+    /// Slug of the list to send to; the default list if absent.
+    list: Option<String>,
+    title: String,
+    html_content: String,
+    text_content: String,
+    /// E.g. `tag:rust AND country:de`; everyone on the list if absent.
+    segment: Option<String>,
+    /// Track opens and clicks, if tracking is enabled.
+    #[serde(default)]
+    tracking: bool,
+    /// Publish the issue in the web archive once it is sent.
+    #[serde(default)]
+    public: bool,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct IssueParameters {
+    status: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Issues {
+    issues: Vec<NewsletterIssue>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ScheduleForm {
+    /// RFC 3339 with the editor's offset, e.g. `2026-10-20T09:00:00+02:00`.
+    send_at: DateTime<FixedOffset>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct PreviewParameters {
+    /// `html` (the default) or `text`.
+    format: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct DeliveryReport {
+    /// Of the issue.
+    status: String,
+    total: i64,
+    #[serde(flatten)]
+    counts: DeliveryCounts,
+    /// The latest failed or bounced deliveries.
+    failures: Vec<Delivery>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct TestRecipient {
+    email: String,
+}
+
+/// Checks the form and resolves its list and segment.
+async fn check_issue(app_state: &AppState, form: IssueForm) -> Result<IssueContent, AdminError> {
+    let title = form.title.trim().to_owned();
+    if title.is_empty() || title.contains(['\r', '\n']) {
+        return Err(AdminError::BadRequest(
+            "title must be a single non-empty line".into(),
+        ));
+    }
+    if form.html_content.trim().is_empty() || form.text_content.trim().is_empty() {
+        return Err(AdminError::BadRequest(
+            "html_content and text_content must not be empty".into(),
+        ));
+    }
+    let segment = form
+        .segment
+        .map(|segment| segment.trim().to_owned())
+        .filter(|segment| !segment.is_empty());
+    if let Some(segment) = &segment {
+        Segment::parse(segment, &app_state.segmentation.attribute_keys)
+            .map_err(|e| AdminError::BadRequest(e.to_string()))?;
+    }
+    let slug = form.list.as_deref().unwrap_or(DEFAULT_LIST);
+    let list = app_state
+        .subscribers
+        .find_list(slug)
+        .await?
+        .ok_or_else(|| AdminError::BadRequest(format!("There is no list `{}`", slug)))?;
+    Ok(IssueContent {
+        list_id: list.id,
+        title,
+        html_content: form.html_content,
+        text_content: form.text_content,
+        segment,
+        tracking: form.tracking,
+        public: form.public,
+    })
+}
+
+async fn find_issue(app_state: &AppState, issue_id: Uuid) -> Result<NewsletterIssue, AdminError> {
+    app_state
+        .subscribers
+        .find_issue(issue_id)
+        .await?
+        .ok_or(AdminError::NotFound)
+}
+
+/// The error for changing an issue that is already being sent, or was.
+fn not_editable(issue: &NewsletterIssue) -> AdminError {
+    AdminError::Conflict(format!("The issue is {} and can no longer change", issue.status))
+}
+
+#[tracing::instrument(name = "Listing newsletter issues", skip(app_state))]
+pub async fn list_issues(
+    State(app_state): State<AppState>,
+    Query(parameters): Query<IssueParameters>,
+) -> Result<Json<Issues>, AdminError> {
+    if let Some(status) = parameters.status.as_deref() {
+        if !ISSUE_STATUSES.contains(&status) {
+            return Err(AdminError::BadRequest(format!(
+                "status must be one of {}",
+                ISSUE_STATUSES.join(", ")
+            )));
+        }
+    }
+    let issues = app_state
+        .subscribers
+        .issues(parameters.status.as_deref())
+        .await?;
+    Ok(Json(Issues { issues }))
+}
+
+#[tracing::instrument(name = "Creating a newsletter issue", skip(app_state, form))]
+pub async fn create_issue(
+    State(app_state): State<AppState>,
+    Json(form): Json<IssueForm>,
+) -> Result<(StatusCode, Json<NewsletterIssue>), AdminError> {
+    let content = check_issue(&app_state, form).await?;
+    let issue = app_state.subscribers.create_issue(&content).await?;
+    Ok((StatusCode::CREATED, Json(issue)))
+}
+
+#[tracing::instrument(name = "Getting a newsletter issue", skip(app_state))]
+pub async fn get_issue(
+    State(app_state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Json<NewsletterIssue>, AdminError> {
+    Ok(Json(find_issue(&app_state, issue_id).await?))
+}
+
+/// Replaces the content of a draft or scheduled issue; a scheduled one
+/// keeps its time.
+#[tracing::instrument(name = "Updating a newsletter issue", skip(app_state, form))]
+pub async fn update_issue(
+    State(app_state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+    Json(form): Json<IssueForm>,
+) -> Result<Json<NewsletterIssue>, AdminError> {
+    let issue = find_issue(&app_state, issue_id).await?;
+    let content = check_issue(&app_state, form).await?;
+    let updated = app_state
+        .subscribers
+        .update_issue(issue_id, &content)
+        .await?
+        .ok_or_else(|| not_editable(&issue))?;
+    Ok(Json(updated))
+}
+
+#[tracing::instrument(name = "Deleting a newsletter issue", skip(app_state))]
+pub async fn delete_issue(
+    State(app_state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<StatusCode, AdminError> {
+    let issue = find_issue(&app_state, issue_id).await?;
+    if app_state.subscribers.delete_issue(issue_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(not_editable(&issue))
+    }
+}
+
+/// Schedules the issue, or moves it if it already was; it goes out on the
+/// first poll of the scheduler after `send_at`.
+#[tracing::instrument(name = "Scheduling a newsletter issue", skip(app_state))]
+pub async fn schedule_issue(
+    State(app_state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+    Json(form): Json<ScheduleForm>,
+) -> Result<Json<NewsletterIssue>, AdminError> {
+    let send_at = form.send_at.with_timezone(&Utc);
+    if send_at <= Utc::now() {
+        return Err(AdminError::BadRequest("send_at must be in the future".into()));
+    }
+    let issue = find_issue(&app_state, issue_id).await?;
+    let scheduled = app_state
+        .subscribers
+        .schedule_issue(issue_id, Some(send_at))
+        .await?
+        .ok_or_else(|| not_editable(&issue))?;
+    Ok(Json(scheduled))
+}
+
+/// Turns a scheduled issue back into a draft.
+#[tracing::instrument(name = "Unscheduling a newsletter issue", skip(app_state))]
+pub async fn unschedule_issue(
+    State(app_state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Json<NewsletterIssue>, AdminError> {
+    let issue = find_issue(&app_state, issue_id).await?;
+    let draft = app_state
+        .subscribers
+        .schedule_issue(issue_id, None)
+        .await?
+        .ok_or_else(|| not_editable(&issue))?;
+    Ok(Json(draft))
+}
+
+/// The issue's body as subscribers will see it.
+#[tracing::instrument(name = "Previewing a newsletter issue", skip(app_state))]
+pub async fn preview_issue(
+    State(app_state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+    Query(parameters): Query<PreviewParameters>,
+) -> Result<Response, AdminError> {
+    let issue = find_issue(&app_state, issue_id).await?;
+    let email = render_issue(&app_state.templates, &issue, DEFAULT_LOCALE).map_err(eyre::Report::from)?;
+    match parameters.format.as_deref() {
+        None | Some("html") => Ok(Html(email.html_body).into_response()),
+        Some("text") => Ok((
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            email.text_body,
+        )
+            .into_response()),
+        Some(_) => Err(AdminError::BadRequest("format must be html or text".into())),
+    }
+}
+
+/// How far sending the issue got: how many deliveries are in each state,
+/// with the latest failures.
+#[tracing::instrument(name = "Reporting newsletter issue deliveries", skip(app_state))]
+pub async fn issue_deliveries(
+    State(app_state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Json<DeliveryReport>, AdminError> {
+    let issue = find_issue(&app_state, issue_id).await?;
+    let counts = app_state.subscribers.delivery_counts(issue_id).await?;
+    let failures = app_state
+        .subscribers
+        .failed_deliveries(issue_id, REPORTED_FAILURES)
+        .await?;
+    Ok(Json(DeliveryReport {
+        status: issue.status,
+        total: counts.queued + counts.sent + counts.failed + counts.bounced,
+        counts,
+        failures,
+    }))
+}
+
+/// Opens and clicks of the issue, with the clicks on each of its links.
+#[tracing::instrument(name = "Reporting newsletter issue engagement", skip(app_state))]
+pub async fn issue_engagement(
+    State(app_state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Json<Engagement>, AdminError> {
+    find_issue(&app_state, issue_id).await?;
+    Ok(Json(app_state.subscribers.engagement(issue_id).await?))
+}
+
+/// Sends the issue to a single address, e.g. the editor's own, whatever
+/// its status.
+#[tracing::instrument(name = "Sending a test newsletter issue", skip(app_state))]
+pub async fn send_test_issue(
+    State(app_state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+    Json(recipient): Json<TestRecipient>,
+) -> Result<StatusCode, AdminError> {
+    let recipient = SubscriberEmail::parse(recipient.email)
+        .map_err(|e| AdminError::BadRequest(e.to_string()))?;
+    let issue = find_issue(&app_state, issue_id).await?;
+    let list = app_state
+        .subscribers
+        .find_list(&issue.list)
+        .await?
+        .ok_or(AdminError::NotFound)?;
+    send_test(&app_state, &issue, &list, recipient).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
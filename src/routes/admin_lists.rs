@@ -0,0 +1,183 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+
+use crate::{
+    app_state::AppState,
+    domain::SubscriberEmail,
+    repository::{ListSettings, MailingList, Subscriber, DEFAULT_LIST},
+    routes::AdminError,
+    segment::Segment,
+};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct NewList {
+    /// Lowercase letters, digits and dashes; what subscribers pass as `list`.
+    slug: String,
+    #[serde(flatten)]
+    settings: ListSettings,
+}
+
+#[derive(serde::Serialize)]
+pub struct Lists {
+    lists: Vec<MailingList>,
+}
+
+fn check_slug(slug: &str) -> Result<(), AdminError> {
+    let valid = !slug.is_empty()
+        && slug.len() <= 64
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(AdminError::BadRequest(
+            "slug must be up to 64 lowercase letters, digits or dashes".into(),
+        ))
+    }
+}
+
+/// Trims the settings, dropping blank optional ones, and rejects those
+/// that would not make a valid `From` header.
+fn check_settings(settings: ListSettings) -> Result<ListSettings, AdminError> {
+    let optional = |value: Option<String>| {
+        value
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty())
+    };
+    let name = settings.name.trim().to_owned();
+    if name.is_empty() {
+        return Err(AdminError::BadRequest("name must not be empty".into()));
+    }
+    let sender_email = optional(settings.sender_email)
+        .map(|email| SubscriberEmail::parse(email).map(|email| email.as_ref().to_owned()))
+        .transpose()
+        .map_err(|_| AdminError::BadRequest("sender_email is not a valid email".into()))?;
+    let sender_name = optional(settings.sender_name);
+    if let Some(sender_name) = &sender_name {
+        if sender_name.contains(['"', '<', '>', '\r', '\n']) {
+            return Err(AdminError::BadRequest(
+                "sender_name must not contain quotes, angle brackets or line breaks".into(),
+            ));
+        }
+    }
+    Ok(ListSettings {
+        name,
+        sender_email,
+        sender_name,
+        confirmation_subject: optional(settings.confirmation_subject),
+        confirmation_intro: optional(settings.confirmation_intro),
+    })
+}
+
+#[tracing::instrument(name = "Listing lists", skip(app_state))]
+pub async fn list_lists(State(app_state): State<AppState>) -> Result<Json<Lists>, AdminError> {
+    let lists = app_state.subscribers.lists().await?;
+    Ok(Json(Lists { lists }))
+}
+
+#[tracing::instrument(name = "Creating a list", skip(app_state))]
+pub async fn create_list(
+    State(app_state): State<AppState>,
+    Json(new_list): Json<NewList>,
+) -> Result<(StatusCode, Json<MailingList>), AdminError> {
+    check_slug(&new_list.slug)?;
+    let settings = check_settings(new_list.settings)?;
+    let list = app_state
+        .subscribers
+        .create_list(&new_list.slug, &settings)
+        .await?
+        .ok_or_else(|| AdminError::Conflict(format!("The slug `{}` is taken", new_list.slug)))?;
+    Ok((StatusCode::CREATED, Json(list)))
+}
+
+#[tracing::instrument(name = "Getting a list", skip(app_state))]
+pub async fn get_list(
+    State(app_state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Json<MailingList>, AdminError> {
+    let list = app_state
+        .subscribers
+        .find_list(&slug)
+        .await?
+        .ok_or(AdminError::NotFound)?;
+    Ok(Json(list))
+}
+
+/// Replaces the list's settings; its slug stays, as forms link to it.
+#[tracing::instrument(name = "Updating a list", skip(app_state))]
+pub async fn update_list(
+    State(app_state): State<AppState>,
+    Path(slug): Path<String>,
+    Json(settings): Json<ListSettings>,
+) -> Result<Json<MailingList>, AdminError> {
+    let settings = check_settings(settings)?;
+    let list = app_state
+        .subscribers
+        .update_list(&slug, &settings)
+        .await?
+        .ok_or(AdminError::NotFound)?;
+    Ok(Json(list))
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct RecipientParameters {
+    /// E.g. `tag:rust AND country:de`; everyone on the list if absent.
+    segment: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Recipients {
+    count: usize,
+    recipients: Vec<Subscriber>,
+}
+
+/// Whom an issue sent to the list, optionally narrowed to a segment, would
+/// reach.
+#[tracing::instrument(name = "Listing list recipients", skip(app_state))]
+pub async fn list_recipients(
+    State(app_state): State<AppState>,
+    Path(slug): Path<String>,
+    Query(parameters): Query<RecipientParameters>,
+) -> Result<Json<Recipients>, AdminError> {
+    let segment = parameters
+        .segment
+        .filter(|segment| !segment.trim().is_empty())
+        .map(|segment| Segment::parse(&segment, &app_state.segmentation.attribute_keys))
+        .transpose()
+        .map_err(|e| AdminError::BadRequest(e.to_string()))?;
+    let list = app_state
+        .subscribers
+        .find_list(&slug)
+        .await?
+        .ok_or(AdminError::NotFound)?;
+    let recipients = app_state
+        .subscribers
+        .recipients(list.id, segment.as_ref())
+        .await?;
+    Ok(Json(Recipients {
+        count: recipients.len(),
+        recipients,
+    }))
+}
+
+/// Deletes the list with its memberships; the subscribers themselves stay.
+/// The default list, which subscriptions without a `list` go to, cannot be
+/// deleted.
+#[tracing::instrument(name = "Deleting a list", skip(app_state))]
+pub async fn delete_list(
+    State(app_state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<StatusCode, AdminError> {
+    if slug == DEFAULT_LIST {
+        return Err(AdminError::Conflict("The default list cannot be deleted".into()));
+    }
+    if app_state.subscribers.delete_list(&slug).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AdminError::NotFound)
+    }
+}
@@ -0,0 +1,296 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    audit::{EventContext, SubscriptionEvent},
+    repository::{
+        DeleteMode, Membership, PageCursor, ProfileUpdate, Subscriber, SubscriberFilter, SubscriberProfile,
+    },
+    segment::{attribute_value, normalize_tag, ProfileRejection},
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+const STATUSES: [&str; 4] = ["pending", "confirmed", "deleted", "erased"];
+
+#[derive(Debug)]
+pub enum AdminError {
+    BadRequest(String),
+    NotFound,
+    /// The request clashes with existing data, e.g. a taken slug.
+    Conflict(String),
+    Unexpected(eyre::Report),
+}
+
+impl From<eyre::Report> for AdminError {
+    fn from(e: eyre::Report) -> Self {
+        AdminError::Unexpected(e)
+    }
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        match self {
+            AdminError::BadRequest(message) => (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "bad_request", "message": message })),
+            )
+                .into_response(),
+            AdminError::NotFound => (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "error": "not_found" })),
+            )
+                .into_response(),
+            AdminError::Conflict(message) => (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "error": "conflict", "message": message })),
+            )
+                .into_response(),
+            AdminError::Unexpected(e) => {
+                tracing::error!("Admin request failed: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ListParameters {
+    status: Option<String>,
+    /// RFC 3339; inclusive.
+    subscribed_after: Option<DateTime<Utc>>,
+    /// RFC 3339; exclusive.
+    subscribed_before: Option<DateTime<Utc>>,
+    /// Prefix of the email or name.
+    q: Option<String>,
+    /// `next_cursor` of the previous page.
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriberPage {
+    subscribers: Vec<Subscriber>,
+    /// Pass back as `cursor` for the next page; absent on the last one.
+    next_cursor: Option<String>,
+}
+
+pub(super) fn check_status(status: Option<&str>) -> Result<(), AdminError> {
+    match status {
+        Some(status) if !STATUSES.contains(&status) => Err(AdminError::BadRequest(format!(
+            "status must be one of {}",
+            STATUSES.join(", ")
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[tracing::instrument(name = "Listing subscribers", skip(app_state))]
+pub async fn list_subscribers(
+    State(app_state): State<AppState>,
+    Query(parameters): Query<ListParameters>,
+) -> Result<Json<SubscriberPage>, AdminError> {
+    check_status(parameters.status.as_deref())?;
+    let limit = parameters.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AdminError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    let cursor = parameters
+        .cursor
+        .as_deref()
+        .map(str::parse::<PageCursor>)
+        .transpose()
+        .map_err(|e| AdminError::BadRequest(e.to_string()))?;
+    let filter = SubscriberFilter {
+        status: parameters.status,
+        subscribed_after: parameters.subscribed_after,
+        subscribed_before: parameters.subscribed_before,
+        search: parameters.q.filter(|q| !q.is_empty()),
+    };
+
+    // One extra row tells us whether there is another page.
+    let mut subscribers = app_state.subscribers.list(&filter, cursor, limit + 1).await?;
+    let next_cursor = if subscribers.len() as i64 > limit {
+        subscribers.truncate(limit as usize);
+        subscribers.last().map(|s| PageCursor::after(s).to_string())
+    } else {
+        None
+    };
+    Ok(Json(SubscriberPage {
+        subscribers,
+        next_cursor,
+    }))
+}
+
+#[derive(serde::Serialize)]
+pub struct ConfirmationToken {
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriberDetails {
+    #[serde(flatten)]
+    subscriber: Subscriber,
+    #[serde(flatten)]
+    profile: SubscriberProfile,
+    lists: Vec<Membership>,
+    /// Confirmation emails sent, newest first.
+    confirmation_tokens: Vec<ConfirmationToken>,
+}
+
+#[tracing::instrument(name = "Getting a subscriber", skip(app_state))]
+pub async fn get_subscriber(
+    State(app_state): State<AppState>,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Json<SubscriberDetails>, AdminError> {
+    let subscriber = app_state
+        .subscribers
+        .find_by_id(subscriber_id)
+        .await?
+        .ok_or(AdminError::NotFound)?;
+    let ttl = app_state.confirmation.token_ttl();
+    let confirmation_tokens = app_state
+        .subscribers
+        .token_history(subscriber_id)
+        .await?
+        .into_iter()
+        .map(|created_at| ConfirmationToken {
+            created_at,
+            expires_at: created_at + ttl,
+        })
+        .collect();
+    let profile = app_state.subscribers.profile(subscriber_id).await?;
+    let lists = app_state.subscribers.memberships(subscriber_id).await?;
+    Ok(Json(SubscriberDetails {
+        subscriber,
+        profile,
+        lists,
+        confirmation_tokens,
+    }))
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ProfileChanges {
+    /// Attributes to set; `null` removes one.
+    #[serde(default)]
+    attributes: BTreeMap<String, Option<String>>,
+    #[serde(default)]
+    add_tags: Vec<String>,
+    #[serde(default)]
+    remove_tags: Vec<String>,
+}
+
+/// Sets attributes and tags; attribute keys must be configured in
+/// `segmentation.attribute_keys`, but any tag goes.
+#[tracing::instrument(name = "Updating a subscriber profile", skip(app_state))]
+pub async fn update_subscriber_profile(
+    State(app_state): State<AppState>,
+    Path(subscriber_id): Path<Uuid>,
+    Json(changes): Json<ProfileChanges>,
+) -> Result<Json<SubscriberProfile>, AdminError> {
+    let bad_request = |e: ProfileRejection| AdminError::BadRequest(e.to_string());
+    let keys = &app_state.segmentation.attribute_keys;
+    let mut update = ProfileUpdate::default();
+    for (key, value) in changes.attributes {
+        let value = match value {
+            Some(value) => Some(attribute_value(&key, &value, keys).map_err(bad_request)?),
+            None if keys.contains(&key) => None,
+            None => return Err(bad_request(ProfileRejection::UnknownAttribute(key))),
+        };
+        update.attributes.insert(key, value);
+    }
+    let normalize = |tags: Vec<String>| {
+        tags.iter()
+            .map(|tag| normalize_tag(tag))
+            .collect::<Result<BTreeSet<_>, _>>()
+            .map_err(bad_request)
+    };
+    update.add_tags = normalize(changes.add_tags)?;
+    update.remove_tags = normalize(changes.remove_tags)?;
+
+    let subscriber = app_state
+        .subscribers
+        .find_by_id(subscriber_id)
+        .await?
+        .ok_or(AdminError::NotFound)?;
+    if subscriber.status == "erased" {
+        return Err(AdminError::Conflict("The subscriber has been erased".into()));
+    }
+    let mut transaction = app_state.subscribers.begin().await?;
+    transaction.update_profile(subscriber_id, &update).await?;
+    transaction.commit().await?;
+    Ok(Json(app_state.subscribers.profile(subscriber_id).await?))
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteParameter {
+    #[default]
+    Soft,
+    Hard,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct DeleteParameters {
+    #[serde(default)]
+    mode: DeleteParameter,
+}
+
+/// Soft-deletes by default; `?mode=hard` removes the subscriber entirely.
+#[tracing::instrument(name = "Deleting a subscriber", skip(app_state, context))]
+pub async fn delete_subscriber(
+    State(app_state): State<AppState>,
+    context: EventContext,
+    Path(subscriber_id): Path<Uuid>,
+    Query(parameters): Query<DeleteParameters>,
+) -> Result<StatusCode, AdminError> {
+    let mode = match parameters.mode {
+        DeleteParameter::Soft => DeleteMode::Soft,
+        DeleteParameter::Hard => DeleteMode::Hard,
+    };
+    let context = context.with_source("admin");
+    if app_state
+        .subscribers
+        .delete(subscriber_id, mode, &context)
+        .await?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AdminError::NotFound)
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriberEvents {
+    events: Vec<SubscriptionEvent>,
+}
+
+/// The subscriber's consent audit trail, oldest first.
+#[tracing::instrument(name = "Listing subscriber events", skip(app_state))]
+pub async fn list_subscriber_events(
+    State(app_state): State<AppState>,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Json<SubscriberEvents>, AdminError> {
+    if app_state
+        .subscribers
+        .find_by_id(subscriber_id)
+        .await?
+        .is_none()
+    {
+        return Err(AdminError::NotFound);
+    }
+    let events = app_state.subscribers.events(subscriber_id).await?;
+    Ok(Json(SubscriberEvents { events }))
+}
@@ -0,0 +1,206 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use minijinja::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app_state::AppState,
+    archive::{atom_feed, published_at},
+    i18n::DEFAULT_LOCALE,
+    repository::NewsletterIssue,
+    templates::Page,
+};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ArchiveParameters {
+    /// Counting from 1, the newest issues.
+    page: Option<i64>,
+}
+
+fn internal_error(e: impl std::fmt::Debug) -> Response {
+    tracing::error!("Failed to serve the archive: {:?}", e);
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// `at` as an HTTP date, e.g. `Sun, 19 Oct 2026 09:00:00 GMT`.
+fn http_date(at: DateTime<Utc>) -> String {
+    at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Whether the client's copy, going by its conditional request headers,
+/// is still current. `If-Modified-Since` only counts without an
+/// `If-None-Match`, as RFC 9110 asks.
+fn not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        let Ok(if_none_match) = if_none_match.to_str() else {
+            return false;
+        };
+        return if_none_match.split(',').map(str::trim).any(|candidate| {
+            candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+        });
+    }
+    let if_modified_since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+    match (if_modified_since, last_modified) {
+        (Some(since), Some(last_modified)) => last_modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+/// Responds with `body`, validators for conditional requests and caching
+/// headers a CDN can go by; or with `304 Not Modified` if the client's copy
+/// is current.
+fn cacheable(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    content_type: &'static str,
+    body: String,
+    last_modified: Option<DateTime<Utc>>,
+) -> Response {
+    let etag = format!("\"{}\"", hex::encode(Sha256::digest(body.as_bytes())));
+    let mut response = if not_modified(headers, &etag, last_modified) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        ([(header::CONTENT_TYPE, content_type)], body).into_response()
+    };
+    let response_headers = response.headers_mut();
+    let cache_control = format!("public, max-age={}", app_state.archive.max_age_seconds);
+    for (name, value) in [
+        (header::ETAG, Some(etag)),
+        (header::LAST_MODIFIED, last_modified.map(http_date)),
+        (header::CACHE_CONTROL, Some(cache_control)),
+    ] {
+        if let Some(value) = value.and_then(|value| HeaderValue::from_str(&value).ok()) {
+            response_headers.insert(name, value);
+        }
+    }
+    response
+}
+
+/// The newest publication date among `issues`.
+fn last_modified(issues: &[NewsletterIssue]) -> Option<DateTime<Utc>> {
+    issues.iter().map(published_at).max()
+}
+
+/// Links we build ourselves, which need no escaping.
+fn link(url: url::Url) -> Value {
+    Value::from_safe_string(url.into())
+}
+
+/// A page of the public archive, newest issues first. Pages past the last
+/// one are not found, the first one always is.
+#[tracing::instrument(name = "Listing archived issues", skip(app_state, headers))]
+pub async fn list_archived_issues(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(parameters): Query<ArchiveParameters>,
+) -> Response {
+    let page = parameters.page.unwrap_or(1);
+    if page < 1 {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let page_size = app_state.archive.page_size.max(1);
+    // One more than fits tells whether there is an older page.
+    let mut issues = match app_state
+        .subscribers
+        .archived_issues(page_size + 1, (page - 1).saturating_mul(page_size))
+        .await
+    {
+        Ok(issues) => issues,
+        Err(e) => return internal_error(e),
+    };
+    let has_older = issues.len() as i64 > page_size;
+    issues.truncate(page_size as usize);
+    if issues.is_empty() && page > 1 {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let links = &app_state.links;
+    let listed: Vec<_> = issues
+        .iter()
+        .map(|issue| {
+            minijinja::context! {
+                title => issue.title,
+                link => link(links.archive_link(&issue.slug)),
+                sent_on => published_at(issue).format("%Y-%m-%d").to_string(),
+            }
+        })
+        .collect();
+    let body = match app_state.templates.render_page(
+        Page::Archive,
+        minijinja::context! {
+            locale => DEFAULT_LOCALE,
+            title => app_state.archive.title,
+            issues => listed,
+            newer_link => (page > 1).then(|| link(links.archive_page_link(page - 1))),
+            older_link => has_older.then(|| link(links.archive_page_link(page + 1))),
+            feed_link => link(links.archive_feed_link()),
+        },
+    ) {
+        Ok(body) => body,
+        Err(e) => return internal_error(e),
+    };
+    cacheable(&app_state, &headers, "text/html; charset=utf-8", body, last_modified(&issues))
+}
+
+#[tracing::instrument(name = "Showing an archived issue", skip(app_state, headers))]
+pub async fn get_archived_issue(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+) -> Response {
+    let issue = match app_state.subscribers.find_archived_issue(&slug).await {
+        Ok(Some(issue)) => issue,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => return internal_error(e),
+    };
+    let links = &app_state.links;
+    let body = match app_state.templates.render_page(
+        Page::ArchivedIssue,
+        minijinja::context! {
+            locale => DEFAULT_LOCALE,
+            title => app_state.archive.title,
+            issue_title => issue.title,
+            sent_on => published_at(&issue).format("%Y-%m-%d").to_string(),
+            html_content => issue.html_content,
+            archive_link => link(links.archive_page_link(1)),
+            feed_link => link(links.archive_feed_link()),
+        },
+    ) {
+        Ok(body) => body,
+        Err(e) => return internal_error(e),
+    };
+    cacheable(
+        &app_state,
+        &headers,
+        "text/html; charset=utf-8",
+        body,
+        Some(published_at(&issue)),
+    )
+}
+
+/// The newest page of the archive as an Atom feed.
+#[tracing::instrument(name = "Serving the archive feed", skip(app_state, headers))]
+pub async fn archive_feed(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    let issues = match app_state
+        .subscribers
+        .archived_issues(app_state.archive.page_size.max(1), 0)
+        .await
+    {
+        Ok(issues) => issues,
+        Err(e) => return internal_error(e),
+    };
+    let body = atom_feed(&app_state.archive.title, &app_state.links, &issues);
+    cacheable(
+        &app_state,
+        &headers,
+        "application/atom+xml; charset=utf-8",
+        body,
+        last_modified(&issues),
+    )
+}
@@ -0,0 +1,450 @@
+use std::collections::BTreeSet;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, header::ACCEPT_LANGUAGE, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use chrono::{Duration, Utc};
+use minijinja::Value;
+
+use super::privacy::verified_subscriber;
+use crate::{
+    app_state::AppState,
+    audit::EventContext,
+    domain::{SubscriberEmail, SubscriberName},
+    i18n,
+    preferences::{EmailChangeRequest, MAX_PAUSE_WEEKS, SESSION_COOKIE},
+    privacy::{suppression_hash, LinkRejection, PrivacyAction, SignedRequest},
+    repository::{EmailChange, PreferenceUpdate, Subscriber},
+    templates::{EmailTemplate, Page},
+};
+
+/// The pauses offered, in weeks; any up to [`MAX_PAUSE_WEEKS`] is accepted.
+const PAUSE_OPTIONS: [i64; 5] = [2, 4, 8, 12, MAX_PAUSE_WEEKS];
+
+fn internal_error(e: impl std::fmt::Debug) -> Response {
+    tracing::error!("Failed to serve the preference center: {:?}", e);
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+fn render_page(app_state: &AppState, page: Page, context: Value) -> Response {
+    match app_state.templates.render_page(page, context) {
+        Ok(page) => Html(page).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// The `Set-Cookie` value for `session`, or one clearing the cookie when
+/// `None`. `SameSite=Lax` keeps other sites from posting the forms with it,
+/// while still sending it on the redirect from the emailed link.
+fn session_cookie(app_state: &AppState, session: Option<&str>) -> String {
+    let path = app_state.links.preferences_link();
+    let max_age = match session {
+        Some(_) => app_state.preference_sessions.ttl().num_seconds(),
+        None => 0,
+    };
+    let secure = if path.scheme() == "https" { "; Secure" } else { "" };
+    format!(
+        "{}={}; Path={}; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        SESSION_COOKIE,
+        session.unwrap_or_default(),
+        path.path(),
+        max_age,
+        secure
+    )
+}
+
+/// The confirmed subscriber whose session cookie came with the request, or
+/// the page to request a new link.
+async fn session_subscriber(app_state: &AppState, headers: &HeaderMap) -> Result<Subscriber, Response> {
+    let subscriber_id = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .and_then(|(_, session)| app_state.preference_sessions.verify(session, Utc::now()));
+    if let Some(subscriber_id) = subscriber_id {
+        match app_state.subscribers.find_by_id(subscriber_id).await {
+            Ok(Some(subscriber)) if subscriber.status == "confirmed" => return Ok(subscriber),
+            Ok(_) => {}
+            Err(e) => return Err(internal_error(e)),
+        }
+    }
+    let accept_language = headers
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok());
+    let request_link = app_state.links.url(&["privacy", "requests"]);
+    let page = render_page(
+        app_state,
+        Page::PreferencesSignedOut,
+        minijinja::context! {
+            locale => i18n::negotiate(accept_language),
+            request_link => Value::from_safe_string(request_link.into()),
+        },
+    );
+    Err((StatusCode::UNAUTHORIZED, [(header::SET_COOKIE, session_cookie(app_state, None))], page).into_response())
+}
+
+/// Renders the preference center for `subscriber` as they are now, with
+/// the translation keys of a notice about what they did or of what was
+/// wrong with it.
+async fn preferences_page(
+    app_state: &AppState,
+    subscriber: &Subscriber,
+    notice: Option<&str>,
+    error: Option<&str>,
+) -> Response {
+    let (lists, memberships, paused_until) = match tokio::try_join!(
+        app_state.subscribers.lists(),
+        app_state.subscribers.memberships(subscriber.id),
+        app_state.subscribers.paused_until(subscriber.id),
+    ) {
+        Ok(found) => found,
+        Err(e) => return internal_error(e),
+    };
+    let lists: Vec<_> = lists
+        .iter()
+        .map(|list| {
+            let subscribed = memberships
+                .iter()
+                .any(|m| m.list == list.slug && (m.status == "confirmed" || m.status == "pending"));
+            minijinja::context! { slug => list.slug, name => list.name, subscribed }
+        })
+        .collect();
+    let links = &app_state.links;
+    let page = render_page(
+        app_state,
+        Page::Preferences,
+        minijinja::context! {
+            locale => subscriber.locale,
+            name => subscriber.name,
+            email => subscriber.email,
+            lists,
+            paused_until => paused_until
+                .filter(|paused_until| *paused_until > Utc::now())
+                .map(|paused_until| paused_until.format("%Y-%m-%d").to_string()),
+            pause_options => PAUSE_OPTIONS,
+            notice,
+            error,
+            preferences_link => Value::from_safe_string(links.preferences_link().into()),
+            email_change_link => Value::from_safe_string(links.url(&["preferences", "email"]).into()),
+            unsubscribe_link => Value::from_safe_string(links.url(&["preferences", "unsubscribe"]).into()),
+        },
+    );
+    match error {
+        Some(_) => (StatusCode::BAD_REQUEST, page).into_response(),
+        None => page,
+    }
+}
+
+/// Follows the emailed link: starts a session and sends the subscriber on
+/// to the preference center. Nothing changes until they submit a form
+/// there, so mail scanners following the link do no harm.
+#[tracing::instrument(name = "Opening the preference center", skip_all)]
+pub async fn open_preferences(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(request): Query<SignedRequest>,
+) -> Response {
+    let subscriber = match verified_subscriber(&app_state, &headers, PrivacyAction::Preferences, &request).await {
+        Ok(subscriber) => subscriber,
+        Err(response) => return response,
+    };
+    match subscriber.status.as_str() {
+        "confirmed" => {}
+        "unsubscribed" => {
+            return render_page(
+                &app_state,
+                Page::Unsubscribed,
+                minijinja::context! { locale => subscriber.locale },
+            )
+        }
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    }
+    let session = app_state.preference_sessions.sign(subscriber.id, Utc::now());
+    (
+        [(header::SET_COOKIE, session_cookie(&app_state, Some(&session)))],
+        Redirect::to(app_state.links.preferences_link().as_str()),
+    )
+        .into_response()
+}
+
+pub async fn show_preferences(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    match session_subscriber(&app_state, &headers).await {
+        Ok(subscriber) => preferences_page(&app_state, &subscriber, None, None).await,
+        Err(response) => response,
+    }
+}
+
+/// Checks the submitted form against `subscriber`'s current preferences;
+/// the translation key of the problem if it is invalid.
+async fn check_preferences(
+    app_state: &AppState,
+    subscriber: &Subscriber,
+    form: &[(String, String)],
+) -> Result<Result<PreferenceUpdate, &'static str>, eyre::Report> {
+    let field = |name: &str| form.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+    let Ok(name) = SubscriberName::parse(field("name").unwrap_or_default().trim().to_owned()) else {
+        return Ok(Err("preferences-error-name"));
+    };
+    let chosen: BTreeSet<_> = form
+        .iter()
+        .filter(|(key, _)| key == "list")
+        .map(|(_, slug)| slug.as_str())
+        .collect();
+    let lists = app_state.subscribers.lists().await?;
+    let list_ids: BTreeSet<_> = lists
+        .iter()
+        .filter(|list| chosen.contains(list.slug.as_str()))
+        .map(|list| list.id)
+        .collect();
+    if list_ids.is_empty() || list_ids.len() != chosen.len() {
+        return Ok(Err("preferences-error-lists"));
+    }
+    let paused_until = match field("pause_weeks").unwrap_or_default() {
+        "" => app_state.subscribers.paused_until(subscriber.id).await?,
+        weeks => match weeks.parse::<i64>() {
+            Ok(0) => None,
+            Ok(weeks @ 1..=MAX_PAUSE_WEEKS) => Some(Utc::now() + Duration::weeks(weeks)),
+            _ => return Ok(Err("preferences-error-pause")),
+        },
+    };
+    Ok(Ok(PreferenceUpdate {
+        name: name.as_ref().to_owned(),
+        list_ids,
+        paused_until,
+    }))
+}
+
+/// Saves the name, lists and pause the subscriber chose. The form repeats
+/// `list` for each list they want to be on.
+#[tracing::instrument(name = "Updating preferences", skip_all)]
+pub async fn update_preferences(
+    State(app_state): State<AppState>,
+    context: EventContext,
+    headers: HeaderMap,
+    Form(form): Form<Vec<(String, String)>>,
+) -> Response {
+    let subscriber = match session_subscriber(&app_state, &headers).await {
+        Ok(subscriber) => subscriber,
+        Err(response) => return response,
+    };
+    let update = match check_preferences(&app_state, &subscriber, &form).await {
+        Ok(Ok(update)) => update,
+        Ok(Err(error)) => return preferences_page(&app_state, &subscriber, None, Some(error)).await,
+        Err(e) => return internal_error(e),
+    };
+    let context = context.with_source("preferences");
+    match app_state
+        .subscribers
+        .update_preferences(subscriber.id, &update, &context)
+        .await
+    {
+        Ok(true) => {}
+        // Unsubscribed since the session started.
+        Ok(false) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => return internal_error(e),
+    }
+    let subscriber = Subscriber {
+        name: update.name,
+        ..subscriber
+    };
+    preferences_page(&app_state, &subscriber, Some("preferences-page-saved"), None).await
+}
+
+#[tracing::instrument(name = "Unsubscribing", skip_all)]
+pub async fn unsubscribe(
+    State(app_state): State<AppState>,
+    context: EventContext,
+    headers: HeaderMap,
+) -> Response {
+    let subscriber = match session_subscriber(&app_state, &headers).await {
+        Ok(subscriber) => subscriber,
+        Err(response) => return response,
+    };
+    let context = context.with_source("preferences");
+    if let Err(e) = app_state.subscribers.unsubscribe(subscriber.id, &context).await {
+        return internal_error(e);
+    }
+    // They are unsubscribed either way, so a failure is only logged.
+    if let Err(e) = send_unsubscribe_email(&app_state, &subscriber).await {
+        tracing::error!("Failed to send unsubscribe email: {:?}", e);
+    }
+    let page = render_page(
+        &app_state,
+        Page::Unsubscribed,
+        minijinja::context! { locale => subscriber.locale },
+    );
+    ([(header::SET_COOKIE, session_cookie(&app_state, None))], page).into_response()
+}
+
+async fn send_unsubscribe_email(app_state: &AppState, subscriber: &Subscriber) -> eyre::Result<()> {
+    let email = app_state.templates.render_email(
+        EmailTemplate::Unsubscribe,
+        minijinja::context! {
+            locale => subscriber.locale,
+            name => subscriber.name,
+        },
+    )?;
+    let recipient = SubscriberEmail::parse(subscriber.email.clone())
+        .map_err(|e| eyre::eyre!("Stored email is invalid: {:?}", e))?;
+    app_state
+        .email_client
+        .send_email(recipient, &email.subject, &email.html_body, &email.text_body)
+        .await?;
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailChangeForm {
+    email: String,
+}
+
+/// Sends a link to the new address to confirm the move there; until it is
+/// followed, everything keeps going to the old one. The notice is the same
+/// whether or not the address can be used, and the checks and sending
+/// happen after the response, so it does not tell who else is subscribed.
+/// Each subscriber, and each address, is only sent a link so often, so
+/// the endpoint cannot be used to send mail to arbitrary addresses.
+#[tracing::instrument(name = "Requesting an email change", skip_all)]
+pub async fn request_email_change(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Form(form): Form<EmailChangeForm>,
+) -> Response {
+    let subscriber = match session_subscriber(&app_state, &headers).await {
+        Ok(subscriber) => subscriber,
+        Err(response) => return response,
+    };
+    let new_email = match SubscriberEmail::parse(form.email.trim().to_owned()) {
+        Ok(email) if email.as_ref() != subscriber.email => email,
+        _ => return preferences_page(&app_state, &subscriber, None, Some("preferences-error-email")).await,
+    };
+    if app_state.email_validator.validate(&new_email).await.is_err() {
+        return preferences_page(&app_state, &subscriber, None, Some("preferences-error-email")).await;
+    }
+    let response = preferences_page(&app_state, &subscriber, Some("preferences-email-change-sent"), None).await;
+    let keys = [subscriber.id.to_string(), new_email.as_ref().to_owned()];
+    let Some(permit) = app_state
+        .email_change_requests
+        .admit_all(&[&keys[0], &keys[1]], Utc::now())
+    else {
+        tracing::info!("Email change request turned away");
+        return response;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = send_email_change(&app_state, &subscriber, new_email).await {
+            tracing::error!("Failed to send email change confirmation: {:?}", e);
+        }
+        drop(permit);
+    });
+    response
+}
+
+/// Emails the link confirming `new_email`, unless another subscriber has
+/// the address or it is suppressed.
+async fn send_email_change(
+    app_state: &AppState,
+    subscriber: &Subscriber,
+    new_email: SubscriberEmail,
+) -> eyre::Result<()> {
+    if app_state.subscribers.find_by_email(new_email.as_ref()).await?.is_some() {
+        return Ok(());
+    }
+    let hash = suppression_hash(new_email.as_ref());
+    if !app_state.subscribers.suppressed(&[hash]).await?.is_empty() {
+        return Ok(());
+    }
+    let request = app_state
+        .email_changes
+        .sign(subscriber.id, &subscriber.email, new_email.as_ref(), Utc::now());
+    let confirmation_link = app_state.links.email_change_link(&request);
+    let email = app_state.templates.render_email(
+        EmailTemplate::EmailChange,
+        minijinja::context! {
+            locale => subscriber.locale,
+            name => subscriber.name,
+            // We build the link ourselves, so it does not need escaping.
+            confirmation_link => Value::from_safe_string(confirmation_link.into()),
+        },
+    )?;
+    app_state
+        .email_client
+        .send_email(new_email, &email.subject, &email.html_body, &email.text_body)
+        .await?;
+    Ok(())
+}
+
+/// Follows the link sent to the new address, moving the subscription
+/// there. Following it again once it worked says so, rather than that the
+/// link is invalid, since mail scanners may have got there first; the
+/// link is checked before either.
+#[tracing::instrument(name = "Confirming an email change", skip_all)]
+pub async fn confirm_email_change(
+    State(app_state): State<AppState>,
+    context: EventContext,
+    headers: HeaderMap,
+    Query(request): Query<EmailChangeRequest>,
+) -> Response {
+    match app_state.email_changes.verify(&request, Utc::now()) {
+        Ok(()) => {}
+        Err(LinkRejection::Invalid) => return invalid_link(&app_state, &headers, StatusCode::UNAUTHORIZED),
+        Err(LinkRejection::Expired) => return invalid_link(&app_state, &headers, StatusCode::GONE),
+    }
+    let subscriber = match app_state.subscribers.find_by_id(request.subscriber_id).await {
+        Ok(subscriber) => subscriber,
+        Err(e) => return internal_error(e),
+    };
+    let Some(subscriber) = subscriber else {
+        return invalid_link(&app_state, &headers, StatusCode::UNAUTHORIZED);
+    };
+    let locale = subscriber.locale.as_str();
+    if subscriber.email == request.email {
+        return render_page(&app_state, Page::EmailChanged, minijinja::context! { locale });
+    }
+    // The address changed some other way since the link was sent.
+    if !app_state.email_changes.issued_from(&request, &subscriber.email) {
+        return invalid_link(&app_state, &headers, StatusCode::UNAUTHORIZED);
+    }
+    let change = app_state
+        .subscribers
+        .change_email(
+            subscriber.id,
+            &subscriber.email,
+            &request.email,
+            &suppression_hash(&request.email),
+            &context.with_source("preferences"),
+        )
+        .await;
+    let reason = match change {
+        Ok(EmailChange::Changed) => {
+            return render_page(&app_state, Page::EmailChanged, minijinja::context! { locale })
+        }
+        Ok(EmailChange::Stale) => "email-change-failed-stale",
+        Ok(EmailChange::Taken) => "email-change-failed-taken",
+        Ok(EmailChange::Suppressed) => "email-change-failed-suppressed",
+        Err(e) => return internal_error(e),
+    };
+    let page = render_page(
+        &app_state,
+        Page::EmailChangeFailed,
+        minijinja::context! { locale, reason },
+    );
+    (StatusCode::CONFLICT, page).into_response()
+}
+
+fn invalid_link(app_state: &AppState, headers: &HeaderMap, status: StatusCode) -> Response {
+    let accept_language = headers
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok());
+    let page = render_page(
+        app_state,
+        Page::InvalidPrivacyLink,
+        minijinja::context! { locale => i18n::negotiate(accept_language) },
+    );
+    (status, page).into_response()
+}
@@ -0,0 +1,279 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, header::ACCEPT_LANGUAGE, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Form, Json,
+};
+use chrono::{DateTime, Utc};
+use eyre::Result;
+
+use crate::{
+    app_state::AppState,
+    audit::{EventContext, SubscriptionEvent},
+    domain::SubscriberEmail,
+    i18n,
+    privacy::{suppression_hash, LinkRejection, PrivacyAction, SignedRequest},
+    repository::{Membership, ReceivedIssue, Subscriber, SubscriberProfile, TrackingEvent},
+    templates::{EmailTemplate, Page},
+};
+
+#[derive(serde::Deserialize)]
+pub struct PrivacyRequestForm {
+    email: String,
+    action: PrivacyAction,
+}
+
+/// Everything we hold about a subscriber, as handed out on an access request.
+#[derive(serde::Serialize)]
+pub struct SubscriberData {
+    exported_at: DateTime<Utc>,
+    /// Includes how and when they gave consent.
+    subscriber: Subscriber,
+    /// Their tags and attributes.
+    #[serde(flatten)]
+    profile: SubscriberProfile,
+    /// The lists they joined, and when.
+    lists: Vec<Membership>,
+    confirmation_tokens: Vec<IssuedToken>,
+    /// Every subscription, confirmation and unsubscription, with where it
+    /// came from.
+    consent_records: Vec<SubscriptionEvent>,
+    /// The issues sent to them, and whether each arrived.
+    received_issues: Vec<ReceivedIssue>,
+    /// The opens and clicks recorded for them.
+    tracking_events: Vec<TrackingEvent>,
+}
+
+#[derive(serde::Serialize)]
+pub struct IssuedToken {
+    created_at: DateTime<Utc>,
+}
+
+/// Emails a signed access or erasure link to the address, if it belongs to
+/// a subscriber. The response is the same either way, and the lookup and
+/// sending happen after it, so neither the answer nor its timing tells
+/// who is subscribed. Requests are throttled before anything is queued,
+/// so the endpoint cannot be used to flood an inbox.
+#[tracing::instrument(name = "Requesting a privacy action", skip(app_state, form), fields(action = ?form.action))]
+pub async fn request_privacy_action(
+    State(app_state): State<AppState>,
+    Form(form): Form<PrivacyRequestForm>,
+) -> StatusCode {
+    let email = form.email.trim().to_owned();
+    let Some(permit) = app_state.privacy_requests.admit(&email, Utc::now()) else {
+        tracing::info!("Privacy request turned away");
+        return StatusCode::ACCEPTED;
+    };
+    tokio::spawn(async move {
+        match app_state.subscribers.find_by_email(&email).await {
+            Ok(Some(subscriber)) => {
+                if let Err(e) = send_privacy_email(&app_state, form.action, &subscriber).await {
+                    tracing::error!("Failed to send privacy email: {:?}", e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to look up subscriber: {:?}", e),
+        }
+        drop(permit);
+    });
+    StatusCode::ACCEPTED
+}
+
+async fn send_privacy_email(
+    app_state: &AppState,
+    action: PrivacyAction,
+    subscriber: &Subscriber,
+) -> Result<()> {
+    let request = app_state.privacy_links.sign(action, subscriber.id, Utc::now());
+    let privacy_link = app_state.links.privacy_link(action, &request);
+    let template = match action {
+        PrivacyAction::Access => EmailTemplate::DataAccess,
+        PrivacyAction::Erasure => EmailTemplate::Erasure,
+        PrivacyAction::Preferences => EmailTemplate::Preferences,
+    };
+    let email = app_state.templates.render_email(
+        template,
+        minijinja::context! {
+            locale => subscriber.locale,
+            name => subscriber.name,
+            // We build the link ourselves, so it does not need escaping.
+            privacy_link => minijinja::Value::from_safe_string(privacy_link.into()),
+        },
+    )?;
+    let recipient = SubscriberEmail::parse(subscriber.email.clone())
+        .map_err(|e| eyre::eyre!("Stored email is invalid: {:?}", e))?;
+    app_state
+        .email_client
+        .send_email(recipient, &email.subject, &email.html_body, &email.text_body)
+        .await?;
+    Ok(())
+}
+
+/// The subscriber a privacy link was issued for, or the page explaining
+/// why it cannot be used.
+pub(crate) async fn verified_subscriber(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    action: PrivacyAction,
+    request: &SignedRequest,
+) -> Result<Subscriber, Response> {
+    let status = match app_state.privacy_links.verify(action, request, Utc::now()) {
+        Err(LinkRejection::Invalid) => StatusCode::UNAUTHORIZED,
+        Err(LinkRejection::Expired) => StatusCode::GONE,
+        Ok(subscriber_id) => match app_state.subscribers.find_by_id(subscriber_id).await {
+            Ok(Some(subscriber)) => return Ok(subscriber),
+            Ok(None) => StatusCode::NOT_FOUND,
+            Err(e) => {
+                tracing::error!("Failed to look up subscriber: {:?}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+        },
+    };
+    let accept_language = headers
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok());
+    let locale = i18n::negotiate(accept_language);
+    Err(render_page(app_state, Page::InvalidPrivacyLink, locale, None)
+        .map(|page| (status, page).into_response())
+        .unwrap_or_else(|status| status.into_response()))
+}
+
+fn render_page(
+    app_state: &AppState,
+    page: Page,
+    locale: &str,
+    erasure_link: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    app_state
+        .templates
+        .render_page(page, minijinja::context! { locale, erasure_link })
+        .map(Html)
+        .map_err(|e| {
+            tracing::error!("Failed to render privacy page: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Downloads the subscriber's data as JSON.
+#[tracing::instrument(name = "Answering an access request", skip_all)]
+pub async fn access_data(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(request): Query<SignedRequest>,
+) -> Response {
+    let subscriber = match verified_subscriber(&app_state, &headers, PrivacyAction::Access, &request).await {
+        Ok(subscriber) => subscriber,
+        Err(response) => return response,
+    };
+    let confirmation_tokens = match app_state.subscribers.token_history(subscriber.id).await {
+        Ok(created_at) => created_at
+            .into_iter()
+            .map(|created_at| IssuedToken { created_at })
+            .collect(),
+        Err(e) => {
+            tracing::error!("Failed to fetch subscriber tokens: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let profile = match app_state.subscribers.profile(subscriber.id).await {
+        Ok(profile) => profile,
+        Err(e) => {
+            tracing::error!("Failed to fetch subscriber profile: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let lists = match app_state.subscribers.memberships(subscriber.id).await {
+        Ok(lists) => lists,
+        Err(e) => {
+            tracing::error!("Failed to fetch subscriber lists: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let consent_records = match app_state.subscribers.events(subscriber.id).await {
+        Ok(events) => events,
+        Err(e) => {
+            tracing::error!("Failed to fetch subscriber events: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let received_issues = match app_state.subscribers.received_issues(subscriber.id).await {
+        Ok(issues) => issues,
+        Err(e) => {
+            tracing::error!("Failed to fetch subscriber deliveries: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let tracking_events = match app_state.subscribers.tracking_events(subscriber.id).await {
+        Ok(events) => events,
+        Err(e) => {
+            tracing::error!("Failed to fetch subscriber tracking events: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let data = SubscriberData {
+        exported_at: Utc::now(),
+        subscriber,
+        profile,
+        lists,
+        confirmation_tokens,
+        consent_records,
+        received_issues,
+        tracking_events,
+    };
+    (
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"my-data.json\"",
+        )],
+        Json(data),
+    )
+        .into_response()
+}
+
+/// Asks for confirmation before erasing: mail scanners follow links, but
+/// they do not submit forms.
+pub async fn confirm_erasure(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(request): Query<SignedRequest>,
+) -> Response {
+    let subscriber = match verified_subscriber(&app_state, &headers, PrivacyAction::Erasure, &request).await {
+        Ok(subscriber) => subscriber,
+        Err(response) => return response,
+    };
+    let erasure_link = app_state.links.privacy_link(PrivacyAction::Erasure, &request);
+    render_page(
+        &app_state,
+        Page::ConfirmErasure,
+        &subscriber.locale,
+        Some(erasure_link.into()),
+    )
+    .into_response()
+}
+
+#[tracing::instrument(name = "Erasing a subscriber", skip_all)]
+pub async fn erase_data(
+    State(app_state): State<AppState>,
+    context: EventContext,
+    headers: HeaderMap,
+    Query(request): Query<SignedRequest>,
+) -> Response {
+    let subscriber = match verified_subscriber(&app_state, &headers, PrivacyAction::Erasure, &request).await {
+        Ok(subscriber) => subscriber,
+        Err(response) => return response,
+    };
+    // Following the link twice must not suppress the placeholder address.
+    if subscriber.status != "erased" {
+        let hash = suppression_hash(&subscriber.email);
+        let context = context.with_source("privacy_request");
+        if let Err(e) = app_state
+            .subscribers
+            .erase(subscriber.id, &hash, &context)
+            .await
+        {
+            tracing::error!("Failed to erase subscriber: {:?}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+    render_page(&app_state, Page::Erased, &subscriber.locale, None).into_response()
+}
@@ -1,16 +1,25 @@
-use std::ops::DerefMut;
-
-use axum::{extract::State, Form};
-use chrono::Utc;
+use axum::{
+    extract::State,
+    http::{header::ACCEPT_LANGUAGE, HeaderMap},
+    response::{IntoResponse, Response},
+    Form, Json,
+};
 use hyper::StatusCode;
+use chrono::Utc;
 use rand::{distr::Alphanumeric, rng, Rng};
-use sqlx::{Acquire, Database, Executor, Transaction};
-use uuid::Uuid;
+use std::collections::{BTreeMap, HashSet};
 
 use crate::{
     app_state::AppState,
-    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    audit::EventContext,
+    configuration::SegmentationSettings,
+    domain::{EmailRejection, NewSubscriber, SubscriberEmail, SubscriberName},
     email_client::EmailClient,
+    i18n,
+    links::LinkBuilder,
+    repository::{MailingList, ProfileUpdate, DEFAULT_LIST},
+    segment::{attribute_value, normalize_tag, ProfileRejection},
+    templates::{EmailTemplate, Templates},
 };
 use eyre::Result;
 
@@ -18,20 +27,107 @@ use eyre::Result;
 pub struct FormData {
     name: String,
     email: String,
+    /// Preferred language; the `Accept-Language` header is used if absent.
+    #[serde(default)]
+    locale: Option<String>,
+    /// Which signup form this came from, kept in the consent trail.
+    #[serde(default)]
+    source: Option<String>,
+    /// Slug of the list to subscribe to; the default list if absent.
+    #[serde(default)]
+    list: Option<String>,
+    /// Comma-separated; only `segmentation.signup_tags` are accepted.
+    #[serde(default)]
+    tags: Option<String>,
+    /// Every other field, which must be one of `segmentation.attribute_keys`.
+    #[serde(flatten)]
+    attributes: BTreeMap<String, String>,
+}
+
+impl FormData {
+    /// Takes the tags and attributes out of the form. Blank attributes are
+    /// skipped, as forms send their optional fields empty.
+    fn take_profile(&mut self, settings: &SegmentationSettings) -> Result<ProfileUpdate, ProfileRejection> {
+        let mut update = ProfileUpdate::default();
+        for (key, value) in std::mem::take(&mut self.attributes) {
+            if !value.trim().is_empty() {
+                let value = attribute_value(&key, &value, &settings.attribute_keys)?;
+                update.attributes.insert(key, Some(value));
+            }
+        }
+        for tag in self.tags.take().iter().flat_map(|tags| tags.split(',')) {
+            if tag.trim().is_empty() {
+                continue;
+            }
+            let tag = normalize_tag(tag)?;
+            if !settings.signup_tags.iter().any(|allowed| allowed.eq_ignore_ascii_case(&tag)) {
+                return Err(ProfileRejection::TagNotAllowed(tag));
+            }
+            update.add_tags.insert(tag);
+        }
+        Ok(update)
+    }
 }
 
 impl TryFrom<FormData> for NewSubscriber {
-    type Error = eyre::Report;
+    type Error = SubscribeError;
 
     fn try_from(form: FormData) -> std::result::Result<Self, Self::Error> {
         Ok(NewSubscriber {
             email: SubscriberEmail::parse(form.email)?,
-            name: SubscriberName::parse(form.name)?,
+            name: SubscriberName::parse(form.name).map_err(|_| SubscribeError::InvalidName)?,
+            locale: i18n::negotiate(form.locale.as_deref()).to_owned(),
         })
     }
 }
 
-fn generate_subscription_token() -> String {
+#[derive(Debug)]
+pub enum SubscribeError {
+    InvalidEmail(EmailRejection),
+    InvalidName,
+    UnknownList,
+    InvalidProfile(ProfileRejection),
+    Unexpected,
+}
+
+impl From<EmailRejection> for SubscribeError {
+    fn from(rejection: EmailRejection) -> Self {
+        SubscribeError::InvalidEmail(rejection)
+    }
+}
+
+impl IntoResponse for SubscribeError {
+    fn into_response(self) -> Response {
+        match self {
+            SubscribeError::InvalidEmail(rejection) => (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "invalid_email",
+                    "reason": rejection.code(),
+                    "message": rejection.to_string(),
+                })),
+            )
+                .into_response(),
+            SubscribeError::InvalidName => StatusCode::BAD_REQUEST.into_response(),
+            SubscribeError::UnknownList => (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "unknown_list" })),
+            )
+                .into_response(),
+            SubscribeError::InvalidProfile(rejection) => (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": rejection.code(),
+                    "message": rejection.to_string(),
+                })),
+            )
+                .into_response(),
+            SubscribeError::Unexpected => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
+
+pub(crate) fn generate_subscription_token() -> String {
     let mut rng = rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -41,7 +137,7 @@ fn generate_subscription_token() -> String {
 
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(app_state, form),
+    skip(app_state, context, form),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
@@ -49,125 +145,324 @@ fn generate_subscription_token() -> String {
 )]
 pub async fn subscribe(
     State(app_state): State<AppState>,
-    Form(form): Form<FormData>,
-) -> Result<(), StatusCode> {
-    tracing::info!("Saving new subscriber details in the database");
-    let new_subscriber: NewSubscriber = form.try_into().map_err(|_| StatusCode::BAD_REQUEST)?;
-
-    let mut transaction:Transaction<'_, _> =
-        match app_state.connection_pool.begin().await {
-            Ok(transaction) => transaction,
-            Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
-        };
-
-    let subscriber_id = match insert_subscriber(&mut transaction, &new_subscriber).await {
-        Ok(subscriber_id) => subscriber_id,
-        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
-    };
+    mut context: EventContext,
+    headers: HeaderMap,
+    Form(mut form): Form<FormData>,
+) -> Result<(), SubscribeError> {
+    context.source = form.source.take();
+    let slug = form.list.take().unwrap_or_else(|| DEFAULT_LIST.to_owned());
+    let list = app_state
+        .subscribers
+        .find_list(&slug)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to look up list {}: {:?}", slug, e);
+            SubscribeError::Unexpected
+        })?
+        .ok_or(SubscribeError::UnknownList)?;
+    let profile = form
+        .take_profile(&app_state.segmentation)
+        .map_err(SubscribeError::InvalidProfile)?;
+    if form.locale.is_none() {
+        form.locale = headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+    }
+    let new_subscriber: NewSubscriber = form.try_into()?;
+    app_state
+        .email_validator
+        .validate(&new_subscriber.email)
+        .await?;
 
+    tracing::info!("Saving new subscriber details in the database");
     let subscription_token = generate_subscription_token();
-    store_token(&mut transaction, subscriber_id, &subscription_token)
+    let pending = store_new_subscriber(&app_state, &new_subscriber, &list, &profile, &subscription_token, &context)
         .await
         .map_err(|e| {
-            tracing::error!("Faield to insert subscription token {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            tracing::error!("Failed to save new subscriber {:?}", e);
+            SubscribeError::Unexpected
         })?;
-
-    if transaction.commit().await.is_err() {
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    if !pending {
+        tracing::info!("Subscriber is confirmed on list {} already", list.slug);
+        return Ok(());
     }
 
     send_confirmation_email(
-        &app_state.host,
+        &app_state.links,
         &app_state.email_client,
-        &new_subscriber.email,
+        &app_state.templates,
+        &new_subscriber,
+        &list,
         &subscription_token,
     )
     .await
     .map_err(|e| {
         tracing::error!("Faield to send confirmation email {:?}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        SubscribeError::Unexpected
     })?;
     Ok(())
 }
 
-pub async fn store_token(
-    transaction: &mut Transaction<'_, sqlx::Postgres>,
-    subscriber_id: Uuid,
+/// Puts the subscriber on the list, pending, with their confirmation token,
+/// atomically. Only someone new gets the tags and attributes from the form,
+/// as anyone can post it: someone already on another list keeps their
+/// details, and someone deleted or unsubscribed signs up afresh but keeps
+/// their profile. Returns `false`, storing nothing, if they are confirmed on
+/// the list already.
+async fn store_new_subscriber(
+    app_state: &AppState,
+    new_subscriber: &NewSubscriber,
+    list: &MailingList,
+    profile: &ProfileUpdate,
     subscription_token: &str,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"INSERT INTO subscription_tokens (token, subscriber_id)
-    VALUES ($1, $2)"#,
-        subscription_token,
-        subscriber_id
-    )
-    .execute(&mut **transaction)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })?;
-    Ok(())
+    context: &EventContext,
+) -> Result<bool> {
+    let existing = app_state
+        .subscribers
+        .find_by_email(new_subscriber.email.as_ref())
+        .await?;
+    let mut transaction = app_state.subscribers.begin().await?;
+    let (subscriber_id, is_new) = match existing {
+        None => (transaction.insert_subscriber(new_subscriber).await?, true),
+        Some(subscriber) if subscriber.status == "deleted" || subscriber.status == "unsubscribed" => {
+            transaction
+                .reactivate_subscriber(subscriber.id, new_subscriber)
+                .await?;
+            (subscriber.id, false)
+        }
+        Some(subscriber) => (subscriber.id, false),
+    };
+    if !transaction.join_list(subscriber_id, list.id, context).await? {
+        return Ok(false);
+    }
+    if is_new && *profile != ProfileUpdate::default() {
+        transaction.update_profile(subscriber_id, profile).await?;
+    }
+    transaction
+        .store_token(subscriber_id, list.id, subscription_token)
+        .await?;
+    transaction.commit().await?;
+    Ok(true)
 }
 
-#[tracing::instrument(
-    name = "Saving new subscriber details in the database",
-    skip(transaction, new_subscriber)
-)]
-pub async fn insert_subscriber(
-    transaction: &mut Transaction<'_, sqlx::Postgres>,
-    new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
-    let subscriber_id = Uuid::new_v4();
-    sqlx::query!(
-        r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending')
-    "#,
-        subscriber_id,
-        new_subscriber.email.as_ref(),
-        new_subscriber.name.as_ref(),
-        Utc::now()
-    )
-    .execute(&mut **transaction)
-    .await
-    .map(|_| {
-        tracing::info!("New subscriber {} saved", new_subscriber.email.as_ref());
-        subscriber_id
-    })
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })
+#[derive(serde::Deserialize)]
+pub struct ResendConfirmationForm {
+    email: String,
+}
+
+/// Sends the confirmation email again, for each list the address is still
+/// pending on, with a new link that replaces the old ones. The response is
+/// the same whether or not the address is subscribed, and the lookup and
+/// sending happen after it, so neither the answer nor its timing tells
+/// who is. An address gets at most one confirmation email per
+/// `confirmation.resend_interval_minutes`; the [`ResendThrottle`] turns
+/// away repeated and excess requests before any work is queued.
+///
+/// [`ResendThrottle`]: crate::confirmation::ResendThrottle
+#[tracing::instrument(name = "Resending confirmation email", skip(app_state, form))]
+pub async fn resend_confirmation(
+    State(app_state): State<AppState>,
+    Form(form): Form<ResendConfirmationForm>,
+) -> StatusCode {
+    let email = form.email.trim().to_owned();
+    let Some(permit) = app_state.resends.admit(&email, Utc::now()) else {
+        tracing::info!("Resend request turned away");
+        return StatusCode::ACCEPTED;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = resend_confirmation_emails(&app_state, &email).await {
+            tracing::error!("Failed to resend confirmation email: {:?}", e);
+        }
+        drop(permit);
+    });
+    StatusCode::ACCEPTED
 }
+
+async fn resend_confirmation_emails(app_state: &AppState, email: &str) -> Result<()> {
+    let Some(subscriber) = app_state.subscribers.find_by_email(email).await? else {
+        return Ok(());
+    };
+    if subscriber.status != "pending" && subscriber.status != "confirmed" {
+        return Ok(());
+    }
+    let pending: HashSet<_> = app_state
+        .subscribers
+        .memberships(subscriber.id)
+        .await?
+        .into_iter()
+        .filter(|membership| membership.status == "pending")
+        .map(|membership| membership.list)
+        .collect();
+    let lists: Vec<_> = app_state
+        .subscribers
+        .lists()
+        .await?
+        .into_iter()
+        .filter(|list| pending.contains(&list.slug))
+        .collect();
+    if lists.is_empty() {
+        return Ok(());
+    }
+    let tokens: Vec<_> = lists
+        .iter()
+        .map(|list| (list.id, generate_subscription_token()))
+        .collect();
+    let issued_after = Utc::now() - app_state.confirmation.resend_interval();
+    if !app_state
+        .subscribers
+        .reissue_tokens(subscriber.id, &tokens, issued_after)
+        .await?
+    {
+        tracing::info!("Subscriber {} was sent a confirmation email too recently", subscriber.id);
+        return Ok(());
+    }
+    let new_subscriber = NewSubscriber {
+        email: SubscriberEmail::parse(subscriber.email)
+            .map_err(|e| eyre::eyre!("Stored email is invalid: {:?}", e))?,
+        name: SubscriberName::parse(subscriber.name)?,
+        locale: subscriber.locale,
+    };
+    for (list, (_, token)) in lists.iter().zip(&tokens) {
+        send_confirmation_email(
+            &app_state.links,
+            &app_state.email_client,
+            &app_state.templates,
+            &new_subscriber,
+            list,
+            token,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
 #[tracing::instrument(
     name = "Sending confirmation email",
-    skip(host, email_client, email, subscription_token)
+    skip(links, email_client, templates, new_subscriber, list, subscription_token)
 )]
 pub async fn send_confirmation_email(
-    host: &str,
+    links: &LinkBuilder,
     email_client: &EmailClient,
-    email: &SubscriberEmail,
+    templates: &Templates,
+    new_subscriber: &NewSubscriber,
+    list: &MailingList,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
-    let confirmation_link = format!(
-        "http://{}/subscriptions/confirm/{}",
-        host, subscription_token
-    );
+) -> Result<()> {
+    let confirmation_link = links.confirmation_link(subscription_token);
 
+    let email = templates.render_email(
+        EmailTemplate::Confirmation,
+        minijinja::context! {
+            locale => new_subscriber.locale,
+            name => new_subscriber.name.as_ref(),
+            list_name => list.name,
+            // The list's own copy, if any, in place of the translated one.
+            subject => list.confirmation_subject,
+            intro => list.confirmation_intro,
+            // We build the link ourselves, so it does not need escaping.
+            confirmation_link => minijinja::Value::from_safe_string(confirmation_link.into()),
+        },
+    )?;
     email_client
-        .send_email(
-            email.clone(),
-            "Welcome!",
-            &format!(
-                "Welcome to my newsletter!<br />Click <a href={}>here</a> to confirm subscription.",
-                confirmation_link
-            ),
-            &format!(
-                "Welcome to my newsletter. Click {} to confirm subscrition.",
-                confirmation_link
-            ),
+        .send_email_from(
+            &list.sender(email_client.sender().as_ref()),
+            new_subscriber.email.clone(),
+            &email.subject,
+            &email.html_body,
+            &email.text_body,
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{extract::State, http::HeaderMap, Form};
+    use claim::assert_ok;
+    use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+
+    use super::{subscribe, FormData, SubscribeError};
+    use crate::app_state::AppState;
+    use crate::audit::EventContext;
+    use crate::repository::{InMemorySubscriberRepository, SubscriberRepository};
+
+    fn form() -> Form<FormData> {
+        Form(FormData {
+            name: "Ursula".into(),
+            email: "ursula@example.com".into(),
+            locale: None,
+            source: None,
+            list: None,
+            tags: None,
+            attributes: Default::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn subscribing_stores_a_pending_subscriber_only_once() {
+        let email_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&email_server)
+            .await;
+        let repository = InMemorySubscriberRepository::default();
+        let state = AppState::for_tests(Arc::new(repository.clone()), email_server.uri());
+
+        let context = EventContext::default();
+        subscribe(State(state.clone()), context.clone(), HeaderMap::new(), form())
+            .await
+            .unwrap();
+        assert_ok!(subscribe(State(state), context, HeaderMap::new(), form()).await);
+
+        let subscribers = repository.subscribers();
+        assert_eq!(subscribers.len(), 1);
+        assert_eq!(subscribers[0].status, "pending");
+    }
+
+    #[tokio::test]
+    async fn extra_fields_are_stored_as_attributes_only_if_configured() {
+        let email_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&email_server)
+            .await;
+        let repository = InMemorySubscriberRepository::default();
+        let mut state = AppState::for_tests(Arc::new(repository.clone()), email_server.uri());
+        state.segmentation.attribute_keys = vec!["country".into()];
+        state.segmentation.signup_tags = vec!["rust".into()];
+        let form_with = |field: &str, value: &str| {
+            let mut form = form();
+            form.attributes.insert(field.into(), value.into());
+            form.tags = Some("Rust, ".into());
+            form
+        };
+
+        let rejected = subscribe(
+            State(state.clone()),
+            EventContext::default(),
+            HeaderMap::new(),
+            form_with("city", "Berlin"),
+        )
+        .await;
+        assert!(matches!(rejected, Err(SubscribeError::InvalidProfile(_))));
+        assert!(repository.subscribers().is_empty());
+
+        subscribe(
+            State(state),
+            EventContext::default(),
+            HeaderMap::new(),
+            form_with("country", " de "),
         )
         .await
+        .unwrap();
+        let id = repository.subscribers()[0].id;
+        let profile = repository.profile(id).await.unwrap();
+        assert_eq!(profile.attributes["country"], "de");
+        assert!(profile.tags.contains("rust"));
+    }
 }
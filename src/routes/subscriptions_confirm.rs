@@ -1,58 +1,238 @@
-use axum::extract::{Path, State};
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{ACCEPT, ACCEPT_LANGUAGE},
+        HeaderMap,
+    },
+    response::{Html, IntoResponse, Redirect, Response},
+    Json,
+};
+use chrono::Utc;
 use hyper::StatusCode;
-use uuid::Uuid;
 
-use crate::app_state::{self, AppState};
+use crate::{app_state::AppState, audit::EventContext, i18n, templates::Page};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationOutcome {
+    Confirmed,
+    AlreadyConfirmed,
+    Expired,
+    InvalidToken,
+}
+
+impl ConfirmationOutcome {
+    fn code(&self) -> &'static str {
+        match self {
+            ConfirmationOutcome::Confirmed => "confirmed",
+            ConfirmationOutcome::AlreadyConfirmed => "already_confirmed",
+            ConfirmationOutcome::Expired => "expired",
+            ConfirmationOutcome::InvalidToken => "invalid_token",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            ConfirmationOutcome::Confirmed | ConfirmationOutcome::AlreadyConfirmed => StatusCode::OK,
+            ConfirmationOutcome::Expired => StatusCode::GONE,
+            ConfirmationOutcome::InvalidToken => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn page(&self) -> Page {
+        match self {
+            ConfirmationOutcome::Confirmed => Page::Confirmed,
+            ConfirmationOutcome::AlreadyConfirmed => Page::AlreadyConfirmed,
+            ConfirmationOutcome::Expired => Page::Expired,
+            ConfirmationOutcome::InvalidToken => Page::InvalidToken,
+        }
+    }
+}
+
+/// Confirms a subscription and tells the subscriber how it went.
+///
+/// Browsers get an HTML page (or a redirect, if one is configured for the
+/// outcome); clients that prefer `application/json` get `{"status": ...}`.
 pub async fn confirm_subscription(
     State(app_state): State<AppState>,
+    context: EventContext,
+    headers: HeaderMap,
     Path(token): Path<String>,
-) -> Result<(), StatusCode> {
-    let id = match get_subscriber_id_from_token(&app_state, &token).await {
-        Ok(id) => id,
+) -> Result<Response, StatusCode> {
+    let token = match app_state.subscribers.find_token(&token).await {
+        Ok(token) => token,
         Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
     };
-    match id {
+    let (outcome, locale) = match token {
         // Non-existing token!
-        None => return Err(StatusCode::UNAUTHORIZED),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&app_state, subscriber_id).await.is_err() {
+        None => {
+            let accept_language = headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok());
+            (ConfirmationOutcome::InvalidToken, i18n::negotiate(accept_language).to_owned())
+        }
+        Some(token) if token.status == "confirmed" => {
+            (ConfirmationOutcome::AlreadyConfirmed, token.locale)
+        }
+        Some(token) if token.created_at + app_state.confirmation.token_ttl() < Utc::now() => {
+            (ConfirmationOutcome::Expired, token.locale)
+        }
+        Some(token) => {
+            if let Err(e) = app_state
+                .subscribers
+                .confirm(token.subscriber_id, token.list_id, &context)
+                .await
+            {
+                tracing::error!("Failed to confirm subscriber: {:?}", e);
                 return Err(StatusCode::INTERNAL_SERVER_ERROR);
             }
-            Ok(())
+            (ConfirmationOutcome::Confirmed, token.locale)
         }
+    };
+
+    if prefers_json(&headers) {
+        let body = Json(serde_json::json!({ "status": outcome.code() }));
+        return Ok((outcome.status(), body).into_response());
     }
+    let redirects = &app_state.confirmation.redirects;
+    let redirect = match outcome {
+        ConfirmationOutcome::Confirmed => &redirects.confirmed,
+        ConfirmationOutcome::AlreadyConfirmed => &redirects.already_confirmed,
+        ConfirmationOutcome::Expired => &redirects.expired,
+        ConfirmationOutcome::InvalidToken => &redirects.invalid_token,
+    };
+    if let Some(url) = redirect {
+        return Ok(Redirect::to(url.as_str()).into_response());
+    }
+    // Where the expired page asks for a new link.
+    let resend_link = app_state.links.url(&["subscriptions", "resend-confirmation"]);
+    let page = app_state
+        .templates
+        .render_page(
+            outcome.page(),
+            minijinja::context! {
+                locale,
+                resend_link => minijinja::Value::from_safe_string(resend_link.into()),
+            },
+        )
+        .map_err(|e| {
+            tracing::error!("Failed to render confirmation page: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok((outcome.status(), Html(page)).into_response())
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, app_state))]
-pub async fn confirm_subscriber(app_state: &AppState, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
-        subscriber_id,
-    )
-    .execute(&app_state.connection_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })?;
-    Ok(())
+/// Whether the `Accept` header ranks `application/json` above `text/html`.
+fn prefers_json(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(ACCEPT).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let quality = |media_type: &str| {
+        accept
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';').map(str::trim);
+                if parts.next()? != media_type {
+                    return None;
+                }
+                let q = parts
+                    .find_map(|param| param.strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some(q)
+            })
+            .fold(0.0, f32::max)
+    };
+    let json = quality("application/json");
+    json > 0.0 && json > quality("text/html")
 }
 
-#[tracing::instrument(name = "Get subscriber_id from token", skip(token, app_state))]
-pub async fn get_subscriber_id_from_token(
-    app_state: &AppState,
-    token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens WHERE token = $1"#,
-        token,
-    )
-    .fetch_optional(&app_state.connection_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })?;
-    Ok(result.map(|r| r.subscriber_id))
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::extract::{Path, State};
+    use axum::http::{header::ACCEPT, HeaderMap, HeaderValue, StatusCode};
+    use chrono::{Duration, Utc};
+
+    use super::{confirm_subscription, prefers_json};
+    use crate::app_state::AppState;
+    use crate::audit::EventContext;
+    use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+    use crate::repository::{InMemorySubscriberRepository, ListRepository, SubscriberRepository, DEFAULT_LIST};
+
+    async fn pending_subscriber(repository: &InMemorySubscriberRepository, token: &str) {
+        let list = repository.find_list(DEFAULT_LIST).await.unwrap().unwrap();
+        let mut transaction = repository.begin().await.unwrap();
+        let id = transaction
+            .insert_subscriber(&NewSubscriber {
+                email: SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+                name: SubscriberName::parse("Ursula".into()).unwrap(),
+                locale: "en".into(),
+            })
+            .await
+            .unwrap();
+        transaction
+            .join_list(id, list.id, &EventContext::default())
+            .await
+            .unwrap();
+        transaction.store_token(id, list.id, token).await.unwrap();
+        transaction.commit().await.unwrap();
+    }
+
+    async fn confirm(repository: &InMemorySubscriberRepository, token: &str) -> StatusCode {
+        let state = AppState::for_tests(Arc::new(repository.clone()), "http://127.0.0.1".into());
+        confirm_subscription(
+            State(state),
+            EventContext::default(),
+            HeaderMap::new(),
+            Path(token.into()),
+        )
+        .await
+        .unwrap()
+        .status()
+    }
+
+    #[tokio::test]
+    async fn a_valid_token_confirms_the_subscriber_once() {
+        let repository = InMemorySubscriberRepository::default();
+        pending_subscriber(&repository, "token").await;
+
+        assert_eq!(confirm(&repository, "token").await, StatusCode::OK);
+        assert_eq!(repository.subscribers()[0].status, "confirmed");
+        assert_eq!(confirm(&repository, "token").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn unknown_and_expired_tokens_do_not_confirm_anyone() {
+        let repository = InMemorySubscriberRepository::default();
+        pending_subscriber(&repository, "token").await;
+        repository.set_token_created_at("token", Utc::now() - Duration::hours(73));
+
+        assert_eq!(confirm(&repository, "other").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(confirm(&repository, "token").await, StatusCode::GONE);
+        assert_eq!(repository.subscribers()[0].status, "pending");
+    }
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn browsers_get_html() {
+        assert!(!prefers_json(&HeaderMap::new()));
+        assert!(!prefers_json(&accept("*/*")));
+        assert!(!prefers_json(&accept(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+        )));
+        assert!(!prefers_json(&accept("text/html, application/json")));
+    }
+
+    #[test]
+    fn api_clients_get_json() {
+        assert!(prefers_json(&accept("application/json")));
+        assert!(prefers_json(&accept("application/json, text/html;q=0.5")));
+        assert!(!prefers_json(&accept("application/json;q=0")));
+    }
 }
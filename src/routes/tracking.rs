@@ -0,0 +1,160 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, header::ACCEPT_LANGUAGE, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use chrono::Utc;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    i18n,
+    repository::TrackedAction,
+    templates::Page,
+    tracking::TrackingToken,
+};
+
+/// A transparent 1x1 GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+fn verify(app_state: &AppState, token: &str) -> Option<TrackingToken> {
+    app_state.tracking_links.as_ref()?.verify(token)
+}
+
+/// Records the action while tracking is enabled. Failing to is no reason
+/// to keep the subscriber from what they asked for, so it is only logged.
+async fn record(app_state: &AppState, issue_id: Uuid, subscriber_id: Uuid, action: TrackedAction) {
+    if !app_state.tracking.enabled {
+        return;
+    }
+    if let Err(e) = app_state
+        .subscribers
+        .record_tracked_action(issue_id, subscriber_id, &action, Utc::now())
+        .await
+    {
+        tracing::error!("Failed to record a tracked {}: {:?}", action.kind(), e);
+    }
+}
+
+#[tracing::instrument(name = "Tracking an open", skip_all)]
+pub async fn track_open(State(app_state): State<AppState>, Path(token): Path<String>) -> Response {
+    let Some(TrackingToken::Open { issue_id, subscriber_id }) = verify(&app_state, &token) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    record(&app_state, issue_id, subscriber_id, TrackedAction::Opened).await;
+    (
+        [
+            (header::CONTENT_TYPE, "image/gif"),
+            // Every open should reach us, not a cache.
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        PIXEL,
+    )
+        .into_response()
+}
+
+/// Redirects to the link's target. Only targets we signed are followed,
+/// so this cannot send anyone elsewhere.
+#[tracing::instrument(name = "Tracking a click", skip_all)]
+pub async fn track_click(State(app_state): State<AppState>, Path(token): Path<String>) -> Response {
+    let Some(TrackingToken::Click { issue_id, subscriber_id, url }) = verify(&app_state, &token) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(target) = Url::parse(&url)
+        .ok()
+        .filter(|target| matches!(target.scheme(), "http" | "https"))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    record(&app_state, issue_id, subscriber_id, TrackedAction::Clicked { url }).await;
+    Redirect::to(target.as_str()).into_response()
+}
+
+fn render_page(
+    app_state: &AppState,
+    page: Page,
+    locale: &str,
+    opt_out_link: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    app_state
+        .templates
+        .render_page(page, minijinja::context! { locale, opt_out_link })
+        .map(Html)
+        .map_err(|e| {
+            tracing::error!("Failed to render tracking page: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// The subscriber an opt-out link was issued for, with their locale, or
+/// the page explaining why it cannot be used.
+async fn opt_out_subscriber(
+    app_state: &AppState,
+    headers: &HeaderMap,
+    token: &str,
+) -> Result<(Uuid, String), Response> {
+    if let Some(TrackingToken::OptOut { subscriber_id }) = verify(app_state, token) {
+        match app_state.subscribers.find_by_id(subscriber_id).await {
+            Ok(Some(subscriber)) => return Ok((subscriber.id, subscriber.locale)),
+            Ok(None) => {}
+            Err(e) => {
+                tracing::error!("Failed to look up subscriber: {:?}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+        }
+    }
+    let accept_language = headers
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok());
+    let locale = i18n::negotiate(accept_language);
+    Err(render_page(app_state, Page::InvalidTrackingLink, locale, None)
+        .map(|page| (StatusCode::NOT_FOUND, page).into_response())
+        .unwrap_or_else(|status| status.into_response()))
+}
+
+/// Asks for confirmation before opting out, like erasure does: mail
+/// scanners follow links, but they do not submit forms.
+pub async fn confirm_tracking_opt_out(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(token): Path<String>,
+) -> Response {
+    let (_, locale) = match opt_out_subscriber(&app_state, &headers, &token).await {
+        Ok(subscriber) => subscriber,
+        Err(response) => return response,
+    };
+    let opt_out_link = app_state.links.tracking_link("opt-out", &token);
+    render_page(
+        &app_state,
+        Page::ConfirmTrackingOptOut,
+        &locale,
+        Some(opt_out_link.into()),
+    )
+    .into_response()
+}
+
+#[tracing::instrument(name = "Opting out of tracking", skip_all)]
+pub async fn opt_out_of_tracking(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(token): Path<String>,
+) -> Response {
+    let (subscriber_id, locale) = match opt_out_subscriber(&app_state, &headers, &token).await {
+        Ok(subscriber) => subscriber,
+        Err(response) => return response,
+    };
+    if let Err(e) = app_state
+        .subscribers
+        .set_tracking_opt_out(subscriber_id, true)
+        .await
+    {
+        tracing::error!("Failed to opt subscriber out of tracking: {:?}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    render_page(&app_state, Page::TrackingOptedOut, &locale, None).into_response()
+}
@@ -0,0 +1,424 @@
+//! src/segment.rs
+//!
+//! Segments pick subscribers by tag and attribute, e.g.
+//! `tag:rust AND (country:de OR country:at) AND NOT tag:churned`.
+//!
+//! A term is `tag:<tag>` or `<attribute key>:<value>`, where the value may
+//! be double-quoted to contain spaces or parentheses. `NOT` binds tighter
+//! than `AND`, which binds tighter than `OR`; keywords are case-insensitive.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use sqlx::QueryBuilder;
+
+/// Longer segments are rejected outright.
+const MAX_LENGTH: usize = 1024;
+const MAX_TAG_LENGTH: usize = 64;
+const MAX_VALUE_LENGTH: usize = 256;
+/// How deeply parentheses and `NOT`s may nest.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Tag(String),
+    Attribute { key: String, value: String },
+    Not(Box<Segment>),
+    And(Box<Segment>, Box<Segment>),
+    Or(Box<Segment>, Box<Segment>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SegmentError {
+    Syntax(String),
+    /// An attribute that is not in `segmentation.attribute_keys`.
+    UnknownAttribute(String),
+}
+
+impl SegmentError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SegmentError::Syntax(_) => "invalid_segment",
+            SegmentError::UnknownAttribute(_) => "unknown_attribute",
+        }
+    }
+}
+
+impl fmt::Display for SegmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SegmentError::Syntax(message) => write!(f, "Invalid segment: {}", message),
+            SegmentError::UnknownAttribute(key) => write!(f, "Unknown attribute `{}`", key),
+        }
+    }
+}
+
+impl std::error::Error for SegmentError {}
+
+/// Why a tag or attribute cannot be stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileRejection {
+    /// Not in `segmentation.attribute_keys`.
+    UnknownAttribute(String),
+    /// A blank or overly long value for the attribute.
+    InvalidAttribute(String),
+    InvalidTag(String),
+    /// Not in `segmentation.signup_tags`, at signup.
+    TagNotAllowed(String),
+}
+
+impl ProfileRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProfileRejection::UnknownAttribute(_) => "unknown_attribute",
+            ProfileRejection::InvalidAttribute(_) => "invalid_attribute",
+            ProfileRejection::InvalidTag(_) => "invalid_tag",
+            ProfileRejection::TagNotAllowed(_) => "unknown_tag",
+        }
+    }
+}
+
+impl fmt::Display for ProfileRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileRejection::UnknownAttribute(key) => write!(f, "Unknown attribute `{}`", key),
+            ProfileRejection::InvalidAttribute(key) => write!(
+                f,
+                "The value of `{}` must be between 1 and {} characters",
+                key, MAX_VALUE_LENGTH
+            ),
+            ProfileRejection::InvalidTag(tag) => write!(f, "`{}` is not a valid tag", tag),
+            ProfileRejection::TagNotAllowed(tag) => write!(f, "The tag `{}` cannot be set here", tag),
+        }
+    }
+}
+
+impl std::error::Error for ProfileRejection {}
+
+/// The trimmed value, if `key` is one of `attribute_keys` and the value
+/// fits.
+pub fn attribute_value(key: &str, value: &str, attribute_keys: &[String]) -> Result<String, ProfileRejection> {
+    if !attribute_keys.iter().any(|allowed| allowed == key) {
+        return Err(ProfileRejection::UnknownAttribute(key.to_owned()));
+    }
+    let value = value.trim();
+    if value.is_empty() || value.chars().count() > MAX_VALUE_LENGTH {
+        return Err(ProfileRejection::InvalidAttribute(key.to_owned()));
+    }
+    Ok(value.to_owned())
+}
+
+/// The SQL flavour to compile a segment to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Postgres,
+    Sqlite,
+}
+
+/// Tags are compared case-insensitively, so they are stored lowercase.
+/// Quotes are not allowed, as segments could not name the tag.
+pub fn normalize_tag(tag: &str) -> Result<String, ProfileRejection> {
+    let normalized = tag.trim().to_lowercase();
+    if normalized.is_empty() || normalized.chars().count() > MAX_TAG_LENGTH || normalized.contains('"') {
+        return Err(ProfileRejection::InvalidTag(tag.to_owned()));
+    }
+    Ok(normalized)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term { key: String, value: String },
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, SegmentError> {
+    let syntax = |message: &str| SegmentError::Syntax(message.to_owned());
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    let ends_word = |c: char| c.is_whitespace() || c == '(' || c == ')';
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            continue;
+        }
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+            if ends_word(c) || c == ':' || c == '"' {
+                break;
+            }
+            word.push(c);
+            chars.next();
+        }
+        if chars.peek() != Some(&':') {
+            tokens.push(match word.to_uppercase().as_str() {
+                "AND" => Token::And,
+                "OR" => Token::Or,
+                "NOT" => Token::Not,
+                _ => return Err(SegmentError::Syntax(format!("expected `key:value`, found `{}`", word))),
+            });
+            continue;
+        }
+        chars.next();
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => return Err(syntax("unterminated quote")),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if ends_word(c) {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+        }
+        if word.is_empty() || value.is_empty() {
+            return Err(syntax("terms need both a key and a value"));
+        }
+        tokens.push(Token::Term { key: word, value });
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+    attribute_keys: &'a [String],
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Result<Segment, SegmentError>) -> Result<Segment, SegmentError> {
+        if self.depth == MAX_DEPTH {
+            return Err(SegmentError::Syntax("the segment is nested too deeply".into()));
+        }
+        self.depth += 1;
+        let segment = parse(self);
+        self.depth -= 1;
+        segment
+    }
+
+    fn or(&mut self) -> Result<Segment, SegmentError> {
+        let mut segment = self.and()?;
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            segment = Segment::Or(Box::new(segment), Box::new(self.and()?));
+        }
+        Ok(segment)
+    }
+
+    fn and(&mut self) -> Result<Segment, SegmentError> {
+        let mut segment = self.unary()?;
+        while self.tokens.next_if_eq(&Token::And).is_some() {
+            segment = Segment::And(Box::new(segment), Box::new(self.unary()?));
+        }
+        Ok(segment)
+    }
+
+    fn unary(&mut self) -> Result<Segment, SegmentError> {
+        match self.tokens.next() {
+            Some(Token::Not) => Ok(Segment::Not(Box::new(self.nested(Self::unary)?))),
+            Some(Token::Open) => {
+                let segment = self.nested(Self::or)?;
+                match self.tokens.next() {
+                    Some(Token::Close) => Ok(segment),
+                    _ => Err(SegmentError::Syntax("unbalanced parentheses".into())),
+                }
+            }
+            Some(Token::Term { key, value }) if key.eq_ignore_ascii_case("tag") => normalize_tag(&value)
+                .map(Segment::Tag)
+                .map_err(|e| SegmentError::Syntax(e.to_string())),
+            Some(Token::Term { key, value }) => {
+                if !self.attribute_keys.contains(&key) {
+                    return Err(SegmentError::UnknownAttribute(key));
+                }
+                Ok(Segment::Attribute { key, value })
+            }
+            Some(_) => Err(SegmentError::Syntax("expected a term".into())),
+            None => Err(SegmentError::Syntax("unexpected end of segment".into())),
+        }
+    }
+}
+
+impl Segment {
+    /// Parses a segment, allowing only the given attribute keys.
+    pub fn parse(input: &str, attribute_keys: &[String]) -> Result<Self, SegmentError> {
+        if input.len() > MAX_LENGTH {
+            return Err(SegmentError::Syntax(format!(
+                "segments are limited to {} characters",
+                MAX_LENGTH
+            )));
+        }
+        let mut parser = Parser {
+            tokens: tokenize(input)?.into_iter().peekable(),
+            attribute_keys,
+            depth: 0,
+        };
+        let segment = parser.or()?;
+        match parser.tokens.next() {
+            None => Ok(segment),
+            Some(Token::Close) => Err(SegmentError::Syntax("unbalanced parentheses".into())),
+            Some(_) => Err(SegmentError::Syntax("expected AND or OR between terms".into())),
+        }
+    }
+
+    pub fn matches(&self, tags: &BTreeSet<String>, attributes: &BTreeMap<String, String>) -> bool {
+        match self {
+            Segment::Tag(tag) => tags.contains(tag),
+            Segment::Attribute { key, value } => attributes.get(key) == Some(value),
+            Segment::Not(segment) => !segment.matches(tags, attributes),
+            Segment::And(left, right) => left.matches(tags, attributes) && right.matches(tags, attributes),
+            Segment::Or(left, right) => left.matches(tags, attributes) || right.matches(tags, attributes),
+        }
+    }
+
+    /// Appends the segment as a boolean expression over `subscriptions`
+    /// aliased as `s`, with every key and value bound as a parameter. A
+    /// missing attribute compares as false rather than NULL, so negations
+    /// select the same subscribers as `matches`.
+    pub fn push_sql<'a, DB>(&'a self, builder: &mut QueryBuilder<'a, DB>, dialect: Dialect)
+    where
+        DB: sqlx::Database,
+        &'a str: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    {
+        match self {
+            Segment::Tag(tag) => {
+                builder.push("EXISTS (SELECT 1 FROM subscriber_tags t WHERE t.subscriber_id = s.id AND t.tag = ");
+                builder.push_bind(tag.as_str());
+                builder.push(")");
+            }
+            Segment::Attribute { key, value } => {
+                builder.push("COALESCE(");
+                match dialect {
+                    Dialect::Postgres => {
+                        builder.push("(s.attributes ->> ");
+                        builder.push_bind(key.as_str());
+                    }
+                    Dialect::Sqlite => {
+                        builder.push("(json_extract(s.attributes, '$.\"' || ");
+                        builder.push_bind(key.as_str());
+                        builder.push(" || '\"')");
+                    }
+                }
+                builder.push(") = ");
+                builder.push_bind(value.as_str());
+                builder.push(", FALSE)");
+            }
+            Segment::Not(segment) => {
+                builder.push("NOT (");
+                segment.push_sql(builder, dialect);
+                builder.push(")");
+            }
+            Segment::And(left, right) | Segment::Or(left, right) => {
+                let operator = if matches!(self, Segment::And(..)) { " AND " } else { " OR " };
+                builder.push("(");
+                left.push_sql(builder, dialect);
+                builder.push(operator);
+                right.push_sql(builder, dialect);
+                builder.push(")");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use sqlx::{Postgres, QueryBuilder};
+
+    use crate::segment::{Dialect, Segment, SegmentError};
+
+    fn keys() -> Vec<String> {
+        vec!["country".into(), "plan".into()]
+    }
+
+    fn parse(input: &str) -> Result<Segment, SegmentError> {
+        Segment::parse(input, &keys())
+    }
+
+    fn tag(tag: &str) -> Box<Segment> {
+        Box::new(Segment::Tag(tag.into()))
+    }
+
+    fn attribute(key: &str, value: &str) -> Box<Segment> {
+        Box::new(Segment::Attribute {
+            key: key.into(),
+            value: value.into(),
+        })
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_which_binds_tighter_than_or() {
+        assert_eq!(
+            parse("tag:Rust and NOT country:de OR plan:\"pro (yearly)\"").unwrap(),
+            Segment::Or(
+                Box::new(Segment::And(tag("rust"), Box::new(Segment::Not(attribute("country", "de"))))),
+                attribute("plan", "pro (yearly)"),
+            )
+        );
+        assert_eq!(
+            parse("tag:rust AND (country:de OR country:at)").unwrap(),
+            Segment::And(
+                tag("rust"),
+                Box::new(Segment::Or(attribute("country", "de"), attribute("country", "at"))),
+            )
+        );
+    }
+
+    #[test]
+    fn malformed_segments_and_unknown_attributes_are_rejected() {
+        for input in [
+            "",
+            "rust",
+            "tag:",
+            "tag:rust AND",
+            "tag:rust country:de",
+            "(tag:rust",
+            "tag:rust)",
+            "plan:\"pro",
+        ] {
+            assert!(matches!(parse(input), Err(SegmentError::Syntax(_))), "{:?}", input);
+        }
+        assert_eq!(
+            parse("tag:rust AND city:berlin"),
+            Err(SegmentError::UnknownAttribute("city".into()))
+        );
+        assert!(parse(&"NOT ".repeat(100)).is_err());
+        assert!(parse(&"(".repeat(2000)).is_err());
+    }
+
+    #[test]
+    fn segments_match_tags_and_attributes() {
+        let segment = parse("tag:rust AND NOT country:de").unwrap();
+        let tags = BTreeSet::from(["rust".to_owned()]);
+        let german = BTreeMap::from([("country".to_owned(), "de".to_owned())]);
+        assert!(segment.matches(&tags, &BTreeMap::new()));
+        assert!(!segment.matches(&tags, &german));
+        assert!(!segment.matches(&BTreeSet::new(), &BTreeMap::new()));
+    }
+
+    #[test]
+    fn segments_compile_to_parameterized_sql() {
+        let segment = parse("tag:rust OR country:\"de'; DROP TABLE subscriptions; --\"").unwrap();
+        let mut builder = QueryBuilder::<Postgres>::new("");
+        segment.push_sql(&mut builder, Dialect::Postgres);
+        assert_eq!(
+            builder.sql(),
+            "(EXISTS (SELECT 1 FROM subscriber_tags t WHERE t.subscriber_id = s.id AND t.tag = $1) \
+            OR COALESCE((s.attributes ->> $2) = $3, FALSE))"
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use axum::{
     http::HeaderName,
-    routing::{get, post},
+    middleware,
+    routing::{get, post, put},
     Router,
 };
 use eyre::Result;
+use secrecy::{ExposeSecret, SecretString};
+use std::sync::Arc;
 use sqlx::PgPool;
 use tower::ServiceBuilder;
 use tower_http::{
@@ -14,28 +17,221 @@ use tower_http::{
     },
     trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
 };
-use crate::{app_state::AppState, configuration::Settings, email_client::EmailClient, routes};
+use crate::{
+    app_state::AppState,
+    audit::TrustedProxies,
+    configuration::{DatabaseBackend, DatabaseSettings, HttpClientSettings, HttpVersion, Settings},
+    confirmation::ResendThrottle,
+    email_client::EmailClient,
+    email_validation::EmailValidator, i18n::Localizer, links::LinkBuilder,
+    migrations::SchemaConnection,
+    preferences::{EmailChangeSigner, SessionSigner},
+    privacy::LinkSigner,
+    repository::{PostgresSubscriberRepository, Repository},
+    routes,
+    templates::Templates,
+    tracking::TrackingSigner,
+};
+
+/// Keys that shipped as defaults in `configuration/base.yaml`. They are
+/// public, so anything signed with them can be forged.
+const DEVELOPMENT_SIGNING_KEYS: [&str; 2] = [
+    "development-only-privacy-signing-key",
+    "development-only-tracking-signing-key",
+];
+
+/// The signing key configured as `name`, refusing a missing or public one.
+fn signing_key(key: Option<&SecretString>, name: &str) -> Result<SecretString> {
+    let key = key.ok_or_else(|| eyre::eyre!("`{}` is not set", name))?;
+    if key.expose_secret().trim().is_empty() || DEVELOPMENT_SIGNING_KEYS.contains(&key.expose_secret()) {
+        return Err(eyre::eyre!("`{}` must be set to a private key", name));
+    }
+    Ok(key.clone())
+}
+
+fn subscriber_repository(database: &DatabaseSettings) -> Result<Arc<dyn Repository>> {
+    match database.backend {
+        DatabaseBackend::Postgres => {
+            let pool = PgPool::connect_lazy_with(database.with_db());
+            Ok(Arc::new(PostgresSubscriberRepository::new(pool)))
+        }
+        #[cfg(feature = "sqlite")]
+        DatabaseBackend::Sqlite => {
+            let pool = sqlx::SqlitePool::connect_lazy_with(database.sqlite());
+            Ok(Arc::new(crate::repository::SqliteSubscriberRepository::new(pool)))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        DatabaseBackend::Sqlite => Err(eyre::eyre!(
+            "The sqlite database backend needs the `sqlite` feature"
+        )),
+    }
+}
+
+/// The client outbound integrations send their requests through, so they
+/// share one connection pool; clones are cheap.
+pub fn http_client(settings: &HttpClientSettings) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(settings.connect_timeout())
+        .read_timeout(settings.read_timeout())
+        .pool_idle_timeout(settings.pool_idle_timeout());
+    if let Some(max_idle) = settings.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    builder = match settings.http_version {
+        HttpVersion::Auto => builder,
+        HttpVersion::Http1 => builder.http1_only(),
+        HttpVersion::Http2 => builder.http2_prior_knowledge(),
+    };
+    if let Some(path) = &settings.ca_bundle_path {
+        let bundle = std::fs::read(path)
+            .map_err(|e| eyre::eyre!("Failed to read CA bundle {}: {}", path, e))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&bundle)
+            .map_err(|e| eyre::eyre!("Invalid CA bundle {}: {}", path, e))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    if let Some(settings) = &settings.proxy {
+        let mut proxy = reqwest::Proxy::all(&settings.url)
+            .map_err(|e| eyre::eyre!("Invalid proxy URL: {}", e))?;
+        if let Some(username) = &settings.username {
+            let password = settings.password.as_ref().map_or("", |password| password.expose_secret());
+            proxy = proxy.basic_auth(username, password);
+        }
+        proxy = proxy.no_proxy(settings.no_proxy.as_deref().and_then(reqwest::NoProxy::from_string));
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .map_err(|e| eyre::eyre!("Failed to build the HTTP client: {}", e))
+}
 
+/// Applies pending migrations when configured to, and otherwise refuses to
+/// serve from a schema older than this binary.
+pub async fn prepare_database(database: &DatabaseSettings) -> Result<()> {
+    let mut connection = SchemaConnection::connect(database).await?;
+    if database.run_migrations_on_startup {
+        connection.up().await?;
+    } else {
+        connection.ensure_current().await?;
+    }
+    connection.close().await
+}
 
 pub fn build(configuration: Settings) -> Result<AppState> {
     let timeout = configuration.email_client.timeout();
-    let connection_pool = PgPool::connect_lazy_with(configuration.database.with_db());
+    let subscribers = subscriber_repository(&configuration.database)?;
+    let http_client = http_client(&configuration.http_client)?;
     let email_client = EmailClient::new(
+        http_client.clone(),
         configuration.email_client.base_url.clone(), 
         configuration.email_client.sender()?,
         configuration.email_client.authorization_token,
         timeout,
+    )
+    .with_batching(configuration.email_client.batch);
+    let email_validator = EmailValidator::from_settings(&configuration.email_validation)?;
+    let templates = Templates::new(
+        configuration.templates.directory.as_deref(),
+        Arc::new(Localizer::new()?),
+    )?;
+    let links = LinkBuilder::new(configuration.application.base_url)?;
+    let privacy_key = signing_key(configuration.privacy.signing_key.as_ref(), "privacy.signing_key")?;
+    let privacy_links = LinkSigner::new(privacy_key.clone(), configuration.privacy.link_ttl());
+    let preference_sessions = SessionSigner::new(
+        privacy_key.clone(),
+        configuration.preferences.session_ttl(),
+    );
+    let email_changes = EmailChangeSigner::new(
+        privacy_key,
+        configuration.preferences.email_change_ttl(),
+    );
+    // Without tracking, a key still lets links in issues sent while it was
+    // on keep working.
+    let tracking_links = match &configuration.tracking.signing_key {
+        None if !configuration.tracking.enabled => None,
+        key => Some(TrackingSigner::new(signing_key(key.as_ref(), "tracking.signing_key")?)),
+    };
+    let resends = ResendThrottle::new(
+        configuration.confirmation.resend_interval(),
+        configuration.confirmation.max_concurrent_resends,
+    );
+    let email_change_requests = ResendThrottle::new(
+        configuration.preferences.email_change_interval(),
+        configuration.preferences.max_concurrent_email_changes,
+    );
+    let privacy_requests = ResendThrottle::new(
+        configuration.privacy.request_interval(),
+        configuration.privacy.max_concurrent_requests,
     );
-    let host = configuration.application.host;
 
     // run(listener, connection_pool, email_client)
     Ok(AppState {
-        connection_pool,
+        subscribers,
+        http_client,
         email_client,
-        host,
+        email_validator,
+        templates,
+        links,
+        confirmation: configuration.confirmation,
+        resends,
+        admin: configuration.admin,
+        privacy_links,
+        privacy_requests,
+        preference_sessions,
+        email_changes,
+        email_change_requests,
+        segmentation: configuration.segmentation,
+        tracking: configuration.tracking,
+        tracking_links,
+        archive: configuration.archive,
+        trusted_proxies: TrustedProxies(configuration.application.trusted_proxies),
+        scheduler: configuration.scheduler,
     })
 }
 
+/// Endpoints for operators, all behind the admin token.
+fn admin_router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/issues", get(routes::list_issues).post(routes::create_issue))
+        .route(
+            "/issues/{id}",
+            get(routes::get_issue)
+                .put(routes::update_issue)
+                .delete(routes::delete_issue),
+        )
+        .route(
+            "/issues/{id}/schedule",
+            put(routes::schedule_issue).delete(routes::unschedule_issue),
+        )
+        .route("/issues/{id}/deliveries", get(routes::issue_deliveries))
+        .route("/issues/{id}/engagement", get(routes::issue_engagement))
+        .route("/issues/{id}/preview", get(routes::preview_issue))
+        .route("/issues/{id}/test", post(routes::send_test_issue))
+        .route("/lists", get(routes::list_lists).post(routes::create_list))
+        .route(
+            "/lists/{slug}",
+            get(routes::get_list)
+                .put(routes::update_list)
+                .delete(routes::delete_list),
+        )
+        .route("/lists/{slug}/recipients", get(routes::list_recipients))
+        .route("/subscribers", get(routes::list_subscribers))
+        .route("/subscribers/export", get(routes::export_subscribers))
+        .route("/subscribers/import", post(routes::import_subscribers))
+        .route(
+            "/subscribers/{id}",
+            get(routes::get_subscriber)
+                .patch(routes::update_subscriber_profile)
+                .delete(routes::delete_subscriber),
+        )
+        .route(
+            "/subscribers/{id}/events",
+            get(routes::list_subscriber_events),
+        )
+        .route_layer(middleware::from_fn_with_state(state, routes::require_admin_token))
+}
+
 pub fn router(state: AppState) -> Router {
     let request_id = HeaderName::from_static("x-request-id");
     
@@ -43,6 +239,25 @@ pub fn router(state: AppState) -> Router {
     .route("/health_check", get(routes::health_check))
     .route("/subscriptions/confirm/{token}", get(routes::confirm_subscription))
     .route("/subscriptions", post(routes::subscribe))
+    .route("/subscriptions/resend-confirmation", post(routes::resend_confirmation))
+    .route("/privacy/requests", post(routes::request_privacy_action))
+    .route("/privacy/access", get(routes::access_data))
+    .route("/privacy/erasure", get(routes::confirm_erasure).post(routes::erase_data))
+    .route("/privacy/preferences", get(routes::open_preferences))
+    .route("/preferences", get(routes::show_preferences).post(routes::update_preferences))
+    .route("/preferences/unsubscribe", post(routes::unsubscribe))
+    .route("/preferences/email", post(routes::request_email_change))
+    .route("/preferences/email/confirm", get(routes::confirm_email_change))
+    .route("/archive", get(routes::list_archived_issues))
+    .route("/archive/feed.atom", get(routes::archive_feed))
+    .route("/archive/{slug}", get(routes::get_archived_issue))
+    .route("/t/o/{token}", get(routes::track_open))
+    .route("/t/c/{token}", get(routes::track_click))
+    .route(
+        "/t/opt-out/{token}",
+        get(routes::confirm_tracking_opt_out).post(routes::opt_out_of_tracking),
+    )
+    .nest("/admin", admin_router(state.clone()))
     .layer(
         ServiceBuilder::new()
             .layer(SetRequestIdLayer::new(request_id.clone(), MakeRequestUuid))
@@ -54,4 +269,84 @@ pub fn router(state: AppState) -> Router {
             ),
     )
     .with_state(state)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::configuration::{HttpClientSettings, ProxySettings};
+    use crate::startup::{http_client, signing_key};
+    use claim::{assert_err, assert_ok};
+    use secrecy::SecretString;
+    use wiremock::matchers::{header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn the_default_http_client_builds() {
+        assert_ok!(http_client(&HttpClientSettings::default()));
+    }
+
+    #[test]
+    fn a_missing_ca_bundle_is_an_error() {
+        let settings = HttpClientSettings {
+            ca_bundle_path: Some("does/not/exist.pem".into()),
+            ..HttpClientSettings::default()
+        };
+        let error = assert_err!(http_client(&settings));
+        assert!(error.to_string().contains("CA bundle"));
+    }
+
+    #[test]
+    fn an_invalid_proxy_url_is_an_error() {
+        let settings = HttpClientSettings {
+            proxy: Some(ProxySettings {
+                url: "not a url".into(),
+                username: None,
+                password: None,
+                no_proxy: None,
+            }),
+            ..HttpClientSettings::default()
+        };
+        let error = assert_err!(http_client(&settings));
+        assert!(error.to_string().contains("proxy URL"));
+    }
+
+    #[test]
+    fn missing_blank_and_public_signing_keys_are_refused() {
+        let key = |value: &str| SecretString::from(value.to_owned());
+
+        assert!(assert_err!(signing_key(None, "privacy.signing_key")).to_string().contains("not set"));
+        for refused in [" ", "development-only-privacy-signing-key", "development-only-tracking-signing-key"] {
+            let error = assert_err!(signing_key(Some(&key(refused)), "privacy.signing_key"));
+            assert!(error.to_string().contains("private key"), "{:?}", refused);
+        }
+        assert_ok!(signing_key(Some(&key("a private key")), "privacy.signing_key"));
+    }
+
+    #[tokio::test]
+    async fn requests_go_through_the_configured_proxy() {
+        // Arrange
+        let proxy = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/feed.xml"))
+            .and(header_exists("proxy-authorization"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&proxy)
+            .await;
+        let settings = HttpClientSettings {
+            proxy: Some(ProxySettings {
+                url: proxy.uri(),
+                username: Some("user".into()),
+                password: Some("password".to_owned().into()),
+                no_proxy: None,
+            }),
+            ..HttpClientSettings::default()
+        };
+        let client = http_client(&settings).unwrap();
+
+        // Act
+        let response = client.get("http://feeds.example.com/feed.xml").send().await.unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
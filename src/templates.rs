@@ -0,0 +1,367 @@
+//! src/templates.rs
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use minijinja::{value::Kwargs, Environment, State};
+use serde::Serialize;
+
+use crate::i18n::{Localizer, DEFAULT_LOCALE};
+
+/// Templates compiled into the binary, used whenever the configured
+/// directory does not override them.
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    ("email/layout.html", include_str!("../templates/email/layout.html")),
+    ("email/layout.txt", include_str!("../templates/email/layout.txt")),
+    ("email/fragment.txt", include_str!("../templates/email/fragment.txt")),
+    ("email/confirmation.html", include_str!("../templates/email/confirmation.html")),
+    ("email/confirmation.txt", include_str!("../templates/email/confirmation.txt")),
+    ("email/welcome.html", include_str!("../templates/email/welcome.html")),
+    ("email/welcome.txt", include_str!("../templates/email/welcome.txt")),
+    ("email/unsubscribe.html", include_str!("../templates/email/unsubscribe.html")),
+    ("email/unsubscribe.txt", include_str!("../templates/email/unsubscribe.txt")),
+    ("email/newsletter_issue.html", include_str!("../templates/email/newsletter_issue.html")),
+    ("email/newsletter_issue.txt", include_str!("../templates/email/newsletter_issue.txt")),
+    ("email/data_access.html", include_str!("../templates/email/data_access.html")),
+    ("email/data_access.txt", include_str!("../templates/email/data_access.txt")),
+    ("email/erasure.html", include_str!("../templates/email/erasure.html")),
+    ("email/erasure.txt", include_str!("../templates/email/erasure.txt")),
+    ("email/feed_digest.html", include_str!("../templates/email/feed_digest.html")),
+    ("email/feed_digest.txt", include_str!("../templates/email/feed_digest.txt")),
+    ("email/preferences.html", include_str!("../templates/email/preferences.html")),
+    ("email/preferences.txt", include_str!("../templates/email/preferences.txt")),
+    ("email/email_change.html", include_str!("../templates/email/email_change.html")),
+    ("email/email_change.txt", include_str!("../templates/email/email_change.txt")),
+    ("pages/layout.html", include_str!("../templates/pages/layout.html")),
+    ("pages/confirmed.html", include_str!("../templates/pages/confirmed.html")),
+    ("pages/already_confirmed.html", include_str!("../templates/pages/already_confirmed.html")),
+    ("pages/expired.html", include_str!("../templates/pages/expired.html")),
+    ("pages/invalid_token.html", include_str!("../templates/pages/invalid_token.html")),
+    ("pages/confirm_erasure.html", include_str!("../templates/pages/confirm_erasure.html")),
+    ("pages/erased.html", include_str!("../templates/pages/erased.html")),
+    ("pages/invalid_privacy_link.html", include_str!("../templates/pages/invalid_privacy_link.html")),
+    ("pages/confirm_tracking_opt_out.html", include_str!("../templates/pages/confirm_tracking_opt_out.html")),
+    ("pages/tracking_opted_out.html", include_str!("../templates/pages/tracking_opted_out.html")),
+    ("pages/invalid_tracking_link.html", include_str!("../templates/pages/invalid_tracking_link.html")),
+    ("pages/archive.html", include_str!("../templates/pages/archive.html")),
+    ("pages/archived_issue.html", include_str!("../templates/pages/archived_issue.html")),
+    ("pages/preferences.html", include_str!("../templates/pages/preferences.html")),
+    ("pages/preferences_signed_out.html", include_str!("../templates/pages/preferences_signed_out.html")),
+    ("pages/unsubscribed.html", include_str!("../templates/pages/unsubscribed.html")),
+    ("pages/email_changed.html", include_str!("../templates/pages/email_changed.html")),
+    ("pages/email_change_failed.html", include_str!("../templates/pages/email_change_failed.html")),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailTemplate {
+    Confirmation,
+    Welcome,
+    Unsubscribe,
+    NewsletterIssue,
+    DataAccess,
+    Erasure,
+    /// The content of a digest issue, rather than an email of its own.
+    FeedDigest,
+    /// The link into the preference center.
+    Preferences,
+    /// Sent to a new address to confirm moving there.
+    EmailChange,
+}
+
+impl EmailTemplate {
+    pub const ALL: [EmailTemplate; 9] = [
+        EmailTemplate::Confirmation,
+        EmailTemplate::Welcome,
+        EmailTemplate::Unsubscribe,
+        EmailTemplate::NewsletterIssue,
+        EmailTemplate::DataAccess,
+        EmailTemplate::Erasure,
+        EmailTemplate::FeedDigest,
+        EmailTemplate::Preferences,
+        EmailTemplate::EmailChange,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::Confirmation => "confirmation",
+            EmailTemplate::Welcome => "welcome",
+            EmailTemplate::Unsubscribe => "unsubscribe",
+            EmailTemplate::NewsletterIssue => "newsletter_issue",
+            EmailTemplate::DataAccess => "data_access",
+            EmailTemplate::Erasure => "erasure",
+            EmailTemplate::FeedDigest => "feed_digest",
+            EmailTemplate::Preferences => "preferences",
+            EmailTemplate::EmailChange => "email_change",
+        }
+    }
+}
+
+/// Browser-facing pages, rendered from `pages/<name>.html`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    Confirmed,
+    AlreadyConfirmed,
+    Expired,
+    InvalidToken,
+    ConfirmErasure,
+    Erased,
+    InvalidPrivacyLink,
+    ConfirmTrackingOptOut,
+    TrackingOptedOut,
+    InvalidTrackingLink,
+    Archive,
+    ArchivedIssue,
+    Preferences,
+    PreferencesSignedOut,
+    Unsubscribed,
+    EmailChanged,
+    EmailChangeFailed,
+}
+
+impl Page {
+    pub const ALL: [Page; 17] = [
+        Page::Confirmed,
+        Page::AlreadyConfirmed,
+        Page::Expired,
+        Page::InvalidToken,
+        Page::ConfirmErasure,
+        Page::Erased,
+        Page::InvalidPrivacyLink,
+        Page::ConfirmTrackingOptOut,
+        Page::TrackingOptedOut,
+        Page::InvalidTrackingLink,
+        Page::Archive,
+        Page::ArchivedIssue,
+        Page::Preferences,
+        Page::PreferencesSignedOut,
+        Page::Unsubscribed,
+        Page::EmailChanged,
+        Page::EmailChangeFailed,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Page::Confirmed => "confirmed",
+            Page::AlreadyConfirmed => "already_confirmed",
+            Page::Expired => "expired",
+            Page::InvalidToken => "invalid_token",
+            Page::ConfirmErasure => "confirm_erasure",
+            Page::Erased => "erased",
+            Page::InvalidPrivacyLink => "invalid_privacy_link",
+            Page::ConfirmTrackingOptOut => "confirm_tracking_opt_out",
+            Page::TrackingOptedOut => "tracking_opted_out",
+            Page::InvalidTrackingLink => "invalid_tracking_link",
+            Page::Archive => "archive",
+            Page::ArchivedIssue => "archived_issue",
+            Page::Preferences => "preferences",
+            Page::PreferencesSignedOut => "preferences_signed_out",
+            Page::Unsubscribed => "unsubscribed",
+            Page::EmailChanged => "email_changed",
+            Page::EmailChangeFailed => "email_change_failed",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+/// Renders our emails and pages.
+///
+/// Every email has an `email/<name>.html` and an `email/<name>.txt`
+/// template; the subject lives in the `subject` block of the text one.
+/// Values are escaped in HTML templates, so subscriber-provided data can
+/// be passed in as is.
+///
+/// Templates translate copy with `t("key", arg=value)`, using the `locale`
+/// variable of the context.
+#[derive(Clone)]
+pub struct Templates {
+    env: Arc<Environment<'static>>,
+}
+
+impl Templates {
+    /// Loads templates from `directory` (if given), falling back to the
+    /// embedded defaults for any file it does not contain.
+    pub fn new(directory: Option<&str>, localizer: Arc<Localizer>) -> eyre::Result<Self> {
+        let directory = directory.map(PathBuf::from);
+        let mut env = Environment::new();
+        env.set_loader(move |name| {
+            if let Some(path) = directory.as_ref().map(|d| d.join(name)) {
+                match std::fs::read_to_string(&path) {
+                    Ok(source) => return Ok(Some(source)),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => {
+                        return Err(minijinja::Error::new(
+                            minijinja::ErrorKind::InvalidOperation,
+                            format!("Failed to read template {}", path.display()),
+                        )
+                        .with_source(e))
+                    }
+                }
+            }
+            Ok(DEFAULT_TEMPLATES
+                .iter()
+                .find(|(default_name, _)| *default_name == name)
+                .map(|(_, source)| source.to_string()))
+        });
+        env.add_function(
+            "t",
+            move |state: &State, key: &str, kwargs: Kwargs| -> Result<String, minijinja::Error> {
+                let locale = state.lookup("locale");
+                let locale = locale.as_ref().and_then(|l| l.as_str()).unwrap_or(DEFAULT_LOCALE);
+                let args = kwargs
+                    .args()
+                    .map(|name| Ok((name, kwargs.get::<minijinja::Value>(name)?.to_string())))
+                    .collect::<Result<Vec<_>, minijinja::Error>>()?;
+                Ok(localizer.translate(locale, key, &args))
+            },
+        );
+        // Surface broken templates at startup rather than on the first signup.
+        for template in EmailTemplate::ALL {
+            for extension in ["html", "txt"] {
+                env.get_template(&format!("email/{}.{}", template.name(), extension))?;
+            }
+        }
+        for page in Page::ALL {
+            env.get_template(&format!("pages/{}.html", page.name()))?;
+        }
+        Ok(Self { env: Arc::new(env) })
+    }
+
+    pub fn render_email<S: Serialize>(
+        &self,
+        template: EmailTemplate,
+        context: S,
+    ) -> Result<RenderedEmail, minijinja::Error> {
+        let text = self
+            .env
+            .get_template(&format!("email/{}.txt", template.name()))?;
+        let html = self
+            .env
+            .get_template(&format!("email/{}.html", template.name()))?;
+
+        let mut text_body = text.render_captured(&context)?;
+        let subject = text_body.with_state_mut(|state| state.render_block("subject"))?;
+        Ok(RenderedEmail {
+            subject: subject.trim().to_owned(),
+            html_body: html.render(&context)?,
+            text_body: text_body.into_output(),
+        })
+    }
+
+    pub fn render_page<S: Serialize>(&self, page: Page, context: S) -> Result<String, minijinja::Error> {
+        self.env
+            .get_template(&format!("pages/{}.html", page.name()))?
+            .render(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::i18n::Localizer;
+    use crate::templates::{EmailTemplate, Page, Templates};
+    use minijinja::{context, Value};
+
+    fn templates(directory: Option<&str>) -> Templates {
+        Templates::new(directory, Arc::new(Localizer::new().unwrap())).unwrap()
+    }
+
+    #[test]
+    fn default_templates_render_every_message() {
+        let templates = templates(None);
+        for template in EmailTemplate::ALL {
+            let email = templates
+                .render_email(
+                    template,
+                    context! {
+                        name => "Ursula",
+                        confirmation_link => "https://example.com/confirm",
+                        privacy_link => "https://example.com/privacy/access",
+                        title => "Issue #1",
+                        html_content => "<p>Hello</p>",
+                        text_content => "Hello",
+                    },
+                )
+                .unwrap();
+            assert!(!email.subject.is_empty(), "{:?} has no subject", template);
+            assert!(!email.html_body.is_empty());
+            assert!(!email.text_body.is_empty());
+        }
+    }
+
+    #[test]
+    fn subscriber_values_are_escaped_in_html_but_not_in_text() {
+        let templates = templates(None);
+        let email = templates
+            .render_email(
+                EmailTemplate::Welcome,
+                context! { name => "Ursula & <Le Guin>" },
+            )
+            .unwrap();
+        assert!(email.html_body.contains("Ursula &amp; &lt;Le Guin&gt;"));
+        assert!(email.text_body.contains("Ursula & <Le Guin>"));
+    }
+
+    #[test]
+    fn confirmation_link_is_quoted_in_the_href() {
+        let templates = templates(None);
+        let link = "https://example.com/subscriptions/confirm/abc";
+        let email = templates
+            .render_email(
+                EmailTemplate::Confirmation,
+                context! {
+                    name => "Ursula",
+                    confirmation_link => Value::from_safe_string(link.to_owned()),
+                },
+            )
+            .unwrap();
+        assert!(email.html_body.contains(&format!(r#"href="{}""#, link)));
+        assert_eq!(email.subject, "Welcome!");
+    }
+
+    #[test]
+    fn emails_and_pages_are_rendered_in_the_requested_locale() {
+        let templates = templates(None);
+        let email = templates
+            .render_email(
+                EmailTemplate::Confirmation,
+                context! { locale => "de", name => "Ursula", confirmation_link => "x" },
+            )
+            .unwrap();
+        assert_eq!(email.subject, "Willkommen!");
+        assert!(email.text_body.contains("Hallo Ursula,"));
+
+        let page = templates
+            .render_page(Page::Confirmed, context! { locale => "pl" })
+            .unwrap();
+        assert!(page.contains("Subskrypcja potwierdzona"));
+        assert!(page.contains(r#"lang="pl""#));
+    }
+
+    #[test]
+    fn templates_in_the_configured_directory_override_the_defaults() {
+        let directory = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(directory.join("email")).unwrap();
+        std::fs::write(
+            directory.join("email/welcome.txt"),
+            "{% block subject %}Hello {{ name }}{% endblock %}Custom body",
+        )
+        .unwrap();
+
+        let templates = templates(directory.to_str());
+        let email = templates
+            .render_email(EmailTemplate::Welcome, context! { name => "Ursula" })
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(email.subject, "Hello Ursula");
+        assert!(email.text_body.ends_with("Custom body"));
+        // Not overridden, so the embedded default is used.
+        assert!(email.html_body.contains("Your subscription is confirmed."));
+    }
+}
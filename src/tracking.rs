@@ -0,0 +1,256 @@
+//! src/tracking.rs
+//!
+//! Open and click tracking. A tracked issue carries a pixel and links that
+//! go through us; each names the issue and the recipient in a signed
+//! token, so nothing is stored until they are followed, and the click
+//! redirector only ever sends people where an issue linked to.
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a tracking URL stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackingToken {
+    /// The pixel of the issue, as loaded by the subscriber's mail client.
+    Open { issue_id: Uuid, subscriber_id: Uuid },
+    /// A link in the issue, to `url`.
+    Click {
+        issue_id: Uuid,
+        subscriber_id: Uuid,
+        url: String,
+    },
+    /// The link in a tracked issue's footer to stop tracking the subscriber.
+    OptOut { subscriber_id: Uuid },
+}
+
+impl TrackingToken {
+    fn payload(&self) -> String {
+        match self {
+            TrackingToken::Open {
+                issue_id,
+                subscriber_id,
+            } => format!("open\n{}\n{}", issue_id, subscriber_id),
+            TrackingToken::Click {
+                issue_id,
+                subscriber_id,
+                url,
+            } => format!("click\n{}\n{}\n{}", issue_id, subscriber_id, url),
+            TrackingToken::OptOut { subscriber_id } => format!("opt-out\n{}", subscriber_id),
+        }
+    }
+
+    fn from_payload(payload: &str) -> Option<Self> {
+        let mut parts = payload.splitn(4, '\n');
+        let kind = parts.next()?;
+        if kind == "opt-out" {
+            let subscriber_id = parts.next()?.parse().ok()?;
+            return parts.next().is_none().then_some(TrackingToken::OptOut { subscriber_id });
+        }
+        let issue_id = parts.next()?.parse().ok()?;
+        let subscriber_id = parts.next()?.parse().ok()?;
+        match (kind, parts.next()) {
+            ("open", None) => Some(TrackingToken::Open {
+                issue_id,
+                subscriber_id,
+            }),
+            ("click", Some(url)) => Some(TrackingToken::Click {
+                issue_id,
+                subscriber_id,
+                url: url.to_owned(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Signs and checks tracking tokens: the payload and its HMAC-SHA256, both
+/// base64url-encoded, so they fit in a path segment.
+#[derive(Clone)]
+pub struct TrackingSigner {
+    key: SecretString,
+}
+
+impl TrackingSigner {
+    pub fn new(key: SecretString) -> Self {
+        Self { key }
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(payload);
+        mac
+    }
+
+    pub fn sign(&self, token: &TrackingToken) -> String {
+        let payload = token.payload();
+        let signature = self.mac(payload.as_bytes()).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    /// What the token stands for, if we signed it.
+    pub fn verify(&self, token: &str) -> Option<TrackingToken> {
+        let (payload, signature) = token.split_once('.')?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(&payload).verify_slice(&signature).ok()?;
+        TrackingToken::from_payload(std::str::from_utf8(&payload).ok()?)
+    }
+}
+
+/// Replaces the target of each `<a href>` in `html` with what `rewrite`
+/// returns for it, leaving it alone on `None`. Targets are handed over
+/// with `&amp;` decoded, and replacements are escaped.
+///
+/// This is a scan for anchors rather than a parser: good enough for the
+/// HTML editors write, which is what it runs on.
+pub fn rewrite_links(html: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -> String {
+    // Lowercasing ASCII keeps byte offsets, so positions found in one hold
+    // in the other.
+    let lower = html.to_ascii_lowercase();
+    let mut rewritten = String::with_capacity(html.len());
+    let mut copied = 0;
+    let mut from = 0;
+    while let Some(start) = lower[from..].find("<a").map(|i| from + i) {
+        let attributes = start + 2;
+        let Some(end) = lower[attributes..].find('>').map(|i| attributes + i) else {
+            break;
+        };
+        from = end;
+        // Not `<abbr>`, `<aside>` and the like.
+        if !lower[attributes..].starts_with(|c: char| c.is_ascii_whitespace()) {
+            from = attributes;
+            continue;
+        }
+        let Some((value_start, value_end)) = href_value(&lower[attributes..end]) else {
+            continue;
+        };
+        let (value_start, value_end) = (attributes + value_start, attributes + value_end);
+        let target = html[value_start..value_end].trim().replace("&amp;", "&");
+        if let Some(replacement) = rewrite(&target) {
+            rewritten.push_str(&html[copied..value_start]);
+            rewritten.push_str(&replacement.replace('&', "&amp;").replace('"', "&quot;"));
+            copied = value_end;
+        }
+    }
+    rewritten.push_str(&html[copied..]);
+    rewritten
+}
+
+/// Where the value of the `href` attribute is among a tag's attributes.
+fn href_value(attributes: &str) -> Option<(usize, usize)> {
+    let mut from = 0;
+    while let Some(name) = attributes[from..].find("href").map(|i| from + i) {
+        from = name + "href".len();
+        // The whole name of an attribute, not the end of `data-href`.
+        if !attributes[..name].ends_with(|c: char| c.is_ascii_whitespace()) {
+            continue;
+        }
+        let Some(value) = attributes[from..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let start = attributes.len() - value.len();
+        return match value.chars().next()? {
+            quote @ ('"' | '\'') => {
+                let length = value[1..].find(quote)?;
+                Some((start + 1, start + 1 + length))
+            }
+            _ => {
+                let length = value
+                    .find(|c: char| c.is_ascii_whitespace())
+                    .unwrap_or(value.len());
+                Some((start, start + length))
+            }
+        };
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_none, assert_some_eq};
+    use uuid::Uuid;
+
+    use crate::tracking::{rewrite_links, TrackingSigner, TrackingToken};
+
+    fn signer(key: &str) -> TrackingSigner {
+        TrackingSigner::new(key.to_owned().into())
+    }
+
+    fn click(url: &str) -> TrackingToken {
+        TrackingToken::Click {
+            issue_id: Uuid::new_v4(),
+            subscriber_id: Uuid::new_v4(),
+            url: url.into(),
+        }
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        let signer = signer("key");
+        for token in [
+            TrackingToken::Open {
+                issue_id: Uuid::new_v4(),
+                subscriber_id: Uuid::new_v4(),
+            },
+            click("https://example.com/a?b=c&d=e\nf"),
+            TrackingToken::OptOut {
+                subscriber_id: Uuid::new_v4(),
+            },
+        ] {
+            assert_some_eq!(signer.verify(&signer.sign(&token)), token);
+        }
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_rejected() {
+        let token = signer("key").sign(&click("https://example.com"));
+        let (payload, signature) = token.split_once('.').unwrap();
+        let forged_payload = signer("key").sign(&click("https://evil.example.com"));
+        let forged_payload = forged_payload.split_once('.').unwrap().0;
+
+        assert_none!(signer("other key").verify(&token));
+        assert_none!(signer("key").verify(&format!("{}.{}", forged_payload, signature)));
+        assert_none!(signer("key").verify(payload));
+        assert_none!(signer("key").verify("not a token"));
+    }
+
+    #[test]
+    fn only_anchor_targets_are_rewritten() {
+        let html = r#"<p><A class="x" HREF = "https://example.com/?a=1&amp;b=2">one</A>
+<a href='https://example.com/two'>two</a> <a data-href="x" href=https://example.com/3>three</a>
+<abbr href="https://example.com/4">four</abbr> <a name="top">top</a> <link href="style.css"></p>"#;
+        let mut targets = Vec::new();
+
+        let rewritten = rewrite_links(html, |target| {
+            targets.push(target.to_owned());
+            Some(format!("https://t.example.com/{}?q=\"&", targets.len()))
+        });
+
+        assert_eq!(
+            targets,
+            ["https://example.com/?a=1&b=2", "https://example.com/two", "https://example.com/3"]
+        );
+        assert_eq!(
+            rewritten,
+            r#"<p><A class="x" HREF = "https://t.example.com/1?q=&quot;&amp;">one</A>
+<a href='https://t.example.com/2?q=&quot;&amp;'>two</a> <a data-href="x" href=https://t.example.com/3?q=&quot;&amp;>three</a>
+<abbr href="https://example.com/4">four</abbr> <a name="top">top</a> <link href="style.css"></p>"#
+        );
+    }
+
+    #[test]
+    fn targets_left_alone_are_kept_as_written() {
+        let html = r#"<a href="mailto:ursula@example.com?subject=Hi&amp;body=x">mail</a>"#;
+        assert_eq!(rewrite_links(html, |_| None), html);
+    }
+}
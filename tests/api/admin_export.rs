@@ -0,0 +1,94 @@
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+
+use crate::helpers::TestApp;
+
+/// Imports one subscriber per request so they are stored in order.
+async fn import_confirmed(app: &TestApp, rows: &[&str]) {
+    for row in rows {
+        app.admin_request(Method::POST, "/subscribers/import?confirmed=true&consent_source=import")
+            .body(format!("email,name\n{}\n", row))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+}
+
+async fn export(app: &TestApp, query: &str) -> reqwest::Response {
+    app.admin_request(Method::GET, &format!("/subscribers/export?{}", query))
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+#[tokio::test]
+async fn csv_export_streams_the_selected_columns_oldest_first() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    import_confirmed(
+        &app,
+        &["ursula@example.com,Ursula", "octavia@example.com,\"Butler, Octavia\""],
+    )
+    .await;
+
+    // Act
+    let response = export(&app, "format=csv&columns=email,name,status").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    assert_eq!(
+        response.headers()["content-disposition"],
+        "attachment; filename=\"subscribers.csv\""
+    );
+    assert_eq!(
+        response.text().await.unwrap(),
+        "email,name,status\n\
+         ursula@example.com,Ursula,confirmed\n\
+         octavia@example.com,\"Butler, Octavia\",confirmed\n"
+    );
+}
+
+#[tokio::test]
+async fn jsonl_export_filters_by_status_and_includes_every_column() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    import_confirmed(&app, &["ursula@example.com,Ursula"]).await;
+    app.admin_request(Method::POST, "/subscribers/import?format=jsonl")
+        .body(r#"{"email": "octavia@example.com", "name": "Octavia"}"#)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = export(&app, "format=jsonl&status=confirmed").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let body = response.text().await.unwrap();
+    let rows: Vec<Value> = body.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["email"], "ursula@example.com");
+    assert_eq!(rows[0]["consent_source"], "import");
+    assert!(rows[0]["confirmed_at"].is_string());
+    assert_eq!(rows[0].as_object().unwrap().len(), 8);
+}
+
+#[tokio::test]
+async fn exports_with_bad_parameters_are_rejected_with_a_400() {
+    // Arrange
+    let app = TestApp::spawn().await;
+
+    for query in ["columns=email,password", "columns=", "status=unsure", "format=xml"] {
+        // Act
+        let response = export(&app, query).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
@@ -0,0 +1,173 @@
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::TestApp;
+
+async fn import(app: &TestApp, query: &str, body: &'static str) -> reqwest::Response {
+    app.admin_request(Method::POST, &format!("/subscribers/import?{}", query))
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+fn errors(report: &Value) -> Vec<(u64, &str)> {
+    report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["line"].as_u64().unwrap(), e["error"].as_str().unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+async fn csv_import_stores_valid_rows_and_reports_the_rest() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriber("name=le%20guin&email=ursula_le_guin%40gmail.com").await;
+
+    // Act
+    let response = import(
+        &app,
+        "format=csv",
+        "email,name,locale\n\
+         ursula_le_guin@gmail.com,Ursula,\n\
+         octavia@example.com,Octavia Butler,de\n\
+         not-an-email,Nobody,\n\
+         octavia@example.com,Octavia again,\n",
+    )
+    .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 1);
+    assert_eq!(report["confirmation_emails_queued"], 1);
+    assert_eq!(
+        errors(&report),
+        [(2, "already_subscribed"), (4, "invalid_email"), (5, "duplicate")]
+    );
+    let saved = app.saved_subscriptions().await;
+    assert_eq!(saved.len(), 2);
+    assert_eq!(saved[1].email, "octavia@example.com");
+    assert_eq!(saved[1].status, "pending");
+    assert_eq!(saved[1].locale, "de");
+}
+
+#[tokio::test]
+async fn pending_imports_are_sent_a_confirmation_email() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    import(
+        &app,
+        "format=jsonl",
+        "{\"email\": \"ursula@example.com\", \"name\": \"Ursula\"}\n\
+         {\"email\": \"octavia@example.com\", \"name\": \"Octavia\"}\n",
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+
+    // Assert: emails go out after the response.
+    let email_request = &app.wait_for_emails(2).await[0];
+    let link = app.get_confirmation_links(email_request).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+    assert_eq!(app.subscription_tokens().await.len(), 2);
+}
+
+#[tokio::test]
+async fn confirmed_imports_record_their_consent_source_and_send_nothing() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = import(
+        &app,
+        "confirmed=true&consent_source=previous%20provider",
+        "email,name\nursula@example.com,Ursula\n",
+    )
+    .await;
+
+    // Assert
+    let report: Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 1);
+    assert_eq!(report["confirmation_emails_queued"], 0);
+    let page: Value = app
+        .admin_request(Method::GET, "/subscribers")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let subscriber = &page["subscribers"][0];
+    assert_eq!(subscriber["status"], "confirmed");
+    assert_eq!(subscriber["consent_source"], "previous provider");
+    assert!(app.subscription_tokens().await.is_empty());
+}
+
+#[tokio::test]
+async fn unusable_imports_are_rejected_with_a_400() {
+    // Arrange
+    let app = TestApp::spawn().await;
+
+    let test_cases = [
+        ("confirmed=true", "email,name\nursula@example.com,Ursula\n", "no consent source"),
+        ("confirmed=true&consent_source=", "email,name\n", "an empty consent source"),
+        ("format=csv", "email,surname\nursula@example.com,Le Guin\n", "no name column"),
+        ("format=xml", "<subscribers/>", "an unknown format"),
+    ];
+    for (query, body, description) in test_cases {
+        // Act
+        let response = import(&app, query, body).await;
+
+        // Assert
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "The import did not fail with {}",
+            description
+        );
+    }
+    assert!(app.saved_subscriptions().await.is_empty());
+}
+
+#[tokio::test]
+async fn imports_require_the_admin_token() {
+    // Arrange
+    let app = TestApp::spawn().await;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("http://{}:{}/admin/subscribers/import", app.base_url, app.port))
+        .body("email,name\nursula@example.com,Ursula\n")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(app.saved_subscriptions().await.is_empty());
+}
@@ -0,0 +1,387 @@
+use chrono::{Duration, Utc};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, Request, Respond, ResponseTemplate,
+};
+use zero_to_prod::issues::send_due_issues;
+use zero_to_prod::repository::DeliveryOutcome;
+
+use crate::helpers::TestApp;
+
+async fn spawn() -> TestApp {
+    let app = TestApp::spawn_with(|c| {
+        c.segmentation.attribute_keys = vec!["country".into()];
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app
+}
+
+/// Answers batch requests the way Postmark does, rejecting the messages
+/// to `rejected` and accepting the rest.
+struct BatchResponder {
+    rejected: &'static str,
+}
+
+impl Respond for BatchResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let emails: Vec<Value> = serde_json::from_slice(&request.body).unwrap();
+        let results: Vec<Value> = emails
+            .iter()
+            .map(|email| {
+                if email["To"] == self.rejected {
+                    json!({ "ErrorCode": 406, "Message": "Recipient is inactive" })
+                } else {
+                    json!({ "ErrorCode": 0, "Message": "OK", "MessageID": format!("message-{}", email["To"].as_str().unwrap()) })
+                }
+            })
+            .collect();
+        ResponseTemplate::new(200).set_body_json(results)
+    }
+}
+
+fn issue(title: &str) -> Value {
+    json!({
+        "title": title,
+        "html_content": "<p>Hello <strong>readers</strong></p>",
+        "text_content": "Hello readers",
+    })
+}
+
+async fn create_issue(app: &TestApp, issue: Value) -> reqwest::Response {
+    app.admin_request(Method::POST, "/issues")
+        .json(&issue)
+        .send()
+        .await
+        .unwrap()
+}
+
+/// Creates a draft, returning its id.
+async fn draft(app: &TestApp, issue: Value) -> String {
+    let response = create_issue(app, issue).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body: Value = response.json().await.unwrap();
+    body["id"].as_str().unwrap().to_owned()
+}
+
+async fn schedule(app: &TestApp, id: &str, send_at: &str) -> reqwest::Response {
+    app.admin_request(Method::PUT, &format!("/issues/{}/schedule", id))
+        .json(&json!({ "send_at": send_at }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn get_issue(app: &TestApp, id: &str) -> Value {
+    app.admin_request(Method::GET, &format!("/issues/{}", id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+/// Subscribes and confirms `name` on the default list.
+async fn confirmed_subscriber(app: &TestApp, name: &str, extra_fields: &str) {
+    let email = format!("{}%40example.com", name.to_lowercase());
+    app.post_subscriber(&format!("name={}&email={}{}", name, email, extra_fields))
+        .await
+        .error_for_status()
+        .unwrap();
+    let requests = app.email_server.received_requests().await.unwrap();
+    let link = app.get_confirmation_links(requests.last().unwrap()).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+}
+
+#[tokio::test]
+async fn issues_can_be_drafted_edited_and_deleted() {
+    // Arrange
+    let app = spawn().await;
+
+    // Act
+    let id = draft(&app, issue("Issue #1")).await;
+    let response = app
+        .admin_request(Method::PUT, &format!("/issues/{}", id))
+        .json(&json!({
+            "title": "Issue #1, revised",
+            "html_content": "<p>Revised</p>",
+            "text_content": "Revised",
+            "segment": "country:de",
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved = get_issue(&app, &id).await;
+    assert_eq!(saved["title"], "Issue #1, revised");
+    assert_eq!(saved["list"], "default");
+    assert_eq!(saved["segment"], "country:de");
+    assert_eq!(saved["status"], "draft");
+
+    let listed: Value = app
+        .admin_request(Method::GET, "/issues?status=draft")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed["issues"].as_array().unwrap().len(), 1);
+
+    let delete = || app.admin_request(Method::DELETE, &format!("/issues/{}", id)).send();
+    assert_eq!(delete().await.unwrap().status(), StatusCode::NO_CONTENT);
+    assert_eq!(delete().await.unwrap().status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn invalid_issues_are_rejected() {
+    // Arrange
+    let app = spawn().await;
+    let test_cases = [
+        (json!({ "title": " ", "html_content": "<p>Hi</p>", "text_content": "Hi" }), "blank title"),
+        (json!({ "title": "Hi", "html_content": "", "text_content": "Hi" }), "no html"),
+        (
+            json!({ "title": "Hi", "html_content": "<p>Hi</p>", "text_content": "Hi", "list": "nope" }),
+            "unknown list",
+        ),
+        (
+            json!({ "title": "Hi", "html_content": "<p>Hi</p>", "text_content": "Hi", "segment": "city:berlin" }),
+            "unknown attribute in segment",
+        ),
+    ];
+
+    for (issue, description) in test_cases {
+        // Act
+        let response = create_issue(&app, issue).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", description);
+    }
+}
+
+#[tokio::test]
+async fn send_at_is_stored_in_utc_and_must_be_in_the_future() {
+    // Arrange
+    let app = spawn().await;
+    let id = draft(&app, issue("Issue #1")).await;
+    let tomorrow = (Utc::now() + Duration::days(1)).date_naive();
+
+    // Act
+    let response = schedule(&app, &id, &format!("{}T09:00:00+02:00", tomorrow)).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let scheduled: Value = response.json().await.unwrap();
+    assert_eq!(scheduled["status"], "scheduled");
+    let send_at: chrono::DateTime<Utc> = scheduled["send_at"].as_str().unwrap().parse().unwrap();
+    assert_eq!(send_at, format!("{}T07:00:00Z", tomorrow).parse::<chrono::DateTime<Utc>>().unwrap());
+
+    let response = schedule(&app, &id, "2020-01-01T09:00:00+02:00").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = schedule(&app, &id, &format!("{}T09:00:00", tomorrow)).await;
+    assert!(response.status().is_client_error());
+
+    let response = app
+        .admin_request(Method::DELETE, &format!("/issues/{}/schedule", id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let issue = get_issue(&app, &id).await;
+    assert_eq!(issue["status"], "draft");
+    assert_eq!(issue["send_at"], Value::Null);
+}
+
+#[tokio::test]
+async fn previews_and_test_sends_render_the_issue() {
+    // Arrange
+    let app = spawn().await;
+    let id = draft(&app, issue("Issue #1")).await;
+
+    // Act
+    let preview = app
+        .admin_request(Method::GET, &format!("/issues/{}/preview", id))
+        .send()
+        .await
+        .unwrap();
+    let response = app
+        .admin_request(Method::POST, &format!("/issues/{}/test", id))
+        .json(&json!({ "email": "editor@example.com" }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(preview.status(), StatusCode::OK);
+    assert!(preview.text().await.unwrap().contains("<strong>readers</strong>"));
+
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(email_requests.len(), 1);
+    let body: Value = serde_json::from_slice(&email_requests[0].body).unwrap();
+    assert_eq!(body["To"], "editor@example.com");
+    assert_eq!(body["Subject"], "[Test] Issue #1");
+    assert!(body["HtmlBody"].as_str().unwrap().contains("<strong>readers</strong>"));
+    assert!(body["TextBody"].as_str().unwrap().contains("Hello readers"));
+    assert_eq!(get_issue(&app, &id).await["status"], "draft");
+
+    let response = app
+        .admin_request(Method::POST, &format!("/issues/{}/test", id))
+        .json(&json!({ "email": "not-an-email" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn due_issues_go_out_once_to_their_segment() {
+    // Arrange
+    let app = spawn().await;
+    confirmed_subscriber(&app, "Ursula", "&country=de").await;
+    confirmed_subscriber(&app, "Octavia", "&country=us").await;
+    let mut segmented = issue("Issue #1");
+    segmented["segment"] = json!("country:de");
+    let id = draft(&app, segmented).await;
+    let send_at = Utc::now() + Duration::hours(1);
+    schedule(&app, &id, &send_at.to_rfc3339()).await.error_for_status().unwrap();
+    let emails_before = app.email_server.received_requests().await.unwrap().len();
+
+    // Act
+    let not_yet = send_due_issues(&app.state, Utc::now()).await.unwrap();
+    let due = send_at + Duration::minutes(1);
+    let (first, second) = tokio::join!(
+        send_due_issues(&app.state, due),
+        send_due_issues(&app.state, due)
+    );
+
+    // Assert
+    assert_eq!(not_yet, 0);
+    assert_eq!(first.unwrap() + second.unwrap(), 1);
+    let sent = app.batch_emails(emails_before).await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["To"], "ursula@example.com");
+    assert_eq!(sent[0]["Subject"], "Issue #1");
+
+    let sent_issue = get_issue(&app, &id).await;
+    assert_eq!(sent_issue["status"], "sent");
+    assert_eq!(sent_issue["recipient_count"], 1);
+    let response = app
+        .admin_request(Method::PUT, &format!("/issues/{}", id))
+        .json(&issue("Issue #1, too late"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = app
+        .admin_request(Method::DELETE, &format!("/issues/{}", id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn issues_stalled_while_sending_are_resumed_for_whoever_is_still_queued() {
+    // Arrange
+    let app = spawn().await;
+    confirmed_subscriber(&app, "Ursula", "").await;
+    confirmed_subscriber(&app, "Octavia", "").await;
+    let id = draft(&app, issue("Issue #1")).await;
+    let send_at = Utc::now() + Duration::hours(1);
+    schedule(&app, &id, &send_at.to_rfc3339()).await.error_for_status().unwrap();
+    // An instance claims the issue, gets it to Ursula and stops.
+    let issue = app.state.subscribers.claim_due_issue(send_at).await.unwrap().unwrap();
+    let mut subscriber_ids = Vec::new();
+    for email in ["ursula@example.com", "octavia@example.com"] {
+        let subscriber = app.state.subscribers.find_by_email(email).await.unwrap().unwrap();
+        subscriber_ids.push(subscriber.id);
+    }
+    app.state.subscribers.queue_deliveries(issue.id, &subscriber_ids).await.unwrap();
+    let sent = DeliveryOutcome::Sent { message_id: None };
+    app.state
+        .subscribers
+        .record_delivery(issue.id, subscriber_ids[0], &sent)
+        .await
+        .unwrap();
+    let emails_before = app.email_server.received_requests().await.unwrap().len();
+
+    // Act
+    let too_soon = send_due_issues(&app.state, send_at + Duration::minutes(1)).await.unwrap();
+    let stalled = send_at + app.state.scheduler.stalled_after() + Duration::minutes(1);
+    let resumed = send_due_issues(&app.state, stalled).await.unwrap();
+    let again = send_due_issues(&app.state, stalled).await.unwrap();
+
+    // Assert
+    assert_eq!((too_soon, resumed, again), (0, 1, 0));
+    let sent = app.batch_emails(emails_before).await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["To"], "octavia@example.com");
+    let sent_issue = get_issue(&app, &id).await;
+    assert_eq!(sent_issue["status"], "sent");
+    assert_eq!(sent_issue["recipient_count"], 2);
+}
+
+#[tokio::test]
+async fn deliveries_record_each_recipient_and_are_summarised() {
+    // Arrange
+    let app = spawn().await;
+    confirmed_subscriber(&app, "Ursula", "").await;
+    confirmed_subscriber(&app, "Octavia", "").await;
+    Mock::given(path("/email/batch"))
+        .respond_with(BatchResponder { rejected: "octavia@example.com" })
+        .with_priority(1)
+        .mount(&app.email_server)
+        .await;
+    let id = draft(&app, issue("Issue #1")).await;
+    let send_at = Utc::now() + Duration::hours(1);
+    schedule(&app, &id, &send_at.to_rfc3339()).await.error_for_status().unwrap();
+
+    // Act
+    send_due_issues(&app.state, send_at).await.unwrap();
+
+    // Assert
+    let report: Value = app
+        .admin_request(Method::GET, &format!("/issues/{}/deliveries", id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["status"], "sent");
+    assert_eq!(report["total"], 2);
+    assert_eq!(report["sent"], 1);
+    assert_eq!(report["failed"], 1);
+    assert_eq!(report["queued"], 0);
+    let failures = report["failures"].as_array().unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0]["email"], "octavia@example.com");
+    assert_eq!(failures[0]["attempts"], 1);
+    assert!(failures[0]["last_error"].as_str().unwrap().contains("Recipient is inactive"));
+
+    let mut deliveries = app.saved_deliveries().await;
+    deliveries.sort();
+    assert_eq!(
+        deliveries,
+        [
+            ("failed".to_owned(), None),
+            ("sent".to_owned(), Some("message-ursula@example.com".to_owned()))
+        ]
+    );
+    assert_eq!(get_issue(&app, &id).await["recipient_count"], 1);
+}
@@ -0,0 +1,220 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::TestApp;
+
+async fn create_list(app: &TestApp, list: Value) -> reqwest::Response {
+    app.admin_request(Method::POST, "/lists")
+        .json(&list)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn subscriber(app: &TestApp, email: &str) -> Value {
+    let page: Value = app
+        .admin_request(Method::GET, &format!("/subscribers?q={}", email))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = page["subscribers"][0]["id"].as_str().unwrap().to_owned();
+    app.admin_request(Method::GET, &format!("/subscribers/{}", id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn there_is_a_default_list_from_the_start() {
+    // Arrange
+    let app = TestApp::spawn().await;
+
+    // Act
+    let response = app.admin_request(Method::GET, "/lists").send().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    let slugs: Vec<_> = body["lists"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|list| list["slug"].as_str().unwrap())
+        .collect();
+    assert_eq!(slugs, ["default"]);
+}
+
+#[tokio::test]
+async fn subscribing_to_a_list_uses_its_sender_and_copy() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = create_list(
+        &app,
+        json!({
+            "slug": "weekly",
+            "name": "The Weekly",
+            "sender_email": "weekly@example.com",
+            "sender_name": "The Weekly",
+            "confirmation_subject": "Confirm your Weekly subscription",
+        }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // Act
+    app.post_subscriber("name=le%20guin&email=ursula_le_guin%40gmail.com&list=weekly")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["From"], "\"The Weekly\" <weekly@example.com>");
+    assert_eq!(body["Subject"], "Confirm your Weekly subscription");
+
+    let link = app.get_confirmation_links(email_request).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+    let details = subscriber(&app, "ursula_le_guin").await;
+    assert_eq!(details["status"], "confirmed");
+    assert_eq!(details["lists"][0]["list"], "weekly");
+    assert_eq!(details["lists"][0]["status"], "confirmed");
+}
+
+#[tokio::test]
+async fn the_same_email_can_join_several_lists() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    create_list(&app, json!({ "slug": "weekly", "name": "The Weekly" }))
+        .await
+        .error_for_status()
+        .unwrap();
+    app.post_subscriber("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let link = app.get_confirmation_links(email_request).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+
+    // Act
+    app.post_subscriber("name=le%20guin&email=ursula_le_guin%40gmail.com&list=weekly")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    assert_eq!(app.saved_subscriptions().await.len(), 1);
+    let details = subscriber(&app, "ursula_le_guin").await;
+    let lists: Vec<_> = details["lists"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| (m["list"].as_str().unwrap(), m["status"].as_str().unwrap()))
+        .collect();
+    assert_eq!(lists, [("default", "confirmed"), ("weekly", "pending")]);
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_is_rejected() {
+    // Arrange
+    let app = TestApp::spawn().await;
+
+    // Act
+    let response = app
+        .post_subscriber("name=le%20guin&email=ursula_le_guin%40gmail.com&list=nope")
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "unknown_list");
+    assert!(app.saved_subscriptions().await.is_empty());
+}
+
+#[tokio::test]
+async fn lists_are_validated_and_slugs_are_unique() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let test_cases = [
+        (json!({ "slug": "Weekly", "name": "Weekly" }), "uppercase slug"),
+        (json!({ "slug": "weekly", "name": " " }), "blank name"),
+        (
+            json!({ "slug": "weekly", "name": "Weekly", "sender_email": "nope" }),
+            "invalid sender email",
+        ),
+        (
+            json!({ "slug": "weekly", "name": "Weekly", "sender_name": "Evil <a@b.c>" }),
+            "sender name with angle brackets",
+        ),
+    ];
+    for (list, description) in test_cases {
+        // Act
+        let response = create_list(&app, list).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", description);
+    }
+
+    let list = json!({ "slug": "weekly", "name": "Weekly" });
+    assert_eq!(create_list(&app, list.clone()).await.status(), StatusCode::CREATED);
+    assert_eq!(create_list(&app, list).await.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn lists_can_be_updated_and_deleted_except_the_default_one() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    create_list(&app, json!({ "slug": "weekly", "name": "Weekly" }))
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = app
+        .admin_request(Method::PUT, "/lists/weekly")
+        .json(&json!({ "name": "The Weekly", "sender_name": "Weekly team" }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let list: Value = app
+        .admin_request(Method::GET, "/lists/weekly")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(list["name"], "The Weekly");
+    assert_eq!(list["sender_name"], "Weekly team");
+
+    let delete = |slug: &'static str| app.admin_request(Method::DELETE, &format!("/lists/{}", slug)).send();
+    assert_eq!(delete("default").await.unwrap().status(), StatusCode::CONFLICT);
+    assert_eq!(delete("weekly").await.unwrap().status(), StatusCode::NO_CONTENT);
+    assert_eq!(delete("weekly").await.unwrap().status(), StatusCode::NOT_FOUND);
+    let response = app.admin_request(Method::GET, "/lists/weekly").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,290 @@
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::TestApp;
+
+/// Subscribes everyone in `names` in order, confirming those in `confirmed`.
+async fn subscribe_all(app: &TestApp, names: &[&str], confirmed: &[&str]) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    for (i, name) in names.iter().enumerate() {
+        app.post_subscriber(&format!("name={}&email={}%40example.com", name, name.to_lowercase()))
+            .await
+            .error_for_status()
+            .unwrap();
+        if confirmed.contains(name) {
+            let email_request = &app.email_server.received_requests().await.unwrap()[i];
+            let link = app.get_confirmation_links(email_request).html;
+            reqwest::get(link).await.unwrap().error_for_status().unwrap();
+        }
+    }
+}
+
+async fn list(app: &TestApp, query: &str) -> Value {
+    let response = app
+        .admin_request(Method::GET, &format!("/subscribers?{}", query))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json().await.unwrap()
+}
+
+fn names(page: &Value) -> Vec<&str> {
+    page["subscribers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn admin_endpoints_require_the_admin_token() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let url = format!("http://{}:{}/admin/subscribers", app.base_url, app.port);
+    let client = reqwest::Client::new();
+
+    for request in [
+        client.get(&url),
+        client.get(&url).bearer_auth("wrong-token"),
+        client.get(&url).basic_auth("admin", Some("admin-token")),
+    ] {
+        // Act
+        let response = request.send().await.unwrap();
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+    }
+}
+
+#[tokio::test]
+async fn admin_endpoints_are_disabled_without_a_configured_token() {
+    let app = TestApp::spawn_with(|c| c.admin.api_token = None).await;
+
+    let response = app
+        .admin_request(Method::GET, "/subscribers")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn subscribers_are_listed_newest_first_one_page_at_a_time() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    subscribe_all(&app, &["Ada", "Bob", "Cid", "Dan", "Eve"], &[]).await;
+
+    // Act
+    let first = list(&app, "limit=2").await;
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let second = list(&app, &format!("limit=2&cursor={}", cursor)).await;
+    let cursor = second["next_cursor"].as_str().unwrap();
+    let third = list(&app, &format!("limit=2&cursor={}", cursor)).await;
+
+    // Assert
+    assert_eq!(names(&first), ["Eve", "Dan"]);
+    assert_eq!(names(&second), ["Cid", "Bob"]);
+    assert_eq!(names(&third), ["Ada"]);
+    assert!(third["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status_date_and_prefix() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    subscribe_all(&app, &["Ada", "Adam", "Bob"], &["Adam", "Bob"]).await;
+
+    // Act & Assert
+    assert_eq!(names(&list(&app, "status=confirmed").await), ["Bob", "Adam"]);
+    assert_eq!(names(&list(&app, "status=pending").await), ["Ada"]);
+    assert_eq!(names(&list(&app, "q=ad").await), ["Adam", "Ada"]);
+    assert_eq!(names(&list(&app, "q=BOB%40").await), ["Bob"]);
+    assert_eq!(names(&list(&app, "status=confirmed&q=ada").await), ["Adam"]);
+    assert!(names(&list(&app, "subscribed_after=2999-01-01T00:00:00Z").await).is_empty());
+    assert_eq!(
+        names(&list(&app, "subscribed_before=2999-01-01T00:00:00Z").await).len(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn invalid_listing_parameters_are_rejected() {
+    let app = TestApp::spawn().await;
+
+    for query in ["status=unknown", "limit=0", "limit=1000", "cursor=nonsense", "subscribed_after=yesterday"] {
+        let response = app
+            .admin_request(Method::GET, &format!("/subscribers?{}", query))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn a_subscriber_is_shown_with_their_confirmation_history() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    subscribe_all(&app, &["Ada"], &["Ada"]).await;
+    let id = list(&app, "").await["subscribers"][0]["id"].as_str().unwrap().to_owned();
+
+    // Act
+    let response = app
+        .admin_request(Method::GET, &format!("/subscribers/{}", id))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let subscriber: Value = response.json().await.unwrap();
+    assert_eq!(subscriber["email"], "ada@example.com");
+    assert_eq!(subscriber["status"], "confirmed");
+    assert!(subscriber["confirmed_at"].is_string());
+    let tokens = subscriber["confirmation_tokens"].as_array().unwrap();
+    assert_eq!(tokens.len(), 1);
+    assert!(tokens[0]["expires_at"].is_string());
+
+    let response = app
+        .admin_request(Method::GET, &format!("/subscribers/{}", uuid::Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn soft_deleted_subscribers_are_kept_but_their_links_stop_working() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    subscribe_all(&app, &["Ada"], &[]).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let link = app.get_confirmation_links(email_request).html;
+    let id = list(&app, "").await["subscribers"][0]["id"].as_str().unwrap().to_owned();
+
+    // Act
+    let response = app
+        .admin_request(Method::DELETE, &format!("/subscribers/{}", id))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(app.saved_subscriptions().await[0].status, "deleted");
+    assert!(app.subscription_tokens().await.is_empty());
+    assert_eq!(reqwest::get(link).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn hard_deletes_remove_the_subscriber_and_their_tokens() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    subscribe_all(&app, &["Ada"], &[]).await;
+    let id = list(&app, "").await["subscribers"][0]["id"].as_str().unwrap().to_owned();
+    let delete = || app.admin_request(Method::DELETE, &format!("/subscribers/{}?mode=hard", id));
+
+    // Act
+    let response = delete().send().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(app.saved_subscriptions().await.is_empty());
+    assert!(app.subscription_tokens().await.is_empty());
+    assert_eq!(delete().send().await.unwrap().status(), StatusCode::NOT_FOUND);
+}
+
+async fn events(app: &TestApp, id: &str) -> Vec<Value> {
+    let response = app
+        .admin_request(Method::GET, &format!("/subscribers/{}/events", id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    body["events"].as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn consent_events_record_who_did_what_from_where() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    reqwest::Client::new()
+        .post(format!("http://{}:{}/subscriptions", app.base_url, app.port))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("User-Agent", "Mozilla/5.0 (test)")
+        .body("name=Ada&email=ada%40example.com&source=footer")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let link = app.get_confirmation_links(email_request).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+    let id = list(&app, "").await["subscribers"][0]["id"].as_str().unwrap().to_owned();
+    app.admin_request(Method::DELETE, &format!("/subscribers/{}", id))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let events = events(&app, &id).await;
+    let kinds: Vec<_> = events.iter().map(|e| e["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["subscribed", "confirmed", "deleted"]);
+    assert_eq!(events[0]["ip"], "127.0.0.1");
+    assert_eq!(events[0]["user_agent"], "Mozilla/5.0 (test)");
+    assert_eq!(events[0]["source"], "footer");
+    assert!(events.iter().all(|e| e["request_id"].is_string()));
+    assert_eq!(events[2]["source"], "admin");
+
+    let response = app
+        .admin_request(Method::GET, &format!("/subscribers/{}/events", uuid::Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn imported_subscribers_record_their_consent_source() {
+    // Arrange
+    let app = TestApp::spawn().await;
+
+    // Act
+    app.admin_request(Method::POST, "/subscribers/import?confirmed=true&consent_source=old%20list")
+        .body("email,name\nada@example.com,Ada\n")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let id = list(&app, "").await["subscribers"][0]["id"].as_str().unwrap().to_owned();
+    let events = events(&app, &id).await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["kind"], "imported");
+    assert_eq!(events[0]["source"], "old list");
+}
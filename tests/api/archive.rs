@@ -0,0 +1,173 @@
+use chrono::{Duration, Utc};
+use reqwest::{header, Method, StatusCode};
+use serde_json::{json, Value};
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+use zero_to_prod::issues::send_due_issues;
+
+use crate::helpers::TestApp;
+
+async fn spawn(page_size: i64) -> TestApp {
+    let app = TestApp::spawn_with(|c| {
+        c.archive.page_size = page_size;
+        c.archive.title = "The Weekly".into();
+    })
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app
+}
+
+/// Drafts an issue, then sends it unless `send` is false; returns its
+/// slug.
+async fn issue(app: &TestApp, title: &str, public: bool, send: bool) -> String {
+    let response = app
+        .admin_request(Method::POST, "/issues")
+        .json(&json!({
+            "title": title,
+            "html_content": format!("<p>All about {}</p>", title),
+            "text_content": format!("All about {}", title),
+            "public": public,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let issue: Value = response.json().await.unwrap();
+    if send {
+        let send_at = Utc::now() + Duration::minutes(1);
+        app.admin_request(Method::PUT, &format!("/issues/{}/schedule", issue["id"].as_str().unwrap()))
+            .json(&json!({ "send_at": send_at.to_rfc3339() }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        send_due_issues(&app.state, send_at).await.unwrap();
+    }
+    issue["slug"].as_str().unwrap().to_owned()
+}
+
+async fn get(app: &TestApp, path: &str) -> reqwest::Response {
+    reqwest::get(format!("http://{}:{}{}", app.base_url, app.port, path))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn only_public_sent_issues_are_archived() {
+    // Arrange
+    let app = spawn(20).await;
+    let public = issue(&app, "Public issue", true, true).await;
+    let private = issue(&app, "Private issue", false, true).await;
+    let draft = issue(&app, "Public draft", true, false).await;
+
+    // Act
+    let index = get(&app, "/archive").await;
+    let archived = get(&app, &format!("/archive/{}", public)).await;
+
+    // Assert
+    assert_eq!(index.status(), StatusCode::OK);
+    let index = index.text().await.unwrap();
+    assert!(index.contains("The Weekly"));
+    assert!(index.contains(&format!(r#"href="http://127.0.0.1:{}/archive/{}""#, app.port, public)));
+    assert!(!index.contains("Private issue"));
+    assert!(!index.contains("Public draft"));
+    assert_eq!(archived.status(), StatusCode::OK);
+    assert!(archived.text().await.unwrap().contains("<p>All about Public issue</p>"));
+    for slug in [private, draft] {
+        assert_eq!(get(&app, &format!("/archive/{}", slug)).await.status(), StatusCode::NOT_FOUND);
+    }
+}
+
+#[tokio::test]
+async fn the_archive_is_paginated_newest_first() {
+    // Arrange
+    let app = spawn(2).await;
+    for title in ["Issue one", "Issue two", "Issue three"] {
+        issue(&app, title, true, true).await;
+    }
+
+    // Act
+    let first = get(&app, "/archive").await.text().await.unwrap();
+    let second = get(&app, "/archive?page=2").await.text().await.unwrap();
+    let third = get(&app, "/archive?page=3").await;
+
+    // Assert
+    assert!(first.contains("Issue three") && first.contains("Issue two"));
+    assert!(!first.contains("Issue one"));
+    assert!(first.find("Issue three") < first.find("Issue two"));
+    assert!(first.contains("/archive?page=2"));
+    assert!(second.contains("Issue one"));
+    assert!(!second.contains("Issue two"));
+    assert!(!second.contains("/archive?page=3"));
+    assert_eq!(third.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_feed_lists_archived_issues() {
+    // Arrange
+    let app = spawn(20).await;
+    let slug = issue(&app, "Rust & you", true, true).await;
+    issue(&app, "Private issue", false, true).await;
+
+    // Act
+    let response = get(&app, "/archive/feed.atom").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/atom+xml; charset=utf-8"
+    );
+    let feed = feed_rs::parser::parse(response.bytes().await.unwrap().as_ref()).unwrap();
+    assert_eq!(feed.title.unwrap().content, "The Weekly");
+    assert_eq!(feed.entries.len(), 1);
+    let entry = &feed.entries[0];
+    assert_eq!(entry.title.as_ref().unwrap().content, "Rust & you");
+    assert_eq!(
+        entry.links[0].href,
+        format!("http://127.0.0.1:{}/archive/{}", app.port, slug)
+    );
+    assert_eq!(
+        entry.content.as_ref().unwrap().body.as_deref(),
+        Some("<p>All about Rust & you</p>")
+    );
+}
+
+#[tokio::test]
+async fn unchanged_archive_responses_are_not_sent_again() {
+    // Arrange
+    let app = spawn(20).await;
+    let slug = issue(&app, "Cached issue", true, true).await;
+    let path = format!("/archive/{}", slug);
+    let response = get(&app, &path).await;
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "public, max-age=300");
+    let etag = response.headers()[header::ETAG].clone();
+    let last_modified = response.headers()[header::LAST_MODIFIED].clone();
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}{}", app.base_url, app.port, path);
+
+    // Act
+    let by_etag = client.get(&url).header(header::IF_NONE_MATCH, etag.clone()).send().await.unwrap();
+    let by_date = client
+        .get(&url)
+        .header(header::IF_MODIFIED_SINCE, last_modified)
+        .send()
+        .await
+        .unwrap();
+    let stale = client
+        .get(&url)
+        .header(header::IF_NONE_MATCH, "\"something-else\"")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(by_etag.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(by_etag.headers()[header::ETAG], etag);
+    assert_eq!(by_date.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(stale.status(), StatusCode::OK);
+    assert!(stale.text().await.unwrap().contains("Cached issue"));
+}
@@ -0,0 +1,122 @@
+use chrono::Utc;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+use zero_to_prod::{
+    configuration::{DigestMode, FeedSettings},
+    feeds::ingest_feeds,
+    issues::send_due_issues,
+};
+
+use crate::helpers::TestApp;
+
+const RSS: &str = include_str!("fixtures/blog.rss");
+const ATOM: &str = include_str!("fixtures/blog.atom");
+
+/// `feed` as it was before its latest entry, the first `<tag>`, was
+/// published.
+fn before_latest(feed: &str, tag: &str) -> String {
+    let start = feed.find(&format!("<{}>", tag)).unwrap();
+    let end = feed.find(&format!("</{}>", tag)).unwrap() + tag.len() + 3;
+    format!("{}{}", &feed[..start], &feed[end..])
+}
+
+/// Serves `earlier` at `feed_path` once, then `later`.
+async fn serve_feed(server: &MockServer, feed_path: &str, earlier: String, later: &str) {
+    Mock::given(method("GET"))
+        .and(path(feed_path))
+        .respond_with(ResponseTemplate::new(200).set_body_string(earlier))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(feed_path))
+        .respond_with(ResponseTemplate::new(200).set_body_string(later))
+        .mount(server)
+        .await;
+}
+
+fn settings(feed_server: &MockServer, feed_paths: &[&str], digest: DigestMode) -> FeedSettings {
+    FeedSettings {
+        urls: feed_paths
+            .iter()
+            .map(|feed_path| format!("{}{}", feed_server.uri(), feed_path).parse().unwrap())
+            .collect(),
+        digest,
+        ..FeedSettings::default()
+    }
+}
+
+#[tokio::test]
+async fn new_entries_are_drafted_into_a_digest_once() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let feed_server = MockServer::start().await;
+    serve_feed(&feed_server, "/blog.rss", before_latest(RSS, "item"), RSS).await;
+    Mock::given(path("/broken.xml"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&feed_server)
+        .await;
+    let settings = settings(&feed_server, &["/broken.xml", "/blog.rss"], DigestMode::Draft);
+
+    // Act
+    let first = ingest_feeds(&app.state, &settings, Utc::now()).await.unwrap();
+    let second = ingest_feeds(&app.state, &settings, Utc::now()).await.unwrap();
+    let third = ingest_feeds(&app.state, &settings, Utc::now()).await.unwrap();
+
+    // Assert
+    assert_eq!(first, None, "The entries a feed had already are not new");
+    assert_eq!(third, None);
+    let issue = second.unwrap();
+    assert_eq!(issue.status, "draft");
+    assert_eq!(issue.list, "default");
+    assert_eq!(issue.title, "New on the blog");
+    assert!(issue.html_content.contains(
+        r#"<a href="https://blog.example.com/posts/new-editor?ref=rss&amp;utm_medium=feed">Shipping the new editor</a>"#
+    ));
+    assert!(issue.html_content.contains("Writing issues got a lot easier &amp; faster."));
+    assert!(issue.text_content.contains("Writing issues got a lot easier & faster."));
+    assert!(issue
+        .text_content
+        .contains("https://blog.example.com/posts/new-editor?ref=rss&utm_medium=feed"));
+    assert!(!issue.html_content.contains("Why we moved to Postgres"));
+}
+
+#[tokio::test]
+async fn atom_digests_can_be_sent_right_away() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriber("name=Ursula&email=ursula%40example.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let link = app.get_confirmation_links(&app.wait_for_emails(1).await[0]).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+    let feed_server = MockServer::start().await;
+    serve_feed(&feed_server, "/releases.atom", before_latest(ATOM, "entry"), ATOM).await;
+    let settings = settings(&feed_server, &["/releases.atom"], DigestMode::Send);
+    ingest_feeds(&app.state, &settings, Utc::now()).await.unwrap();
+    let emails_before = app.email_server.received_requests().await.unwrap().len();
+
+    // Act
+    let issue = ingest_feeds(&app.state, &settings, Utc::now()).await.unwrap().unwrap();
+    let sent = send_due_issues(&app.state, Utc::now()).await.unwrap();
+
+    // Assert
+    assert_eq!(issue.status, "scheduled");
+    assert_eq!(sent, 1);
+    let emails = app.batch_emails(emails_before).await;
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0]["To"], "ursula@example.com");
+    let html = emails[0]["HtmlBody"].as_str().unwrap();
+    assert!(html.contains("Version 2.0 is out"));
+    assert!(html.contains(&format!(r#"href="{}/releases/2.0""#, feed_server.uri())));
+    assert!(html.contains("Batch sending and feed digests."));
+    assert!(!html.contains("Version 1.0"));
+}
@@ -1,10 +1,16 @@
+use chrono::{DateTime, Utc};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::future::IntoFuture;
-use std::{net::ToSocketAddrs, sync::LazyLock};
+use std::net::SocketAddr;
+use std::sync::LazyLock;
 use uuid::Uuid;
 use wiremock::MockServer;
 
-use zero_to_prod::{configuration::get_configuration, get_subscriber, init_subscriber, startup};
+use zero_to_prod::{
+    app_state::AppState,
+    configuration::{get_configuration, DatabaseBackend, DatabaseSettings, Settings},
+    get_subscriber, init_subscriber, startup,
+};
 
 static INIT_SUBSCRIBER: LazyLock<()> = LazyLock::new(|| {
     if std::env::var("TEST_LOG").is_ok() {
@@ -18,16 +24,96 @@ static INIT_SUBSCRIBER: LazyLock<()> = LazyLock::new(|| {
     }
 });
 
+pub const ADMIN_TOKEN: &str = "admin-token";
+
 pub struct ConfirmationLinks {
     pub html: reqwest::Url,
     pub plain_text: reqwest::Url,
 }
 
+/// The test's own connection to whichever database the app was configured
+/// with (`APP_DATABASE__BACKEND`).
+pub enum TestDatabase {
+    Postgres(PgPool),
+    #[cfg(feature = "sqlite")]
+    Sqlite(sqlx::SqlitePool),
+}
+
+/// Runs `$body` with `$pool` bound to the pool of either backend; both
+/// accept `$1`-style placeholders.
+macro_rules! with_pool {
+    ($db:expr, |$pool:ident| $body:expr) => {
+        match $db {
+            TestDatabase::Postgres($pool) => $body,
+            #[cfg(feature = "sqlite")]
+            TestDatabase::Sqlite($pool) => $body,
+        }
+    };
+}
+
+#[derive(sqlx::FromRow)]
+pub struct SavedSubscription {
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub locale: String,
+}
+
+impl TestDatabase {
+    /// Creates a fresh, migrated database and points `settings` at it.
+    async fn create(settings: &mut DatabaseSettings) -> TestDatabase {
+        create_empty_database(settings).await;
+        startup::prepare_database(&DatabaseSettings {
+            run_migrations_on_startup: true,
+            ..settings.clone()
+        })
+        .await
+        .expect("Failed to migrate the database");
+        match settings.backend {
+            DatabaseBackend::Postgres => {
+                TestDatabase::Postgres(PgPool::connect_lazy_with(settings.with_db()))
+            }
+            #[cfg(feature = "sqlite")]
+            DatabaseBackend::Sqlite => {
+                TestDatabase::Sqlite(sqlx::SqlitePool::connect_lazy_with(settings.sqlite()))
+            }
+            #[cfg(not(feature = "sqlite"))]
+            DatabaseBackend::Sqlite => unreachable!(),
+        }
+    }
+}
+
+/// Creates a database without any tables and points `settings` at it.
+pub async fn create_empty_database(settings: &mut DatabaseSettings) {
+    match settings.backend {
+        DatabaseBackend::Postgres => {
+            settings.database_name = Uuid::new_v4().to_string();
+            let mut connection = PgConnection::connect_with(&settings.without_db())
+                .await
+                .expect("Failed to connect to Postgres");
+            connection
+                .execute(format!(r#"CREATE DATABASE "{}";"#, settings.database_name).as_str())
+                .await
+                .expect("Failed to create database.");
+        }
+        // The file is created on first connection.
+        DatabaseBackend::Sqlite => {
+            settings.sqlite_path = std::env::temp_dir()
+                .join(format!("{}.sqlite3", Uuid::new_v4()))
+                .to_string_lossy()
+                .into_owned();
+        }
+    }
+}
+
 pub struct TestApp {
     pub base_url: String,
     pub port: u16,
-    pub db_pool: PgPool,
+    pub db: TestDatabase,
     pub email_server: MockServer,
+    /// What the app runs with, for driving background work such as the
+    /// issue scheduler, which tests do not spawn.
+    pub state: AppState,
 }
 
 impl TestApp {
@@ -35,46 +121,90 @@ impl TestApp {
         LazyLock::force(&INIT_SUBSCRIBER);
     }
     pub async fn spawn() -> TestApp {
+        Self::spawn_with(|_| {}).await
+    }
+
+    /// Like `spawn`, but lets the test adjust the configuration first.
+    pub async fn spawn_with(customise: impl FnOnce(&mut Settings)) -> TestApp {
         Self::init_subscriber();
         let mut configuration = get_configuration().expect("Failed to read configuration");
-        let database = &mut configuration.database;
-        database.database_name = Uuid::new_v4().to_string();
-
-        let mut connection = PgConnection::connect_with(&database.without_db())
-            .await
-            .expect("Failed to connect to Postgres");
-        connection
-            .execute(format!(r#"CREATE DATABASE "{}";"#, database.database_name).as_str())
-            .await
-            .expect("Failed to create database.");
+        configuration.admin.api_token = Some(ADMIN_TOKEN.into());
+        configuration.privacy.signing_key = Some(Uuid::new_v4().to_string().into());
+        configuration.tracking.signing_key = Some(Uuid::new_v4().to_string().into());
+        // Issue sends should not wait on the rate limit or between retries.
+        configuration.email_client.batch.requests_per_second = 0;
+        configuration.email_client.batch.retry_delay_milliseconds = 0;
+        customise(&mut configuration);
+        let db = TestDatabase::create(&mut configuration.database).await;
 
         let email_server = MockServer::start().await;
         configuration.email_client.base_url = email_server.uri();
 
-        let state = startup::build(configuration).unwrap();
-        let db_pool = state.connection_pool.clone();
-
-        sqlx::migrate!("./migrations")
-            .run(&state.connection_pool)
-            .await
-            .expect("Failed to migrate the database");
-
         let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
         let port = listener.local_addr().unwrap().port();
+        configuration.application.base_url =
+            reqwest::Url::parse(&format!("http://127.0.0.1:{}", port)).unwrap();
+
+        let state = startup::build(configuration).unwrap();
+
         // tokio::spawn(zero_to_prod::run(listener, db_pool.clone(), email_client));
-        tokio::spawn(axum::serve(listener, startup::router(state)).into_future());
+        let app = startup::router(state.clone()).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(axum::serve(listener, app).into_future());
 
         TestApp {
             base_url: "127.0.0.1".to_owned(),
             port,
-            db_pool,
+            db,
             email_server,
+            state,
         }
     }
 
+    /// Stored subscriptions, oldest first.
+    pub async fn saved_subscriptions(&self) -> Vec<SavedSubscription> {
+        let query = "SELECT email, name, status, locale FROM subscriptions ORDER BY subscribed_at";
+        with_pool!(&self.db, |pool| sqlx::query_as(query).fetch_all(pool).await)
+            .expect("Failed to fetch saved subscriptions.")
+    }
+
+    pub async fn subscription_tokens(&self) -> Vec<String> {
+        let query = "SELECT token FROM subscription_tokens";
+        with_pool!(&self.db, |pool| sqlx::query_scalar(query).fetch_all(pool).await)
+            .expect("Failed to fetch subscription tokens.")
+    }
+
+    /// Pretends every confirmation link was sent at `created_at`.
+    pub async fn set_subscription_tokens_created_at(&self, created_at: DateTime<Utc>) {
+        let query = "UPDATE subscription_tokens SET created_at = $1";
+        with_pool!(&self.db, |pool| sqlx::query(query)
+            .bind(created_at)
+            .execute(pool)
+            .await
+            .map(|_| ()))
+        .expect("Failed to update subscription tokens.");
+    }
+
+    /// Status and provider message id of every delivery, in no particular
+    /// order.
+    pub async fn saved_deliveries(&self) -> Vec<(String, Option<String>)> {
+        let query = "SELECT status, message_id FROM deliveries";
+        with_pool!(&self.db, |pool| sqlx::query_as(query).fetch_all(pool).await)
+            .expect("Failed to fetch deliveries.")
+    }
+
+    /// A request to `/admin{path}` carrying the admin token.
+    pub fn admin_request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .request(
+                method,
+                format!("http://{}:{}/admin{}", self.base_url, self.port, path),
+            )
+            .bearer_auth(ADMIN_TOKEN)
+    }
+
     pub async fn post_subscriber(&self, body: &str) -> reqwest::Response {
         reqwest::Client::new()
-            .post(&format!("http://{}:{}/subscriptions", self.base_url, self.port))
+            .post(format!("http://{}:{}/subscriptions", self.base_url, self.port))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body.to_string())
             .send()
@@ -82,6 +212,49 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn post_privacy_request(&self, body: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("http://{}:{}/privacy/requests", self.base_url, self.port))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_resend_confirmation(&self, body: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("http://{}:{}/subscriptions/resend-confirmation", self.base_url, self.port))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// The emails sent so far, once there are at least `count`; some are
+    /// sent in the background after the response.
+    pub async fn wait_for_emails(&self, count: usize) -> Vec<wiremock::Request> {
+        for _ in 0..50 {
+            let requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= count {
+                return requests;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("Expected {} emails to be sent", count);
+    }
+
+    /// The messages sent through the batch API, skipping the first `skip`
+    /// requests the email server received.
+    pub async fn batch_emails(&self, skip: usize) -> Vec<serde_json::Value> {
+        self.email_server.received_requests().await.unwrap()[skip..]
+            .iter()
+            .filter(|request| request.url.path() == "/email/batch")
+            .flat_map(|request| serde_json::from_slice::<Vec<serde_json::Value>>(&request.body).unwrap())
+            .collect()
+    }
+
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
 
@@ -92,14 +265,14 @@ impl TestApp {
                 .collect();
             assert_eq!(links.len(), 1);
             let raw_link = links[0].as_str().to_owned();
-            let mut confirmation_link = reqwest::Url::parse(&raw_link).unwrap();
-            
+            let confirmation_link = reqwest::Url::parse(&raw_link).unwrap();
+
             assert_eq!(confirmation_link.host_str().unwrap(), self.base_url);
-            confirmation_link.set_port(Some(self.port)).unwrap();
+            assert_eq!(confirmation_link.port(), Some(self.port));
             confirmation_link
         };
-        let html = get_link(&body["HtmlBody"].as_str().unwrap());
-        let plain_text = get_link(&body["TextBody"].as_str().unwrap());
+        let html = get_link(body["HtmlBody"].as_str().unwrap());
+        let plain_text = get_link(body["TextBody"].as_str().unwrap());
         ConfirmationLinks { html, plain_text }
     }
 }
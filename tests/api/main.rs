@@ -1,3 +1,16 @@
+mod admin_export;
+mod admin_import;
+mod admin_issues;
+mod admin_lists;
+mod admin_subscribers;
+mod archive;
+mod feeds;
 mod helpers;
 mod health_check;
-mod subscriptions;
\ No newline at end of file
+mod migrations;
+mod preferences;
+mod privacy;
+mod segments;
+mod subscriptions;
+mod subscriptions_confirm;
+mod tracking;
@@ -0,0 +1,110 @@
+#![allow(unused_must_use)]
+
+use claim::{assert_err, assert_ok};
+use zero_to_prod::{
+    configuration::{get_configuration, DatabaseBackend, DatabaseSettings},
+    migrations::{MigrationState, SchemaConnection},
+    startup::prepare_database,
+};
+
+use crate::helpers::{create_empty_database, TestApp};
+
+async fn empty_database() -> DatabaseSettings {
+    TestApp::init_subscriber();
+    let mut settings = get_configuration()
+        .expect("Failed to read configuration")
+        .database;
+    create_empty_database(&mut settings).await;
+    settings
+}
+
+async fn states(settings: &DatabaseSettings) -> Vec<MigrationState> {
+    let mut connection = SchemaConnection::connect(settings).await.unwrap();
+    let states = connection
+        .status()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|m| m.state)
+        .collect();
+    connection.close().await.unwrap();
+    states
+}
+
+#[tokio::test]
+async fn the_app_refuses_to_start_on_an_outdated_schema_unless_told_to_migrate() {
+    // Arrange
+    let mut settings = empty_database().await;
+
+    // Act & Assert
+    assert_err!(prepare_database(&settings).await);
+    assert!(states(&settings).await.iter().all(|s| *s == MigrationState::Pending));
+
+    settings.run_migrations_on_startup = true;
+    assert_ok!(prepare_database(&settings).await);
+    settings.run_migrations_on_startup = false;
+    assert_ok!(prepare_database(&settings).await);
+    assert!(states(&settings).await.iter().all(|s| *s == MigrationState::Applied));
+}
+
+#[tokio::test]
+async fn down_to_reverts_newer_migrations_and_up_reapplies_them() {
+    // Arrange
+    let settings = empty_database().await;
+    let mut connection = SchemaConnection::connect(&settings).await.unwrap();
+    connection.up().await.unwrap();
+    let migrations = connection.status().await.unwrap();
+    let target = migrations[1].version;
+
+    // Act
+    connection.down_to(target).await.unwrap();
+
+    // Assert
+    let states: Vec<_> = connection
+        .status()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|m| m.state)
+        .collect();
+    assert_eq!(&states[..2], [MigrationState::Applied; 2]);
+    assert!(states[2..].iter().all(|s| *s == MigrationState::Pending));
+    assert_err!(connection.ensure_current().await);
+
+    connection.up().await.unwrap();
+    assert_ok!(connection.ensure_current().await);
+}
+
+#[tokio::test]
+async fn down_to_an_unknown_version_is_rejected() {
+    let settings = empty_database().await;
+    let mut connection = SchemaConnection::connect(&settings).await.unwrap();
+    connection.up().await.unwrap();
+
+    assert_err!(connection.down_to(42).await);
+    assert_ok!(connection.ensure_current().await);
+}
+
+#[tokio::test]
+async fn instances_starting_together_migrate_once() {
+    // Arrange
+    let mut settings = empty_database().await;
+    if settings.backend != DatabaseBackend::Postgres {
+        // Only Postgres has the advisory lock; SQLite runs a single instance.
+        return;
+    }
+    settings.run_migrations_on_startup = true;
+
+    // Act
+    let (first, second, third) = tokio::join!(
+        prepare_database(&settings),
+        prepare_database(&settings),
+        prepare_database(&settings),
+    );
+
+    // Assert
+    assert_ok!(first);
+    assert_ok!(second);
+    assert_ok!(third);
+    assert!(states(&settings).await.iter().all(|s| *s == MigrationState::Applied));
+}
@@ -0,0 +1,342 @@
+use reqwest::{header, redirect::Policy, Method, StatusCode};
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero_to_prod::repository::Subscriber;
+
+use crate::helpers::TestApp;
+
+/// Confirms Ursula on the default list, creates a `weekly` list and
+/// follows the preference link emailed to her; returns her and the session
+/// cookie.
+async fn signed_in(app: &TestApp) -> (Subscriber, String) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.admin_request(Method::POST, "/lists")
+        .json(&json!({ "slug": "weekly", "name": "The Weekly" }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    app.post_subscriber("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let confirmation_link = app.get_confirmation_links(&app.wait_for_emails(1).await[0]).html;
+    reqwest::get(confirmation_link).await.unwrap().error_for_status().unwrap();
+    let response = app
+        .post_privacy_request("email=ursula_le_guin%40gmail.com&action=preferences")
+        .await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let link = app.get_confirmation_links(&app.wait_for_emails(2).await[1]).html;
+
+    let client = reqwest::Client::builder().redirect(Policy::none()).build().unwrap();
+    let response = client.get(link).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        format!("http://127.0.0.1:{}/preferences", app.port).as_str()
+    );
+    let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+    assert!(cookie.contains("HttpOnly"));
+    let session = cookie.split(';').next().unwrap().to_owned();
+    let subscriber = app
+        .state
+        .subscribers
+        .find_by_email("ursula_le_guin@gmail.com")
+        .await
+        .unwrap()
+        .unwrap();
+    (subscriber, session)
+}
+
+fn preferences_url(app: &TestApp, path: &str) -> String {
+    format!("http://{}:{}/preferences{}", app.base_url, app.port, path)
+}
+
+async fn post(app: &TestApp, path: &str, session: &str, form: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(preferences_url(app, path))
+        .header(header::COOKIE, session)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(form.to_owned())
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_link_opens_the_preference_center() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let (_, session) = signed_in(&app).await;
+
+    // Act
+    let page = reqwest::Client::new()
+        .get(preferences_url(&app, ""))
+        .header(header::COOKIE, &session)
+        .send()
+        .await
+        .unwrap();
+    let signed_out = reqwest::get(preferences_url(&app, "")).await.unwrap();
+
+    // Assert
+    assert_eq!(page.status(), StatusCode::OK);
+    let page = page.text().await.unwrap();
+    assert!(page.contains(r#"value="le guin""#));
+    assert!(page.contains(r#"value="default" checked"#));
+    assert!(page.contains(r#"value="weekly">"#));
+    assert_eq!(signed_out.status(), StatusCode::UNAUTHORIZED);
+    assert!(signed_out.text().await.unwrap().contains(r#"name="action" value="preferences""#));
+}
+
+#[tokio::test]
+async fn subscribers_can_rename_themselves_switch_lists_and_pause() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let (subscriber, session) = signed_in(&app).await;
+    let repository = &app.state.subscribers;
+    let weekly = repository.find_list("weekly").await.unwrap().unwrap();
+
+    // Act
+    let response = post(&app, "", &session, "name=Ursula%20K.&list=weekly&pause_weeks=4").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().contains("Your changes are saved."));
+    let saved = repository.find_by_id(subscriber.id).await.unwrap().unwrap();
+    assert_eq!(saved.name, "Ursula K.");
+    let memberships: Vec<_> = repository
+        .memberships(subscriber.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|m| (m.list, m.status))
+        .collect();
+    assert_eq!(
+        memberships,
+        [
+            ("default".to_owned(), "unsubscribed".to_owned()),
+            ("weekly".to_owned(), "confirmed".to_owned())
+        ]
+    );
+    let trail: Vec<_> = repository
+        .events(subscriber.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .skip(2)
+        .map(|event| (event.kind, event.list.unwrap(), event.source.unwrap()))
+        .collect();
+    let preferences = |kind: &str, list: &str| (kind.to_owned(), list.to_owned(), "preferences".to_owned());
+    assert_eq!(
+        trail,
+        [
+            preferences("subscribed", "weekly"),
+            preferences("confirmed", "weekly"),
+            preferences("unsubscribed", "default")
+        ]
+    );
+    assert!(repository.paused_until(subscriber.id).await.unwrap().is_some());
+    assert!(repository.recipients(weekly.id, None).await.unwrap().is_empty());
+
+    // Resuming brings issues back.
+    let response = post(&app, "", &session, "name=Ursula%20K.&list=weekly&pause_weeks=0").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(repository.recipients(weekly.id, None).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn invalid_preferences_are_rejected_and_nothing_changes() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let (subscriber, session) = signed_in(&app).await;
+    let test_cases = [
+        ("name=%3Cscript%3E&list=default", "Please enter a valid name."),
+        ("name=Ursula", "Please choose at least one list"),
+        ("name=Ursula&list=default&list=nonexistent", "Please choose at least one list"),
+        ("name=Ursula&list=default&pause_weeks=500", "Please choose how long to pause for."),
+    ];
+
+    for (form, error) in test_cases {
+        // Act
+        let response = post(&app, "", &session, form).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", form);
+        assert!(response.text().await.unwrap().contains(error), "{}", form);
+    }
+    let saved = app.state.subscribers.find_by_id(subscriber.id).await.unwrap().unwrap();
+    assert_eq!(saved.name, "le guin");
+    assert_eq!(app.state.subscribers.events(subscriber.id).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn unsubscribing_ends_every_membership_and_the_session() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let (subscriber, session) = signed_in(&app).await;
+
+    // Act
+    let response = post(&app, "/unsubscribe", &session, "").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()[header::SET_COOKIE]
+        .to_str()
+        .unwrap()
+        .contains("Max-Age=0"));
+    let repository = &app.state.subscribers;
+    let saved = repository.find_by_id(subscriber.id).await.unwrap().unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+    assert!(repository
+        .memberships(subscriber.id)
+        .await
+        .unwrap()
+        .iter()
+        .all(|m| m.status == "unsubscribed"));
+    let last_event = repository.events(subscriber.id).await.unwrap().pop().unwrap();
+    assert_eq!(last_event.kind, "unsubscribed");
+    assert_eq!(last_event.list, None);
+    let email: serde_json::Value = serde_json::from_slice(&app.wait_for_emails(3).await[2].body).unwrap();
+    assert_eq!(email["Subject"], "You have been unsubscribed");
+
+    // The session no longer works.
+    assert_eq!(
+        post(&app, "", &session, "name=Ursula&list=default").await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+/// Asks to move Ursula to `new_email` and returns the link sent there.
+async fn request_email_change(app: &TestApp, session: &str, new_email: &str) -> reqwest::Url {
+    let response = post(app, "/email", session, &format!("email={}", new_email.replace('@', "%40"))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().contains("we sent a link there"));
+    let email = &app.wait_for_emails(3).await[2];
+    let body: serde_json::Value = serde_json::from_slice(&email.body).unwrap();
+    assert_eq!(body["To"], new_email);
+    app.get_confirmation_links(email).html
+}
+
+#[tokio::test]
+async fn subscribers_move_to_a_new_address_once_they_confirm_it() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let (subscriber, session) = signed_in(&app).await;
+    let repository = &app.state.subscribers;
+    let link = request_email_change(&app, &session, "ursula@example.com").await;
+    let unchanged = repository.find_by_id(subscriber.id).await.unwrap().unwrap();
+    assert_eq!(unchanged.email, "ursula_le_guin@gmail.com");
+
+    // Act
+    let response = reqwest::get(link.clone()).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().contains("Your email address is changed"));
+    let moved = repository.find_by_id(subscriber.id).await.unwrap().unwrap();
+    assert_eq!(moved, Subscriber { email: "ursula@example.com".into(), ..subscriber });
+    assert_eq!(repository.memberships(subscriber.id).await.unwrap()[0].status, "confirmed");
+    let last_event = repository.events(subscriber.id).await.unwrap().pop().unwrap();
+    assert_eq!(last_event.kind, "email_changed");
+    assert_eq!(last_event.source.as_deref(), Some("preferences"));
+
+    // Following the link again, as after a mail scanner, still says so.
+    assert_eq!(reqwest::get(link).await.unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn links_are_checked_before_saying_the_address_is_changed() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let (subscriber, _) = signed_in(&app).await;
+    let mut forged = reqwest::Url::parse(&format!(
+        "http://{}:{}/preferences/email/confirm",
+        app.base_url, app.port
+    ))
+    .unwrap();
+    forged
+        .query_pairs_mut()
+        .append_pair("subscriber_id", &subscriber.id.to_string())
+        .append_pair("email", &subscriber.email)
+        .append_pair("from", "00")
+        .append_pair("expires", "99999999999")
+        .append_pair("signature", "00");
+
+    // Act
+    let response = reqwest::get(forged).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(!response.text().await.unwrap().contains("Your email address is changed"));
+}
+
+#[tokio::test]
+async fn an_address_taken_before_the_change_is_confirmed_is_not_moved_to() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let (subscriber, session) = signed_in(&app).await;
+    let link = request_email_change(&app, &session, "octavia@example.com").await;
+    app.post_subscriber("name=Octavia&email=octavia%40example.com")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert!(response.text().await.unwrap().contains("already subscribed with this address"));
+    let saved = app.state.subscribers.find_by_id(subscriber.id).await.unwrap().unwrap();
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+}
+
+#[tokio::test]
+async fn links_to_new_addresses_are_sent_at_most_once_per_interval() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let (_, session) = signed_in(&app).await;
+    request_email_change(&app, &session, "ursula@example.com").await;
+
+    // Act
+    for address in ["ursula%40example.com", "octavia%40example.com"] {
+        let response = post(&app, "/email", &session, &format!("email={}", address)).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.text().await.unwrap().contains("we sent a link there"));
+    }
+    app.state.email_change_requests.until_idle().await;
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn no_link_is_sent_to_addresses_that_cannot_be_used() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let (_, session) = signed_in(&app).await;
+    app.post_subscriber("name=Octavia&email=octavia%40example.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    app.wait_for_emails(3).await;
+
+    // Act
+    let taken = post(&app, "/email", &session, "email=octavia%40example.com").await;
+    let invalid = post(&app, "/email", &session, "email=not-an-email").await;
+
+    // Assert
+    assert_eq!(taken.status(), StatusCode::OK);
+    assert!(taken.text().await.unwrap().contains("we sent a link there"));
+    assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 3);
+}
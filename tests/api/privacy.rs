@@ -0,0 +1,239 @@
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use zero_to_prod::repository::{DeliveryOutcome, IssueContent, TrackedAction, DEFAULT_LIST};
+
+use crate::helpers::TestApp;
+
+/// Subscribes Ursula and asks for `action`; returns the link from the
+/// privacy email.
+async fn request_link(app: &TestApp, action: &str) -> reqwest::Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriber("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let response = app
+        .post_privacy_request(&format!("email=ursula_le_guin%40gmail.com&action={}", action))
+        .await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let email_request = &app.wait_for_emails(2).await[1];
+    app.get_confirmation_links(email_request).html
+}
+
+#[tokio::test]
+async fn access_links_download_everything_held_about_the_subscriber() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let link = request_link(&app, "access").await;
+    let repository = &app.state.subscribers;
+    let ursula = repository.find_by_email("ursula_le_guin@gmail.com").await.unwrap().unwrap();
+    let list = repository.find_list(DEFAULT_LIST).await.unwrap().unwrap();
+    let issue = repository
+        .create_issue(&IssueContent {
+            list_id: list.id,
+            title: "Issue #1".into(),
+            html_content: "<p>Hello</p>".into(),
+            text_content: "Hello".into(),
+            segment: None,
+            tracking: true,
+            public: false,
+        })
+        .await
+        .unwrap();
+    repository.queue_deliveries(issue.id, &[ursula.id]).await.unwrap();
+    let sent = DeliveryOutcome::Sent { message_id: None };
+    repository.record_delivery(issue.id, ursula.id, &sent).await.unwrap();
+    let click = TrackedAction::Clicked { url: "https://example.com/".into() };
+    assert!(repository
+        .record_tracked_action(issue.id, ursula.id, &click, chrono::Utc::now())
+        .await
+        .unwrap());
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-disposition"],
+        "attachment; filename=\"my-data.json\""
+    );
+    let data: Value = response.json().await.unwrap();
+    assert_eq!(data["subscriber"]["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(data["subscriber"]["name"], "le guin");
+    assert_eq!(data["subscriber"]["status"], "pending");
+    assert_eq!(data["confirmation_tokens"].as_array().unwrap().len(), 1);
+    let consent = &data["consent_records"][0];
+    assert_eq!(consent["kind"], "subscribed");
+    assert_eq!(consent["list"], DEFAULT_LIST);
+    assert_eq!(consent["ip"], "127.0.0.1");
+    assert_eq!(data["received_issues"][0]["title"], "Issue #1");
+    assert_eq!(data["received_issues"][0]["status"], "sent");
+    assert_eq!(data["tracking_events"][0]["kind"], "click");
+    assert_eq!(data["tracking_events"][0]["url"], "https://example.com/");
+}
+
+#[tokio::test]
+async fn requests_for_unknown_addresses_look_the_same_but_send_nothing() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_privacy_request("email=nobody%40example.com&action=erasure")
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn links_are_sent_at_most_once_per_interval() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriber("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    for action in ["access", "erasure", "access"] {
+        let response = app
+            .post_privacy_request(&format!("email=ursula_le_guin%40gmail.com&action={}", action))
+            .await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    // Assert
+    app.state.privacy_requests.until_idle().await;
+    app.email_server.verify().await;
+}
+
+#[tokio::test]
+async fn erasure_asks_for_confirmation_then_pseudonymizes_and_suppresses() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let link = request_link(&app, "erasure").await;
+
+    // Act 1: following the link only asks.
+    let page = reqwest::get(link.clone()).await.unwrap();
+    assert_eq!(page.status(), StatusCode::OK);
+    assert!(page.text().await.unwrap().contains(r#"<form method="post""#));
+    assert_eq!(app.saved_subscriptions().await[0].status, "pending");
+
+    // Act 2: submitting the form erases.
+    let client = reqwest::Client::new();
+    let response = client.post(link.clone()).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Assert
+    let saved = &app.saved_subscriptions().await[0];
+    assert_eq!(saved.status, "erased");
+    assert_eq!(saved.name, "");
+    assert!(!saved.email.contains("le_guin"));
+    assert!(app.subscription_tokens().await.is_empty());
+    let listing: Value = app
+        .admin_request(Method::GET, "/subscribers")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = listing["subscribers"][0]["id"].as_str().unwrap();
+    let trail: Value = app
+        .admin_request(Method::GET, &format!("/subscribers/{}/events", id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let trail = trail["events"].as_array().unwrap();
+    assert_eq!(trail.last().unwrap()["kind"], "erased");
+    assert!(trail.iter().all(|e| e["ip"].is_null() && e["user_agent"].is_null()));
+
+    // Submitting again changes nothing.
+    let response = client.post(link).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The address cannot be imported again.
+    let report: Value = app
+        .admin_request(Method::POST, "/subscribers/import")
+        .body("email,name\nUrsula_Le_Guin@gmail.com,Ursula\n")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["imported"], 0);
+    assert_eq!(report["errors"][0]["error"], "suppressed");
+}
+
+#[tokio::test]
+async fn tampered_links_are_rejected() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let link = request_link(&app, "access").await;
+
+    // Act: use an access link for erasure, and alter one.
+    let mut erasure_link = link.clone();
+    erasure_link.set_path("/privacy/erasure");
+    let mut altered = link.clone();
+    let pairs: Vec<(String, String)> = link
+        .query_pairs()
+        .map(|(key, value)| match key.as_ref() {
+            "expires" => (key.into_owned(), format!("{}", value.parse::<i64>().unwrap() + 1)),
+            _ => (key.into_owned(), value.into_owned()),
+        })
+        .collect();
+    altered.query_pairs_mut().clear().extend_pairs(pairs);
+
+    // Assert
+    for link in [erasure_link, altered] {
+        let response = reqwest::get(link.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", link);
+    }
+    let response = reqwest::Client::new()
+        .post(link.as_str().replace("/access", "/erasure"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(app.saved_subscriptions().await[0].status, "pending");
+}
+
+#[tokio::test]
+async fn expired_links_are_rejected() {
+    // Arrange
+    let app = TestApp::spawn_with(|c| c.privacy.link_ttl_hours = -1).await;
+    let link = request_link(&app, "access").await;
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::GONE);
+}
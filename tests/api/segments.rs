@@ -0,0 +1,231 @@
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::TestApp;
+
+async fn spawn() -> TestApp {
+    let app = TestApp::spawn_with(|c| {
+        c.segmentation.attribute_keys = vec!["country".into(), "plan".into()];
+        c.segmentation.signup_tags = vec!["rust".into(), "go".into()];
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app
+}
+
+/// Subscribes and confirms `name`, returning their id.
+async fn confirmed_subscriber(app: &TestApp, name: &str, extra_fields: &str) -> String {
+    let email = format!("{}%40example.com", name.to_lowercase());
+    app.post_subscriber(&format!("name={}&email={}{}", name, email, extra_fields))
+        .await
+        .error_for_status()
+        .unwrap();
+    let requests = app.email_server.received_requests().await.unwrap();
+    let link = app.get_confirmation_links(requests.last().unwrap()).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+    let page: Value = app
+        .admin_request(Method::GET, &format!("/subscribers?q={}", name))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    page["subscribers"][0]["id"].as_str().unwrap().to_owned()
+}
+
+async fn recipients(app: &TestApp, segment: &str) -> reqwest::Response {
+    app.admin_request(Method::GET, "/lists/default/recipients")
+        .query(&[("segment", segment)])
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn recipient_names(app: &TestApp, segment: &str) -> Vec<String> {
+    let response = recipients(app, segment).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    body["recipients"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["name"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn signup_forms_can_set_configured_attributes_and_tags() {
+    // Arrange
+    let app = spawn().await;
+
+    // Act
+    let id = confirmed_subscriber(&app, "Ursula", "&country=de&plan=&tags=Rust,go").await;
+
+    // Assert
+    let details: Value = app
+        .admin_request(Method::GET, &format!("/subscribers/{}", id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(details["attributes"], json!({ "country": "de" }));
+    assert_eq!(details["tags"], json!(["go", "rust"]));
+}
+
+#[tokio::test]
+async fn signup_forms_cannot_set_unknown_attributes_or_tags() {
+    // Arrange
+    let app = spawn().await;
+    let test_cases = [
+        ("&city=berlin", "unknown_attribute"),
+        ("&tags=vip", "unknown_tag"),
+    ];
+
+    for (extra_fields, error) in test_cases {
+        // Act
+        let response = app
+            .post_subscriber(&format!("name=le%20guin&email=ursula%40example.com{}", extra_fields))
+            .await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", extra_fields);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"], error);
+    }
+    assert!(app.saved_subscriptions().await.is_empty());
+}
+
+#[tokio::test]
+async fn signing_up_again_cannot_change_an_existing_subscribers_profile() {
+    // Arrange
+    let app = spawn().await;
+    let id = confirmed_subscriber(&app, "Ursula", "&country=de&tags=go").await;
+    app.admin_request(Method::POST, "/lists")
+        .json(&json!({ "slug": "weekly", "name": "The Weekly" }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    for list in ["default", "weekly"] {
+        // Act
+        let response = app
+            .post_subscriber(&format!(
+                "name=Ursula&email=ursula%40example.com&list={}&country=us&tags=rust",
+                list
+            ))
+            .await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK, "{}", list);
+    }
+    let details: Value = app
+        .admin_request(Method::GET, &format!("/subscribers/{}", id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(details["attributes"], json!({ "country": "de" }));
+    assert_eq!(details["tags"], json!(["go"]));
+}
+
+#[tokio::test]
+async fn admins_can_set_and_remove_attributes_and_tags() {
+    // Arrange
+    let app = spawn().await;
+    let id = confirmed_subscriber(&app, "Ursula", "&country=de&tags=go").await;
+    let patch = |body: Value| {
+        app.admin_request(Method::PATCH, &format!("/subscribers/{}", id))
+            .json(&body)
+            .send()
+    };
+
+    // Act
+    let response = patch(json!({
+        "attributes": { "country": null, "plan": "pro" },
+        "add_tags": ["VIP"],
+        "remove_tags": ["go"],
+    }))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let profile: Value = response.json().await.unwrap();
+    assert_eq!(profile, json!({ "tags": ["vip"], "attributes": { "plan": "pro" } }));
+
+    let response = patch(json!({ "attributes": { "city": "Berlin" } })).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = app
+        .admin_request(Method::PATCH, &format!("/subscribers/{}", uuid::Uuid::new_v4()))
+        .json(&json!({ "add_tags": ["vip"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn segments_narrow_down_the_recipients_of_a_list() {
+    // Arrange
+    let app = spawn().await;
+    confirmed_subscriber(&app, "Ursula", "&country=de&tags=rust").await;
+    confirmed_subscriber(&app, "Octavia", "&country=us&tags=rust,go").await;
+    confirmed_subscriber(&app, "Ted", "&country=de").await;
+    app.post_subscriber("name=Pending&email=pending%40example.com&tags=rust")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act & Assert
+    assert_eq!(recipient_names(&app, "").await, ["Ursula", "Octavia", "Ted"]);
+    assert_eq!(recipient_names(&app, "tag:rust").await, ["Ursula", "Octavia"]);
+    assert_eq!(recipient_names(&app, "tag:rust AND country:de").await, ["Ursula"]);
+    assert_eq!(
+        recipient_names(&app, "country:de AND NOT (tag:rust OR tag:go)").await,
+        ["Ted"]
+    );
+    assert!(recipient_names(&app, "country:\"de' OR 1=1 --\"").await.is_empty());
+}
+
+#[tokio::test]
+async fn subscribers_without_an_attribute_are_not_excluded_by_negating_it() {
+    // Arrange
+    let app = spawn().await;
+    confirmed_subscriber(&app, "Ursula", "&country=de").await;
+    confirmed_subscriber(&app, "Octavia", "&country=us").await;
+    confirmed_subscriber(&app, "Ted", "").await;
+
+    // Act & Assert
+    assert_eq!(recipient_names(&app, "NOT country:de").await, ["Octavia", "Ted"]);
+    assert_eq!(recipient_names(&app, "NOT (country:de OR plan:pro)").await, ["Octavia", "Ted"]);
+    assert!(recipient_names(&app, "NOT NOT plan:pro").await.is_empty());
+}
+
+#[tokio::test]
+async fn malformed_segments_and_unknown_attributes_are_rejected() {
+    // Arrange
+    let app = spawn().await;
+
+    for segment in ["tag:rust AND", "(tag:rust", "city:berlin", "rust"] {
+        // Act
+        let response = recipients(&app, segment).await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", segment);
+    }
+}
@@ -1,13 +1,12 @@
-use std::sync::Arc;
-
-use reqwest::Url;
+use reqwest::Method;
+use zero_to_prod::audit::EventContext;
 use zero_to_prod::email_client::EmailClient;
 
 use crate::helpers::TestApp;
 
 use wiremock::{
     matchers::{method, path},
-    Mock, MockServer, Request, ResponseTemplate,
+    Mock, ResponseTemplate,
 };
 
 #[tokio::test]
@@ -23,22 +22,19 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
         .mount(&app.email_server)
         .await;
 
-    let response_subscribe = app.post_subscriber(body).await;
+    app.post_subscriber(body).await;
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
     
     // Act
-    let response_confimartion = client
+    client
         .get(confirmation_links.html)
         .send()
         .await
         .expect("Failed to execute request");
 
     // Assert
-    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
-        .fetch_one(&app.db_pool)
-        .await
-        .expect("Failed to fetch saved subscription.");
+    let saved = &app.saved_subscriptions().await[0];
 
     assert_eq!(saved.email, "mail@marszy.com");
     assert_eq!(saved.name, "Marcin");
@@ -73,7 +69,6 @@ async fn the_link_returned_by_subscribe_has_the_same_token_as_in_database() {
     // Arrange
     let app = TestApp::spawn().await;
     let body = "name=Marcin&email=mail%40marszy.com";
-    let client = reqwest::Client::new();
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -83,29 +78,16 @@ async fn the_link_returned_by_subscribe_has_the_same_token_as_in_database() {
         .await;
 
     // Act
-    let response_subscribe = app.post_subscriber(body).await;
+    app.post_subscriber(body).await;
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
-    let link_token = confirmation_links.html.path_segments().unwrap().last().unwrap();
-
-    let saved = sqlx::query!("SELECT id FROM subscriptions")
-        .fetch_one(&app.db_pool)
-        .await
-        .expect("Failed to fetch saved subscription.");
-
-    let db_token = sqlx::query!(
-            r#"SELECT token FROM subscription_tokens WHERE subscriber_id = $1"#,
-            saved.id,
-        )
-        .fetch_one(&app.db_pool)
-        .await
-        .expect("Failed to execute query");
-        
+    let link_token = confirmation_links.html.path_segments().unwrap().next_back().unwrap();
 
+    let db_tokens = app.subscription_tokens().await;
 
     // Assert
-    assert_eq!(link_token, db_token.token);
+    assert_eq!(db_tokens, vec![link_token.to_owned()]);
 }
 
 #[tokio::test]
@@ -127,10 +109,7 @@ async fn subscribe_returns_a_200_for_valid_form_data() {
     // Arrange
     assert_eq!(200, response.status().as_u16());
 
-    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
-        .fetch_one(&app.db_pool)
-        .await
-        .expect("Failed to fetch saved subscription.");
+    let saved = &app.saved_subscriptions().await[0];
 
     assert_eq!(saved.email, "mail@marszy.com");
     assert_eq!(saved.name, "marcin");
@@ -151,13 +130,10 @@ async fn subscribe_inserts_subscriber_into_db_for_valid_form_data() {
         .await;
 
     // Act
-    let response = app.post_subscriber(body).await;
+    app.post_subscriber(body).await;
 
     // Assert
-    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
-        .fetch_one(&app.db_pool)
-        .await
-        .expect("Failed to fetch saved subscription.");
+    let saved = &app.saved_subscriptions().await[0];
 
     assert_eq!(saved.email, "mail@marszy.com");
     assert_eq!(saved.name, "marcin");
@@ -168,7 +144,6 @@ async fn subscribe_inserts_subscriber_into_db_for_valid_form_data() {
 async fn subscribe_returns_a_422_when_data_is_missing() {
     // Arrange
     let app = TestApp::spawn().await;
-    let client = reqwest::Client::new();
     let test_cases = vec![
         ("name=marcin", "missing mail"),
         ("email=mail%40marszy.com", "missing name"),
@@ -193,7 +168,6 @@ async fn subscribe_returns_a_422_when_data_is_missing() {
 async fn subscribe_returns_a_400_when_fields_are_present_but_empty() {
     // Arrange
     let app = TestApp::spawn().await;
-    let client = reqwest::Client::new();
     let test_cases = vec![
         ("name=&email=ursula_le_guin%40gmail.com", "empty name"),
         ("name=Ursula&email=", "empty email"),
@@ -211,11 +185,76 @@ async fn subscribe_returns_a_400_when_fields_are_present_but_empty() {
     }
 }
 
+#[tokio::test]
+async fn subscribe_returns_the_rejection_reason_for_a_disposable_email() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let body = "name=Ursula&email=ursula%40mailinator.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriber(body).await;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["reason"], "disposable_domain");
+
+    assert!(app.saved_subscriptions().await.is_empty());
+}
+
+#[tokio::test]
+async fn subscribe_uses_the_accept_language_header_unless_a_locale_is_given() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let client = reqwest::Client::new();
+    let test_cases = vec![
+        ("name=Ursula&email=ursula%40example.com", "de-DE,de;q=0.9", "de", "Willkommen!"),
+        ("name=Marcin&email=mail%40marszy.com&locale=pl", "de", "pl", "Witaj!"),
+        ("name=Jean&email=jean%40example.com", "fr", "en", "Welcome!"),
+    ];
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    for (i, (body, accept_language, expected_locale, expected_subject)) in
+        test_cases.into_iter().enumerate()
+    {
+        // Act
+        let response = client
+            .post(format!("http://{}:{}/subscriptions", app.base_url, app.port))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("Accept-Language", accept_language)
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+
+        // Assert
+        assert_eq!(200, response.status().as_u16());
+        let email_request = &app.email_server.received_requests().await.unwrap()[i];
+        let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        assert_eq!(email["Subject"], expected_subject);
+
+        let saved = app.saved_subscriptions().await.pop().unwrap();
+        assert_eq!(saved.locale, expected_locale);
+    }
+}
+
 #[tokio::test]
 async fn subscribe_sends_a_confirmation_email_for_valid_data() {
     let app = TestApp::spawn().await;
     let body = "name=Marcin&email=mail%40marszy.com";
-    let client = reqwest::Client::new();
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -229,7 +268,7 @@ async fn subscribe_sends_a_confirmation_email_for_valid_data() {
 
     // Assert
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     assert_eq!(confirmation_links.html, confirmation_links.plain_text);
     assert_eq!(200, response.status().as_u16());
@@ -256,6 +295,7 @@ async fn send_email_tests_postmark() {
     tracing::info!("Sending e-mail via postmark!");
 
     let email_client = EmailClient::new(
+        reqwest::Client::new(),
         "https://api.postmarkapp.com".into(),
         SubscriberEmail::parse("mail@danirut.com".into()).unwrap(),
         SecretString::new("c78b0e40-cb3a-44e8-8501-e8a055ebb7d5".into()),
@@ -264,10 +304,182 @@ async fn send_email_tests_postmark() {
     let _ = email_client
         .send_email(
             SubscriberEmail::parse("mail@danirut.com".into()).unwrap(),
-            "Hello from Postmark".into(),
-            "<strong>Hello</strong> dear Postmark user.".into(),
-            "Hello dear Postmark user.".into(),
+            "Hello from Postmark",
+            "<strong>Hello</strong> dear Postmark user.",
+            "Hello dear Postmark user.",
         )
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn resending_the_confirmation_replaces_the_earlier_link() {
+    // Arrange
+    let app = TestApp::spawn_with(|c| c.confirmation.resend_interval_minutes = 0).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriber("name=Marcin&email=mail%40marszy.com").await;
+    let first_link = app.get_confirmation_links(&app.wait_for_emails(1).await[0]).html;
+
+    // Act
+    let response = app.post_resend_confirmation("email=mail%40marszy.com").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let second_link = app.get_confirmation_links(&app.wait_for_emails(2).await[1]).html;
+    assert_ne!(first_link, second_link);
+    assert_eq!(reqwest::get(first_link).await.unwrap().status().as_u16(), 401);
+    assert_eq!(reqwest::get(second_link).await.unwrap().status().as_u16(), 200);
+    let saved = app.saved_subscriptions().await;
+    assert_eq!(saved[0].status, "confirmed");
+}
+
+#[tokio::test]
+async fn confirmations_are_resent_at_most_once_per_interval() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriber("name=Marcin&email=mail%40marszy.com").await;
+    let link = app.get_confirmation_links(&app.wait_for_emails(1).await[0]).html;
+
+    // Act
+    for _ in 0..3 {
+        let response = app.post_resend_confirmation("email=mail%40marszy.com").await;
+        assert_eq!(response.status().as_u16(), 202);
+    }
+
+    // Assert
+    app.state.resends.until_idle().await;
+    app.email_server.verify().await;
+    assert_eq!(reqwest::get(link).await.unwrap().status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn resending_does_not_tell_whether_an_address_is_subscribed() {
+    // Arrange
+    let app = TestApp::spawn_with(|c| c.confirmation.resend_interval_minutes = 0).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriber("name=Marcin&email=mail%40marszy.com").await;
+    let link = app.get_confirmation_links(&app.wait_for_emails(1).await[0]).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+
+    // Act
+    let confirmed = app.post_resend_confirmation("email=mail%40marszy.com").await;
+    let unknown = app.post_resend_confirmation("email=nobody%40example.com").await;
+
+    // Assert
+    assert_eq!(confirmed.status(), unknown.status());
+    assert_eq!(confirmed.text().await.unwrap(), unknown.text().await.unwrap());
+    app.state.resends.until_idle().await;
+    app.email_server.verify().await;
+}
+
+#[tokio::test]
+async fn signing_up_again_sends_a_new_link_until_the_subscriber_confirms() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    app.post_subscriber(body).await.error_for_status().unwrap();
+
+    // Act
+    let pending = app.post_subscriber(body).await;
+    let link = app.get_confirmation_links(&app.wait_for_emails(2).await[1]).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+    let confirmed = app.post_subscriber(body).await;
+
+    // Assert
+    assert_eq!(pending.status().as_u16(), 200);
+    assert_eq!(confirmed.status().as_u16(), 200);
+    let saved = app.saved_subscriptions().await;
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].status, "confirmed");
+}
+
+#[tokio::test]
+async fn deleted_subscribers_can_sign_up_again() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    app.post_subscriber(body).await.error_for_status().unwrap();
+    let link = app.get_confirmation_links(&app.wait_for_emails(1).await[0]).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+    let subscriber = app
+        .state
+        .subscribers
+        .find_by_email("ursula_le_guin@gmail.com")
+        .await
+        .unwrap()
+        .unwrap();
+    app.admin_request(Method::DELETE, &format!("/subscribers/{}", subscriber.id))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = app.post_subscriber("name=Ursula&email=ursula_le_guin%40gmail.com").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.saved_subscriptions().await;
+    assert_eq!(saved.len(), 1);
+    assert_eq!((saved[0].name.as_str(), saved[0].status.as_str()), ("Ursula", "pending"));
+    let link = app.get_confirmation_links(&app.wait_for_emails(2).await[1]).html;
+    assert_eq!(reqwest::get(link).await.unwrap().status().as_u16(), 200);
+    assert_eq!(app.saved_subscriptions().await[0].status, "confirmed");
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_can_sign_up_again() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    app.post_subscriber(body).await.error_for_status().unwrap();
+    let link = app.get_confirmation_links(&app.wait_for_emails(1).await[0]).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+    let repository = &app.state.subscribers;
+    let subscriber = repository.find_by_email("ursula_le_guin@gmail.com").await.unwrap().unwrap();
+    assert!(repository.unsubscribe(subscriber.id, &EventContext::default()).await.unwrap());
+
+    // Act
+    let response = app.post_subscriber(body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let memberships = repository.memberships(subscriber.id).await.unwrap();
+    assert_eq!(memberships[0].status, "pending");
+    let link = app.get_confirmation_links(&app.wait_for_emails(2).await[1]).html;
+    assert_eq!(reqwest::get(link).await.unwrap().status().as_u16(), 200);
+    assert_eq!(app.saved_subscriptions().await[0].status, "confirmed");
+    assert_eq!(repository.memberships(subscriber.id).await.unwrap()[0].status, "confirmed");
+}
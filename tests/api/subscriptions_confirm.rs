@@ -0,0 +1,155 @@
+use chrono::{Duration, Utc};
+use reqwest::{redirect::Policy, Url};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::TestApp;
+
+/// Subscribes someone and returns the link from their confirmation email.
+async fn subscribe_and_get_link(app: &TestApp) -> Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriber("name=Ursula&email=ursula%40example.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.get_confirmation_links(email_request).html
+}
+
+#[tokio::test]
+async fn an_unknown_token_shows_the_invalid_link_page() {
+    // Arrange
+    let app = TestApp::spawn().await;
+
+    // Act
+    let response = reqwest::get(format!(
+        "http://{}:{}/subscriptions/confirm/not-a-token",
+        app.base_url, app.port
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    assert!(response.text().await.unwrap().contains("Invalid link"));
+}
+
+#[tokio::test]
+async fn confirming_twice_reports_that_the_subscription_was_already_confirmed() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let link = subscribe_and_get_link(&app).await;
+    reqwest::get(link.clone()).await.unwrap().error_for_status().unwrap();
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    assert!(response.text().await.unwrap().contains("Already confirmed"));
+}
+
+#[tokio::test]
+async fn an_expired_link_does_not_confirm_the_subscriber() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let link = subscribe_and_get_link(&app).await;
+    app.set_subscription_tokens_created_at(Utc::now() - Duration::hours(73))
+        .await;
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_eq!(410, response.status().as_u16());
+    assert!(response.text().await.unwrap().contains("Link expired"));
+    let saved = &app.saved_subscriptions().await[0];
+    assert_eq!(saved.status, "pending");
+}
+
+#[tokio::test]
+async fn the_expired_page_sends_a_new_link_that_confirms_the_subscriber() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let link = subscribe_and_get_link(&app).await;
+    app.set_subscription_tokens_created_at(Utc::now() - Duration::hours(73))
+        .await;
+    let page = reqwest::get(link).await.unwrap().text().await.unwrap();
+    let action = page
+        .split(r#"<form method="post" action=""#)
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .expect("The expired page has no form");
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(action)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("email=ursula%40example.com")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(202, response.status().as_u16());
+    let new_link = app.get_confirmation_links(&app.wait_for_emails(2).await[1]).html;
+    assert_eq!(200, reqwest::get(new_link).await.unwrap().status().as_u16());
+    let saved = &app.saved_subscriptions().await[0];
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn api_clients_get_the_outcome_as_json() {
+    // Arrange
+    let app = TestApp::spawn().await;
+    let link = subscribe_and_get_link(&app).await;
+    let client = reqwest::Client::new();
+
+    for expected in ["confirmed", "already_confirmed"] {
+        // Act
+        let response = client
+            .get(link.clone())
+            .header("Accept", "application/json")
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["status"], expected);
+    }
+}
+
+#[tokio::test]
+async fn browsers_are_redirected_when_a_redirect_is_configured() {
+    // Arrange
+    let app = TestApp::spawn_with(|c| {
+        c.confirmation.redirects.confirmed =
+            Some(Url::parse("https://example.com/welcome").unwrap());
+    })
+    .await;
+    let link = subscribe_and_get_link(&app).await;
+    let client = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .build()
+        .unwrap();
+
+    // Act
+    let response = client.get(link).send().await.unwrap();
+
+    // Assert
+    assert_eq!(303, response.status().as_u16());
+    assert_eq!(
+        response.headers()["Location"].to_str().unwrap(),
+        "https://example.com/welcome"
+    );
+    let saved = &app.saved_subscriptions().await[0];
+    assert_eq!(saved.status, "confirmed");
+}
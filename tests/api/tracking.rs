@@ -0,0 +1,205 @@
+use chrono::{Duration, Utc};
+use reqwest::{header, redirect::Policy, Method, StatusCode};
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero_to_prod::issues::send_due_issues;
+
+use crate::helpers::TestApp;
+
+async fn spawn(tracking: bool) -> TestApp {
+    let app = TestApp::spawn_with(|c| c.tracking.enabled = tracking).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder().redirect(Policy::none()).build().unwrap()
+}
+
+/// Subscribes and confirms `name` on the default list.
+async fn confirmed_subscriber(app: &TestApp, name: &str) {
+    let email = format!("{}%40example.com", name.to_lowercase());
+    app.post_subscriber(&format!("name={}&email={}", name, email))
+        .await
+        .error_for_status()
+        .unwrap();
+    let requests = app.email_server.received_requests().await.unwrap();
+    let link = app.get_confirmation_links(requests.last().unwrap()).html;
+    reqwest::get(link).await.unwrap().error_for_status().unwrap();
+}
+
+/// Creates a tracked issue and sends it right away, returning its id and
+/// the HTML body each recipient got.
+async fn send_tracked_issue(app: &TestApp) -> (String, Vec<String>) {
+    let response = app
+        .admin_request(Method::POST, "/issues")
+        .json(&json!({
+            "title": "Issue #1",
+            "html_content": r#"<p><a href="https://example.com/post?a=1&amp;b=2">Read on</a></p>"#,
+            "text_content": "Read on at https://example.com/post",
+            "tracking": true,
+        }))
+        .send()
+        .await
+        .unwrap();
+    let id = response.json::<Value>().await.unwrap()["id"].as_str().unwrap().to_owned();
+    let send_at = Utc::now() + Duration::hours(1);
+    app.admin_request(Method::PUT, &format!("/issues/{}/schedule", id))
+        .json(&json!({ "send_at": send_at.to_rfc3339() }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let emails_before = app.email_server.received_requests().await.unwrap().len();
+    send_due_issues(&app.state, send_at).await.unwrap();
+    let bodies = app
+        .batch_emails(emails_before)
+        .await
+        .iter()
+        .map(|email| email["HtmlBody"].as_str().unwrap().to_owned())
+        .collect();
+    (id, bodies)
+}
+
+/// The links in `html` under `/t/<kind>/`.
+fn tracking_links(app: &TestApp, html: &str, kind: &str) -> Vec<reqwest::Url> {
+    let prefix = format!("http://{}:{}/t/{}/", app.base_url, app.port, kind);
+    linkify::LinkFinder::new()
+        .links(html)
+        .map(|link| link.as_str().to_owned())
+        .filter(|link| link.starts_with(&prefix))
+        .map(|link| reqwest::Url::parse(&link).unwrap())
+        .collect()
+}
+
+async fn engagement(app: &TestApp, id: &str) -> Value {
+    app.admin_request(Method::GET, &format!("/issues/{}/engagement", id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn opens_and_clicks_of_tracked_issues_are_recorded() {
+    // Arrange
+    let app = spawn(true).await;
+    confirmed_subscriber(&app, "Ursula").await;
+    let (id, bodies) = send_tracked_issue(&app).await;
+    let clicks = tracking_links(&app, &bodies[0], "c");
+    let opens = tracking_links(&app, &bodies[0], "o");
+    assert_eq!(clicks.len(), 1);
+    assert_eq!(opens.len(), 1);
+
+    // Act
+    let pixel = client().get(opens[0].clone()).send().await.unwrap();
+    let mut redirects = Vec::new();
+    for _ in 0..2 {
+        redirects.push(client().get(clicks[0].clone()).send().await.unwrap());
+    }
+
+    // Assert
+    assert_eq!(pixel.status(), StatusCode::OK);
+    assert_eq!(pixel.headers()[header::CONTENT_TYPE], "image/gif");
+    assert_eq!(pixel.headers()[header::CACHE_CONTROL], "no-store");
+    for redirect in redirects {
+        assert!(redirect.status().is_redirection());
+        assert_eq!(redirect.headers()[header::LOCATION], "https://example.com/post?a=1&b=2");
+    }
+
+    let engagement = engagement(&app, &id).await;
+    assert_eq!(engagement["opens"], 1);
+    assert_eq!(engagement["unique_opens"], 1);
+    assert_eq!(engagement["clicks"], 2);
+    assert_eq!(engagement["unique_clicks"], 1);
+    assert_eq!(
+        engagement["links"],
+        json!([{ "url": "https://example.com/post?a=1&b=2", "clicks": 2, "unique_clicks": 1 }])
+    );
+}
+
+#[tokio::test]
+async fn the_redirector_only_follows_links_it_signed() {
+    // Arrange
+    let app = spawn(true).await;
+    confirmed_subscriber(&app, "Ursula").await;
+    let (id, bodies) = send_tracked_issue(&app).await;
+    let click = tracking_links(&app, &bodies[0], "c").remove(0);
+    let token = click.path_segments().unwrap().next_back().unwrap().to_owned();
+    let (payload, signature) = token.split_once('.').unwrap();
+    let open = tracking_links(&app, &bodies[0], "o").remove(0);
+    let open_token = open.path_segments().unwrap().next_back().unwrap().to_owned();
+
+    for (token, description) in [
+        (format!("{}x.{}", payload, signature), "tampered payload"),
+        (format!("{}.{}x", payload, signature), "tampered signature"),
+        (payload.to_owned(), "no signature"),
+        (open_token, "an open token"),
+    ] {
+        // Act
+        let response = client()
+            .get(format!("http://{}:{}/t/c/{}", app.base_url, app.port, token))
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", description);
+    }
+    assert_eq!(engagement(&app, &id).await["clicks"], 0);
+}
+
+#[tokio::test]
+async fn subscribers_who_opt_out_are_no_longer_tracked() {
+    // Arrange
+    let app = spawn(true).await;
+    confirmed_subscriber(&app, "Ursula").await;
+    let (first_id, bodies) = send_tracked_issue(&app).await;
+    let opt_out = tracking_links(&app, &bodies[0], "opt-out").remove(0);
+    let click = tracking_links(&app, &bodies[0], "c").remove(0);
+
+    // Act
+    let confirmation = client().get(opt_out.clone()).send().await.unwrap();
+    let opted_out = client().post(opt_out).send().await.unwrap();
+    let redirect = client().get(click).send().await.unwrap();
+    let (_, bodies) = send_tracked_issue(&app).await;
+
+    // Assert
+    assert_eq!(confirmation.status(), StatusCode::OK);
+    assert!(confirmation.text().await.unwrap().contains("<form method=\"post\""));
+    assert_eq!(opted_out.status(), StatusCode::OK);
+    assert!(redirect.status().is_redirection());
+    assert_eq!(engagement(&app, &first_id).await["clicks"], 0);
+    assert!(bodies[0].contains(r#"href="https://example.com/post?a=1&amp;b=2""#));
+    assert!(!bodies[0].contains("/t/"));
+}
+
+#[tokio::test]
+async fn issues_go_out_untracked_while_tracking_is_disabled() {
+    // Arrange
+    let app = spawn(false).await;
+    confirmed_subscriber(&app, "Ursula").await;
+
+    // Act
+    let (_, bodies) = send_tracked_issue(&app).await;
+
+    // Assert
+    assert_eq!(bodies.len(), 1);
+    assert!(bodies[0].contains(r#"href="https://example.com/post?a=1&amp;b=2""#));
+    assert!(!bodies[0].contains("/t/"));
+}